@@ -1,3 +1,5 @@
+use std::process::Command;
+
 fn main() {
     println!("cargo:rerun-if-changed=.env",);
     let dotpath = dotenvy::dotenv();
@@ -8,4 +10,16 @@ fn main() {
             println!("cargo:rustc-env={key}={value}");
         }
     }
+
+    // shown in the about dialog, not having git available is fine
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=MUZAK_COMMIT={}", commit.trim());
+    }
 }
@@ -0,0 +1,58 @@
+CREATE TABLE IF NOT EXISTS track_artist (
+    track_id INTEGER NOT NULL,
+    artist_id INTEGER NOT NULL,
+    PRIMARY KEY (track_id, artist_id),
+    FOREIGN KEY (track_id) REFERENCES track (id),
+    FOREIGN KEY (artist_id) REFERENCES artist (id)
+);
+
+CREATE TABLE IF NOT EXISTS track_genre (
+    track_id INTEGER NOT NULL,
+    genre TEXT NOT NULL,
+    PRIMARY KEY (track_id, genre),
+    FOREIGN KEY (track_id) REFERENCES track (id)
+);
+
+CREATE INDEX IF NOT EXISTS track_artist_artist_id_idx ON track_artist (artist_id);
+CREATE INDEX IF NOT EXISTS track_genre_genre_idx ON track_genre (genre);
+
+CREATE TRIGGER IF NOT EXISTS delete_track_links_trigger BEFORE DELETE ON track
+BEGIN
+    DELETE FROM track_artist WHERE track_id = OLD.id;
+    DELETE FROM track_genre WHERE track_id = OLD.id;
+END;
+
+-- artists can now be referenced by tracks without having an album of their own
+DROP TRIGGER IF EXISTS delete_artist_trigger;
+
+CREATE TRIGGER IF NOT EXISTS delete_artist_trigger AFTER DELETE ON album
+BEGIN
+    DELETE FROM artist
+    WHERE artist.id = OLD.artist_id
+    AND NOT EXISTS (
+        SELECT 1
+        FROM album
+        WHERE album.artist_id = OLD.artist_id
+    )
+    AND NOT EXISTS (
+        SELECT 1
+        FROM track_artist
+        WHERE track_artist.artist_id = OLD.artist_id
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS delete_track_artist_trigger AFTER DELETE ON track_artist
+BEGIN
+    DELETE FROM artist
+    WHERE artist.id = OLD.artist_id
+    AND NOT EXISTS (
+        SELECT 1
+        FROM album
+        WHERE album.artist_id = OLD.artist_id
+    )
+    AND NOT EXISTS (
+        SELECT 1
+        FROM track_artist
+        WHERE track_artist.artist_id = OLD.artist_id
+    );
+END;
@@ -0,0 +1,2 @@
+ALTER TABLE track ADD gain_offset REAL NOT NULL DEFAULT 0.0;
+ALTER TABLE album ADD gain_offset REAL NOT NULL DEFAULT 0.0;
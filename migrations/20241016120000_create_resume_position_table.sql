@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS resume_position (
+    album_id INTEGER PRIMARY KEY,
+    location TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (album_id) REFERENCES album (id)
+);
+
+CREATE TRIGGER IF NOT EXISTS delete_resume_position_trigger AFTER DELETE ON album
+BEGIN
+    DELETE FROM resume_position WHERE album_id = OLD.id;
+END;
@@ -0,0 +1 @@
+ALTER TABLE track ADD skip_always BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,7 @@
+ALTER TABLE album ADD grouping_key TEXT;
+
+-- albums were previously grouped by title and artist
+UPDATE album SET grouping_key = 'title:' || IFNULL(artist_id, '') || ':' || title;
+
+DROP INDEX IF EXISTS album_title_artist_id_idx;
+CREATE UNIQUE INDEX IF NOT EXISTS album_grouping_key_idx ON album (grouping_key);
@@ -0,0 +1,2 @@
+ALTER TABLE track ADD mbid TEXT;
+ALTER TABLE album ADD mbid TEXT;
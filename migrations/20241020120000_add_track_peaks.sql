@@ -0,0 +1 @@
+ALTER TABLE track ADD peaks BLOB;
@@ -0,0 +1 @@
+ALTER TABLE track ADD disc_subtitle TEXT;
@@ -0,0 +1,3 @@
+ALTER TABLE track ADD play_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE track ADD rating INTEGER;
+ALTER TABLE track ADD last_played DATETIME;
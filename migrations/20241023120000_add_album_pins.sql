@@ -0,0 +1 @@
+ALTER TABLE album ADD pinned_position INTEGER;
@@ -0,0 +1,2 @@
+ALTER TABLE track ADD start_offset INTEGER;
+ALTER TABLE track ADD end_offset INTEGER;
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS track_credit (
+    track_id INTEGER NOT NULL,
+    role TEXT NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY (track_id, role, name),
+    FOREIGN KEY (track_id) REFERENCES track (id)
+);
+
+CREATE TRIGGER IF NOT EXISTS delete_track_credits_trigger BEFORE DELETE ON track
+BEGIN
+    DELETE FROM track_credit WHERE track_id = OLD.id;
+END;
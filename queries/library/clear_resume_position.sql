@@ -0,0 +1,2 @@
+DELETE FROM resume_position
+WHERE album_id = $1;
@@ -0,0 +1,4 @@
+SELECT track_credit.track_id, track_credit.role, track_credit.name FROM track_credit
+JOIN track ON track.id = track_credit.track_id
+WHERE track.album_id = $1
+ORDER BY track.disc_number, track.track_number, track_credit.rowid;
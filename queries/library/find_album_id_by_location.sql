@@ -0,0 +1,2 @@
+SELECT album_id FROM track
+WHERE location = $1;
@@ -0,0 +1,7 @@
+SELECT track.album_id, NOT EXISTS (
+    SELECT 1 FROM track AS later
+    WHERE later.album_id = track.album_id
+    AND (COALESCE(later.disc_number, -1), COALESCE(later.track_number, -1))
+        > (COALESCE(track.disc_number, -1), COALESCE(track.track_number, -1))
+) AS is_last FROM track
+WHERE track.location = $1;
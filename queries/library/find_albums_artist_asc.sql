@@ -0,0 +1,3 @@
+SELECT album.id, album.title_sortable FROM album
+LEFT JOIN artist ON album.artist_id = artist.id
+ORDER BY artist.name_sortable ASC, album.release_date ASC, album.title_sortable ASC;
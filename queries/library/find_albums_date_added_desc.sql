@@ -0,0 +1,2 @@
+SELECT id, title_sortable FROM album
+ORDER BY created_at DESC, title_sortable ASC;
@@ -0,0 +1,4 @@
+SELECT album.id, album.title_sortable FROM album
+LEFT JOIN track ON track.album_id = album.id
+GROUP BY album.id
+ORDER BY SUM(track.play_count) DESC, album.title_sortable ASC;
@@ -0,0 +1,2 @@
+SELECT id, title_sortable FROM album
+ORDER BY release_date DESC NULLS LAST, title_sortable ASC;
@@ -0,0 +1,2 @@
+SELECT duration FROM track
+WHERE location = $1;
@@ -0,0 +1,3 @@
+SELECT track.gain_offset + COALESCE(album.gain_offset, 0.0) FROM track
+LEFT JOIN album ON album.id = track.album_id
+WHERE track.location = $1;
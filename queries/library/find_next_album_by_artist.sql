@@ -0,0 +1,8 @@
+SELECT next.id FROM track
+JOIN album AS current ON current.id = track.album_id
+JOIN album AS next ON next.artist_id = current.artist_id
+WHERE track.location = $1
+AND (COALESCE(next.release_date, ''), next.title_sortable)
+    > (COALESCE(current.release_date, ''), current.title_sortable)
+ORDER BY COALESCE(next.release_date, '') ASC, next.title_sortable ASC
+LIMIT 1;
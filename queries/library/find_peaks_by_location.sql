@@ -0,0 +1,2 @@
+SELECT peaks FROM track
+WHERE location = $1 AND peaks IS NOT NULL;
@@ -0,0 +1,3 @@
+SELECT id, title_sortable FROM album
+WHERE pinned_position IS NOT NULL
+ORDER BY pinned_position ASC;
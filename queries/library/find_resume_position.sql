@@ -0,0 +1,2 @@
+SELECT album_id, location, position FROM resume_position
+WHERE album_id = $1;
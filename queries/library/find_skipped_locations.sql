@@ -0,0 +1,2 @@
+SELECT location FROM track
+WHERE skip_always = TRUE;
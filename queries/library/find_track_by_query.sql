@@ -0,0 +1,8 @@
+SELECT track.location FROM track
+LEFT JOIN album ON album.id = track.album_id
+LEFT JOIN artist ON artist.id = album.artist_id
+WHERE track.title LIKE '%' || $1 || '%' ESCAPE '\'
+OR (artist.name || ' ' || track.title) LIKE '%' || $1 || '%' ESCAPE '\'
+OR (track.title || ' ' || artist.name) LIKE '%' || $1 || '%' ESCAPE '\'
+ORDER BY track.title = $2 COLLATE NOCASE DESC, length(track.title) ASC
+LIMIT 1;
@@ -0,0 +1,2 @@
+SELECT id FROM track
+WHERE location = $1;
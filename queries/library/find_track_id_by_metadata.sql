@@ -0,0 +1,8 @@
+SELECT track.id FROM track
+LEFT JOIN album ON album.id = track.album_id
+LEFT JOIN track_artist ON track_artist.track_id = track.id
+JOIN artist ON artist.id = track_artist.artist_id OR artist.id = album.artist_id
+WHERE track.title = $1 COLLATE NOCASE
+AND artist.name = $2 COLLATE NOCASE
+AND ($3 IS NULL OR album.title = $3 COLLATE NOCASE)
+LIMIT 1;
@@ -0,0 +1,4 @@
+SELECT track.location FROM track
+JOIN album ON album.id = track.album_id
+WHERE album.artist_id = $1
+ORDER BY album.release_date ASC, album.title_sortable ASC, track.disc_number ASC, track.track_number ASC;
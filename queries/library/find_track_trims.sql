@@ -0,0 +1,2 @@
+SELECT location, start_offset, end_offset FROM track
+WHERE start_offset IS NOT NULL OR end_offset IS NOT NULL;
@@ -0,0 +1 @@
+SELECT id FROM album WHERE image IS NOT NULL AND id > $1 ORDER BY id;
@@ -0,0 +1,4 @@
+UPDATE album SET pinned_position = (
+    SELECT COALESCE(MAX(pinned_position), -1) + 1 FROM album
+)
+WHERE id = $1 AND pinned_position IS NULL;
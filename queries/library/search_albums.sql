@@ -0,0 +1,6 @@
+SELECT album.id, album.title, artist.name FROM album
+LEFT JOIN artist ON artist.id = album.artist_id
+WHERE album.title LIKE '%' || $1 || '%' ESCAPE '\'
+OR (artist.name || ' ' || album.title) LIKE '%' || $1 || '%' ESCAPE '\'
+ORDER BY length(album.title) ASC
+LIMIT 20;
@@ -0,0 +1,4 @@
+SELECT id, name FROM artist
+WHERE name LIKE '%' || $1 || '%' ESCAPE '\'
+ORDER BY length(name) ASC
+LIMIT 10;
@@ -0,0 +1,7 @@
+SELECT track.location, track.title, artist.name FROM track
+LEFT JOIN album ON album.id = track.album_id
+LEFT JOIN artist ON artist.id = album.artist_id
+WHERE track.title LIKE '%' || $1 || '%' ESCAPE '\'
+OR (artist.name || ' ' || track.title) LIKE '%' || $1 || '%' ESCAPE '\'
+ORDER BY length(track.title) ASC
+LIMIT 20;
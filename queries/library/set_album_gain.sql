@@ -0,0 +1,2 @@
+UPDATE album SET gain_offset = $1
+WHERE id = $2;
@@ -0,0 +1,2 @@
+UPDATE album SET pinned_position = $1
+WHERE id = $2;
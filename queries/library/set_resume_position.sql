@@ -0,0 +1,6 @@
+INSERT INTO resume_position (album_id, location, position)
+VALUES ($1, $2, $3)
+ON CONFLICT (album_id) DO UPDATE SET
+    location = excluded.location,
+    position = excluded.position,
+    updated_at = CURRENT_TIMESTAMP;
@@ -0,0 +1,2 @@
+UPDATE track SET gain_offset = $1
+WHERE id = $2;
@@ -0,0 +1,2 @@
+UPDATE track SET peaks = $1
+WHERE location = $2;
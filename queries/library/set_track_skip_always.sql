@@ -0,0 +1,2 @@
+UPDATE track SET skip_always = $1
+WHERE id = $2;
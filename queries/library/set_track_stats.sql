@@ -0,0 +1,5 @@
+UPDATE track SET
+    play_count = MAX(play_count, COALESCE($1, 0)),
+    rating = COALESCE($2, rating),
+    last_played = COALESCE(MAX(last_played, $3), last_played, $3)
+WHERE id = $4;
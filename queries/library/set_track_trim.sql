@@ -0,0 +1,2 @@
+UPDATE track SET start_offset = $1, end_offset = $2
+WHERE id = $3;
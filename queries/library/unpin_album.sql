@@ -0,0 +1,2 @@
+UPDATE album SET pinned_position = NULL
+WHERE id = $1;
@@ -1,4 +1,4 @@
-INSERT INTO album (title, title_sortable, artist_id, image, thumb, release_date, label, catalog_number, isrc)
-    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-    ON CONFLICT (title, artist_id) DO NOTHING -- TODO: ideally we should have some way of updating this
+INSERT INTO album (title, title_sortable, artist_id, image, thumb, release_date, label, catalog_number, isrc, grouping_key, mbid)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+    ON CONFLICT (grouping_key) DO NOTHING -- TODO: ideally we should have some way of updating this
     RETURNING id;
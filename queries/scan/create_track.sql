@@ -1,5 +1,5 @@
-INSERT INTO track (title, title_sortable, album_id, track_number, disc_number, duration, location, genres)
-    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+INSERT INTO track (title, title_sortable, album_id, track_number, disc_number, duration, location, genres, mbid, disc_subtitle)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
     ON CONFLICT (location) DO UPDATE SET
         title = EXCLUDED.title,
         title_sortable = EXCLUDED.title_sortable,
@@ -8,5 +8,7 @@ INSERT INTO track (title, title_sortable, album_id, track_number, disc_number, d
         disc_number = EXCLUDED.disc_number,
         duration = EXCLUDED.duration,
         location = EXCLUDED.location,
-        genres = EXCLUDED.genres
+        genres = EXCLUDED.genres,
+        mbid = EXCLUDED.mbid,
+        disc_subtitle = EXCLUDED.disc_subtitle
     RETURNING id;
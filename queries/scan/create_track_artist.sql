@@ -0,0 +1,3 @@
+INSERT INTO track_artist (track_id, artist_id)
+    VALUES ($1, $2)
+    ON CONFLICT (track_id, artist_id) DO NOTHING;
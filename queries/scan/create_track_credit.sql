@@ -0,0 +1,3 @@
+INSERT INTO track_credit (track_id, role, name)
+    VALUES ($1, $2, $3)
+    ON CONFLICT (track_id, role, name) DO NOTHING;
@@ -0,0 +1,3 @@
+INSERT INTO track_genre (track_id, genre)
+    VALUES ($1, $2)
+    ON CONFLICT (track_id, genre) DO NOTHING;
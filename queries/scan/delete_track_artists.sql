@@ -0,0 +1 @@
+DELETE FROM track_artist WHERE track_id = $1;
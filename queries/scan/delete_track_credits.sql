@@ -0,0 +1 @@
+DELETE FROM track_credit WHERE track_id = $1;
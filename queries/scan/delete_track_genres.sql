@@ -0,0 +1 @@
+DELETE FROM track_genre WHERE track_id = $1;
@@ -1 +1 @@
-SELECT id FROM album WHERE title = $1;
+SELECT id FROM album WHERE grouping_key = $1;
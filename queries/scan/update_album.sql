@@ -0,0 +1,9 @@
+UPDATE album SET
+    image = $2,
+    thumb = $3,
+    release_date = $4,
+    label = $5,
+    catalog_number = $6,
+    isrc = $7,
+    mbid = $8
+    WHERE id = $1;
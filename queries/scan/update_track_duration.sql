@@ -0,0 +1 @@
+UPDATE track SET duration = $1 WHERE location = $2;
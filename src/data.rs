@@ -1,4 +1,8 @@
+pub mod cover_art;
+pub mod disk_cache;
 pub mod events;
+pub mod filename;
 pub mod interface;
+pub mod peaks;
 pub mod thread;
 pub mod types;
@@ -0,0 +1,273 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{Sender, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use ahash::{AHashMap, AHashSet};
+use isahc::{config::RedirectPolicy, prelude::*, Request};
+use tracing::{debug, warn};
+
+use crate::ui::app::get_dirs;
+
+use super::events::DataEvent;
+
+/// How long a lookup can take before giving up on it.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a release that has no cover is remembered for before it's looked up again, in case one
+/// has been added since.
+const MISSING_EXPIRY: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+/// The most covers that are kept on disk, in bytes. When the cache grows past this, the covers
+/// that were used least recently are removed.
+const MAX_CACHE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// How many releases can be waiting to be looked up before new ones are dropped. Dropped releases
+/// are requested again the next time one of their files is read.
+const MAX_QUEUED: usize = 256;
+
+/// The lookups that have been requested but not finished, shared with the thread performing them.
+#[derive(Default)]
+struct Lookups {
+    /// The files waiting on each release's cover, so that they can be read again once it arrives.
+    pending: AHashMap<String, Vec<String>>,
+    /// Releases that couldn't be looked up this session, e.g. because the network is down.
+    failed: AHashSet<String>,
+}
+
+/// Fetches front covers from the Cover Art Archive by release MBID, for files without embedded art.
+/// Covers are fetched one at a time on a separate thread, so reading metadata never waits for the
+/// network. Covers are kept on disk once fetched, and releases without a cover are remembered on
+/// disk (as an empty file) for `MISSING_EXPIRY`.
+pub struct CoverArtLookup {
+    directory: PathBuf,
+    events_tx: Sender<DataEvent>,
+    lookups: Arc<Mutex<Lookups>>,
+    /// Started the first time a cover has to be fetched.
+    requests_tx: Option<SyncSender<String>>,
+}
+
+impl CoverArtLookup {
+    pub fn new(events_tx: Sender<DataEvent>) -> Self {
+        Self {
+            directory: get_dirs().cache_dir().join("covers"),
+            events_tx,
+            lookups: Arc::new(Mutex::new(Lookups::default())),
+            requests_tx: None,
+        }
+    }
+
+    /// Returns the release's cover if it's on disk. Otherwise, it's fetched in the background, and
+    /// `DataEvent::CoverArtFetched` is sent with `path` (and any other files waiting on the same
+    /// release) once it has been.
+    pub fn lookup(&mut self, mbid: &str, path: &str) -> Option<Box<[u8]>> {
+        // the MBID is used as a file name, so anything that isn't one is rejected
+        if mbid.is_empty() || !mbid.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+            return None;
+        }
+
+        let file = self.directory.join(mbid);
+
+        if let Ok(metadata) = std::fs::metadata(&file) {
+            if metadata.len() > 0 {
+                debug!("cover art for {} found on disk", mbid);
+                // the modification time is used to decide which covers are removed first
+                if let Ok(handle) = std::fs::File::options().write(true).open(&file) {
+                    let _ = handle.set_modified(SystemTime::now());
+                }
+
+                return std::fs::read(&file).ok().map(Vec::into_boxed_slice);
+            }
+
+            let age = metadata
+                .modified()
+                .ok()
+                .and_then(|v| v.elapsed().ok())
+                .unwrap_or(MISSING_EXPIRY);
+
+            if age < MISSING_EXPIRY {
+                return None;
+            }
+        }
+
+        let mut lookups = self.lookups.lock().expect("cover art lookups poisoned");
+
+        if lookups.failed.contains(mbid) {
+            return None;
+        }
+
+        if let Some(waiting) = lookups.pending.get_mut(mbid) {
+            if !waiting.iter().any(|v| v == path) {
+                waiting.push(path.to_string());
+            }
+
+            return None;
+        }
+
+        let requests_tx = self.requests_tx.get_or_insert_with(|| {
+            start_fetching(
+                self.directory.clone(),
+                self.events_tx.clone(),
+                self.lookups.clone(),
+            )
+        });
+
+        match requests_tx.try_send(mbid.to_string()) {
+            Ok(()) => {
+                lookups
+                    .pending
+                    .insert(mbid.to_string(), vec![path.to_string()]);
+            }
+            Err(TrySendError::Full(_)) => debug!("too many covers queued, skipping {}", mbid),
+            Err(TrySendError::Disconnected(_)) => {
+                warn!("Cover art thread stopped, restarting it");
+                self.requests_tx = None;
+            }
+        }
+
+        None
+    }
+}
+
+/// Starts the thread that fetches the covers of the releases it's sent, one at a time.
+fn start_fetching(
+    directory: PathBuf,
+    events_tx: Sender<DataEvent>,
+    lookups: Arc<Mutex<Lookups>>,
+) -> SyncSender<String> {
+    let (requests_tx, requests_rx) = std::sync::mpsc::sync_channel::<String>(MAX_QUEUED);
+
+    let result = std::thread::Builder::new()
+        .name("cover-art".to_string())
+        .spawn(move || {
+            for mbid in requests_rx {
+                let fetched = fetch(&directory, &mbid);
+                let mut lookups = lookups.lock().expect("cover art lookups poisoned");
+                let waiting = lookups.pending.remove(&mbid).unwrap_or_default();
+
+                match fetched {
+                    Ok(true) => {
+                        // the data thread may have stopped, in which case there's nobody to tell
+                        let _ = events_tx.send(DataEvent::CoverArtFetched(waiting));
+                    }
+                    Ok(false) => (),
+                    Err(e) => {
+                        warn!("Could not fetch cover art for {}: {:?}", mbid, e);
+                        lookups.failed.insert(mbid);
+                    }
+                }
+            }
+        });
+
+    if let Err(e) = result {
+        warn!("Could not start fetching cover art: {:?}", e);
+    }
+
+    requests_tx
+}
+
+/// Fetches the release's cover and stores it in `directory`, or stores an empty file if it has no
+/// cover. Returns whether a cover was found.
+fn fetch(directory: &Path, mbid: &str) -> anyhow::Result<bool> {
+    let data = request(mbid)?;
+
+    if data.is_none() {
+        debug!("no cover art for {}", mbid);
+    }
+
+    std::fs::create_dir_all(directory)?;
+    std::fs::write(directory.join(mbid), data.as_deref().unwrap_or_default())?;
+
+    if data.is_some() {
+        evict(directory, MAX_CACHE_SIZE);
+    }
+
+    Ok(data.is_some())
+}
+
+/// Removes the covers that were used least recently until the directory is no larger than
+/// `max_size` bytes.
+fn evict(directory: &Path, max_size: u64) {
+    let Ok(dir) = std::fs::read_dir(directory) else {
+        return;
+    };
+
+    let mut files: Vec<(PathBuf, u64, SystemTime)> = dir
+        .flatten()
+        .filter_map(|file| {
+            let metadata = file.metadata().ok()?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+
+            Some((file.path(), metadata.len(), modified))
+        })
+        .collect();
+
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    files.sort_by_key(|(_, _, modified)| *modified);
+
+    for (path, size, _) in files {
+        if total <= max_size {
+            break;
+        }
+
+        match std::fs::remove_file(&path) {
+            Ok(()) => total -= size,
+            Err(e) => warn!("Could not remove cover art from cache: {:?}", e),
+        }
+    }
+}
+
+/// Requests the 250px front cover of the release. Returns None if the release has no cover.
+fn request(mbid: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let url = format!("https://coverartarchive.org/release/{}/front-250", mbid);
+
+    // the archive redirects to wherever the image is actually stored
+    let mut response = Request::get(url)
+        .redirect_policy(RedirectPolicy::Follow)
+        .timeout(TIMEOUT)
+        .body(())?
+        .send()?;
+
+    if response.status() == 404 {
+        return Ok(None);
+    }
+
+    if !response.status().is_success() {
+        anyhow::bail!("unexpected status {}", response.status());
+    }
+
+    Ok(Some(response.bytes()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evict_removes_least_recently_used() {
+        let directory = std::env::temp_dir().join(format!("muzak-covers-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let now = SystemTime::now();
+
+        for (name, age) in [("old", 30), ("middle", 20), ("new", 10)] {
+            let path = directory.join(name);
+            std::fs::write(&path, [0; 100]).unwrap();
+
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+
+        evict(&directory, 200);
+
+        assert!(!directory.join("old").exists());
+        assert!(directory.join("middle").exists());
+        assert!(directory.join("new").exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
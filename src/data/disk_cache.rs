@@ -0,0 +1,232 @@
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use ahash::{AHashMap, RandomState};
+use image::RgbaImage;
+use tracing::{debug, warn};
+
+use crate::{
+    ui::app::get_dirs,
+    util::{make_thumbnail, rgb_to_bgr},
+};
+
+use super::{events::ImageLayout, thread::decode_rgba};
+
+/// The size of the thumbnails shown in the queue, in pixels.
+const THUMBNAIL_SIZE: u32 = 80;
+
+/// The hasher used for cache keys. The seeds are fixed so that keys stay the same between
+/// sessions, for the thumbnails cached on disk.
+pub fn cache_hash_state() -> RandomState {
+    RandomState::with_seeds(0x6d757a616b, 0x7468756d62, 0x6e61696c73, 0x6361636865)
+}
+
+/// The key the thumbnail of an encoded image is cached under. The layout is part of the key, since
+/// the disk cache stores converted thumbnails.
+pub fn thumbnail_key(image: &[u8], crop: bool, layout: ImageLayout) -> u64 {
+    cache_hash_state().hash_one((image, crop, layout))
+}
+
+/// Decodes the image and makes the thumbnail that's shown in the queue.
+pub fn render_thumbnail(image: &[u8], crop: bool, layout: ImageLayout) -> Option<RgbaImage> {
+    let mut image = decode_rgba(image).ok()?;
+
+    if layout == ImageLayout::BGR {
+        rgb_to_bgr(&mut image);
+    }
+
+    Some(make_thumbnail(&image, THUMBNAIL_SIZE, crop))
+}
+
+struct CacheEntry {
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// Keeps decoded thumbnails on disk so that they don't have to be decoded again after a restart.
+/// Thumbnails are stored as their raw pixels, prefixed with their width and height, in files named
+/// after their key. When the cache grows past its size limit, the thumbnails that were used least
+/// recently are removed.
+pub struct ThumbnailCache {
+    directory: PathBuf,
+    /// Read from the directory the first time the cache is used.
+    entries: Option<AHashMap<u64, CacheEntry>>,
+    total_size: u64,
+}
+
+impl Default for ThumbnailCache {
+    fn default() -> Self {
+        Self {
+            directory: get_dirs().cache_dir().join("thumbnails"),
+            entries: None,
+            total_size: 0,
+        }
+    }
+}
+
+impl ThumbnailCache {
+    fn path(&self, key: u64) -> PathBuf {
+        self.directory.join(format!("{:016x}", key))
+    }
+
+    fn entries(&mut self) -> &mut AHashMap<u64, CacheEntry> {
+        if self.entries.is_none() {
+            let mut entries = AHashMap::new();
+            self.total_size = 0;
+
+            if let Ok(dir) = std::fs::read_dir(&self.directory) {
+                for file in dir.flatten() {
+                    let Some(key) = file
+                        .file_name()
+                        .to_str()
+                        .and_then(|v| u64::from_str_radix(v, 16).ok())
+                    else {
+                        continue;
+                    };
+                    let Ok(metadata) = file.metadata() else {
+                        continue;
+                    };
+
+                    self.total_size += metadata.len();
+                    entries.insert(
+                        key,
+                        CacheEntry {
+                            size: metadata.len(),
+                            last_used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                        },
+                    );
+                }
+            }
+
+            debug!(
+                "loaded thumbnail cache index: {} entries, {} bytes",
+                entries.len(),
+                self.total_size
+            );
+
+            self.entries = Some(entries);
+        }
+
+        self.entries.as_mut().unwrap()
+    }
+
+    /// Removes every stored thumbnail.
+    pub fn clear(&mut self) {
+        match std::fs::remove_dir_all(&self.directory) {
+            Ok(()) => debug!("thumbnail cache cleared"),
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => warn!("Could not clear thumbnail cache: {:?}", e),
+        }
+
+        self.entries = Some(AHashMap::new());
+        self.total_size = 0;
+    }
+
+    /// Whether the thumbnail is stored, without reading it.
+    pub fn contains(&mut self, key: u64) -> bool {
+        self.entries().contains_key(&key)
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<RgbaImage> {
+        let entry = self.entries().get_mut(&key)?;
+        entry.last_used = SystemTime::now();
+
+        let path = self.path(key);
+        let mut data = Vec::new();
+        File::open(&path).ok()?.read_to_end(&mut data).ok()?;
+
+        // the file's modification time is what's used to order the entries after a restart
+        if let Ok(file) = File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        if data.len() < 8 {
+            return None;
+        }
+
+        let (header, pixels) = data.split_at(8);
+        let width = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let height = u32::from_le_bytes(header[4..].try_into().unwrap());
+
+        RgbaImage::from_raw(width, height, pixels.to_vec())
+    }
+
+    /// Stores the thumbnail, then removes the least recently used thumbnails until the cache is
+    /// no larger than `max_size` bytes.
+    pub fn insert(&mut self, key: u64, image: &RgbaImage, max_size: u64) {
+        // the index has to be read before the new file is written, or it'd be counted twice
+        self.entries();
+
+        if let Err(e) = std::fs::create_dir_all(&self.directory) {
+            warn!("Could not create thumbnail cache directory: {:?}", e);
+            return;
+        }
+
+        let mut data = Vec::with_capacity(8 + image.as_raw().len());
+        data.extend_from_slice(&image.width().to_le_bytes());
+        data.extend_from_slice(&image.height().to_le_bytes());
+        data.extend_from_slice(image.as_raw());
+
+        if let Err(e) = File::create(self.path(key)).and_then(|mut v| v.write_all(&data)) {
+            warn!("Could not write to thumbnail cache: {:?}", e);
+            return;
+        }
+
+        let size = data.len() as u64;
+        let previous = self.entries().insert(
+            key,
+            CacheEntry {
+                size,
+                last_used: SystemTime::now(),
+            },
+        );
+        self.total_size = self.total_size + size - previous.map_or(0, |v| v.size);
+
+        self.evict(max_size);
+    }
+
+    /// Stores the thumbnail only if no other thumbnail has to be removed to make room for it.
+    /// Returns whether it was stored.
+    pub fn insert_if_room(&mut self, key: u64, image: &RgbaImage, max_size: u64) -> bool {
+        let size = 8 + image.as_raw().len() as u64;
+        self.entries();
+
+        if self.total_size + size > max_size {
+            return false;
+        }
+
+        self.insert(key, image, max_size);
+        true
+    }
+
+    fn evict(&mut self, max_size: u64) {
+        if self.total_size <= max_size {
+            return;
+        }
+
+        let mut by_use: Vec<(u64, SystemTime)> = self
+            .entries()
+            .iter()
+            .map(|(key, entry)| (*key, entry.last_used))
+            .collect();
+        by_use.sort_by_key(|(_, last_used)| *last_used);
+
+        for (key, _) in by_use {
+            if self.total_size <= max_size {
+                break;
+            }
+
+            if let Some(entry) = self.entries().remove(&key) {
+                self.total_size -= entry.size;
+
+                if let Err(e) = std::fs::remove_file(self.path(key)) {
+                    warn!("Could not remove thumbnail from cache: {:?}", e);
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,10 @@
 use std::sync::Arc;
 
-use gpui::RenderImage;
+use gpui::{RenderImage, Rgba};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::settings::interface::InterfaceSettings;
 
 use super::types::UIQueueItem;
 
@@ -9,10 +13,12 @@ pub enum ImageType {
     CurrentAlbumArt,
     CachedImage(u64),
     AlbumArt(i64),
+    AlbumArtBackdrop(i64),
     ArtistPortrait(i64),
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ImageLayout {
     BGR,
     RGB,
@@ -21,15 +27,59 @@ pub enum ImageLayout {
 /// A command to the data thread. This is used to control the playback thread from other threads.
 /// The data thread recieves these commands from an MPSC channel, and processes them in the order
 /// they are recieved, every 10 seconds.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub enum DataCommand {
     /// Requests that the data proccessing thread decode the specified image. The image type is
     /// used to keep track of which image is being decoded, and the layout is used to determine
     /// whether or not RGB to BGR conversion is necessary.
     DecodeImage(Box<[u8]>, ImageType, ImageLayout, bool),
-    /// Requests that the data processing thread perform cache maintenance.
-    EvictQueueCache,
+    /// Requests that the data processing thread decode the specified image, and produce a small,
+    /// blurred version of it suitable for use as a backdrop.
+    DecodeBackdrop(Box<[u8]>, ImageType, ImageLayout),
+    /// Requests that the data processing thread read the metadata of the specified file, for
+    /// display in the queue. Unused images are evicted from the cache once these stop coming in.
     ReadMetadata(String),
+    /// Requests that the data processing thread compute the waveform peaks of the specified file.
+    /// The file is decoded on a separate thread, since this can take a while for long tracks. Other
+    /// files whose peaks are still being computed are cancelled, and requests for a file that is
+    /// already being computed are ignored.
+    ComputePeaks(String),
+    /// Cancels computing the peaks of every file.
+    CancelPeaks,
+    /// Switches to a new database, after the library has been reset.
+    ReplacePool(SqlitePool),
+    /// Drops every decoded image, and deletes the thumbnails cached on disk, since their albums
+    /// may no longer exist.
+    ClearImageCache,
+    /// Queues the art of the albums that haven't been queued yet this session to be pre-rendered
+    /// into the disk thumbnail cache, one at a time. Pre-rendering stops once the cache is full.
+    PrerenderThumbnails,
+    /// Drops the albums that are waiting to be pre-rendered.
+    StopPrerendering,
+    /// Informs the data processing thread that the user's interface settings have changed. This
+    /// affects how images are processed (for example, whether or not they are cropped).
+    UpdateSettings(InterfaceSettings),
+}
+
+/// Why the metadata of a file couldn't be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadFailure {
+    /// The file doesn't exist.
+    Missing,
+    /// No media provider could open the file.
+    Unopenable,
+    /// The file was opened, but its metadata couldn't be read.
+    NoMetadata,
+}
+
+impl ReadFailure {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            ReadFailure::Missing => "File not found",
+            ReadFailure::Unopenable => "Could not open file",
+            ReadFailure::NoMetadata => "Could not read metadata",
+        }
+    }
 }
 
 /// An event from the data thread. This is used to communicate information from the data thread to
@@ -37,10 +87,19 @@ pub enum DataCommand {
 /// processes them in the order they are recieved.
 #[derive(Debug, Clone)]
 pub enum DataEvent {
-    /// Indicates that the data processing thread has decoded the specified image.
-    ImageDecoded(Arc<RenderImage>, ImageType),
+    /// Indicates that the data processing thread has decoded the specified image. Thumbnails
+    /// include their dominant color if dynamic accent colors are enabled.
+    ImageDecoded(Arc<RenderImage>, ImageType, Option<Rgba>),
     /// Indicates that the data processing thread has encountered an error while decoding the
     /// specified image.
     DecodeError(ImageType),
     MetadataRead(String, UIQueueItem),
+    /// Indicates that the metadata of the specified file couldn't be read, so a queue item was
+    /// made from its path instead.
+    MetadataUnreadable(String, ReadFailure),
+    /// Indicates that the waveform peaks of the specified file have been computed.
+    PeaksComputed(String, Vec<u8>),
+    /// Indicates that a cover has been fetched for the specified files, which didn't have one when
+    /// their metadata was read.
+    CoverArtFetched(Vec<String>),
 }
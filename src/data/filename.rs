@@ -0,0 +1,243 @@
+use std::path::Path;
+
+/// Metadata guessed from a file's name and the folders it's in, for files without tags. Albums
+/// and track numbers are matched so that they aren't mistaken for the title or artist, but aren't
+/// kept, since the queue doesn't show them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilenameMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    Artist,
+    Album,
+    Track,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(String),
+    Field(Field),
+}
+
+/// Splits a pattern like `{artist}/{album}/{track} - {title}` into literals and fields. Braces that
+/// aren't one of the known fields are kept as literals.
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut rest = pattern;
+
+    while !rest.is_empty() {
+        let field = [
+            ("{title}", Field::Title),
+            ("{artist}", Field::Artist),
+            ("{album}", Field::Album),
+            ("{track}", Field::Track),
+        ]
+        .into_iter()
+        .find(|(name, _)| rest.starts_with(name));
+
+        if let Some((name, field)) = field {
+            if !literal.is_empty() {
+                tokens.push(Token::Literal(std::mem::take(&mut literal)));
+            }
+
+            tokens.push(Token::Field(field));
+            rest = &rest[name.len()..];
+        } else {
+            let c = rest.chars().next().unwrap();
+            literal.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+
+    tokens
+}
+
+/// Matches the tokens against the whole input, filling in the fields of `out` if they match.
+/// Fields match as little as they can, so `{artist} - {title}` splits at the first separator.
+fn match_tokens(tokens: &[Token], input: &str, out: &mut FilenameMetadata) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return input.is_empty();
+    };
+
+    match token {
+        Token::Literal(literal) => input
+            .strip_prefix(literal.as_str())
+            .is_some_and(|input| match_tokens(rest, input, out)),
+        Token::Field(field) => {
+            for (end, c) in input.char_indices().map(|(i, c)| (i + c.len_utf8(), c)) {
+                // fields never span more than one folder
+                if c == '/' {
+                    return false;
+                }
+
+                let value = input[..end].trim();
+
+                if *field == Field::Track && !value.chars().all(|c| c.is_ascii_digit()) {
+                    return false;
+                }
+
+                if value.is_empty() || !match_tokens(rest, &input[end..], out) {
+                    continue;
+                }
+
+                match field {
+                    Field::Title => out.title = Some(value.to_string()),
+                    Field::Artist => out.artist = Some(value.to_string()),
+                    Field::Album | Field::Track => (),
+                }
+
+                return true;
+            }
+
+            false
+        }
+    }
+}
+
+/// Guesses the metadata of a file from its path, using the first of the patterns that matches the
+/// end of the path (without the extension). Patterns separate folders with `/` on every platform.
+/// Returns None if none of the patterns match.
+pub fn parse_filename(path: &str, patterns: &[String]) -> Option<FilenameMetadata> {
+    let path = Path::new(path);
+    let stem = path.file_stem()?.to_string_lossy();
+    let folders: Vec<_> = path
+        .parent()
+        .map(|v| v.iter().map(|v| v.to_string_lossy()).collect())
+        .unwrap_or_default();
+
+    patterns.iter().find_map(|pattern| {
+        let depth = pattern.matches('/').count();
+
+        if depth > folders.len() {
+            return None;
+        }
+
+        let mut input = folders[folders.len() - depth..].join("/");
+        if depth > 0 {
+            input.push('/');
+        }
+        input.push_str(&stem);
+
+        let mut metadata = FilenameMetadata::default();
+
+        match_tokens(&tokenize(pattern), &input, &mut metadata).then_some(metadata)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn guessed(title: &str, artist: Option<&str>) -> Option<FilenameMetadata> {
+        Some(FilenameMetadata {
+            title: Some(title.to_string()),
+            artist: artist.map(str::to_string),
+        })
+    }
+
+    #[test]
+    fn file_name_only() {
+        let patterns = patterns(&["{track} - {artist} - {title}"]);
+
+        assert_eq!(
+            parse_filename("/music/01 - Artist - Some Song.flac", &patterns),
+            guessed("Some Song", Some("Artist"))
+        );
+    }
+
+    #[test]
+    fn folders() {
+        let patterns = patterns(&["{artist}/{album}/{track} - {title}"]);
+
+        assert_eq!(
+            parse_filename("/music/Artist/Album/03 - Some Song.mp3", &patterns),
+            guessed("Some Song", Some("Artist"))
+        );
+    }
+
+    #[test]
+    fn first_matching_pattern_wins() {
+        let patterns = patterns(&["{track} - {artist} - {title}", "{track} {title}"]);
+
+        assert_eq!(
+            parse_filename("/music/07 Some Song.ogg", &patterns),
+            guessed("Some Song", None)
+        );
+        assert_eq!(
+            parse_filename("/music/07 - Artist - Some Song.ogg", &patterns),
+            guessed("Some Song", Some("Artist"))
+        );
+    }
+
+    #[test]
+    fn fields_match_as_little_as_they_can() {
+        let patterns = patterns(&["{artist} - {title}"]);
+
+        assert_eq!(
+            parse_filename("/music/Artist - Some - Song.wav", &patterns),
+            guessed("Some - Song", Some("Artist"))
+        );
+    }
+
+    #[test]
+    fn track_numbers_are_digits() {
+        let patterns = patterns(&["{track} - {title}"]);
+
+        assert_eq!(
+            parse_filename("/music/Artist - Some Song.wav", &patterns),
+            None
+        );
+        assert_eq!(
+            parse_filename("/music/12 - Some Song.wav", &patterns),
+            guessed("Some Song", None)
+        );
+    }
+
+    #[test]
+    fn too_few_folders() {
+        let patterns = patterns(&["{artist}/{album}/{title}"]);
+
+        assert_eq!(parse_filename("Album/Some Song.flac", &patterns), None);
+    }
+
+    #[test]
+    fn fields_stay_within_folders() {
+        let patterns = patterns(&["{artist}/{title}"]);
+
+        assert_eq!(
+            parse_filename("/music/Artist/Some Song.flac", &patterns),
+            guessed("Some Song", Some("Artist"))
+        );
+        assert_eq!(
+            parse_filename("/music/Some Song.flac", &["{title}".into()]),
+            guessed("Some Song", None)
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_literals() {
+        let patterns = patterns(&["{year} {title}"]);
+
+        assert_eq!(
+            parse_filename("/music/{year} Some Song.flac", &patterns),
+            guessed("Some Song", None)
+        );
+        assert_eq!(
+            parse_filename("/music/1999 Some Song.flac", &patterns),
+            None
+        );
+    }
+}
@@ -4,10 +4,17 @@ use std::{
 };
 
 use gpui::AppContext;
+use sqlx::SqlitePool;
 
-use crate::ui::models::{ImageTransfer, Models};
+use crate::{
+    settings::interface::InterfaceSettings,
+    ui::models::{ImageTransfer, ImageTransferFailed, Models},
+};
 
-use super::events::{DataCommand, DataEvent, ImageLayout, ImageType};
+use super::{
+    events::{DataCommand, DataEvent, ImageLayout, ImageType},
+    peaks::peaks_computed,
+};
 
 /// The DataInterface trait defines the method used to create the struct that will be used to
 /// communicate between the data thread and the main thread.
@@ -58,9 +65,14 @@ impl GPUIDataInterface {
             .expect("could not send tx");
     }
 
-    pub fn evict_cache(&self) {
+    pub fn decode_backdrop(
+        &self,
+        data: Box<[u8]>,
+        image_type: ImageType,
+        image_layout: ImageLayout,
+    ) {
         self.commands_tx
-            .send(DataCommand::EvictQueueCache)
+            .send(DataCommand::DecodeBackdrop(data, image_type, image_layout))
             .expect("could not send tx");
     }
 
@@ -70,6 +82,48 @@ impl GPUIDataInterface {
             .expect("could not send tx");
     }
 
+    pub fn compute_peaks(&self, path: String) {
+        self.commands_tx
+            .send(DataCommand::ComputePeaks(path))
+            .expect("could not send tx");
+    }
+
+    pub fn cancel_peaks(&self) {
+        self.commands_tx
+            .send(DataCommand::CancelPeaks)
+            .expect("could not send tx");
+    }
+
+    pub fn replace_pool(&self, pool: SqlitePool) {
+        self.commands_tx
+            .send(DataCommand::ReplacePool(pool))
+            .expect("could not send tx");
+    }
+
+    pub fn clear_image_cache(&self) {
+        self.commands_tx
+            .send(DataCommand::ClearImageCache)
+            .expect("could not send tx");
+    }
+
+    pub fn prerender_thumbnails(&self) {
+        self.commands_tx
+            .send(DataCommand::PrerenderThumbnails)
+            .expect("could not send tx");
+    }
+
+    pub fn stop_prerendering(&self) {
+        self.commands_tx
+            .send(DataCommand::StopPrerendering)
+            .expect("could not send tx");
+    }
+
+    pub fn update_settings(&self, settings: InterfaceSettings) {
+        self.commands_tx
+            .send(DataCommand::UpdateSettings(settings))
+            .expect("could not send tx");
+    }
+
     /// Starts the broadcast loop that will read events from the data thread and update data models
     /// accordingly. This function should be called once, and will panic if called more than once.
     pub fn start_broadcast(&mut self, cx: &mut AppContext) {
@@ -77,15 +131,18 @@ impl GPUIDataInterface {
         std::mem::swap(&mut self.events_rx, &mut events_rx);
 
         let albumart_model = cx.global::<Models>().albumart.clone();
+        let albumart_color_model = cx.global::<Models>().albumart_color.clone();
         let queue_model = cx.global::<Models>().queue.clone();
+        let queue_metadata_model = cx.global::<Models>().queue_metadata.clone();
         let image_transfer_model = cx.global::<Models>().image_transfer_model.clone();
+        let unreadable_files_model = cx.global::<Models>().unreadable_files.clone();
 
         if let Some(events_rx) = events_rx {
             cx.spawn(|mut cx| async move {
                 loop {
                     while let Ok(event) = events_rx.try_recv() {
                         match event {
-                            DataEvent::ImageDecoded(v, image_type) => match image_type {
+                            DataEvent::ImageDecoded(v, image_type, color) => match image_type {
                                 ImageType::CurrentAlbumArt => {
                                     albumart_model
                                         .update(&mut cx, |m, cx| {
@@ -93,6 +150,12 @@ impl GPUIDataInterface {
                                             cx.notify()
                                         })
                                         .expect("failed to update albumart");
+                                    albumart_color_model
+                                        .update(&mut cx, |m, cx| {
+                                            *m = color;
+                                            cx.notify()
+                                        })
+                                        .expect("failed to update albumart color");
                                 }
                                 _ => image_transfer_model
                                     .update(&mut cx, |_, cx| cx.emit(ImageTransfer(image_type, v)))
@@ -106,16 +169,54 @@ impl GPUIDataInterface {
                                             cx.notify()
                                         })
                                         .expect("failed to update albumart");
+                                    albumart_color_model
+                                        .update(&mut cx, |m, cx| {
+                                            *m = None;
+                                            cx.notify()
+                                        })
+                                        .expect("failed to update albumart color");
                                 }
-                                _ => todo!(),
+                                _ => image_transfer_model
+                                    .update(&mut cx, |_, cx| {
+                                        cx.emit(ImageTransferFailed(image_type))
+                                    })
+                                    .expect("failed to transfer image error"),
                             },
-                            DataEvent::MetadataRead(_, item) => {
+                            DataEvent::MetadataRead(path, item) => {
+                                // the views waiting for this item are notified by the event
+                                queue_metadata_model
+                                    .update(&mut cx, |m, _| {
+                                        m.insert(path, item.clone());
+                                    })
+                                    .expect("failed to update queue metadata");
+
                                 queue_model
                                     .update(&mut cx, |_, cx| {
                                         cx.emit(item);
                                     })
                                     .expect("failed to update queue");
                             }
+                            DataEvent::MetadataUnreadable(path, failure) => {
+                                unreadable_files_model
+                                    .update(&mut cx, |m, cx| {
+                                        m.retain(|(v, _)| *v != path);
+                                        m.push((path, failure));
+                                        cx.notify();
+                                    })
+                                    .expect("failed to update unreadable files");
+                            }
+                            DataEvent::PeaksComputed(path, peaks) => cx
+                                .update(|cx| peaks_computed(cx, path, peaks))
+                                .expect("failed to update peaks"),
+                            DataEvent::CoverArtFetched(paths) => cx
+                                .update(|cx| {
+                                    let interface = cx.global::<GPUIDataInterface>();
+
+                                    for path in paths {
+                                        interface.get_metadata(path);
+                                    }
+                                })
+                                .expect("failed to read metadata"),
                         }
                     }
 
@@ -0,0 +1,213 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use ahash::AHashMap;
+use gpui::{AppContext, Global};
+use tracing::{debug, error, warn};
+
+use crate::{
+    devices::resample::convert_samples,
+    library::db::{find_track_peaks, set_track_peaks},
+    media::{builtin::providers, registry::ProviderRegistry},
+    settings::SettingsGlobal,
+    ui::{
+        app::Pool,
+        models::{Models, PlaybackInfo},
+    },
+};
+
+use super::interface::GPUIDataInterface;
+
+/// How many peaks a waveform is made of, regardless of the track's length.
+pub const PEAK_COUNT: usize = 200;
+
+/// Decodes the whole file and returns the loudest sample in each of `PEAK_COUNT` evenly sized
+/// sections of it, scaled so that the loudest peak of the track is 255. Returns None if the file
+/// can't be decoded, or if `cancelled` is set before it has been decoded.
+pub fn compute_peaks(path: &str, cancelled: &AtomicBool) -> Option<Vec<u8>> {
+    let mut registry = ProviderRegistry::new(providers());
+    let provider = registry.open(path).ok()?;
+
+    // the loudest sample of each frame, since the number of frames isn't known until the end
+    let mut frame_peaks: Vec<f32> = Vec::new();
+
+    while let Ok(frame) = provider.read_samples() {
+        if cancelled.load(Ordering::Relaxed) {
+            debug!("cancelled computing peaks for {}", path);
+            let _ = provider.stop_playback();
+            let _ = provider.close();
+            return None;
+        }
+
+        let samples: Vec<Vec<f32>> = convert_samples(frame.samples);
+        let peak = samples
+            .iter()
+            .flatten()
+            .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+
+        frame_peaks.push(peak);
+    }
+
+    let _ = provider.stop_playback();
+    let _ = provider.close();
+
+    if frame_peaks.is_empty() {
+        return None;
+    }
+
+    let peaks: Vec<f32> = (0..PEAK_COUNT)
+        .map(|i| {
+            let start = i * frame_peaks.len() / PEAK_COUNT;
+            let end = ((i + 1) * frame_peaks.len() / PEAK_COUNT).max(start + 1);
+
+            frame_peaks[start..end]
+                .iter()
+                .fold(0.0_f32, |peak, v| peak.max(*v))
+        })
+        .collect();
+
+    let loudest = peaks.iter().fold(0.0_f32, |peak, v| peak.max(*v));
+
+    if loudest <= 0.0 {
+        return Some(vec![0; PEAK_COUNT]);
+    }
+
+    Some(
+        peaks
+            .into_iter()
+            .map(|v| (v / loudest * 255.0).round() as u8)
+            .collect(),
+    )
+}
+
+/// Peaks computed for files that aren't in the library, and so can't be stored there. Each is only
+/// `PEAK_COUNT` bytes, so they're kept for the rest of the session.
+#[derive(Default)]
+struct UnstoredPeaks(AHashMap<String, Arc<Vec<u8>>>);
+
+impl Global for UnstoredPeaks {}
+
+/// Keeps the peaks of the current track up to date for the waveform seek bar. Peaks are read from
+/// the library if they have been stored, and computed by the data thread otherwise.
+pub fn setup_peaks(cx: &mut AppContext) {
+    cx.set_global(UnstoredPeaks::default());
+
+    let current_track = cx.global::<PlaybackInfo>().current_track.clone();
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+
+    cx.observe(&current_track, |_, cx| refresh_peaks(cx))
+        .detach();
+
+    let mut enabled = settings.read(cx).interface.waveform_seek_bar;
+
+    cx.observe(&settings, move |settings, cx| {
+        let now_enabled = settings.read(cx).interface.waveform_seek_bar;
+
+        if now_enabled != enabled {
+            enabled = now_enabled;
+            refresh_peaks(cx);
+        }
+    })
+    .detach();
+}
+
+fn refresh_peaks(cx: &mut AppContext) {
+    let peaks = cx.global::<Models>().peaks.clone();
+    peaks.update(cx, |m, cx| {
+        *m = None;
+        cx.notify();
+    });
+
+    // the previous track's peaks are no longer needed
+    cx.global::<GPUIDataInterface>().cancel_peaks();
+
+    if !cx
+        .global::<SettingsGlobal>()
+        .model
+        .read(cx)
+        .interface
+        .waveform_seek_bar
+    {
+        return;
+    }
+
+    let Some(track) = cx.global::<PlaybackInfo>().current_track.read(cx).clone() else {
+        return;
+    };
+
+    if let Some(unstored) = cx.global::<UnstoredPeaks>().0.get(&track).cloned() {
+        peaks.update(cx, |m, cx| {
+            *m = Some(unstored);
+            cx.notify();
+        });
+        return;
+    }
+
+    let pool = cx.global::<Pool>().0.clone();
+
+    cx.spawn(|mut cx| async move {
+        let stored = match find_track_peaks(&pool, &track).await {
+            Ok(stored) => stored,
+            Err(e) => {
+                error!("Could not retrieve peaks for {}: {:?}", track, e);
+                None
+            }
+        };
+
+        cx.update(|cx| {
+            // the track may have changed while the peaks were being retrieved
+            if cx.global::<PlaybackInfo>().current_track.read(cx).as_ref() != Some(&track) {
+                return;
+            }
+
+            match stored {
+                Some(stored) if stored.len() == PEAK_COUNT => {
+                    debug!("using stored peaks for {}", track);
+                    peaks.update(cx, |m, cx| {
+                        *m = Some(Arc::new(stored));
+                        cx.notify();
+                    });
+                }
+                Some(_) => {
+                    warn!("Stored peaks for {} are the wrong size, recomputing", track);
+                    cx.global::<GPUIDataInterface>().compute_peaks(track);
+                }
+                None => cx.global::<GPUIDataInterface>().compute_peaks(track),
+            }
+        })
+        .expect("failed to update peaks");
+    })
+    .detach();
+}
+
+/// Stores newly computed peaks in the library, and shows them if they're for the current track.
+pub fn peaks_computed(cx: &mut AppContext, path: String, peaks: Vec<u8>) {
+    let pool = cx.global::<Pool>().0.clone();
+    let peaks = Arc::new(peaks);
+
+    if cx.global::<PlaybackInfo>().current_track.read(cx).as_ref() == Some(&path) {
+        let model = cx.global::<Models>().peaks.clone();
+        let peaks = peaks.clone();
+
+        model.update(cx, |m, cx| {
+            *m = Some(peaks);
+            cx.notify();
+        });
+    }
+
+    cx.spawn(|mut cx| async move {
+        match set_track_peaks(&pool, &path, &peaks).await {
+            Ok(true) => (),
+            // files that aren't in the library are kept in memory instead
+            Ok(false) => cx
+                .update(|cx| {
+                    cx.global_mut::<UnstoredPeaks>().0.insert(path, peaks);
+                })
+                .expect("failed to update peaks"),
+            Err(e) => error!("Could not store peaks for {}: {:?}", path, e),
+        }
+    })
+    .detach();
+}
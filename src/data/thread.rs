@@ -1,39 +1,144 @@
 use std::{
+    collections::VecDeque,
     io::Cursor,
+    path::Path,
     sync::{
-        mpsc::{Receiver, Sender},
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, RecvTimeoutError, Sender},
         Arc,
     },
-    thread::sleep,
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
 use ahash::{AHashMap, RandomState};
-use gpui::{RenderImage, SharedString};
-use image::{imageops::thumbnail, Frame};
+use async_std::task;
+use gpui::{rgb, RenderImage, Rgba, SharedString};
+use image::{imageops::blur, Frame, RgbaImage};
 use smallvec::SmallVec;
-use tracing::{debug, warn};
+use sqlx::SqlitePool;
+use tracing::{debug, error, warn};
 
 use crate::{
-    media::{builtin::symphonia::SymphoniaProvider, traits::MediaProvider},
-    util::rgb_to_bgr,
+    library::db::{find_album_id_by_location, find_album_image, list_albums_with_image},
+    media::{builtin::providers, registry::ProviderRegistry},
+    settings::interface::InterfaceSettings,
+    util::{crop_square, dominant_color, image_layout, make_thumbnail, rgb_to_bgr},
 };
 
 use super::{
-    events::{DataCommand, DataEvent, ImageLayout, ImageType},
+    cover_art::CoverArtLookup,
+    disk_cache::{cache_hash_state, render_thumbnail, thumbnail_key, ThumbnailCache},
+    events::{DataCommand, DataEvent, ImageLayout, ImageType, ReadFailure},
+    filename::parse_filename,
     interface::DataInterface,
+    peaks::compute_peaks,
     types::UIQueueItem,
 };
 
-fn create_generic_queue_item(path: String) -> UIQueueItem {
+pub(super) fn decode_rgba(data: &[u8]) -> Result<RgbaImage, ()> {
+    Ok(image::ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|_| ())?
+        .decode()
+        .map_err(|_| ())?
+        .into_rgba8())
+}
+
+/// Creates a queue item for a file without tags, guessing its title and artist from its path if
+/// one of the patterns matches, and using the file's name as the title otherwise.
+/// Whether decoded images made with the old settings would look different with the new ones. Most
+/// interface settings have nothing to do with images, so changing them keeps the cache.
+fn changes_images(old: &InterfaceSettings, new: &InterfaceSettings) -> bool {
+    old.preserve_album_art_aspect != new.preserve_album_art_aspect
+        || old.dynamic_accent != new.dynamic_accent
+        || old.disable_album_art != new.disable_album_art
+        || old.image_layout != new.image_layout
+}
+
+fn create_generic_queue_item(path: String, patterns: &[String]) -> UIQueueItem {
+    let guessed = parse_filename(&path, patterns).unwrap_or_default();
+
     UIQueueItem {
-        track_name: path
-            .split(std::path::MAIN_SEPARATOR_STR)
-            .last()
-            .map(|v| SharedString::from(v.to_string()))
-            .unwrap(),
-        artist_name: SharedString::from("Unknown Artist"),
+        track_name: guessed.title.map(SharedString::from).unwrap_or_else(|| {
+            path.split(std::path::MAIN_SEPARATOR_STR)
+                .last()
+                .map(|v| SharedString::from(v.to_string()))
+                .unwrap()
+        }),
+        artist_name: guessed
+            .artist
+            .map(SharedString::from)
+            .unwrap_or_else(|| SharedString::from("Unknown Artist")),
+        quality: None,
         file_path: path,
         album_art: None,
+        duration: None,
+        album_id: None,
+    }
+}
+
+/// A decoded image, and its dominant color if it was determined.
+type Decoded = (Arc<RenderImage>, Option<Rgba>);
+
+/// A decode that has already been performed in the current batch of commands, along with every
+/// image type its result has been sent for.
+struct InFlightDecode {
+    result: Result<Decoded, ()>,
+    sent: Vec<ImageType>,
+}
+
+/// A file whose peaks are being computed on another thread.
+struct PeaksJob {
+    cancelled: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+/// How long the queue has to go without requesting anything before unused images are evicted.
+/// Requests arrive in bursts when the queue changes, before the views holding the old queue's
+/// images have been dropped, so waiting for things to settle lets a single eviction free
+/// everything.
+const EVICTION_DELAY: Duration = Duration::from_secs(2);
+/// How often unused images are evicted even if the queue hasn't changed, so that the cache can't
+/// grow without bound during long sessions.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+/// How long to wait between pre-rendered thumbnails, so that pre-rendering doesn't take up a whole
+/// core while the library is idle.
+const THUMBNAIL_INTERVAL: Duration = Duration::from_millis(25);
+
+/// Decides when unused images are evicted from the cache: `EVICTION_DELAY` after the last burst of
+/// queue activity, and at least every `EVICTION_INTERVAL` regardless.
+struct EvictionSchedule {
+    /// When the queue last requested something, if it hasn't been followed by an eviction.
+    activity: Option<Instant>,
+    last_eviction: Instant,
+}
+
+impl EvictionSchedule {
+    fn new(now: Instant) -> Self {
+        Self {
+            activity: None,
+            last_eviction: now,
+        }
+    }
+
+    /// Records that the queue requested metadata (along with the album art it shows), which means
+    /// its items have changed.
+    fn activity(&mut self, now: Instant) {
+        self.activity = Some(now);
+    }
+
+    fn next(&self) -> Instant {
+        let periodic = self.last_eviction + EVICTION_INTERVAL;
+
+        self.activity.map_or(periodic, |activity| {
+            (activity + EVICTION_DELAY).min(periodic)
+        })
+    }
+
+    fn evicted(&mut self, now: Instant) {
+        self.last_eviction = now;
+        self.activity = None;
     }
 }
 
@@ -41,14 +146,28 @@ pub struct DataThread {
     commands_rx: Receiver<DataCommand>,
     events_tx: Sender<DataEvent>,
     image_cache: AHashMap<u64, Arc<RenderImage>>,
-    // TODO: get metadata from other providers as well
-    media_provider: Box<dyn MediaProvider>,
+    /// Thumbnails from earlier sessions, if `thumbnail_cache_size` isn't 0.
+    disk_cache: ThumbnailCache,
+    /// Covers for files without embedded art, if `remote_album_art` is enabled.
+    cover_art: CoverArtLookup,
+    media_providers: ProviderRegistry,
     hash_state: RandomState,
+    settings: InterfaceSettings,
+    eviction: EvictionSchedule,
+    peaks_jobs: AHashMap<String, PeaksJob>,
+    /// The library, which queued files are looked up in.
+    pool: SqlitePool,
+    /// The albums whose art is waiting to be pre-rendered into the disk cache, in ID order.
+    pending_thumbnails: VecDeque<i64>,
+    /// The highest album ID that has been queued for pre-rendering, so that each album is only
+    /// checked against the disk cache once per session.
+    thumbnails_queued_up_to: i64,
+    next_thumbnail: Instant,
 }
 
 impl DataThread {
     /// Starts the data thread and returns the created interface.
-    pub fn start<T: DataInterface>() -> T {
+    pub fn start<T: DataInterface>(settings: InterfaceSettings, pool: SqlitePool) -> T {
         let (commands_tx, commands_rx) = std::sync::mpsc::channel();
         let (events_tx, events_rx) = std::sync::mpsc::channel();
 
@@ -57,10 +176,19 @@ impl DataThread {
             .spawn(move || {
                 let mut thread = DataThread {
                     commands_rx,
+                    cover_art: CoverArtLookup::new(events_tx.clone()),
                     events_tx,
                     image_cache: AHashMap::new(),
-                    media_provider: Box::new(SymphoniaProvider::default()),
-                    hash_state: RandomState::new(),
+                    disk_cache: ThumbnailCache::default(),
+                    media_providers: ProviderRegistry::new(providers()),
+                    hash_state: cache_hash_state(),
+                    settings,
+                    eviction: EvictionSchedule::new(Instant::now()),
+                    peaks_jobs: AHashMap::new(),
+                    pool,
+                    pending_thumbnails: VecDeque::new(),
+                    thumbnails_queued_up_to: 0,
+                    next_thumbnail: Instant::now(),
                 };
 
                 thread.run();
@@ -70,154 +198,427 @@ impl DataThread {
         T::new(commands_tx, events_rx)
     }
 
+    /// Processes commands as soon as they are recieved. `recv_timeout` blocks while the channel is
+    /// empty, so there's no need to sleep between commands, and queued commands are handled back
+    /// to back. The wait is cut short when the image cache is due to be evicted. The loop ends
+    /// when the interface (and with it the sender) is dropped.
     fn run(&mut self) {
-        while let Ok(command) = self.commands_rx.recv() {
-            match command {
-                DataCommand::DecodeImage(data, image_type, layout, thumb) => {
-                    if self.decode_image(data, image_type, layout, thumb).is_err() {
-                        self.events_tx
-                            .send(DataEvent::DecodeError(image_type))
-                            .expect("could not send event");
+        loop {
+            let next = if self.pending_thumbnails.is_empty() {
+                self.eviction.next()
+            } else {
+                self.eviction.next().min(self.next_thumbnail)
+            };
+            let timeout = next.saturating_duration_since(Instant::now());
+
+            match self.commands_rx.recv_timeout(timeout) {
+                Ok(command) => {
+                    // everything that piled up while the last batch was being processed is
+                    // handled together, so that duplicate decode requests can share a single
+                    // result
+                    let mut batch = vec![command];
+                    batch.extend(self.commands_rx.try_iter());
+
+                    let mut in_flight: AHashMap<u64, InFlightDecode> = AHashMap::new();
+
+                    for command in batch {
+                        self.handle_command(command, &mut in_flight);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if Instant::now() >= self.eviction.next() {
+                self.evict_unneeded_data();
+            }
+
+            if !self.pending_thumbnails.is_empty() && Instant::now() >= self.next_thumbnail {
+                self.prerender_thumbnail();
+                self.next_thumbnail = Instant::now() + THUMBNAIL_INTERVAL;
+            }
+        }
+    }
+
+    fn handle_command(
+        &mut self,
+        command: DataCommand,
+        in_flight: &mut AHashMap<u64, InFlightDecode>,
+    ) {
+        match command {
+            DataCommand::DecodeImage(_, image_type, ..)
+            | DataCommand::DecodeBackdrop(_, image_type, ..)
+                if self.settings.disable_album_art =>
+            {
+                debug!(
+                    "album art is disabled, skipping decode for {:?}",
+                    image_type
+                );
+                // the view that asked for the image is still waiting for it
+                self.events_tx
+                    .send(DataEvent::DecodeError(image_type))
+                    .expect("could not send event");
+            }
+            DataCommand::DecodeImage(data, image_type, layout, thumb) => {
+                let key = self.hash_state.hash_one((&data, layout, thumb, false));
+                let decode = in_flight.entry(key).or_insert_with(|| InFlightDecode {
+                    result: self.decode_image(&data, layout, thumb),
+                    sent: Vec::new(),
+                });
+
+                self.send_decoded(decode, image_type);
+            }
+            DataCommand::DecodeBackdrop(data, image_type, layout) => {
+                let key = self.hash_state.hash_one((&data, layout, false, true));
+                let decode = in_flight.entry(key).or_insert_with(|| InFlightDecode {
+                    result: self.decode_backdrop(&data, layout),
+                    sent: Vec::new(),
+                });
+
+                self.send_decoded(decode, image_type);
+            }
+            DataCommand::ReadMetadata(path) => {
+                self.eviction.activity(Instant::now());
+                let item = self.read_metadata(path.clone());
+
+                self.events_tx
+                    .send(DataEvent::MetadataRead(path, item))
+                    .expect("could not send event");
+            }
+            DataCommand::ComputePeaks(path) => {
+                // only the current track's peaks are ever shown, so the others aren't needed
+                self.peaks_jobs.retain(|job_path, job| {
+                    if *job_path != path {
+                        job.cancelled.store(true, Ordering::Relaxed);
                     }
+
+                    *job_path == path && !job.handle.is_finished()
+                });
+
+                if self.peaks_jobs.contains_key(&path) {
+                    debug!("peaks for {} are already being computed", path);
+                    return;
                 }
-                DataCommand::EvictQueueCache => self.evict_unneeded_data(),
-                DataCommand::ReadMetadata(path) => {
-                    let item = self.read_metadata(path.clone());
 
-                    self.events_tx
-                        .send(DataEvent::MetadataRead(path, item))
-                        .expect("could not send event");
+                let events_tx = self.events_tx.clone();
+                let cancelled = Arc::new(AtomicBool::new(false));
+                let job_path = path.clone();
+                let job_cancelled = cancelled.clone();
+
+                let handle = std::thread::Builder::new()
+                    .name("peaks".to_string())
+                    .spawn(move || {
+                        if let Some(peaks) = compute_peaks(&job_path, &job_cancelled) {
+                            events_tx
+                                .send(DataEvent::PeaksComputed(job_path, peaks))
+                                .expect("could not send event");
+                        } else if !job_cancelled.load(Ordering::Relaxed) {
+                            warn!("Could not compute peaks for {}", job_path);
+                        }
+                    })
+                    .expect("could not start peaks thread");
+
+                self.peaks_jobs.insert(path, PeaksJob { cancelled, handle });
+            }
+            DataCommand::ReplacePool(pool) => {
+                self.pool = pool;
+                // the new database's albums start over from the first ID
+                self.pending_thumbnails.clear();
+                self.thumbnails_queued_up_to = 0;
+            }
+            DataCommand::ClearImageCache => {
+                debug!("clearing image cache");
+                self.image_cache.clear();
+                self.disk_cache.clear();
+            }
+            DataCommand::PrerenderThumbnails => self.queue_thumbnails(),
+            DataCommand::StopPrerendering => self.pending_thumbnails.clear(),
+            DataCommand::CancelPeaks => {
+                for job in self.peaks_jobs.values() {
+                    job.cancelled.store(true, Ordering::Relaxed);
+                }
+
+                self.peaks_jobs.clear();
+            }
+            DataCommand::UpdateSettings(settings) => {
+                if changes_images(&self.settings, &settings) {
+                    debug!("image settings changed, clearing image cache");
+                    self.image_cache.clear();
+                    // decodes from earlier in the batch used the old settings
+                    in_flight.clear();
                 }
+
+                self.settings = settings;
             }
+        }
+    }
 
-            sleep(std::time::Duration::from_millis(1));
+    /// Queues the art of every album that hasn't been queued this session to be pre-rendered.
+    fn queue_thumbnails(&mut self) {
+        let result = task::block_on(list_albums_with_image(
+            &self.pool,
+            self.thumbnails_queued_up_to,
+        ));
+
+        match result {
+            Ok(albums) => {
+                if let Some(last) = albums.last() {
+                    self.thumbnails_queued_up_to = *last;
+                }
+
+                debug!(
+                    "checking {} albums for thumbnails to pre-render",
+                    albums.len()
+                );
+                self.pending_thumbnails.extend(albums);
+            }
+            Err(e) => error!("Database error while listing album art: {:?}", e),
         }
     }
 
+    /// Renders the thumbnail of the next queued album into the disk cache, unless it's already
+    /// there. Thumbnails that have been shown are never removed to make room for pre-rendered
+    /// ones, so pre-rendering stops once the cache is full.
+    fn prerender_thumbnail(&mut self) {
+        let max_size = self.settings.thumbnail_cache_size * 1024 * 1024;
+
+        if self.settings.disable_album_art || max_size == 0 {
+            self.pending_thumbnails.clear();
+            return;
+        }
+
+        let Some(album_id) = self.pending_thumbnails.pop_front() else {
+            return;
+        };
+
+        let image = match task::block_on(find_album_image(&self.pool, album_id)) {
+            Ok(Some(image)) => image,
+            Ok(None) => return,
+            Err(e) => {
+                error!("Database error while reading album art: {:?}", e);
+                return;
+            }
+        };
+
+        let crop = !self.settings.preserve_album_art_aspect;
+        let layout = image_layout();
+        let key = thumbnail_key(&image, crop, layout);
+
+        if self.disk_cache.contains(key) {
+            return;
+        }
+
+        let Some(thumb) = render_thumbnail(&image, crop, layout) else {
+            warn!("Could not decode art of album {}", album_id);
+            return;
+        };
+
+        if self.disk_cache.insert_if_room(key, &thumb, max_size) {
+            debug!("pre-rendered thumbnail for album {}", album_id);
+        } else {
+            debug!("thumbnail cache is full, stopping pre-rendering");
+            self.pending_thumbnails.clear();
+        }
+    }
+
+    /// Sends the result of a decode to the main thread, unless it has already been sent for the
+    /// specified image type.
+    fn send_decoded(&self, decode: &mut InFlightDecode, image_type: ImageType) {
+        if decode.sent.contains(&image_type) {
+            debug!("skipping duplicate decode request for {:?}", image_type);
+            return;
+        }
+
+        decode.sent.push(image_type);
+
+        let event = match &decode.result {
+            Ok((image, color)) => DataEvent::ImageDecoded(image.clone(), image_type, *color),
+            Err(()) => DataEvent::DecodeError(image_type),
+        };
+
+        self.events_tx.send(event).expect("could not send event");
+    }
+
     // The only real possible error here is if the image format is unsupported, or the image is
     // corrupt. In either case, there's literally nothing we can do about it, and the only
-    // required information is that there was an error. So, we just return `Result<(), ()>`.
+    // required information is that there was an error. So, we just return `Result<_, ()>`.
     fn decode_image(
         &self,
-        data: Box<[u8]>,
-        image_type: ImageType,
+        data: &[u8],
         image_layout: ImageLayout,
         thumb: bool,
-    ) -> Result<(), ()> {
-        let mut image = image::ImageReader::new(Cursor::new(data.clone()))
-            .with_guessed_format()
-            .map_err(|_| ())?
-            .decode()
-            .map_err(|_| ())?
-            .into_rgba8();
+    ) -> Result<Decoded, ()> {
+        let mut image = decode_rgba(data)?;
 
         if image_layout == ImageLayout::BGR {
             rgb_to_bgr(&mut image);
         }
 
+        let crop = !self.settings.preserve_album_art_aspect;
+        let mut color = None;
+
         if thumb {
-            self.events_tx
-                .send(DataEvent::ImageDecoded(
-                    Arc::new(RenderImage::new(SmallVec::from_vec(vec![Frame::new(
-                        thumbnail(&image, 80, 80),
-                    )]))),
-                    image_type,
-                ))
-                .expect("could not send event");
-        } else {
-            self.events_tx
-                .send(DataEvent::ImageDecoded(
-                    Arc::new(RenderImage::new(SmallVec::from_vec(vec![Frame::new(
-                        image,
-                    )]))),
-                    image_type,
-                ))
-                .expect("could not send event");
+            image = make_thumbnail(&image, 80, crop);
+
+            // the thumbnail is small enough that finding its color is cheap
+            if self.settings.dynamic_accent {
+                color = dominant_color(&image).map(|[r, g, b]| {
+                    let (r, b) = match image_layout {
+                        ImageLayout::BGR => (b, r),
+                        ImageLayout::RGB => (r, b),
+                    };
+
+                    rgb(u32::from_be_bytes([0, r, g, b]))
+                });
+            }
+        } else if crop {
+            image = crop_square(&image);
         }
 
-        Ok(())
+        Ok((
+            Arc::new(RenderImage::new(SmallVec::from_vec(vec![Frame::new(
+                image,
+            )]))),
+            color,
+        ))
+    }
+
+    fn decode_backdrop(&self, data: &[u8], image_layout: ImageLayout) -> Result<Decoded, ()> {
+        let mut image = decode_rgba(data)?;
+
+        if image_layout == ImageLayout::BGR {
+            rgb_to_bgr(&mut image);
+        }
+
+        // the backdrop is going to be blurry anyways, so there's no point in blurring (or
+        // uploading) a full size image - it's scaled back up by the GPU
+        let small = make_thumbnail(&image, 64, !self.settings.preserve_album_art_aspect);
+        let blurred = blur(&small, 4.0);
+
+        Ok((
+            Arc::new(RenderImage::new(SmallVec::from_vec(vec![Frame::new(
+                blurred,
+            )]))),
+            None,
+        ))
+    }
+
+    /// Reports that the file's metadata couldn't be read, and returns a queue item made from its
+    /// path instead.
+    fn unreadable(&self, path: String, failure: ReadFailure) -> UIQueueItem {
+        self.events_tx
+            .send(DataEvent::MetadataUnreadable(path.clone(), failure))
+            .expect("could not send event");
+
+        create_generic_queue_item(path, &self.settings.filename_patterns)
     }
 
     fn read_metadata(&mut self, path: String) -> UIQueueItem {
-        let file = if let Ok(file) = std::fs::File::open(path.clone()) {
-            file
-        } else {
+        if !Path::new(&path).exists() {
             warn!("Failed to open file {}, queue may be desynced", path);
             warn!("Ensure the file exists before placing it in the queue");
-            return create_generic_queue_item(path);
-        };
-
-        if self
-            .media_provider
-            .open(file, path.split(".").last().map(|v| v.to_string()))
-            .is_err()
-        {
-            warn!("Media provider couldn't open file, creating generic queue item");
-            return create_generic_queue_item(path);
+            return self.unreadable(path, ReadFailure::Missing);
         }
 
-        if self.media_provider.start_playback().is_err() {
-            warn!("Media provider couldn't start playback, creating generic queue item");
-            return create_generic_queue_item(path);
-        }
+        let Ok(media_provider) = self.media_providers.open(&path) else {
+            warn!("No media provider could open the file, creating generic queue item");
+            return self.unreadable(path, ReadFailure::Unopenable);
+        };
 
-        let metadata = if let Ok(metadata) = self.media_provider.read_metadata() {
+        let metadata = if let Ok(metadata) = media_provider.read_metadata() {
             metadata.clone()
         } else {
             warn!("Media provider couldn't retrieve metadata, creating generic queue item");
-            return create_generic_queue_item(path);
+            return self.unreadable(path, ReadFailure::NoMetadata);
         };
+        let duration = media_provider.duration_secs().ok();
 
-        let album_art = self
-            .media_provider
-            .read_image()
-            .ok()
-            .flatten()
-            .and_then(|v| {
-                // we do this because we do not want to be storing entire encoded images
-                // long-term, collisions don't particuarly matter here so the benefits outweigh
-                // the tradeoffs
-                let key = self.hash_state.hash_one(v.clone());
-
-                if let Some(cached) = self.image_cache.get(&key) {
-                    debug!("Image cache hit for key {}", key);
-                    Some(cached.clone())
-                } else {
-                    debug!("Image cache miss for key {}, decoding and caching", key);
-                    let mut image = image::ImageReader::new(Cursor::new(v.clone()))
-                        .with_guessed_format()
-                        .map_err(|_| ())
-                        .ok()?
-                        .decode()
-                        .ok()?
-                        .into_rgba8();
-
-                    rgb_to_bgr(&mut image);
-
-                    let value = Arc::new(RenderImage::new(SmallVec::from_vec(vec![Frame::new(
-                        thumbnail(&image, 80, 80),
-                    )])));
-                    self.image_cache.insert(key, value.clone());
-
-                    Some(value)
+        let album_art = if self.settings.disable_album_art {
+            None
+        } else {
+            media_provider.read_image().ok().flatten()
+        };
+
+        let album_art = album_art.or_else(|| {
+            if self.settings.disable_album_art || !self.settings.remote_album_art {
+                return None;
+            }
+
+            self.cover_art
+                .lookup(metadata.mbid_album.as_deref()?, &path)
+        });
+
+        let album_art = album_art.and_then(|v| {
+            // we do this because we do not want to be storing entire encoded images
+            // long-term, collisions don't particuarly matter here so the benefits outweigh
+            // the tradeoffs
+            let crop = !self.settings.preserve_album_art_aspect;
+            let layout = image_layout();
+            let key = thumbnail_key(&v, crop, layout);
+            let disk_cache_size = self.settings.thumbnail_cache_size * 1024 * 1024;
+
+            if let Some(cached) = self.image_cache.get(&key) {
+                debug!("Image cache hit for key {}", key);
+                return Some(cached.clone());
+            }
+
+            let thumb = if let Some(thumb) = (disk_cache_size > 0)
+                .then(|| self.disk_cache.get(key))
+                .flatten()
+            {
+                debug!("Disk cache hit for key {}", key);
+                thumb
+            } else {
+                debug!("Image cache miss for key {}, decoding and caching", key);
+                let thumb = render_thumbnail(&v, crop, layout)?;
+
+                if disk_cache_size > 0 {
+                    self.disk_cache.insert(key, &thumb, disk_cache_size);
                 }
-            });
+
+                thumb
+            };
+
+            let value = Arc::new(RenderImage::new(SmallVec::from_vec(vec![Frame::new(
+                thumb,
+            )])));
+            self.image_cache.insert(key, value.clone());
+
+            Some(value)
+        });
+
+        // files that were queued from outside the library may still be in it
+        let album_id = match task::block_on(find_album_id_by_location(&self.pool, &path)) {
+            Ok(album_id) => album_id,
+            Err(e) => {
+                error!("Could not find album of {}: {:?}", path, e);
+                None
+            }
+        };
+
+        let generic = create_generic_queue_item(path.clone(), &self.settings.filename_patterns);
+        let artist_name = metadata
+            .artist()
+            .map(SharedString::from)
+            .unwrap_or(generic.artist_name);
 
         UIQueueItem {
-            file_path: path.clone(),
+            file_path: path,
             track_name: metadata
                 .name
                 .map(SharedString::from)
-                .unwrap_or_else(|| create_generic_queue_item(path).track_name),
-            artist_name: metadata
-                .artist
-                .map(SharedString::from)
-                .unwrap_or_else(|| SharedString::from("Unknown Artist")),
+                .unwrap_or(generic.track_name),
+            artist_name,
+            quality: metadata.quality().map(SharedString::from),
             album_art,
+            duration,
+            album_id,
         }
     }
 
     fn evict_unneeded_data(&mut self) {
+        self.eviction.evicted(Instant::now());
+
         // we have to duplicate this data in order to get around borrowing rules
         let keys: Vec<u64> = self.image_cache.keys().cloned().collect();
 
@@ -232,3 +633,65 @@ impl DataThread {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eviction_is_periodic_without_activity() {
+        let start = Instant::now();
+        let mut schedule = EvictionSchedule::new(start);
+
+        assert_eq!(schedule.next(), start + EVICTION_INTERVAL);
+
+        schedule.evicted(start + EVICTION_INTERVAL);
+        assert_eq!(schedule.next(), start + EVICTION_INTERVAL * 2);
+    }
+
+    #[test]
+    fn eviction_waits_for_activity_to_settle() {
+        let start = Instant::now();
+        let mut schedule = EvictionSchedule::new(start);
+
+        schedule.activity(start + Duration::from_secs(10));
+        assert_eq!(
+            schedule.next(),
+            start + Duration::from_secs(10) + EVICTION_DELAY
+        );
+
+        // each request in a burst pushes eviction back
+        schedule.activity(start + Duration::from_secs(11));
+        assert_eq!(
+            schedule.next(),
+            start + Duration::from_secs(11) + EVICTION_DELAY
+        );
+
+        // and evicting goes back to the periodic schedule
+        let evicted = start + Duration::from_secs(11) + EVICTION_DELAY;
+        schedule.evicted(evicted);
+        assert_eq!(schedule.next(), evicted + EVICTION_INTERVAL);
+    }
+
+    #[test]
+    fn constant_activity_still_evicts_periodically() {
+        let start = Instant::now();
+        let mut schedule = EvictionSchedule::new(start);
+
+        schedule.activity(start + EVICTION_INTERVAL - Duration::from_millis(500));
+        assert_eq!(schedule.next(), start + EVICTION_INTERVAL);
+    }
+
+    #[test]
+    fn only_image_settings_clear_the_cache() {
+        let old = InterfaceSettings::default();
+
+        let mut new = old.clone();
+        new.date_format = Some("%Y-%m-%d".to_string());
+        new.log_level = Some("debug".to_string());
+        assert!(!changes_images(&old, &new));
+
+        new.preserve_album_art_aspect = !old.preserve_album_art_aspect;
+        assert!(changes_images(&old, &new));
+    }
+}
@@ -6,6 +6,11 @@ use gpui::{RenderImage, SharedString};
 pub struct UIQueueItem {
     pub track_name: SharedString,
     pub artist_name: SharedString,
+    pub quality: Option<SharedString>,
     pub file_path: String,
     pub album_art: Option<Arc<RenderImage>>,
+    /// The duration of the file in seconds, if it's known.
+    pub duration: Option<u64>,
+    /// The album the file belongs to, if it's in the library.
+    pub album_id: Option<i64>,
 }
@@ -2,5 +2,6 @@ pub mod builtin;
 pub mod errors;
 pub mod format;
 pub mod resample;
+pub mod stretch;
 pub mod traits;
 pub mod util;
@@ -43,6 +43,16 @@ pub struct FormatInfo {
     pub buffer_size: BufferSize,
     pub channels: ChannelSpec,
 }
+
+/// The format audio is actually being sent to the device in, after any resampling or
+/// downmixing, and the device it's being sent to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputInfo {
+    pub format: FormatInfo,
+    pub device_name: Option<String>,
+    pub device_uid: Option<String>,
+}
+
 pub struct SupportedFormat {
     pub originating_provider: &'static str,
     pub sample_type: SampleFormat,
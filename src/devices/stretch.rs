@@ -0,0 +1,231 @@
+use crate::media::playback::{PlaybackFrame, Samples};
+
+use super::resample::convert_samples;
+
+/// The slowest supported playback speed.
+pub const MIN_SPEED: f64 = 0.5;
+/// The fastest supported playback speed.
+pub const MAX_SPEED: f64 = 2.0;
+
+/// Changes the speed of audio without changing its pitch, using WSOLA (waveform similarity
+/// overlap-add). Short overlapping segments of the input are crossfaded together, each one taken
+/// from near where it would be at the requested speed but shifted so that its waveform lines up
+/// with the end of the previous segment.
+///
+/// The output is produced in frames of exactly `block` samples, since the resampler requires
+/// fixed size input. At normal speed the input is passed through untouched.
+pub struct TimeStretcher {
+    block: usize,
+    channels: usize,
+    rate: u32,
+    /// The length of the crossfade between segments. Each segment is twice this long.
+    overlap: usize,
+    /// How far a segment can be moved from its nominal position to line it up.
+    search: usize,
+    /// The fade-in curve of the crossfade.
+    window: Vec<f32>,
+    input: Vec<Vec<f32>>,
+    /// The nominal position of the next segment in `input`.
+    position: f64,
+    /// The position (in `input`) of the second half of the last segment, which hasn't been
+    /// output yet. None if no segment has been taken from the current input.
+    tail: Option<usize>,
+    output: Vec<Vec<f32>>,
+}
+
+impl TimeStretcher {
+    pub fn new(block: usize) -> Self {
+        TimeStretcher {
+            block,
+            channels: 0,
+            rate: 0,
+            overlap: 0,
+            search: 0,
+            window: Vec::new(),
+            input: Vec::new(),
+            position: 0.0,
+            tail: None,
+            output: Vec::new(),
+        }
+    }
+
+    /// Discards any buffered audio, for when playback jumps to a different position.
+    pub fn reset(&mut self) {
+        self.input.iter_mut().for_each(Vec::clear);
+        self.output.iter_mut().for_each(Vec::clear);
+        self.position = 0.0;
+        self.tail = None;
+    }
+
+    fn configure(&mut self, channels: usize, rate: u32) {
+        self.channels = channels;
+        self.rate = rate;
+        // 12.5ms segments are short enough to avoid audible echoes, and long enough to contain a
+        // few periods of most voices
+        self.overlap = (rate as usize / 80).max(64);
+        self.search = self.overlap / 2;
+        self.window = (0..self.overlap)
+            .map(|i| {
+                let x = i as f32 / self.overlap as f32;
+                0.5 - 0.5 * (x * std::f32::consts::PI).cos()
+            })
+            .collect();
+        self.input = vec![Vec::new(); channels];
+        self.output = vec![Vec::new(); channels];
+        self.position = 0.0;
+        self.tail = None;
+    }
+
+    fn buffered(&self) -> bool {
+        self.tail.is_some() || self.output.first().is_some_and(|v| !v.is_empty())
+    }
+
+    /// Stretches the frame to play at the specified speed, returning any output that is ready.
+    pub fn process(&mut self, frame: PlaybackFrame, speed: f64) -> Vec<PlaybackFrame> {
+        let speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        let rate = frame.rate;
+
+        if speed == 1.0 && !self.buffered() {
+            return vec![frame];
+        }
+
+        let samples: Vec<Vec<f32>> = convert_samples(frame.samples);
+
+        if samples.len() != self.channels || rate != self.rate {
+            self.configure(samples.len(), rate);
+        }
+
+        if speed == 1.0 {
+            // the untouched half of the last segment continues straight into the new input
+            if let Some(tail) = self.tail.take() {
+                for (output, input) in self.output.iter_mut().zip(self.input.iter_mut()) {
+                    output.extend_from_slice(&input[tail..]);
+                    input.clear();
+                }
+                self.position = 0.0;
+            }
+
+            for (output, samples) in self.output.iter_mut().zip(samples) {
+                output.extend(samples);
+            }
+        } else {
+            for (input, samples) in self.input.iter_mut().zip(samples) {
+                input.extend(samples);
+            }
+
+            self.stretch(speed);
+        }
+
+        self.drain_blocks(rate)
+    }
+
+    fn stretch(&mut self, speed: f64) {
+        let overlap = self.overlap;
+        let hop = overlap as f64 * speed;
+        let len = self.input[0].len();
+
+        loop {
+            let nominal = self.position.round() as usize;
+
+            // both halves of the furthest possible segment have to be available
+            if nominal + self.search + overlap * 2 > len {
+                break;
+            }
+
+            let start = match self.tail {
+                None => {
+                    for (output, input) in self.output.iter_mut().zip(self.input.iter()) {
+                        output.extend_from_slice(&input[nominal..nominal + overlap]);
+                    }
+
+                    nominal
+                }
+                Some(tail) => {
+                    let start = self.best_match(tail, nominal);
+
+                    for (output, input) in self.output.iter_mut().zip(self.input.iter()) {
+                        output.extend(
+                            input[tail..tail + overlap]
+                                .iter()
+                                .zip(&input[start..start + overlap])
+                                .zip(&self.window)
+                                .map(|((a, b), w)| a * (1.0 - w) + b * w),
+                        );
+                    }
+
+                    start
+                }
+            };
+
+            self.tail = Some(start + overlap);
+            self.position += hop;
+        }
+
+        // drop the input that can't be used by any future segment
+        let keep_from = self
+            .tail
+            .unwrap_or(0)
+            .min((self.position as usize).saturating_sub(self.search));
+
+        if keep_from > 0 {
+            for input in self.input.iter_mut() {
+                input.drain(..keep_from);
+            }
+
+            self.position -= keep_from as f64;
+            self.tail = self.tail.map(|v| v - keep_from);
+        }
+    }
+
+    /// Finds the start of the segment near `nominal` that lines up best with the segment at
+    /// `tail`, by comparing their normalized cross-correlation.
+    fn best_match(&self, tail: usize, nominal: usize) -> usize {
+        let overlap = self.overlap;
+        let mono = |start: usize, i: usize| -> f32 {
+            self.input.iter().map(|channel| channel[start + i]).sum()
+        };
+        let template: Vec<f32> = (0..overlap).map(|i| mono(tail, i)).collect();
+
+        let mut best = nominal;
+        let mut best_score = f32::MIN;
+
+        for start in nominal.saturating_sub(self.search)..=nominal + self.search {
+            let mut correlation = 0.0;
+            let mut energy = 0.0;
+
+            for (i, t) in template.iter().enumerate() {
+                let v = mono(start, i);
+                correlation += t * v;
+                energy += v * v;
+            }
+
+            let score = correlation / energy.sqrt().max(f32::EPSILON);
+
+            if score > best_score {
+                best_score = score;
+                best = start;
+            }
+        }
+
+        best
+    }
+
+    fn drain_blocks(&mut self, rate: u32) -> Vec<PlaybackFrame> {
+        let mut frames = Vec::new();
+
+        while self.output.first().is_some_and(|v| v.len() >= self.block) {
+            let samples = self
+                .output
+                .iter_mut()
+                .map(|channel| channel.drain(..self.block).collect())
+                .collect();
+
+            frames.push(PlaybackFrame {
+                samples: Samples::Float32(samples),
+                rate,
+            });
+        }
+
+        frames
+    }
+}
@@ -0,0 +1,247 @@
+//! Communication with an already running instance of Muzak. The running instance listens on a
+//! local socket, and other invocations of the binary forward their commands to it instead of
+//! opening a second window.
+//!
+//! Each connection carries a single request: one line of JSON containing an [`IpcCommand`],
+//! answered by one line of JSON containing an [`IpcResponse`].
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver, Sender},
+    time::Duration,
+};
+
+use gpui::AppContext;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    library::db::{block_on_ui, find_track_by_query},
+    playback::{interface::GPUIPlaybackInterface, thread::PlaybackState},
+    ui::{
+        app::{get_dirs, Pool},
+        models::PlaybackInfo,
+    },
+};
+
+/// How long a client waits for the running instance to respond.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum IpcCommand {
+    /// Searches the library and plays the best matching track.
+    Play(String),
+    PlayPause,
+    Pause,
+    Next,
+    Previous,
+    /// Adds the specified files to the end of the queue.
+    Enqueue(Vec<String>),
+    /// Replaces the queue with the specified files and starts playing them.
+    ReplaceQueue(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum IpcResponse {
+    Ok,
+    /// The search performed by [`IpcCommand::Play`] didn't match anything.
+    NotFound,
+    Error(String),
+}
+
+fn socket_path() -> PathBuf {
+    let dirs = get_dirs();
+
+    dirs.runtime_dir()
+        .unwrap_or_else(|| dirs.data_dir())
+        .join("muzak.sock")
+}
+
+/// Sends a command to the running instance. Returns Err if no instance is running.
+#[cfg(unix)]
+pub fn send(command: &IpcCommand) -> Result<IpcResponse, ()> {
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(socket_path()).map_err(|_| ())?;
+    stream
+        .set_read_timeout(Some(RESPONSE_TIMEOUT))
+        .map_err(|_| ())?;
+
+    let mut request = serde_json::to_string(command).expect("couldn't serialize command");
+    request.push('\n');
+    stream.write_all(request.as_bytes()).map_err(|_| ())?;
+
+    let mut response = String::new();
+    BufReader::new(stream)
+        .read_line(&mut response)
+        .map_err(|_| ())?;
+
+    serde_json::from_str(&response).map_err(|e| {
+        warn!("Running instance sent an invalid response: {:?}", e);
+    })
+}
+
+// TODO: named pipes on Windows
+#[cfg(not(unix))]
+pub fn send(_: &IpcCommand) -> Result<IpcResponse, ()> {
+    warn!("Controlling a running instance isn't supported on this platform yet");
+    Err(())
+}
+
+type Request = (IpcCommand, Sender<IpcResponse>);
+
+/// Starts listening for commands from other invocations of Muzak. Commands are received on a
+/// separate thread and handled on the main thread.
+pub fn start_server(cx: &mut AppContext) {
+    let Some(requests_rx) = listen() else {
+        return;
+    };
+
+    cx.spawn(|mut cx| async move {
+        loop {
+            while let Ok((command, response_tx)) = requests_rx.try_recv() {
+                let Ok(response) = cx.update(|cx| handle_command(command, cx)) else {
+                    return;
+                };
+
+                // the client may have given up waiting
+                let _ = response_tx.send(response);
+            }
+
+            cx.background_executor()
+                .timer(Duration::from_millis(10))
+                .await;
+        }
+    })
+    .detach();
+}
+
+#[cfg(unix)]
+fn listen() -> Option<Receiver<Request>> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let path = socket_path();
+
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            warn!("Another instance is already listening for commands");
+            return None;
+        }
+
+        // left over from an instance that didn't shut down cleanly
+        if let Err(e) = std::fs::remove_file(&path) {
+            warn!("Couldn't remove stale socket {:?}: {:?}", path, e);
+            return None;
+        }
+    }
+
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Couldn't listen for commands on {:?}: {:?}", path, e);
+            return None;
+        }
+    };
+
+    let (requests_tx, requests_rx) = channel::<Request>();
+
+    std::thread::Builder::new()
+        .name("ipc".to_string())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(&stream);
+                let mut request = String::new();
+
+                if reader.read_line(&mut request).is_err() {
+                    continue;
+                }
+
+                let response = match serde_json::from_str::<IpcCommand>(&request) {
+                    Ok(command) => {
+                        let (response_tx, response_rx) = channel();
+                        requests_tx
+                            .send((command, response_tx))
+                            .expect("could not send tx");
+
+                        response_rx
+                            .recv_timeout(RESPONSE_TIMEOUT)
+                            .unwrap_or_else(|_| {
+                                IpcResponse::Error("timed out waiting for a response".to_string())
+                            })
+                    }
+                    Err(e) => IpcResponse::Error(format!("invalid command: {}", e)),
+                };
+
+                let mut response =
+                    serde_json::to_string(&response).expect("couldn't serialize response");
+                response.push('\n');
+
+                let _ = (&stream).write_all(response.as_bytes());
+            }
+        })
+        .expect("could not start ipc thread");
+
+    info!("Listening for commands on {:?}", path);
+
+    Some(requests_rx)
+}
+
+#[cfg(not(unix))]
+fn listen() -> Option<Receiver<Request>> {
+    None
+}
+
+fn handle_command(command: IpcCommand, cx: &mut AppContext) -> IpcResponse {
+    info!("Received command from another instance: {:?}", command);
+
+    let interface = cx.global::<GPUIPlaybackInterface>();
+
+    match command {
+        IpcCommand::Play(query) => {
+            let Some(pool) = cx.try_global::<Pool>() else {
+                return IpcResponse::Error("the library isn't available".to_string());
+            };
+
+            // this runs on the UI thread, so a locked database can't be waited on for long
+            let result = block_on_ui(find_track_by_query(&pool.0, &query));
+
+            match result {
+                Ok(Some(location)) => {
+                    crate::playback::interface::replace_queue(vec![location], cx);
+                    IpcResponse::Ok
+                }
+                Ok(None) => IpcResponse::NotFound,
+                Err(e) => IpcResponse::Error(format!("database error: {}", e)),
+            }
+        }
+        IpcCommand::PlayPause => {
+            match cx.global::<PlaybackInfo>().playback_state.read(cx) {
+                PlaybackState::Playing => interface.pause(),
+                _ => interface.play(),
+            }
+
+            IpcResponse::Ok
+        }
+        IpcCommand::Pause => {
+            interface.pause();
+            IpcResponse::Ok
+        }
+        IpcCommand::Next => {
+            interface.next();
+            IpcResponse::Ok
+        }
+        IpcCommand::Previous => {
+            crate::playback::history::previous(cx);
+            IpcResponse::Ok
+        }
+        IpcCommand::Enqueue(paths) => {
+            interface.queue_list(paths);
+            IpcResponse::Ok
+        }
+        IpcCommand::ReplaceQueue(paths) => {
+            crate::playback::interface::replace_queue(paths, cx);
+            IpcResponse::Ok
+        }
+    }
+}
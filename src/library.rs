@@ -1,3 +1,5 @@
 pub mod db;
+pub mod import;
+pub mod reset;
 pub mod scan;
 pub mod types;
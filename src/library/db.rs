@@ -1,14 +1,19 @@
-use std::{path::Path, sync::Arc, time::Duration};
+use std::{future::Future, path::Path, sync::Arc, time::Duration};
 
-use async_std::task;
+use async_std::{future::timeout, task};
+use chrono::{DateTime, Utc};
 use gpui::{AppContext, Global};
 use moka::future::Cache;
-use sqlx::{sqlite::SqliteConnectOptions, SqlitePool};
-use tracing::debug;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteSynchronous},
+    SqlitePool,
+};
+use tracing::{debug, warn};
 
-use crate::ui::app::Pool;
+use crate::{media::metadata::CreditRole, ui::app::Pool};
 
-use super::types::{Album, Artist, Track};
+use super::types::{Album, Artist, ResumePosition, SearchResult, Track};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AlbumMethod {
@@ -17,11 +22,22 @@ pub enum AlbumMethod {
     UncachedThumb,
 }
 
+/// How long a connection waits for another one to finish writing before giving up with "database
+/// is locked".
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a query from the interface can take before it's given up on. These queries block the
+/// UI thread, so they fail quickly instead of waiting out `BUSY_TIMEOUT` while the scanner writes.
+const UI_QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub async fn create_pool(path: impl AsRef<Path>) -> Result<SqlitePool, sqlx::Error> {
     debug!("Creating database pool at {:?}", path.as_ref());
+    // with write-ahead logging, reads don't have to wait for the scanner's writes
     let options = SqliteConnectOptions::new()
         .filename(path)
-        .create_if_missing(true);
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(BUSY_TIMEOUT);
     let pool = SqlitePool::connect_with(options).await?;
 
     sqlx::migrate!("./migrations").run(&pool).await?;
@@ -29,6 +45,17 @@ pub async fn create_pool(path: impl AsRef<Path>) -> Result<SqlitePool, sqlx::Err
     Ok(pool)
 }
 
+/// Runs a query on the UI thread, giving up on it with `PoolTimedOut` if it takes longer than
+/// `UI_QUERY_TIMEOUT`.
+pub(crate) fn block_on_ui<T>(
+    query: impl Future<Output = Result<T, sqlx::Error>>,
+) -> Result<T, sqlx::Error> {
+    task::block_on(timeout(UI_QUERY_TIMEOUT, query)).unwrap_or_else(|_| {
+        warn!("Database query took too long, giving up on it");
+        Err(sqlx::Error::PoolTimedOut)
+    })
+}
+
 pub struct DbCache {
     artist_name_cache: Cache<i64, Arc<String>>,
     album_cache: Cache<i64, Arc<Album>>,
@@ -37,6 +64,13 @@ pub struct DbCache {
 
 impl Global for DbCache {}
 
+impl DbCache {
+    /// Drops every cached album, for when their records have been changed.
+    pub fn invalidate_albums(&self) {
+        self.album_cache.invalidate_all();
+    }
+}
+
 pub fn create_cache() -> DbCache {
     let artist_name_cache = Cache::builder()
         .time_to_live(Duration::from_secs(60 * 5))
@@ -58,10 +92,45 @@ pub fn create_cache() -> DbCache {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum AlbumSortMethod {
+    #[default]
     TitleAsc,
     TitleDesc,
+    /// By album artist, then chronologically.
+    Artist,
+    /// Newest releases first, with undated releases last.
+    ReleaseDate,
+    /// The most recently scanned albums first.
+    DateAdded,
+    /// By the total play count of each album's tracks.
+    MostPlayed,
+}
+
+impl AlbumSortMethod {
+    /// The sort method that follows this one when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            AlbumSortMethod::TitleAsc => AlbumSortMethod::TitleDesc,
+            AlbumSortMethod::TitleDesc => AlbumSortMethod::Artist,
+            AlbumSortMethod::Artist => AlbumSortMethod::ReleaseDate,
+            AlbumSortMethod::ReleaseDate => AlbumSortMethod::DateAdded,
+            AlbumSortMethod::DateAdded => AlbumSortMethod::MostPlayed,
+            AlbumSortMethod::MostPlayed => AlbumSortMethod::TitleAsc,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AlbumSortMethod::TitleAsc => "Title (A-Z)",
+            AlbumSortMethod::TitleDesc => "Title (Z-A)",
+            AlbumSortMethod::Artist => "Artist",
+            AlbumSortMethod::ReleaseDate => "Release date",
+            AlbumSortMethod::DateAdded => "Date added",
+            AlbumSortMethod::MostPlayed => "Most played",
+        }
+    }
 }
 
 pub async fn list_albums(
@@ -75,6 +144,18 @@ pub async fn list_albums(
         AlbumSortMethod::TitleDesc => {
             include_str!("../../queries/library/find_albums_title_desc.sql")
         }
+        AlbumSortMethod::Artist => {
+            include_str!("../../queries/library/find_albums_artist_asc.sql")
+        }
+        AlbumSortMethod::ReleaseDate => {
+            include_str!("../../queries/library/find_albums_release_date_desc.sql")
+        }
+        AlbumSortMethod::DateAdded => {
+            include_str!("../../queries/library/find_albums_date_added_desc.sql")
+        }
+        AlbumSortMethod::MostPlayed => {
+            include_str!("../../queries/library/find_albums_play_count_desc.sql")
+        }
     };
 
     let albums = sqlx::query_as::<_, (u32, String)>(query)
@@ -100,6 +181,22 @@ pub async fn list_tracks_in_album(
     Ok(albums)
 }
 
+/// Returns the credits of every track in the album, as (track ID, role, name), in track order.
+pub async fn list_album_credits(
+    pool: &SqlitePool,
+    album_id: i64,
+) -> Result<Vec<(i64, CreditRole, String)>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_album_credits.sql");
+
+    let credits: Vec<(i64, String, String)> =
+        sqlx::query_as(query).bind(album_id).fetch_all(pool).await?;
+
+    Ok(credits
+        .into_iter()
+        .filter_map(|(track_id, role, name)| Some((track_id, CreditRole::parse(&role)?, name)))
+        .collect())
+}
+
 pub async fn get_album_by_id(
     pool: &SqlitePool,
     db_cache: &DbCache,
@@ -187,9 +284,500 @@ pub async fn get_artist_by_id(
     }
 }
 
+/// Finds a track in the library by its title and one of its artists (either a track artist or
+/// the album artist), ignoring case. Returns the location of the first match.
+pub async fn find_track_location(
+    pool: &SqlitePool,
+    artist: &str,
+    title: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_track_by_artist_title.sql");
+
+    sqlx::query_scalar(query)
+        .bind(title)
+        .bind(artist)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Finds the album by the same album artist that follows the album containing the specified
+/// track, ordered by release date and then title.
+pub async fn find_next_album(
+    pool: &SqlitePool,
+    location: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_next_album_by_artist.sql");
+
+    sqlx::query_scalar(query)
+        .bind(location)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn find_album_id_by_location(
+    pool: &SqlitePool,
+    location: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_album_id_by_location.sql");
+
+    // tracks without an album have a NULL album_id
+    sqlx::query_scalar::<_, Option<i64>>(query)
+        .bind(location)
+        .fetch_optional(pool)
+        .await
+        .map(Option::flatten)
+}
+
+/// Returns the IDs of the albums with art whose ID is greater than `after`, in ascending order.
+pub async fn list_albums_with_image(
+    pool: &SqlitePool,
+    after: i64,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let query = include_str!("../../queries/library/list_albums_with_image.sql");
+
+    sqlx::query_scalar(query).bind(after).fetch_all(pool).await
+}
+
+/// Returns the full size art of the specified album, or None if it has none.
+pub async fn find_album_image(
+    pool: &SqlitePool,
+    album_id: i64,
+) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_album_image.sql");
+
+    sqlx::query_scalar::<_, Option<Vec<u8>>>(query)
+        .bind(album_id)
+        .fetch_optional(pool)
+        .await
+        .map(Option::flatten)
+}
+
+/// Returns the combined track and album gain offset of the track at the specified location, or
+/// None if the track isn't in the library.
+pub async fn find_gain_offset(
+    pool: &SqlitePool,
+    location: &str,
+) -> Result<Option<f64>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_gain_by_location.sql");
+
+    sqlx::query_scalar(query)
+        .bind(location)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Finds a track in the library by its location, or by its title, one of its artists, and its
+/// album (if known), ignoring case. Returns the ID of the first match.
+pub async fn find_track_id(
+    pool: &SqlitePool,
+    location: Option<&str>,
+    artist: Option<&str>,
+    title: Option<&str>,
+    album: Option<&str>,
+) -> Result<Option<i64>, sqlx::Error> {
+    if let Some(location) = location {
+        let query = include_str!("../../queries/library/find_track_id_by_location.sql");
+        let id = sqlx::query_scalar(query)
+            .bind(location)
+            .fetch_optional(pool)
+            .await?;
+
+        if id.is_some() {
+            return Ok(id);
+        }
+    }
+
+    let (Some(artist), Some(title)) = (artist, title) else {
+        return Ok(None);
+    };
+
+    let query = include_str!("../../queries/library/find_track_id_by_metadata.sql");
+
+    sqlx::query_scalar(query)
+        .bind(title)
+        .bind(artist)
+        .bind(album)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Merges statistics from elsewhere into the track's: the higher play count and the later
+/// last-played date are kept, and the rating is replaced if one is given.
+pub async fn set_track_stats(
+    pool: &SqlitePool,
+    track_id: i64,
+    play_count: Option<i64>,
+    rating: Option<i64>,
+    last_played: Option<DateTime<Utc>>,
+) -> Result<(), sqlx::Error> {
+    let query = include_str!("../../queries/library/set_track_stats.sql");
+
+    sqlx::query(query)
+        .bind(play_count)
+        .bind(rating)
+        .bind(last_played)
+        .bind(track_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the waveform peaks stored for the track at the specified location, if they have been
+/// computed.
+pub async fn find_track_peaks(
+    pool: &SqlitePool,
+    location: &str,
+) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_peaks_by_location.sql");
+
+    sqlx::query_scalar(query)
+        .bind(location)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Stores the waveform peaks of the track at the specified location. Returns false if there's no
+/// track at that location in the library.
+pub async fn set_track_peaks(
+    pool: &SqlitePool,
+    location: &str,
+    peaks: &[u8],
+) -> Result<bool, sqlx::Error> {
+    let query = include_str!("../../queries/library/set_track_peaks.sql");
+
+    let result = sqlx::query(query)
+        .bind(peaks)
+        .bind(location)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn set_track_gain(
+    pool: &SqlitePool,
+    track_id: i64,
+    gain: f64,
+) -> Result<(), sqlx::Error> {
+    let query = include_str!("../../queries/library/set_track_gain.sql");
+
+    sqlx::query(query)
+        .bind(gain)
+        .bind(track_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_track_skip_always(
+    pool: &SqlitePool,
+    track_id: i64,
+    skip: bool,
+) -> Result<(), sqlx::Error> {
+    let query = include_str!("../../queries/library/set_track_skip_always.sql");
+
+    sqlx::query(query)
+        .bind(skip)
+        .bind(track_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Sets where playback of the track starts and ends, in seconds. None plays from the start or to
+/// the end of the file.
+pub async fn set_track_trim(
+    pool: &SqlitePool,
+    track_id: i64,
+    start: Option<i64>,
+    end: Option<i64>,
+) -> Result<(), sqlx::Error> {
+    let query = include_str!("../../queries/library/set_track_trim.sql");
+
+    sqlx::query(query)
+        .bind(start)
+        .bind(end)
+        .bind(track_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the location, start offset, and end offset of every track that is trimmed.
+pub async fn find_track_trims(
+    pool: &SqlitePool,
+) -> Result<Vec<(String, Option<i64>, Option<i64>)>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_track_trims.sql");
+
+    sqlx::query_as(query).fetch_all(pool).await
+}
+
+/// Returns the location of every track that is always skipped.
+pub async fn find_skipped_locations(pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_skipped_locations.sql");
+
+    sqlx::query_scalar(query).fetch_all(pool).await
+}
+
+pub async fn set_album_gain(
+    pool: &SqlitePool,
+    db_cache: &DbCache,
+    album_id: i64,
+    gain: f64,
+) -> Result<(), sqlx::Error> {
+    let query = include_str!("../../queries/library/set_album_gain.sql");
+
+    sqlx::query(query)
+        .bind(gain)
+        .bind(album_id)
+        .execute(pool)
+        .await?;
+
+    db_cache.album_cache.invalidate(&album_id).await;
+
+    Ok(())
+}
+
+/// Returns the pinned albums, in the order they're shown in.
+pub async fn list_pinned_albums(pool: &SqlitePool) -> Result<Vec<(u32, String)>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_pinned_albums.sql");
+
+    sqlx::query_as::<_, (u32, String)>(query)
+        .fetch_all(pool)
+        .await
+}
+
+/// Pins the album after every other pinned album, or unpins it.
+pub async fn set_album_pinned(
+    pool: &SqlitePool,
+    db_cache: &DbCache,
+    album_id: i64,
+    pinned: bool,
+) -> Result<(), sqlx::Error> {
+    let query = if pinned {
+        include_str!("../../queries/library/pin_album.sql")
+    } else {
+        include_str!("../../queries/library/unpin_album.sql")
+    };
+
+    sqlx::query(query).bind(album_id).execute(pool).await?;
+
+    db_cache.album_cache.invalidate(&album_id).await;
+
+    Ok(())
+}
+
+/// Moves a pinned album up (with a negative offset) or down the pinned section. The positions of
+/// every pinned album are rewritten, which also closes any gaps left by unpinned albums.
+pub async fn move_pinned_album(
+    pool: &SqlitePool,
+    db_cache: &DbCache,
+    album_id: i64,
+    offset: isize,
+) -> Result<(), sqlx::Error> {
+    let mut ids: Vec<i64> = list_pinned_albums(pool)
+        .await?
+        .into_iter()
+        .map(|(id, _)| id as i64)
+        .collect();
+
+    let Some(from) = ids.iter().position(|id| *id == album_id) else {
+        return Ok(());
+    };
+    let to = from.saturating_add_signed(offset).min(ids.len() - 1);
+
+    if from == to {
+        return Ok(());
+    }
+
+    let id = ids.remove(from);
+    ids.insert(to, id);
+
+    let query = include_str!("../../queries/library/set_album_pinned_position.sql");
+    let mut transaction = pool.begin().await?;
+
+    for (position, id) in ids.iter().enumerate() {
+        sqlx::query(query)
+            .bind(position as i64)
+            .bind(*id)
+            .execute(&mut *transaction)
+            .await?;
+    }
+
+    transaction.commit().await?;
+
+    for id in ids {
+        db_cache.album_cache.invalidate(&id).await;
+    }
+
+    Ok(())
+}
+
+/// Escapes the wildcards in user input, so that it's matched literally by `LIKE ... ESCAPE '\'`.
+fn escape_like(query: &str) -> String {
+    let mut escaped = String::with_capacity(query.len());
+
+    for c in query.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Searches the library for the track that best matches the query, which can contain the title
+/// and optionally the artist name. Exact title matches are preferred, followed by shorter titles.
+pub async fn find_track_by_query(
+    pool: &SqlitePool,
+    query: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    let sql = include_str!("../../queries/library/find_track_by_query.sql");
+
+    sqlx::query_scalar(sql)
+        .bind(escape_like(query))
+        .bind(query)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Searches the titles of tracks and albums (optionally with their artist's name), and the names
+/// of artists. The results are grouped by kind, with shorter titles first.
+pub async fn search(pool: &SqlitePool, query: &str) -> Result<Vec<SearchResult>, sqlx::Error> {
+    let query = escape_like(query);
+    let tracks: Vec<(String, String, Option<String>)> =
+        sqlx::query_as(include_str!("../../queries/library/search_tracks.sql"))
+            .bind(&query)
+            .fetch_all(pool)
+            .await?;
+    let albums: Vec<(i64, String, Option<String>)> =
+        sqlx::query_as(include_str!("../../queries/library/search_albums.sql"))
+            .bind(&query)
+            .fetch_all(pool)
+            .await?;
+    let artists: Vec<(i64, String)> =
+        sqlx::query_as(include_str!("../../queries/library/search_artists.sql"))
+            .bind(&query)
+            .fetch_all(pool)
+            .await?;
+
+    let tracks = tracks
+        .into_iter()
+        .map(|(location, title, artist)| SearchResult::Track {
+            location,
+            title,
+            artist,
+        });
+    let albums = albums
+        .into_iter()
+        .map(|(id, title, artist)| SearchResult::Album { id, title, artist });
+    let artists = artists
+        .into_iter()
+        .map(|(id, name)| SearchResult::Artist { id, name });
+
+    Ok(tracks.chain(albums).chain(artists).collect())
+}
+
+/// Returns the locations of every track on the artist's albums, oldest album first.
+pub async fn find_track_locations_by_artist(
+    pool: &SqlitePool,
+    artist_id: i64,
+) -> Result<Vec<String>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_track_locations_by_artist.sql");
+
+    sqlx::query_scalar(query)
+        .bind(artist_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn count_tracks(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    let query = include_str!("../../queries/library/count_tracks.sql");
+
+    sqlx::query_scalar(query).fetch_one(pool).await
+}
+
+/// Returns the duration of the track at the specified location in seconds, or None if the track
+/// isn't in the library.
+pub async fn find_duration_by_location(
+    pool: &SqlitePool,
+    location: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_duration_by_location.sql");
+
+    sqlx::query_scalar(query)
+        .bind(location)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Returns the album of the track at the specified location, and whether it is the album's last
+/// track.
+pub async fn find_album_position_by_location(
+    pool: &SqlitePool,
+    location: &str,
+) -> Result<Option<(i64, bool)>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_album_position_by_location.sql");
+
+    let result: Option<(Option<i64>, bool)> = sqlx::query_as(query)
+        .bind(location)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(result.and_then(|(album_id, is_last)| album_id.map(|id| (id, is_last))))
+}
+
+pub async fn find_resume_position(
+    pool: &SqlitePool,
+    album_id: i64,
+) -> Result<Option<ResumePosition>, sqlx::Error> {
+    let query = include_str!("../../queries/library/find_resume_position.sql");
+
+    sqlx::query_as(query)
+        .bind(album_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn set_resume_position(
+    pool: &SqlitePool,
+    album_id: i64,
+    location: &str,
+    position: i64,
+) -> Result<(), sqlx::Error> {
+    let query = include_str!("../../queries/library/set_resume_position.sql");
+
+    sqlx::query(query)
+        .bind(album_id)
+        .bind(location)
+        .bind(position)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn clear_resume_position(pool: &SqlitePool, album_id: i64) -> Result<(), sqlx::Error> {
+    let query = include_str!("../../queries/library/clear_resume_position.sql");
+
+    sqlx::query(query).bind(album_id).execute(pool).await?;
+
+    Ok(())
+}
+
 pub trait LibraryAccess {
     fn list_albums(&self, sort_method: AlbumSortMethod) -> Result<Vec<(u32, String)>, sqlx::Error>;
     fn list_tracks_in_album(&self, album_id: i64) -> Result<Arc<Vec<Track>>, sqlx::Error>;
+    fn list_album_credits(
+        &self,
+        album_id: i64,
+    ) -> Result<Vec<(i64, CreditRole, String)>, sqlx::Error>;
     fn get_album_by_id(
         &self,
         album_id: i64,
@@ -197,18 +785,41 @@ pub trait LibraryAccess {
     ) -> Result<Arc<Album>, sqlx::Error>;
     fn get_artist_name_by_id(&self, artist_id: i64) -> Result<Arc<String>, sqlx::Error>;
     fn get_artist_by_id(&self, artist_id: i64) -> Result<Arc<Artist>, sqlx::Error>;
+    fn set_track_gain(&self, track_id: i64, gain: f64) -> Result<(), sqlx::Error>;
+    fn set_album_gain(&self, album_id: i64, gain: f64) -> Result<(), sqlx::Error>;
+    fn set_track_skip_always(&self, track_id: i64, skip: bool) -> Result<(), sqlx::Error>;
+    fn set_track_trim(
+        &self,
+        track_id: i64,
+        start: Option<i64>,
+        end: Option<i64>,
+    ) -> Result<(), sqlx::Error>;
+    fn list_pinned_albums(&self) -> Result<Vec<(u32, String)>, sqlx::Error>;
+    fn set_album_pinned(&self, album_id: i64, pinned: bool) -> Result<(), sqlx::Error>;
+    fn move_pinned_album(&self, album_id: i64, offset: isize) -> Result<(), sqlx::Error>;
+    fn get_resume_position(&self, album_id: i64) -> Result<Option<ResumePosition>, sqlx::Error>;
+    fn search(&self, query: &str) -> Result<Vec<SearchResult>, sqlx::Error>;
+    fn list_track_locations_by_artist(&self, artist_id: i64) -> Result<Vec<String>, sqlx::Error>;
 }
 
 // TODO: profile this with a large library
 impl LibraryAccess for AppContext {
     fn list_albums(&self, sort_method: AlbumSortMethod) -> Result<Vec<(u32, String)>, sqlx::Error> {
         let pool: &Pool = self.global();
-        task::block_on(list_albums(&pool.0, sort_method))
+        block_on_ui(list_albums(&pool.0, sort_method))
     }
 
     fn list_tracks_in_album(&self, album_id: i64) -> Result<Arc<Vec<Track>>, sqlx::Error> {
         let pool: &Pool = self.global();
-        task::block_on(list_tracks_in_album(&pool.0, album_id))
+        block_on_ui(list_tracks_in_album(&pool.0, album_id))
+    }
+
+    fn list_album_credits(
+        &self,
+        album_id: i64,
+    ) -> Result<Vec<(i64, CreditRole, String)>, sqlx::Error> {
+        let pool: &Pool = self.global();
+        block_on_ui(list_album_credits(&pool.0, album_id))
     }
 
     fn get_album_by_id(
@@ -218,18 +829,89 @@ impl LibraryAccess for AppContext {
     ) -> Result<Arc<Album>, sqlx::Error> {
         let pool: &Pool = self.global();
         let db_cache: &DbCache = self.global();
-        task::block_on(get_album_by_id(&pool.0, db_cache, album_id, method))
+        block_on_ui(get_album_by_id(&pool.0, db_cache, album_id, method))
     }
 
     fn get_artist_name_by_id(&self, artist_id: i64) -> Result<Arc<String>, sqlx::Error> {
         let pool: &Pool = self.global();
         let db_cache: &DbCache = self.global();
-        task::block_on(get_artist_name_by_id(&pool.0, db_cache, artist_id))
+        block_on_ui(get_artist_name_by_id(&pool.0, db_cache, artist_id))
     }
 
     fn get_artist_by_id(&self, artist_id: i64) -> Result<Arc<Artist>, sqlx::Error> {
         let pool: &Pool = self.global();
         let db_cache: &DbCache = self.global();
-        task::block_on(get_artist_by_id(&pool.0, db_cache, artist_id))
+        block_on_ui(get_artist_by_id(&pool.0, db_cache, artist_id))
+    }
+
+    fn set_track_gain(&self, track_id: i64, gain: f64) -> Result<(), sqlx::Error> {
+        let pool: &Pool = self.global();
+        block_on_ui(set_track_gain(&pool.0, track_id, gain))
+    }
+
+    fn set_album_gain(&self, album_id: i64, gain: f64) -> Result<(), sqlx::Error> {
+        let pool: &Pool = self.global();
+        let db_cache: &DbCache = self.global();
+        block_on_ui(set_album_gain(&pool.0, db_cache, album_id, gain))
+    }
+
+    fn set_track_skip_always(&self, track_id: i64, skip: bool) -> Result<(), sqlx::Error> {
+        let pool: &Pool = self.global();
+        block_on_ui(set_track_skip_always(&pool.0, track_id, skip))
+    }
+
+    fn set_track_trim(
+        &self,
+        track_id: i64,
+        start: Option<i64>,
+        end: Option<i64>,
+    ) -> Result<(), sqlx::Error> {
+        let pool: &Pool = self.global();
+        block_on_ui(set_track_trim(&pool.0, track_id, start, end))
+    }
+
+    fn list_pinned_albums(&self) -> Result<Vec<(u32, String)>, sqlx::Error> {
+        let pool: &Pool = self.global();
+        block_on_ui(list_pinned_albums(&pool.0))
+    }
+
+    fn set_album_pinned(&self, album_id: i64, pinned: bool) -> Result<(), sqlx::Error> {
+        let pool: &Pool = self.global();
+        let db_cache: &DbCache = self.global();
+        block_on_ui(set_album_pinned(&pool.0, db_cache, album_id, pinned))
+    }
+
+    fn move_pinned_album(&self, album_id: i64, offset: isize) -> Result<(), sqlx::Error> {
+        let pool: &Pool = self.global();
+        let db_cache: &DbCache = self.global();
+        block_on_ui(move_pinned_album(&pool.0, db_cache, album_id, offset))
+    }
+
+    fn get_resume_position(&self, album_id: i64) -> Result<Option<ResumePosition>, sqlx::Error> {
+        let pool: &Pool = self.global();
+        block_on_ui(find_resume_position(&pool.0, album_id))
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<SearchResult>, sqlx::Error> {
+        let pool: &Pool = self.global();
+        block_on_ui(search(&pool.0, query))
+    }
+
+    fn list_track_locations_by_artist(&self, artist_id: i64) -> Result<Vec<String>, sqlx::Error> {
+        let pool: &Pool = self.global();
+        block_on_ui(find_track_locations_by_artist(&pool.0, artist_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_like_wildcards() {
+        assert_eq!(escape_like("100% pure"), "100\\% pure");
+        assert_eq!(escape_like("track_01"), "track\\_01");
+        assert_eq!(escape_like("AC\\DC"), "AC\\\\DC");
+        assert_eq!(escape_like("unchanged"), "unchanged");
     }
 }
@@ -0,0 +1,204 @@
+use std::{path::Path, time::SystemTime};
+
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use tracing::{debug, warn};
+
+use super::db::{find_track_id, set_track_stats};
+
+/// The statistics of a track in another player's export.
+#[derive(Debug, Clone, Default)]
+pub struct ImportedTrack {
+    pub location: Option<String>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub play_count: Option<i64>,
+    /// From 0 to 5 stars.
+    pub rating: Option<i64>,
+    pub last_played: Option<DateTime<Utc>>,
+}
+
+impl ImportedTrack {
+    /// Describes the track for reporting, e.g. "Artist - Title".
+    pub fn describe(&self) -> String {
+        match (&self.artist, &self.title, &self.location) {
+            (Some(artist), Some(title), _) => format!("{} - {}", artist, title),
+            (None, Some(title), _) => title.clone(),
+            (_, _, Some(location)) => location.clone(),
+            _ => "Unknown track".to_string(),
+        }
+    }
+}
+
+/// The outcome of an import.
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    pub matched: usize,
+    /// The tracks that aren't in the library, as described by [`ImportedTrack::describe`].
+    pub unmatched: Vec<String>,
+}
+
+/// Reads an export from another player, either an iTunes/Music library XML file or a CSV file.
+pub fn read_export(path: &Path) -> anyhow::Result<Vec<ImportedTrack>> {
+    let is_xml = path
+        .extension()
+        .and_then(|v| v.to_str())
+        .is_some_and(|v| v.eq_ignore_ascii_case("xml"));
+
+    if is_xml {
+        read_itunes(path)
+    } else {
+        read_csv(path)
+    }
+}
+
+/// Converts a location from an iTunes library (e.g. `file://localhost/Music/A%20B.mp3`) to a path.
+fn itunes_location(url: &str) -> Option<String> {
+    let path = url
+        .strip_prefix("file://localhost")
+        .or_else(|| url.strip_prefix("file://"))?;
+    let path = urlencoding::decode(path).ok()?.into_owned();
+
+    // Windows locations look like /C:/Music/...
+    if path.as_bytes().get(2) == Some(&b':') {
+        Some(path[1..].replace('/', "\\"))
+    } else {
+        Some(path)
+    }
+}
+
+fn read_itunes(path: &Path) -> anyhow::Result<Vec<ImportedTrack>> {
+    let library = plist::Value::from_file(path)?;
+    let tracks = library
+        .as_dictionary()
+        .and_then(|v| v.get("Tracks"))
+        .and_then(|v| v.as_dictionary())
+        .ok_or_else(|| anyhow::anyhow!("not an iTunes library"))?;
+
+    Ok(tracks
+        .values()
+        .filter_map(|v| v.as_dictionary())
+        .map(|track| {
+            let string = |key: &str| {
+                track
+                    .get(key)
+                    .and_then(|v| v.as_string())
+                    .map(|v| v.to_string())
+            };
+
+            ImportedTrack {
+                location: string("Location").and_then(|v| itunes_location(&v)),
+                title: string("Name"),
+                artist: string("Artist"),
+                album: string("Album"),
+                play_count: track.get("Play Count").and_then(|v| v.as_signed_integer()),
+                // iTunes rates from 0 to 100, in steps of 20 per star
+                rating: track
+                    .get("Rating")
+                    .and_then(|v| v.as_signed_integer())
+                    .map(|v| (v / 20).clamp(0, 5)),
+                last_played: track
+                    .get("Play Date UTC")
+                    .and_then(|v| v.as_date())
+                    .map(|v| DateTime::<Utc>::from(SystemTime::from(v))),
+            }
+        })
+        .collect())
+}
+
+/// Reads a CSV file with a header row. The columns are matched by name, ignoring case:
+/// `location` (or `path`), `title`, `artist`, `album`, `play_count`, `rating` (0 to 5), and
+/// `last_played`. Columns that are missing are left alone.
+fn read_csv(path: &Path) -> anyhow::Result<Vec<ImportedTrack>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let headers: Vec<String> = reader
+        .headers()?
+        .iter()
+        .map(|v| v.trim().to_lowercase())
+        .collect();
+    let column = |names: &[&str]| headers.iter().position(|v| names.contains(&v.as_str()));
+
+    let location = column(&["location", "path"]);
+    let title = column(&["title", "name"]);
+    let artist = column(&["artist"]);
+    let album = column(&["album"]);
+    let play_count = column(&["play_count", "plays"]);
+    let rating = column(&["rating"]);
+    let last_played = column(&["last_played"]);
+
+    let mut tracks = Vec::new();
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                warn!("Skipping unreadable row: {:?}", e);
+                continue;
+            }
+        };
+
+        let field = |idx: Option<usize>| {
+            idx.and_then(|idx| record.get(idx))
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+        };
+
+        tracks.push(ImportedTrack {
+            location: field(location).map(|v| v.to_string()),
+            title: field(title).map(|v| v.to_string()),
+            artist: field(artist).map(|v| v.to_string()),
+            album: field(album).map(|v| v.to_string()),
+            play_count: field(play_count).and_then(|v| v.parse().ok()),
+            rating: field(rating)
+                .and_then(|v| v.parse::<i64>().ok())
+                .map(|v| v.clamp(0, 5)),
+            last_played: field(last_played).and_then(|v| dateparser::parse(v).ok()),
+        });
+    }
+
+    Ok(tracks)
+}
+
+/// Imports the statistics of the tracks that are in the library, matching them by location
+/// first, and by title, artist, and album otherwise.
+pub async fn import_stats(pool: &SqlitePool, tracks: Vec<ImportedTrack>) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    for track in tracks {
+        let id = find_track_id(
+            pool,
+            track.location.as_deref(),
+            track.artist.as_deref(),
+            track.title.as_deref(),
+            track.album.as_deref(),
+        )
+        .await;
+
+        let id = match id {
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                debug!("no match for {:?}", track);
+                report.unmatched.push(track.describe());
+                continue;
+            }
+            Err(e) => {
+                warn!("Could not look up {}: {:?}", track.describe(), e);
+                report.unmatched.push(track.describe());
+                continue;
+            }
+        };
+
+        if let Err(e) =
+            set_track_stats(pool, id, track.play_count, track.rating, track.last_played).await
+        {
+            warn!("Could not import stats for {}: {:?}", track.describe(), e);
+            report.unmatched.push(track.describe());
+            continue;
+        }
+
+        report.matched += 1;
+    }
+
+    report
+}
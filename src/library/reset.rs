@@ -0,0 +1,28 @@
+use std::{io::ErrorKind, path::Path};
+
+use sqlx::SqlitePool;
+use tracing::{info, warn};
+
+use super::db::create_pool;
+
+/// Closes the pool, deletes the database at `path`, and returns a pool for a new, empty database.
+pub async fn reset_database(pool: SqlitePool, path: &Path) -> Result<SqlitePool, sqlx::Error> {
+    // the files can't be removed safely while any connection is still open
+    pool.close().await;
+
+    // the write-ahead log and its index are deleted too, or they'd be replayed into the new file
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+
+        match std::fs::remove_file(&file) {
+            Ok(_) => (),
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => warn!("Could not remove {:?}: {:?}", file, e),
+        }
+    }
+
+    info!("Library database removed, creating a new one");
+
+    create_pool(path).await
+}
@@ -1,4 +1,5 @@
 use std::{
+    collections::VecDeque,
     fs::{self, File},
     io::{BufReader, Cursor, Write},
     path::{Path, PathBuf},
@@ -6,36 +7,88 @@ use std::{
     time::{Duration, SystemTime},
 };
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use async_std::task;
-use gpui::{AppContext, Global};
-use image::imageops::thumbnail;
+use gpui::{AppContext, AsyncAppContext, Global, Model};
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    media::{
-        builtin::symphonia::SymphoniaProvider,
-        metadata::Metadata,
-        traits::{MediaPlugin, MediaProvider},
+    data::interface::GPUIDataInterface,
+    library::db::{count_tracks, DbCache},
+    media::{builtin::providers, metadata::Metadata, traits::MediaProvider},
+    playback::thread::PlaybackState,
+    settings::{
+        scan::{AlbumGrouping, ScanSettings},
+        SettingsGlobal,
     },
-    settings::scan::ScanSettings,
-    ui::models::Models,
+    ui::{
+        app::{get_dirs, Pool},
+        models::{Models, PlaybackInfo},
+    },
+    util::make_thumbnail,
 };
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ScanEvent {
     Cleaning,
     DiscoverProgress(u64),
-    ScanProgress { current: u64, total: u64 },
+    ScanProgress {
+        current: u64,
+        total: u64,
+    },
     ScanCompleteWatching,
     ScanCompleteIdle,
+    /// The metadata of the files passed to [`ScanInterface::reload`] has been read again. This
+    /// isn't a scan state, so it isn't stored in the scan state model.
+    MetadataReloaded,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+/// When the last scan finished (in seconds since the Unix epoch), and how many tracks were in the
+/// library afterwards.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct LastScan {
+    pub timestamp: u64,
+    pub tracks: u64,
+}
+
+fn last_scan_path() -> PathBuf {
+    get_dirs().data_dir().join("last_scan.json")
+}
+
+pub fn load_last_scan() -> Option<LastScan> {
+    let file = File::open(last_scan_path()).ok()?;
+
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| warn!("Could not read last scan information: {:?}", e))
+        .ok()
+}
+
+fn save_last_scan(last_scan: &LastScan) {
+    let data = serde_json::to_string(last_scan).expect("couldn't serialize last scan");
+
+    if let Err(e) = fs::write(last_scan_path(), data) {
+        error!("Could not save last scan information: {:?}", e);
+    }
+}
+
+#[derive(Debug, Clone)]
 enum ScanCommand {
     Scan,
     Stop,
+    SetPaused(bool),
+    Reload(Vec<PathBuf>),
+    UpdateSettings(ScanSettings),
+    /// Scans every file, even the ones that haven't changed since they were last scanned.
+    Rescan,
+    /// Scans every file in the folder, without scanning the rest of the library. If a scan is
+    /// already running, the folder is scanned once it (and any folders queued before it) is done.
+    ScanFolder(PathBuf),
+    /// Switches to a new database, after the library has been reset.
+    ReplacePool(SqlitePool),
+    /// Works out the duration of files whose duration wasn't known when they were scanned.
+    ComputeDurations(Vec<PathBuf>),
 }
 
 pub struct ScanInterface {
@@ -66,22 +119,104 @@ impl ScanInterface {
             .expect("could not send tx");
     }
 
+    /// Pauses or resumes the current scan. A paused scan keeps its progress.
+    pub fn set_paused(&self, paused: bool) {
+        self.command_tx
+            .send(ScanCommand::SetPaused(paused))
+            .expect("could not send tx");
+    }
+
+    pub fn update_settings(&self, settings: ScanSettings) {
+        self.command_tx
+            .send(ScanCommand::UpdateSettings(settings))
+            .expect("could not send tx");
+    }
+
+    /// Scans the whole library again, including files that haven't changed.
+    pub fn rescan(&self) {
+        self.command_tx
+            .send(ScanCommand::Rescan)
+            .expect("could not send tx");
+    }
+
+    /// Scans every file in the folder again, including files that haven't changed, without
+    /// scanning the rest of the library. The folder has to be inside one of the library's paths.
+    pub fn scan_folder(&self, path: PathBuf) {
+        self.command_tx
+            .send(ScanCommand::ScanFolder(path))
+            .expect("could not send tx");
+    }
+
+    /// Works out the duration of files that were stored without one, and updates their records.
+    /// Each file is only tried once per session, so files that can't be read aren't retried every
+    /// time they're shown.
+    pub fn compute_durations(&self, paths: Vec<PathBuf>) {
+        self.command_tx
+            .send(ScanCommand::ComputeDurations(paths))
+            .expect("could not send tx");
+    }
+
+    /// Switches to a new database. The current scan is stopped, since its files were being written
+    /// to the old one.
+    pub fn replace_pool(&self, pool: SqlitePool) {
+        self.command_tx
+            .send(ScanCommand::ReplacePool(pool))
+            .expect("could not send tx");
+    }
+
+    /// Reads the metadata of the files again and updates their records, without scanning the
+    /// rest of the library.
+    pub fn reload(&self, paths: Vec<PathBuf>) {
+        self.command_tx
+            .send(ScanCommand::Reload(paths))
+            .expect("could not send tx");
+    }
+
     pub fn start_broadcast(&mut self, cx: &mut AppContext) {
         let mut events_rx = None;
         std::mem::swap(&mut self.events_rx, &mut events_rx);
 
         let state_model = cx.global::<Models>().scan_state.clone();
+        let last_scan_model = cx.global::<Models>().last_scan.clone();
+        let reloaded_model = cx.global::<Models>().metadata_reloaded.clone();
 
         if let Some(events_rx) = events_rx {
             cx.spawn(|mut cx| async move {
                 loop {
                     while let Ok(event) = events_rx.try_recv() {
-                        state_model
+                        if event == ScanEvent::MetadataReloaded {
+                            cx.update(|cx| cx.global::<DbCache>().invalidate_albums())
+                                .expect("failed to invalidate album cache");
+
+                            reloaded_model
+                                .update(&mut cx, |m, cx| {
+                                    *m += 1;
+                                    cx.notify();
+                                })
+                                .expect("failed to update metadata reloaded model");
+
+                            continue;
+                        }
+
+                        let previous = state_model
                             .update(&mut cx, |m, cx| {
+                                let previous = *m;
                                 *m = event;
-                                cx.notify()
+                                cx.notify();
+
+                                previous
                             })
                             .expect("failed to update scan state model");
+
+                        let finished = event == ScanEvent::ScanCompleteIdle
+                            && !matches!(
+                                previous,
+                                ScanEvent::ScanCompleteIdle | ScanEvent::ScanCompleteWatching
+                            );
+
+                        if finished {
+                            record_last_scan(&mut cx, &last_scan_model).await;
+                        }
                     }
 
                     cx.background_executor()
@@ -96,6 +231,167 @@ impl ScanInterface {
 
 impl Global for ScanInterface {}
 
+/// Pauses scanning while music is playing, if `pause_while_playing` is enabled.
+pub fn setup_scan_pausing(cx: &mut AppContext) {
+    let playback_state = cx.global::<PlaybackInfo>().playback_state.clone();
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+
+    let update = |cx: &mut AppContext| {
+        let enabled = cx
+            .global::<SettingsGlobal>()
+            .model
+            .read(cx)
+            .scanning
+            .pause_while_playing;
+        let playing =
+            *cx.global::<PlaybackInfo>().playback_state.read(cx) == PlaybackState::Playing;
+
+        cx.global::<ScanInterface>().set_paused(enabled && playing);
+    };
+
+    cx.observe(&playback_state, move |_, cx| update(cx))
+        .detach();
+    cx.observe(&settings, move |_, cx| update(cx)).detach();
+}
+
+/// Whether the library has to be scanned again for the new settings to take effect: the folders in
+/// it, how albums are grouped, or whether symlinks are followed have changed.
+fn needs_rescan(old: &ScanSettings, new: &ScanSettings) -> bool {
+    old.paths != new.paths
+        || old.album_grouping != new.album_grouping
+        || old.follow_symlinks != new.follow_symlinks
+}
+
+/// Keeps the scanner's settings in sync, rescanning the library when the settings that decide
+/// what's in it change.
+pub fn setup_scan_settings(cx: &mut AppContext) {
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+    let mut current = settings.read(cx).scanning.clone();
+
+    cx.observe(&settings, move |settings, cx| {
+        let scanning = settings.read(cx).scanning.clone();
+
+        if scanning == current {
+            return;
+        }
+
+        let rescan = needs_rescan(&current, &scanning);
+        current = scanning.clone();
+
+        let scan_interface = cx.global::<ScanInterface>();
+        scan_interface.update_settings(scanning);
+
+        if rescan {
+            info!("Scan settings changed, rescanning library");
+            scan_interface.rescan();
+        }
+    })
+    .detach();
+}
+
+/// Whether album art should be pre-rendered into the disk thumbnail cache.
+fn prerender_enabled(cx: &AppContext) -> bool {
+    let settings = cx.global::<SettingsGlobal>().model.read(cx);
+
+    settings.scanning.prerender_thumbnails
+        && !settings.interface.disable_album_art
+        && settings.interface.thumbnail_cache_size > 0
+}
+
+/// Pre-renders album art into the data thread's disk thumbnail cache while it's enabled: the
+/// albums already in the library once per session, and then the albums each scan adds.
+pub fn setup_thumbnail_prerender(cx: &mut AppContext) {
+    let mut enabled = prerender_enabled(cx);
+
+    if enabled {
+        cx.global::<GPUIDataInterface>().prerender_thumbnails();
+    }
+
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+
+    cx.observe(&settings, move |_, cx| {
+        let now_enabled = prerender_enabled(cx);
+
+        if now_enabled == enabled {
+            return;
+        }
+
+        enabled = now_enabled;
+
+        if enabled {
+            cx.global::<GPUIDataInterface>().prerender_thumbnails();
+        } else {
+            cx.global::<GPUIDataInterface>().stop_prerendering();
+        }
+    })
+    .detach();
+
+    let scan_state = cx.global::<Models>().scan_state.clone();
+
+    cx.observe(&scan_state, |scan_state, cx| {
+        let complete = matches!(
+            scan_state.read(cx),
+            ScanEvent::ScanCompleteIdle | ScanEvent::ScanCompleteWatching
+        );
+
+        if complete && prerender_enabled(cx) {
+            cx.global::<GPUIDataInterface>().prerender_thumbnails();
+        }
+    })
+    .detach();
+}
+
+/// Reads the metadata of the files again, both for the library and for the queue.
+pub fn reload_metadata(cx: &mut AppContext, paths: Vec<String>) {
+    let queue_metadata = cx.global::<Models>().queue_metadata.clone();
+    let queued: Vec<String> = queue_metadata.update(cx, |m, _| {
+        paths
+            .iter()
+            .filter(|path| m.remove(*path).is_some())
+            .cloned()
+            .collect()
+    });
+
+    // the views showing queued files update once the new metadata has been read
+    for path in queued {
+        cx.global::<GPUIDataInterface>().get_metadata(path);
+    }
+
+    cx.global::<ScanInterface>()
+        .reload(paths.into_iter().map(PathBuf::from).collect());
+}
+
+async fn record_last_scan(cx: &mut AsyncAppContext, last_scan_model: &Model<Option<LastScan>>) {
+    let Ok(Some(pool)) = cx.update(|cx| cx.try_global::<Pool>().map(|v| v.0.clone())) else {
+        return;
+    };
+
+    let tracks = match count_tracks(&pool).await {
+        Ok(tracks) => tracks as u64,
+        Err(e) => {
+            error!("Could not count tracks in library: {:?}", e);
+            return;
+        }
+    };
+
+    let last_scan = LastScan {
+        timestamp: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+        tracks,
+    };
+
+    save_last_scan(&last_scan);
+
+    last_scan_model
+        .update(cx, |m, cx| {
+            *m = Some(last_scan);
+            cx.notify();
+        })
+        .expect("failed to update last scan model");
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ScanState {
     Idle,
@@ -109,7 +405,8 @@ pub struct ScanThread {
     command_rx: mpsc::Receiver<ScanCommand>,
     pool: SqlitePool,
     scan_settings: ScanSettings,
-    visited: Vec<PathBuf>,
+    /// The canonical paths of the folders that have been discovered, so that none is read twice.
+    visited: AHashSet<PathBuf>,
     discovered: Vec<PathBuf>,
     to_process: Vec<PathBuf>,
     scan_state: ScanState,
@@ -118,14 +415,21 @@ pub struct ScanThread {
     scan_record_path: Option<PathBuf>,
     scanned: u64,
     discovered_total: u64,
+    paused: bool,
+    /// Whether the current scan includes files that haven't changed since they were last scanned.
+    force: bool,
+    /// Folders that were asked to be scanned while another scan was running, in the order they
+    /// were asked for.
+    queued_folders: VecDeque<PathBuf>,
+    /// The files whose duration has been worked out by [`ScanCommand::ComputeDurations`].
+    durations_computed: AHashSet<PathBuf>,
 }
 
 fn build_provider_table() -> Vec<(&'static [&'static str], Box<dyn MediaProvider>)> {
-    // TODO: dynamic plugin loading
-    vec![(
-        SymphoniaProvider::SUPPORTED_EXTENSIONS,
-        Box::new(SymphoniaProvider::default()),
-    )]
+    providers()
+        .into_iter()
+        .map(|provider| (provider.supported_extensions(), provider))
+        .collect()
 }
 
 fn retrieve_base_paths() -> Vec<PathBuf> {
@@ -154,8 +458,57 @@ fn file_is_scannable_with_provider(path: &Path, exts: &&[&str]) -> bool {
 
 type FileInformation = (Metadata, u64, Option<Box<[u8]>>);
 
-// We don't care about the error message. If the file can't be scanned, we just ignore it.
-// TODO: it might be worth logging why the file couldn't be scanned (for plugin development)
+/// The key that decides which album a track belongs to. Tracks are only part of an album if
+/// they're tagged with an album title, however albums are grouped.
+fn album_grouping_key(
+    grouping: AlbumGrouping,
+    metadata: &Metadata,
+    artist_id: Option<i64>,
+    path: &Path,
+) -> String {
+    let by_title = || {
+        format!(
+            "title:{}:{}",
+            artist_id.map(|v| v.to_string()).unwrap_or_default(),
+            metadata.album.as_deref().unwrap_or_default()
+        )
+    };
+
+    match grouping {
+        AlbumGrouping::ArtistAndTitle => by_title(),
+        AlbumGrouping::MusicbrainzId => metadata
+            .mbid_album
+            .as_ref()
+            .map(|id| format!("mbid:{}", id))
+            .unwrap_or_else(by_title),
+        AlbumGrouping::Folder => path
+            .parent()
+            .map(|dir| format!("folder:{}", dir.display()))
+            .unwrap_or_else(by_title),
+    }
+}
+
+/// Creates the small version of an album's art shown in the album list, encoded as a BMP.
+fn make_album_thumb(image: &[u8]) -> Option<Vec<u8>> {
+    let decoded = image::ImageReader::new(Cursor::new(image))
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .ok()?
+        .into_rgba8();
+
+    let thumb = make_thumbnail(&decoded, 70, true);
+
+    let mut buf: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+
+    thumb
+        .write_to(&mut buf, image::ImageFormat::Bmp)
+        .expect("i don't know how Cursor could fail");
+    buf.flush().expect("could not flush buffer");
+
+    Some(buf.into_inner())
+}
+
 fn scan_file_with_provider(
     path: &PathBuf,
     provider: &mut Box<dyn MediaProvider>,
@@ -165,11 +518,23 @@ fn scan_file_with_provider(
     provider.start_playback().map_err(|_| ())?;
     let metadata = provider.read_metadata().cloned().map_err(|_| ())?;
     let image = provider.read_image().map_err(|_| ())?;
-    let len = provider.duration_secs().map_err(|_| ())?;
+    let len = provider.analyze_duration().map_err(|_| ())?;
     provider.close().map_err(|_| ())?;
     Ok((metadata, len, image))
 }
 
+fn analyze_duration_with_provider(
+    path: &PathBuf,
+    provider: &mut Box<dyn MediaProvider>,
+) -> Result<u64, ()> {
+    let src = std::fs::File::open(path).map_err(|_| ())?;
+    provider.open(src, None).map_err(|_| ())?;
+    provider.start_playback().map_err(|_| ())?;
+    let len = provider.analyze_duration().map_err(|_| ())?;
+    provider.close().map_err(|_| ())?;
+    Ok(len)
+}
+
 impl ScanThread {
     pub fn start(pool: SqlitePool, settings: ScanSettings) -> ScanInterface {
         let (commands_tx, commands_rx) = std::sync::mpsc::channel();
@@ -182,7 +547,7 @@ impl ScanThread {
                     event_tx: events_tx,
                     command_rx: commands_rx,
                     pool,
-                    visited: Vec::new(),
+                    visited: AHashSet::new(),
                     discovered: Vec::new(),
                     to_process: Vec::new(),
                     scan_state: ScanState::Idle,
@@ -192,6 +557,10 @@ impl ScanThread {
                     scan_record_path: None,
                     scanned: 0,
                     discovered_total: 0,
+                    paused: false,
+                    force: false,
+                    queued_folders: VecDeque::new(),
+                    durations_computed: AHashSet::new(),
                 };
 
                 thread.run();
@@ -202,8 +571,7 @@ impl ScanThread {
     }
 
     fn run(&mut self) {
-        let dirs = directories::ProjectDirs::from("me", "william341", "muzak")
-            .expect("couldn't find project dirs");
+        let dirs = get_dirs();
         let directory = dirs.data_dir();
         if !directory.exists() {
             fs::create_dir(directory).expect("couldn't create data directory");
@@ -236,6 +604,11 @@ impl ScanThread {
             // TODO: clear out old files if they've been deleted or moved
             // TODO: connect to user interface to display progress
             // TODO: start file watcher to update db automatically when files are added or removed
+            if self.paused {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                continue;
+            }
+
             match self.scan_state {
                 ScanState::Idle => {
                     std::thread::sleep(std::time::Duration::from_millis(100));
@@ -269,13 +642,57 @@ impl ScanThread {
                 }
                 ScanCommand::Stop => {
                     self.scan_state = ScanState::Idle;
+                    self.queued_folders.clear();
+                    self.visited.clear();
+                    self.discovered.clear();
+                    self.to_process.clear();
+                }
+                ScanCommand::SetPaused(paused) => {
+                    if paused != self.paused {
+                        info!("{} scanning", if paused { "Pausing" } else { "Resuming" });
+                        self.paused = paused;
+                    }
+                }
+                ScanCommand::Reload(paths) => {
+                    self.reload(paths);
+                }
+                ScanCommand::UpdateSettings(settings) => {
+                    self.scan_settings = settings;
+                }
+                ScanCommand::Rescan => {
+                    // a scan that's already running starts over, so that no file is skipped
+                    self.force = true;
+                    // every folder is about to be scanned anyways
+                    self.queued_folders.clear();
+                    self.visited.clear();
+                    self.to_process.clear();
+                    self.discovered = self.scan_settings.paths.clone();
+                    self.scan_state = ScanState::Cleanup;
+                    self.scanned = 0;
+                    self.discovered_total = 0;
+                    self.event_tx
+                        .send(ScanEvent::Cleaning)
+                        .expect("could not send scan started event");
+                }
+                ScanCommand::ScanFolder(path) => self.scan_folder(path),
+                ScanCommand::ComputeDurations(paths) => self.compute_durations(paths),
+                ScanCommand::ReplacePool(pool) => {
+                    self.pool = pool;
+                    self.scan_state = ScanState::Idle;
+                    self.queued_folders.clear();
                     self.visited.clear();
                     self.discovered.clear();
                     self.to_process.clear();
+                    // every file has to be added to the new database
+                    self.scan_record.clear();
                 }
             }
         }
 
+        if self.paused {
+            return;
+        }
+
         if self.scan_state == ScanState::Discovering {
             self.discover();
         } else if self.scan_state == ScanState::Scanning {
@@ -285,6 +702,52 @@ impl ScanThread {
         }
     }
 
+    fn scan_folder(&mut self, path: PathBuf) {
+        let Ok(path) = path.canonicalize() else {
+            warn!("Could not scan folder {:?}, it doesn't exist", path);
+            return;
+        };
+
+        let in_library = self
+            .scan_settings
+            .paths
+            .iter()
+            .filter_map(|v| v.canonicalize().ok())
+            .any(|v| path.starts_with(v));
+
+        if !in_library {
+            warn!("Not scanning {:?}, it isn't in the library", path);
+            return;
+        }
+
+        if self.scan_state != ScanState::Idle {
+            if !self.queued_folders.contains(&path) {
+                debug!("already scanning, queueing {:?}", path);
+                self.queued_folders.push_back(path);
+            }
+
+            return;
+        }
+
+        self.start_folder_scan(path);
+    }
+
+    fn start_folder_scan(&mut self, path: PathBuf) {
+        info!("Scanning folder {:?}", path);
+
+        // files that haven't changed are scanned too, and nothing outside the folder is cleaned
+        self.force = true;
+        self.visited.clear();
+        self.to_process.clear();
+        self.discovered = vec![path];
+        self.scan_state = ScanState::Discovering;
+        self.scanned = 0;
+        self.discovered_total = 0;
+        self.event_tx
+            .send(ScanEvent::DiscoverProgress(0))
+            .expect("could not send scan started event");
+    }
+
     fn file_is_scannable(&mut self, path: &PathBuf) -> bool {
         let timestamp = match fs::metadata(path) {
             Ok(metadata) => metadata
@@ -301,7 +764,7 @@ impl ScanThread {
 
             if x {
                 if let Some(last_scan) = self.scan_record.get(path) {
-                    if *last_scan == timestamp {
+                    if *last_scan == timestamp && !self.force {
                         return false;
                     }
                 }
@@ -322,16 +785,50 @@ impl ScanThread {
 
         let path = self.discovered.pop().unwrap();
 
-        if self.visited.contains(&path) {
+        // folders are tracked by their canonical path, so that a symlink that points back up the
+        // tree isn't followed forever
+        let Ok(path) = path.canonicalize() else {
+            warn!("Could not scan folder {:?}, it doesn't exist", path);
+            return;
+        };
+
+        if !self.visited.insert(path.clone()) {
             return;
         }
 
-        let paths = fs::read_dir(&path).unwrap();
+        let entries = match fs::read_dir(&path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Could not read folder {:?}: {:?}", path, e);
+                return;
+            }
+        };
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("Could not read an entry of {:?}: {:?}", path, e);
+                    continue;
+                }
+            };
+
+            if !self.scan_settings.follow_symlinks
+                && entry.file_type().is_ok_and(|v| v.is_symlink())
+            {
+                debug!("not following symlink {:?}", entry.path());
+                continue;
+            }
 
-        for paths in paths {
-            // TODO: handle errors
             // this might be slower than just reading the path directly but this prevents loops
-            let path = paths.unwrap().path().canonicalize().unwrap();
+            let Ok(path) = entry.path().canonicalize() else {
+                warn!(
+                    "Skipping {:?}, it points to a file that doesn't exist",
+                    entry.path()
+                );
+                continue;
+            };
+
             if path.is_dir() {
                 self.discovered.push(path);
             } else if self.file_is_scannable(&path) {
@@ -346,58 +843,138 @@ impl ScanThread {
                 }
             }
         }
-
-        self.visited.push(path.clone());
     }
 
-    async fn insert_artist(&self, metadata: &Metadata) -> Option<i64> {
-        let artist = metadata.album_artist.clone().or(metadata.artist.clone());
-
-        if let Some(artist) = artist {
-            let result: Result<(i64,), sqlx::Error> =
-                sqlx::query_as(include_str!("../../queries/scan/create_artist.sql"))
-                    .bind(&artist)
-                    .bind(metadata.artist_sort.as_ref().unwrap_or(&artist))
-                    .fetch_one(&self.pool)
-                    .await;
+    async fn insert_artist(&self, name: &str, name_sortable: &str) -> Option<i64> {
+        let result: Result<(i64,), sqlx::Error> =
+            sqlx::query_as(include_str!("../../queries/scan/create_artist.sql"))
+                .bind(name)
+                .bind(name_sortable)
+                .fetch_one(&self.pool)
+                .await;
 
-            match result {
-                Ok(v) => Some(v.0),
-                Err(sqlx::Error::RowNotFound) => {
-                    let result: Result<(i64,), sqlx::Error> =
-                        sqlx::query_as(include_str!("../../queries/scan/get_artist_id.sql"))
-                            .bind(&artist)
-                            .fetch_one(&self.pool)
-                            .await;
+        match result {
+            Ok(v) => Some(v.0),
+            Err(sqlx::Error::RowNotFound) => {
+                let result: Result<(i64,), sqlx::Error> =
+                    sqlx::query_as(include_str!("../../queries/scan/get_artist_id.sql"))
+                        .bind(name)
+                        .fetch_one(&self.pool)
+                        .await;
 
-                    match result {
-                        Ok(v) => Some(v.0),
-                        Err(e) => {
-                            error!("Database error while retriving artist: {:?}", e);
-                            None
-                        }
+                match result {
+                    Ok(v) => Some(v.0),
+                    Err(e) => {
+                        error!("Database error while retriving artist: {:?}", e);
+                        None
                     }
                 }
-                Err(e) => {
-                    error!("Database error while creating artist: {:?}", e);
-                    None
-                }
             }
+            Err(e) => {
+                error!("Database error while creating artist: {:?}", e);
+                None
+            }
+        }
+    }
+
+    async fn insert_album_artist(&self, metadata: &Metadata) -> Option<i64> {
+        let artist = metadata
+            .album_artist
+            .clone()
+            .or(metadata.artists.first().cloned());
+
+        if let Some(artist) = artist {
+            self.insert_artist(&artist, metadata.artist_sort.as_ref().unwrap_or(&artist))
+                .await
         } else {
             None
         }
     }
 
+    async fn insert_track_links(&self, metadata: &Metadata, track_id: i64) {
+        // the links are replaced wholesale, which handles tags being removed from the file
+        let result = sqlx::query(include_str!("../../queries/scan/delete_track_artists.sql"))
+            .bind(track_id)
+            .execute(&self.pool)
+            .await;
+
+        if let Err(e) = result {
+            error!("Database error while clearing track artists: {:?}", e);
+        }
+
+        let result = sqlx::query(include_str!("../../queries/scan/delete_track_genres.sql"))
+            .bind(track_id)
+            .execute(&self.pool)
+            .await;
+
+        if let Err(e) = result {
+            error!("Database error while clearing track genres: {:?}", e);
+        }
+
+        let result = sqlx::query(include_str!("../../queries/scan/delete_track_credits.sql"))
+            .bind(track_id)
+            .execute(&self.pool)
+            .await;
+
+        if let Err(e) = result {
+            error!("Database error while clearing track credits: {:?}", e);
+        }
+
+        for artist in &metadata.artists {
+            if let Some(artist_id) = self.insert_artist(artist, artist).await {
+                let result =
+                    sqlx::query(include_str!("../../queries/scan/create_track_artist.sql"))
+                        .bind(track_id)
+                        .bind(artist_id)
+                        .execute(&self.pool)
+                        .await;
+
+                if let Err(e) = result {
+                    error!("Database error while creating track artist: {:?}", e);
+                }
+            }
+        }
+
+        for genre in &metadata.genres {
+            let result = sqlx::query(include_str!("../../queries/scan/create_track_genre.sql"))
+                .bind(track_id)
+                .bind(genre)
+                .execute(&self.pool)
+                .await;
+
+            if let Err(e) = result {
+                error!("Database error while creating track genre: {:?}", e);
+            }
+        }
+
+        for (role, name) in &metadata.credits {
+            let result = sqlx::query(include_str!("../../queries/scan/create_track_credit.sql"))
+                .bind(track_id)
+                .bind(role.as_str())
+                .bind(name)
+                .execute(&self.pool)
+                .await;
+
+            if let Err(e) = result {
+                error!("Database error while creating track credit: {:?}", e);
+            }
+        }
+    }
+
     async fn insert_album(
         &self,
         metadata: &Metadata,
         artist_id: Option<i64>,
         image: &Option<Box<[u8]>>,
+        path: &Path,
     ) -> Option<i64> {
         if let Some(album) = &metadata.album {
+            let key =
+                album_grouping_key(self.scan_settings.album_grouping, metadata, artist_id, path);
+
             let result: Result<(i64,), sqlx::Error> =
                 sqlx::query_as(include_str!("../../queries/scan/get_album_id.sql"))
-                    .bind(album)
+                    .bind(&key)
                     .fetch_one(&self.pool)
                     .await;
 
@@ -405,25 +982,7 @@ impl ScanThread {
                 Ok(v) => Some(v.0),
                 Err(sqlx::Error::RowNotFound) => {
                     let thumb = match image {
-                        Some(image) => {
-                            let decoded = image::ImageReader::new(Cursor::new(&image))
-                                .with_guessed_format()
-                                .ok()?
-                                .decode()
-                                .ok()?
-                                .into_rgba8();
-
-                            let thumb = thumbnail(&decoded, 70, 70);
-
-                            let mut buf: Cursor<Vec<u8>> = Cursor::new(Vec::new());
-
-                            thumb
-                                .write_to(&mut buf, image::ImageFormat::Bmp)
-                                .expect("i don't know how Cursor could fail");
-                            buf.flush().expect("could not flush buffer");
-
-                            Some(buf.get_mut().clone())
-                        }
+                        Some(image) => Some(make_album_thumb(image)?),
                         None => None,
                     };
 
@@ -438,6 +997,8 @@ impl ScanThread {
                             .bind(&metadata.label)
                             .bind(&metadata.catalog)
                             .bind(&metadata.isrc)
+                            .bind(&key)
+                            .bind(&metadata.mbid_album)
                             .fetch_one(&self.pool)
                             .await;
 
@@ -465,7 +1026,7 @@ impl ScanThread {
         album_id: Option<i64>,
         path: &Path,
         length: u64,
-    ) {
+    ) -> Option<i64> {
         // literally i do not know how this could possibly fail
         let name = metadata
             .name
@@ -486,15 +1047,18 @@ impl ScanThread {
                 .bind(metadata.disc_current.map(|x| x as i32))
                 .bind(length as i32)
                 .bind(path.to_str())
-                .bind(&metadata.genre)
+                .bind(metadata.genre())
+                .bind(&metadata.mbid_track)
+                .bind(&metadata.disc_subtitle)
                 .fetch_one(&self.pool)
                 .await;
 
         match result {
-            Ok(_) => (),
-            Err(sqlx::Error::RowNotFound) => (),
+            Ok(v) => Some(v.0),
+            Err(sqlx::Error::RowNotFound) => None,
             Err(e) => {
                 error!("Database error while creating track: {:?}", e);
+                None
             }
         }
     }
@@ -506,17 +1070,127 @@ impl ScanThread {
     ) -> anyhow::Result<()> {
         debug!(
             "Adding/updating record for {:?} - {:?}",
-            metadata.0.artist, metadata.0.name
+            metadata.0.artist(),
+            metadata.0.name
         );
 
-        let artist_id = self.insert_artist(&metadata.0).await;
-        let album_id = self.insert_album(&metadata.0, artist_id, &metadata.2).await;
-        self.insert_track(&metadata.0, album_id, path, metadata.1)
+        let artist_id = self.insert_album_artist(&metadata.0).await;
+        let album_id = self
+            .insert_album(&metadata.0, artist_id, &metadata.2, path)
             .await;
+        let track_id = self
+            .insert_track(&metadata.0, album_id, path, metadata.1)
+            .await;
+
+        if let Some(track_id) = track_id {
+            self.insert_track_links(&metadata.0, track_id).await;
+        }
 
         Ok(())
     }
 
+    /// Albums are only created by scans, never updated, so their details (including the art) are
+    /// updated here from the reloaded file.
+    async fn update_album(&self, metadata: &Metadata, album_id: i64, image: &Option<Box<[u8]>>) {
+        let thumb = image.as_deref().and_then(make_album_thumb);
+
+        let result = sqlx::query(include_str!("../../queries/scan/update_album.sql"))
+            .bind(album_id)
+            .bind(image)
+            .bind(thumb)
+            .bind(metadata.date)
+            .bind(&metadata.label)
+            .bind(&metadata.catalog)
+            .bind(&metadata.isrc)
+            .bind(&metadata.mbid_album)
+            .execute(&self.pool)
+            .await;
+
+        if let Err(e) = result {
+            error!("Database error while updating album: {:?}", e);
+        }
+    }
+
+    fn reload(&mut self, paths: Vec<PathBuf>) {
+        for path in paths {
+            let Some(metadata) = self.read_metadata_for_path(&path) else {
+                warn!("Could not reload metadata for file: {:?}", path);
+                continue;
+            };
+
+            task::block_on(async {
+                let artist_id = self.insert_album_artist(&metadata.0).await;
+                let album_id = self
+                    .insert_album(&metadata.0, artist_id, &metadata.2, &path)
+                    .await;
+
+                if let Some(album_id) = album_id {
+                    self.update_album(&metadata.0, album_id, &metadata.2).await;
+                }
+
+                let track_id = self
+                    .insert_track(&metadata.0, album_id, &path, metadata.1)
+                    .await;
+
+                if let Some(track_id) = track_id {
+                    self.insert_track_links(&metadata.0, track_id).await;
+                }
+            });
+
+            // the file doesn't need to be scanned again until it changes
+            if let Ok(modified) = fs::metadata(&path).and_then(|v| v.modified()) {
+                if let Ok(timestamp) = modified.duration_since(SystemTime::UNIX_EPOCH) {
+                    self.scan_record.insert(path, timestamp.as_secs());
+                }
+            }
+        }
+
+        self.write_scan_record();
+        self.event_tx
+            .send(ScanEvent::MetadataReloaded)
+            .expect("could not send metadata reloaded event");
+    }
+
+    fn compute_durations(&mut self, paths: Vec<PathBuf>) {
+        let mut updated = false;
+
+        for path in paths {
+            if !self.durations_computed.insert(path.clone()) {
+                continue;
+            }
+
+            let duration = self
+                .provider_table
+                .iter_mut()
+                .filter(|(exts, _)| file_is_scannable_with_provider(&path, exts))
+                .find_map(|(_, provider)| analyze_duration_with_provider(&path, provider).ok())
+                .filter(|v| *v > 0);
+
+            let Some(duration) = duration else {
+                debug!("could not work out the duration of {:?}", path);
+                continue;
+            };
+
+            let result = task::block_on(
+                sqlx::query(include_str!("../../queries/scan/update_track_duration.sql"))
+                    .bind(duration as i64)
+                    .bind(path.to_str())
+                    .execute(&self.pool),
+            );
+
+            match result {
+                Ok(_) => updated = true,
+                Err(e) => error!("Database error while updating duration: {:?}", e),
+            }
+        }
+
+        if updated {
+            self.event_tx
+                .send(ScanEvent::MetadataReloaded)
+                .expect("could not send metadata reloaded event");
+        }
+    }
+
     fn read_metadata_for_path(&mut self, path: &PathBuf) -> Option<FileInformation> {
         for (exts, provider) in &mut self.provider_table {
             if file_is_scannable_with_provider(path, exts) {
@@ -548,7 +1222,24 @@ impl ScanThread {
         if self.to_process.is_empty() {
             info!("Scan complete, writing scan record and stopping");
             self.write_scan_record();
+
+            // tracks can move to a different album when it's retagged or albums are regrouped
+            if let Err(e) = task::block_on(
+                sqlx::query(include_str!("../../queries/scan/delete_empty_albums.sql"))
+                    .execute(&self.pool),
+            ) {
+                error!("Database error while deleting empty albums: {:?}", e);
+            }
+
+            self.force = false;
             self.scan_state = ScanState::Idle;
+
+            // folders that were dropped while scanning are scanned one after another
+            if let Some(next) = self.queued_folders.pop_front() {
+                self.start_folder_scan(next);
+                return;
+            }
+
             self.event_tx.send(ScanEvent::ScanCompleteIdle).unwrap();
             return;
         }
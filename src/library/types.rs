@@ -8,7 +8,10 @@ use image::Frame;
 use smallvec::SmallVec;
 use sqlx::{Database, Decode, Sqlite, Type};
 
-use crate::util::rgb_to_bgr;
+use crate::{
+    data::events::ImageLayout,
+    util::{image_layout, rgb_to_bgr},
+};
 
 #[derive(sqlx::FromRow)]
 pub struct Artist {
@@ -43,7 +46,9 @@ impl From<Box<[u8]>> for Thumbnail {
             .expect("invalid thumbnail")
             .to_owned();
 
-        rgb_to_bgr(&mut image);
+        if image_layout() == ImageLayout::BGR {
+            rgb_to_bgr(&mut image);
+        }
 
         Self(Arc::new(RenderImage::new(SmallVec::from_vec(vec![
             Frame::new(image),
@@ -206,6 +211,15 @@ pub struct Album {
     pub catalog_number: Option<DBString>,
     #[sqlx(default)]
     pub isrc: Option<DBString>,
+    /// Manual gain adjustment for every track in the album, in dB.
+    #[sqlx(default)]
+    pub gain_offset: f64,
+    /// The MusicBrainz ID of the release.
+    #[sqlx(default)]
+    pub mbid: Option<DBString>,
+    /// Where the album is shown in the pinned section of the library, if it's pinned.
+    #[sqlx(default)]
+    pub pinned_position: Option<i64>,
 }
 
 #[derive(sqlx::FromRow, Clone)]
@@ -219,6 +233,9 @@ pub struct Track {
     pub track_number: Option<i32>,
     #[sqlx(default)]
     pub disc_number: Option<i32>,
+    /// The name of the track's disc, if it has one.
+    #[sqlx(default)]
+    pub disc_subtitle: Option<DBString>,
     pub duration: i64,
     pub created_at: DateTime<Utc>,
     #[sqlx(skip)]
@@ -226,4 +243,65 @@ pub struct Track {
     #[sqlx(skip)]
     pub tags: Option<Vec<DBString>>,
     pub location: String,
+    /// Manual gain adjustment for the track, in dB. This is added to the album's gain offset.
+    #[sqlx(default)]
+    pub gain_offset: f64,
+    /// Whether the track is passed over when the queue advances.
+    #[sqlx(default)]
+    pub skip_always: bool,
+    /// The MusicBrainz ID of the recording.
+    #[sqlx(default)]
+    pub mbid: Option<DBString>,
+    #[sqlx(default)]
+    pub play_count: i64,
+    /// The track's rating, from 0 to 5 stars.
+    #[sqlx(default)]
+    pub rating: Option<i64>,
+    #[sqlx(default)]
+    pub last_played: Option<DateTime<Utc>>,
+    /// Where playback of the track starts, in seconds, if it doesn't start at the beginning.
+    #[sqlx(default)]
+    pub start_offset: Option<i64>,
+    /// Where playback of the track ends, in seconds, if it doesn't end at the end of the file.
+    #[sqlx(default)]
+    pub end_offset: Option<i64>,
+}
+
+/// Where playback of an album was last stopped, so that it can be resumed.
+#[derive(sqlx::FromRow, Clone, Debug)]
+pub struct ResumePosition {
+    pub album_id: i64,
+    pub location: String,
+    /// The position within the track, in seconds.
+    pub position: i64,
+}
+
+/// Something in the library that matched a search.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SearchResult {
+    Track {
+        location: String,
+        title: String,
+        artist: Option<String>,
+    },
+    Album {
+        id: i64,
+        title: String,
+        artist: Option<String>,
+    },
+    Artist {
+        id: i64,
+        name: String,
+    },
+}
+
+impl SearchResult {
+    /// The text that the result is ranked by.
+    pub fn title(&self) -> &str {
+        match self {
+            SearchResult::Track { title, .. } => title,
+            SearchResult::Album { title, .. } => title,
+            SearchResult::Artist { name, .. } => name,
+        }
+    }
 }
@@ -0,0 +1,138 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+use chrono::{DateTime, Local};
+use tracing::{
+    field::{Field, Visit},
+    warn, Event, Level, Subscriber,
+};
+use tracing_subscriber::{fmt, layer::Context, prelude::*, reload, EnvFilter, Layer, Registry};
+
+/// How many events are kept for the log viewer.
+const BUFFER_SIZE: usize = 1000;
+
+/// The level used when neither the environment nor the settings specify one.
+const DEFAULT_LEVEL: &str = "info";
+
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+static BUFFER: Mutex<VecDeque<LogLine>> = Mutex::new(VecDeque::new());
+/// Incremented whenever an event is buffered, so that the log viewer knows when to refresh.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+/// Only set if the level isn't fixed by the environment.
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Sets up logging to stderr and to the log viewer. `MUZAK_LOG` (or `RUST_LOG`) takes the usual
+/// filter directives, e.g. `muzak=debug,sqlx=warn`, and overrides the `log_level` setting.
+pub fn init() {
+    let from_env = std::env::var("MUZAK_LOG")
+        .or_else(|_| std::env::var("RUST_LOG"))
+        .ok()
+        .and_then(|v| EnvFilter::try_new(v).ok());
+
+    let fixed = from_env.is_some();
+    let (filter, handle) =
+        reload::Layer::new(from_env.unwrap_or_else(|| EnvFilter::new(DEFAULT_LEVEL)));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .with(BufferLayer)
+        .init();
+
+    if !fixed {
+        FILTER.set(handle).ok();
+    }
+}
+
+/// Changes the filter to the `log_level` setting, unless it was set through the environment.
+pub fn set_log_level(level: Option<&str>) {
+    let Some(handle) = FILTER.get() else {
+        return;
+    };
+
+    let filter = match EnvFilter::try_new(level.unwrap_or(DEFAULT_LEVEL)) {
+        Ok(filter) => filter,
+        Err(e) => {
+            warn!("Invalid log level {:?}: {}", level, e);
+            return;
+        }
+    };
+
+    if let Err(e) = handle.reload(filter) {
+        warn!("Could not change log level: {:?}", e);
+    }
+}
+
+/// The buffered events, oldest first.
+pub fn recent_lines() -> Vec<LogLine> {
+    BUFFER
+        .lock()
+        .expect("log buffer poisoned")
+        .iter()
+        .cloned()
+        .collect()
+}
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// Keeps the most recent events that pass the filter.
+struct BufferLayer;
+
+impl<S: Subscriber> Layer<S> for BufferLayer {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let line = LogLine {
+            time: Local::now(),
+            level: *event.metadata().level(),
+            target: event.metadata().target().to_string(),
+            message: visitor.0,
+        };
+
+        // an event logged while the lock is held (or after a panic) is dropped instead
+        let Ok(mut buffer) = BUFFER.try_lock() else {
+            return;
+        };
+
+        if buffer.len() == BUFFER_SIZE {
+            buffer.pop_front();
+        }
+
+        buffer.push_back(line);
+        GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Formats the message of an event followed by its other fields, like the fmt layer does.
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+
+        if field.name() == "message" {
+            write!(self.0, "{:?}", value).ok();
+        } else {
+            write!(self.0, "{}={:?}", field.name(), value).ok();
+        }
+    }
+}
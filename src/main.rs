@@ -2,9 +2,12 @@ use services::mmb::lastfm::{LASTFM_API_KEY, LASTFM_API_SECRET};
 
 mod data;
 mod devices;
+mod ipc;
 mod library;
+mod logging;
 mod media;
 mod playback;
+mod remote;
 mod services;
 mod settings;
 mod ui;
@@ -12,7 +15,10 @@ mod util;
 
 #[async_std::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    logging::init();
+
+    crate::ui::app::setup_data_dir();
+    crate::ui::arguments::forward_to_running_instance();
 
     tracing::info!("Starting application");
 
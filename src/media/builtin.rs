@@ -1 +1,37 @@
+use std::path::Path;
+
+use self::{au::AuProvider, symphonia::SymphoniaProvider};
+
+use super::traits::MediaProvider;
+
+mod au;
+mod opus;
 pub mod symphonia;
+
+/// Creates an instance of every built in provider.
+// TODO: dynamic plugin loading
+pub fn providers() -> Vec<Box<dyn MediaProvider>> {
+    vec![
+        Box::new(SymphoniaProvider::default()),
+        Box::new(AuProvider::default()),
+    ]
+}
+
+/// Every file extension supported by at least one provider.
+pub fn supported_extensions() -> Vec<&'static str> {
+    let mut extensions: Vec<&'static str> = providers()
+        .iter()
+        .flat_map(|provider| provider.supported_extensions().iter().copied())
+        .collect();
+
+    extensions.sort_unstable();
+    extensions.dedup();
+    extensions
+}
+
+/// Whether the file has one of the specified extensions, ignoring case.
+pub fn has_supported_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.iter().any(|v| v.eq_ignore_ascii_case(ext)))
+}
@@ -0,0 +1,567 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+};
+
+use intx::I24;
+
+use crate::media::{
+    errors::{
+        CloseError, FrameDurationError, MetadataError, OpenError, PlaybackReadError,
+        PlaybackStartError, PlaybackStopError, SeekError, TrackDurationError,
+    },
+    metadata::Metadata,
+    playback::{PlaybackFrame, Samples},
+    traits::{MediaPlugin, MediaProvider},
+};
+
+/// Every AU file starts with ".snd".
+const MAGIC: &[u8; 4] = b".snd";
+/// The length of the fixed part of the header. It's followed by an annotation, which runs until
+/// the start of the audio data.
+const HEADER_LENGTH: usize = 24;
+/// How many samples (per channel) are read at once.
+const FRAMES_PER_READ: usize = 1024;
+
+/// The ways samples can be stored in an AU file. The IDs are the ones used in the header; the
+/// rarely used ones (like ADPCM and the DSP formats) aren't supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    MuLaw,
+    ALaw,
+    Linear8,
+    Linear16,
+    Linear24,
+    Linear32,
+    Float32,
+    Float64,
+}
+
+impl Encoding {
+    fn from_id(id: u32) -> Option<Self> {
+        match id {
+            1 => Some(Encoding::MuLaw),
+            2 => Some(Encoding::Linear8),
+            3 => Some(Encoding::Linear16),
+            4 => Some(Encoding::Linear24),
+            5 => Some(Encoding::Linear32),
+            6 => Some(Encoding::Float32),
+            7 => Some(Encoding::Float64),
+            27 => Some(Encoding::ALaw),
+            _ => None,
+        }
+    }
+
+    /// The size of a single sample, in bytes.
+    fn width(&self) -> usize {
+        match self {
+            Encoding::MuLaw | Encoding::ALaw | Encoding::Linear8 => 1,
+            Encoding::Linear16 => 2,
+            Encoding::Linear24 => 3,
+            Encoding::Linear32 | Encoding::Float32 => 4,
+            Encoding::Float64 => 8,
+        }
+    }
+
+    fn codec_name(&self) -> &'static str {
+        match self {
+            Encoding::MuLaw => "ULAW",
+            Encoding::ALaw => "ALAW",
+            _ => "PCM",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    data_offset: u64,
+    /// The length of the audio data in bytes, if the file says what it is. Files that were
+    /// written as a stream often don't, in which case the data runs until the end of the file.
+    data_size: Option<u64>,
+    encoding: Encoding,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl Header {
+    /// The size of one sample for every channel, in bytes.
+    fn frame_width(&self) -> u64 {
+        self.encoding.width() as u64 * self.channels as u64
+    }
+}
+
+fn parse_header(bytes: &[u8]) -> Result<Header, OpenError> {
+    if bytes.len() < HEADER_LENGTH || &bytes[0..4] != MAGIC {
+        return Err(OpenError::UnsupportedFormat);
+    }
+
+    let field = |idx: usize| {
+        let start = 4 + idx * 4;
+        u32::from_be_bytes(bytes[start..start + 4].try_into().unwrap())
+    };
+
+    let data_offset = field(0) as u64;
+    let data_size = Some(field(1) as u64).filter(|v| *v != u32::MAX as u64);
+    let encoding = Encoding::from_id(field(2)).ok_or(OpenError::UnsupportedFormat)?;
+    let sample_rate = field(3);
+    let channels = u16::try_from(field(4)).map_err(|_| OpenError::FileCorrupt)?;
+
+    if data_offset < HEADER_LENGTH as u64 || sample_rate == 0 || channels == 0 {
+        return Err(OpenError::FileCorrupt);
+    }
+
+    Ok(Header {
+        data_offset,
+        data_size,
+        encoding,
+        sample_rate,
+        channels,
+    })
+}
+
+/// Expands a G.711 μ-law sample to 16 bits.
+fn decode_mulaw(value: u8) -> i16 {
+    let value = !value;
+    let exponent = (value >> 4) & 0x07;
+    let mantissa = (value & 0x0F) as i16;
+    let sample = (((mantissa << 3) + 0x84) << exponent) - 0x84;
+
+    if value & 0x80 != 0 {
+        -sample
+    } else {
+        sample
+    }
+}
+
+/// Expands a G.711 A-law sample to 16 bits.
+fn decode_alaw(value: u8) -> i16 {
+    let value = value ^ 0x55;
+    let exponent = (value >> 4) & 0x07;
+    let mantissa = (value & 0x0F) as i16;
+    let sample = if exponent == 0 {
+        (mantissa << 4) + 8
+    } else {
+        ((mantissa << 4) + 0x108) << (exponent - 1)
+    };
+
+    if value & 0x80 != 0 {
+        sample
+    } else {
+        -sample
+    }
+}
+
+/// Splits interleaved big-endian samples into channels, converting each one with `convert`.
+fn deinterleave<T>(
+    data: &[u8],
+    channels: usize,
+    width: usize,
+    convert: impl Fn(&[u8]) -> T,
+) -> Vec<Vec<T>> {
+    let mut samples: Vec<Vec<T>> = (0..channels)
+        .map(|_| Vec::with_capacity(data.len() / width / channels))
+        .collect();
+
+    for (idx, sample) in data.chunks_exact(width).enumerate() {
+        samples[idx % channels].push(convert(sample));
+    }
+
+    samples
+}
+
+fn decode(data: &[u8], header: &Header) -> Samples {
+    let channels = header.channels as usize;
+    let width = header.encoding.width();
+
+    match header.encoding {
+        Encoding::MuLaw => {
+            Samples::Signed16(deinterleave(data, channels, width, |v| decode_mulaw(v[0])))
+        }
+        Encoding::ALaw => {
+            Samples::Signed16(deinterleave(data, channels, width, |v| decode_alaw(v[0])))
+        }
+        Encoding::Linear8 => Samples::Signed8(deinterleave(data, channels, width, |v| v[0] as i8)),
+        Encoding::Linear16 => Samples::Signed16(deinterleave(data, channels, width, |v| {
+            i16::from_be_bytes([v[0], v[1]])
+        })),
+        Encoding::Linear24 => Samples::Signed24(deinterleave(data, channels, width, |v| {
+            // shifting back down sign-extends the value
+            let value = i32::from_be_bytes([v[0], v[1], v[2], 0]) >> 8;
+            I24::try_from(value).expect("24bit number is not 24bits long")
+        })),
+        Encoding::Linear32 => Samples::Signed32(deinterleave(data, channels, width, |v| {
+            i32::from_be_bytes([v[0], v[1], v[2], v[3]])
+        })),
+        Encoding::Float32 => Samples::Float32(deinterleave(data, channels, width, |v| {
+            f32::from_be_bytes([v[0], v[1], v[2], v[3]])
+        })),
+        Encoding::Float64 => Samples::Float64(deinterleave(data, channels, width, |v| {
+            f64::from_be_bytes(v.try_into().unwrap())
+        })),
+    }
+}
+
+/// Plays Sun/NeXT AU files, which symphonia doesn't support. These are still common in sound
+/// effect and sample libraries.
+#[derive(Default)]
+pub struct AuProvider {
+    reader: Option<BufReader<File>>,
+    header: Option<Header>,
+    current_metadata: Metadata,
+    pending_metadata_update: bool,
+    started: bool,
+    /// The length of the audio data, in samples per channel.
+    current_length: u64,
+    /// How far into the audio data playback is, in samples per channel.
+    current_position: u64,
+    current_duration: u64,
+}
+
+impl MediaProvider for AuProvider {
+    fn open(&mut self, file: File, _ext: Option<String>) -> Result<(), OpenError> {
+        let file_size = file.metadata().ok().map(|v| v.len());
+        let mut reader = BufReader::new(file);
+        let mut bytes = [0; HEADER_LENGTH];
+
+        reader
+            .read_exact(&mut bytes)
+            .map_err(|_| OpenError::UnsupportedFormat)?;
+
+        let header = parse_header(&bytes)?;
+
+        // streamed files don't know their length, so the data is assumed to fill the file
+        let data_size = header
+            .data_size
+            .or(file_size.map(|v| v.saturating_sub(header.data_offset)))
+            .unwrap_or_default();
+
+        reader
+            .seek(SeekFrom::Start(header.data_offset))
+            .map_err(|_| OpenError::FileCorrupt)?;
+
+        self.current_metadata = Metadata {
+            codec: Some(header.encoding.codec_name().to_string()),
+            sample_rate: Some(header.sample_rate),
+            bit_depth: Some(header.encoding.width() as u32 * 8),
+            channels: Some(header.channels),
+            bitrate: Some(header.sample_rate as u64 * header.frame_width() * 8 / 1000),
+            ..Default::default()
+        };
+        self.pending_metadata_update = true;
+        self.current_length = data_size / header.frame_width();
+        self.current_position = 0;
+        self.current_duration = 0;
+        self.started = false;
+        self.header = Some(header);
+        self.reader = Some(reader);
+
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), CloseError> {
+        self.stop_playback().expect("invalid outcome");
+        self.current_metadata = Metadata::default();
+        self.reader = None;
+        self.header = None;
+        Ok(())
+    }
+
+    fn start_playback(&mut self) -> Result<(), PlaybackStartError> {
+        if self.reader.is_none() {
+            return Err(PlaybackStartError::NothingOpen);
+        }
+
+        if self.current_length == 0 {
+            return Err(PlaybackStartError::NothingToPlay);
+        }
+
+        self.started = true;
+        Ok(())
+    }
+
+    fn stop_playback(&mut self) -> Result<(), PlaybackStopError> {
+        self.started = false;
+        Ok(())
+    }
+
+    fn seek(&mut self, time: f64) -> Result<(), SeekError> {
+        let (Some(reader), Some(header)) = (&mut self.reader, &self.header) else {
+            return Err(SeekError::NothingOpen);
+        };
+
+        let position =
+            ((time.max(0.0) * header.sample_rate as f64) as u64).min(self.current_length);
+
+        reader
+            .seek(SeekFrom::Start(
+                header.data_offset + position * header.frame_width(),
+            ))
+            .map_err(|_| SeekError::Unknown)?;
+
+        self.current_position = position;
+
+        Ok(())
+    }
+
+    fn read_samples(&mut self) -> Result<PlaybackFrame, PlaybackReadError> {
+        let (Some(reader), Some(header)) = (&mut self.reader, &self.header) else {
+            return Err(PlaybackReadError::NothingOpen);
+        };
+
+        if !self.started {
+            return Err(PlaybackReadError::NeverStarted);
+        }
+
+        let frames = (self.current_length - self.current_position).min(FRAMES_PER_READ as u64);
+
+        if frames == 0 {
+            return Err(PlaybackReadError::Eof);
+        }
+
+        let mut data = Vec::with_capacity((frames * header.frame_width()) as usize);
+
+        reader
+            .by_ref()
+            .take(frames * header.frame_width())
+            .read_to_end(&mut data)
+            .map_err(|_| PlaybackReadError::DecodeFatal)?;
+
+        // a file that ends early (or with part of a sample) is played up until that point
+        let frames = data.len() as u64 / header.frame_width();
+
+        if frames == 0 {
+            return Err(PlaybackReadError::Eof);
+        }
+
+        data.truncate((frames * header.frame_width()) as usize);
+
+        self.current_position += frames;
+        self.current_duration = frames;
+
+        Ok(PlaybackFrame {
+            rate: header.sample_rate,
+            samples: decode(&data, header),
+        })
+    }
+
+    fn frame_duration(&self) -> Result<u64, FrameDurationError> {
+        if self.reader.is_none() {
+            Err(FrameDurationError::NothingOpen)
+        } else if self.current_duration == 0 {
+            Err(FrameDurationError::NeverDecoded)
+        } else {
+            Ok(self.current_duration)
+        }
+    }
+
+    fn read_metadata(&mut self) -> Result<&Metadata, MetadataError> {
+        self.pending_metadata_update = false;
+
+        if self.reader.is_some() {
+            Ok(&self.current_metadata)
+        } else {
+            Err(MetadataError::NothingOpen)
+        }
+    }
+
+    fn metadata_updated(&self) -> bool {
+        self.pending_metadata_update
+    }
+
+    fn read_image(&mut self) -> Result<Option<Box<[u8]>>, MetadataError> {
+        // AU files can't contain pictures
+        if self.reader.is_some() {
+            Ok(None)
+        } else {
+            Err(MetadataError::NothingOpen)
+        }
+    }
+
+    fn duration_secs(&self) -> Result<u64, TrackDurationError> {
+        match (&self.header, self.started) {
+            (None, _) => Err(TrackDurationError::NothingOpen),
+            (Some(_), false) => Err(TrackDurationError::NeverStarted),
+            (Some(header), true) => Ok(self.current_length / header.sample_rate as u64),
+        }
+    }
+
+    fn position_secs(&self) -> Result<u64, TrackDurationError> {
+        match (&self.header, self.started) {
+            (None, _) => Err(TrackDurationError::NothingOpen),
+            (Some(_), false) => Err(TrackDurationError::NeverStarted),
+            (Some(header), true) => Ok(self.current_position / header.sample_rate as u64),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn supported_extensions(&self) -> &'static [&'static str] {
+        Self::SUPPORTED_EXTENSIONS
+    }
+
+    fn supported_mimetypes(&self) -> &'static [&'static str] {
+        Self::SUPPORTED_MIMETYPES
+    }
+}
+
+impl MediaPlugin for AuProvider {
+    const NAME: &'static str = "AU";
+
+    const VERSION: &'static str = "0.1.0";
+
+    const SUPPORTED_MIMETYPES: &'static [&'static str] = &["audio/basic"];
+
+    const PROVIDES_DECODING: bool = true;
+    const PROVIDES_METADATA: bool = true;
+    const ALWAYS_CHECK_METADATA: bool = false;
+
+    const SUPPORTED_EXTENSIONS: &'static [&'static str] = &["au", "snd"];
+    const INDEXING_SUPPORTED: bool = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn header(encoding: u32, sample_rate: u32, channels: u32, data_size: u32) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+
+        for field in [
+            HEADER_LENGTH as u32 + 8,
+            data_size,
+            encoding,
+            sample_rate,
+            channels,
+        ] {
+            bytes.extend_from_slice(&field.to_be_bytes());
+        }
+
+        // the annotation, which is ignored
+        bytes.extend_from_slice(b"muzak\0\0\0");
+        bytes
+    }
+
+    fn open(name: &str, bytes: &[u8]) -> AuProvider {
+        let path =
+            std::env::temp_dir().join(format!("muzak-au-{}-{}.au", name, std::process::id()));
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+
+        let mut provider = AuProvider::default();
+        provider.open(File::open(&path).unwrap(), None).unwrap();
+        provider.start_playback().unwrap();
+
+        let _ = std::fs::remove_file(&path);
+        provider
+    }
+
+    #[test]
+    fn parses_header() {
+        let header = parse_header(&header(3, 44100, 2, 400)).unwrap();
+
+        assert_eq!(header.data_offset, 32);
+        assert_eq!(header.data_size, Some(400));
+        assert_eq!(header.encoding, Encoding::Linear16);
+        assert_eq!(header.sample_rate, 44100);
+        assert_eq!(header.channels, 2);
+        assert_eq!(header.frame_width(), 4);
+    }
+
+    #[test]
+    fn unknown_data_size() {
+        let header = parse_header(&header(1, 8000, 1, u32::MAX)).unwrap();
+
+        assert_eq!(header.data_size, None);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert_eq!(
+            parse_header(b"RIFF\0\0\0\0WAVEfmt \0\0\0\0\0\0\0\0"),
+            Err(OpenError::UnsupportedFormat)
+        );
+        assert_eq!(
+            parse_header(&header(23, 44100, 2, 400)),
+            Err(OpenError::UnsupportedFormat)
+        );
+        assert_eq!(
+            parse_header(&header(3, 0, 2, 400)),
+            Err(OpenError::FileCorrupt)
+        );
+    }
+
+    #[test]
+    fn companded_samples() {
+        assert_eq!(decode_mulaw(0xFF), 0);
+        assert_eq!(decode_mulaw(0x7F), 0);
+        assert_eq!(decode_mulaw(0x80), 32124);
+        assert_eq!(decode_mulaw(0x00), -32124);
+
+        assert_eq!(decode_alaw(0xD5), 8);
+        assert_eq!(decode_alaw(0x55), -8);
+        assert_eq!(decode_alaw(0xAA), 32256);
+        assert_eq!(decode_alaw(0x2A), -32256);
+    }
+
+    #[test]
+    fn reads_samples() {
+        let mut bytes = header(3, 8000, 2, 8);
+
+        for sample in [1i16, -1, 256, -256] {
+            bytes.extend_from_slice(&sample.to_be_bytes());
+        }
+
+        let mut provider = open("reads", &bytes);
+        let frame = provider.read_samples().unwrap();
+
+        assert_eq!(frame.rate, 8000);
+        match frame.samples {
+            Samples::Signed16(samples) => assert_eq!(samples, vec![vec![1, 256], vec![-1, -256]]),
+            _ => panic!("wrong sample format"),
+        }
+        assert_eq!(provider.frame_duration(), Ok(2));
+        assert_eq!(provider.read_samples().err(), Some(PlaybackReadError::Eof));
+    }
+
+    #[test]
+    fn sign_extends_24bit_samples() {
+        let mut bytes = header(4, 8000, 1, 6);
+        bytes.extend_from_slice(&[0xFF, 0xFF, 0xFE, 0x00, 0x00, 0x02]);
+
+        let mut provider = open("24bit", &bytes);
+
+        match provider.read_samples().unwrap().samples {
+            Samples::Signed24(samples) => {
+                let samples: Vec<i32> = samples[0].iter().map(|v| i32::from(*v)).collect();
+                assert_eq!(samples, vec![-2, 2]);
+            }
+            _ => panic!("wrong sample format"),
+        }
+    }
+
+    #[test]
+    fn seeks_and_reports_position() {
+        // three seconds of silence, with the length left for the reader to work out
+        let mut bytes = header(2, 1000, 1, u32::MAX);
+        bytes.extend(std::iter::repeat(0).take(3000));
+
+        let mut provider = open("seek", &bytes);
+
+        assert_eq!(provider.duration_secs(), Ok(3));
+
+        provider.seek(2.5).unwrap();
+        assert_eq!(provider.position_secs(), Ok(2));
+        match provider.read_samples().unwrap().samples {
+            Samples::Signed8(samples) => assert_eq!(samples[0].len(), 500),
+            _ => panic!("wrong sample format"),
+        }
+        assert_eq!(provider.read_samples().err(), Some(PlaybackReadError::Eof));
+
+        // seeking past the end stops at the end
+        provider.seek(10.0).unwrap();
+        assert_eq!(provider.position_secs(), Ok(3));
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::OnceLock;
+
+use symphonia::{
+    core::{
+        audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec},
+        codecs::{
+            CodecDescriptor, CodecParameters, CodecRegistry, Decoder, DecoderOptions,
+            FinalizeResult, CODEC_TYPE_OPUS,
+        },
+        errors::{unsupported_error, Error, Result},
+        formats::Packet,
+    },
+    default::register_enabled_codecs,
+    support_codec,
+};
+
+/// Opus is always decoded at 48kHz, regardless of the rate the stream was encoded at.
+const SAMPLE_RATE: u32 = 48000;
+/// The longest frame an Opus packet can contain (120ms), in samples per channel.
+const MAX_FRAME_LENGTH: usize = 5760;
+
+/// The codecs symphonia provides, along with an Opus decoder, which symphonia doesn't have one of.
+pub fn codecs() -> &'static CodecRegistry {
+    static CODECS: OnceLock<CodecRegistry> = OnceLock::new();
+
+    CODECS.get_or_init(|| {
+        let mut registry = CodecRegistry::new();
+        register_enabled_codecs(&mut registry);
+        registry.register_all::<OpusDecoder>();
+        registry
+    })
+}
+
+/// Decodes Opus packets demuxed by symphonia (usually from Ogg) using libopus.
+pub struct OpusDecoder {
+    params: CodecParameters,
+    decoder: opus::Decoder,
+    channels: usize,
+    /// The interleaved output of libopus, before it is split into channels.
+    interleaved: Vec<f32>,
+    buffer: AudioBuffer<f32>,
+    /// How many more samples at the start of the stream have to be dropped. Encoders prepend a few
+    /// milliseconds of audio that only primes the decoder, which gapless playback would otherwise
+    /// hear as a click or a short gap.
+    skip: usize,
+}
+
+/// The number of samples at the start of the stream that have to be dropped, as given in the
+/// stream's OpusHead header.
+fn pre_skip(params: &CodecParameters) -> usize {
+    params
+        .delay
+        .map(|v| v as usize)
+        .or_else(|| {
+            let head = params.extra_data.as_ref()?;
+
+            (head.len() >= 12 && head.starts_with(b"OpusHead"))
+                .then(|| u16::from_le_bytes([head[10], head[11]]) as usize)
+        })
+        .unwrap_or(0)
+}
+
+impl Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _: &DecoderOptions) -> Result<Self> {
+        let layout = params
+            .channels
+            .unwrap_or(Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let channels = layout.count();
+
+        let opus_channels = match channels {
+            1 => opus::Channels::Mono,
+            2 => opus::Channels::Stereo,
+            _ => return unsupported_error("opus: only mono and stereo streams are supported"),
+        };
+
+        let decoder = opus::Decoder::new(SAMPLE_RATE, opus_channels)
+            .map_err(|_| Error::DecodeError("opus: could not create decoder"))?;
+
+        Ok(Self {
+            params: params.clone(),
+            decoder,
+            channels,
+            interleaved: vec![0.0; MAX_FRAME_LENGTH * channels],
+            buffer: AudioBuffer::new(
+                MAX_FRAME_LENGTH as u64,
+                SignalSpec::new(SAMPLE_RATE, layout),
+            ),
+            skip: pre_skip(params),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus")]
+    }
+
+    fn reset(&mut self) {
+        let _ = self.decoder.reset_state();
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef> {
+        let frames = self
+            .decoder
+            .decode_float(&packet.data, &mut self.interleaved, false)
+            .map_err(|_| Error::DecodeError("opus: invalid packet"))?;
+
+        // the container trims the pre-skip itself when gapless playback is enabled
+        let (start, end) = if packet.trim_start > 0 || packet.trim_end > 0 {
+            self.skip = 0;
+            (packet.trim_start as usize, packet.trim_end as usize)
+        } else {
+            let skip = self.skip.min(frames);
+            self.skip -= skip;
+            (skip, 0)
+        };
+
+        let start = start.min(frames);
+        let kept = frames - start - end.min(frames - start);
+
+        self.buffer.clear();
+        self.buffer.render_reserved(Some(kept));
+
+        for channel in 0..self.channels {
+            for (i, sample) in self.buffer.chan_mut(channel).iter_mut().enumerate() {
+                *sample = self.interleaved[(start + i) * self.channels + channel];
+            }
+        }
+
+        Ok(self.buffer.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef {
+        self.buffer.as_audio_buffer_ref()
+    }
+}
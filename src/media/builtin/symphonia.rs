@@ -1,30 +1,103 @@
-use std::fs::File;
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
 
 use intx::{I24, U24};
-use symphonia::{
-    core::{
-        audio::{AudioBufferRef, Signal},
-        codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL},
-        errors::Error,
-        formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
-        io::MediaSourceStream,
-        meta::{MetadataOptions, StandardTagKey, Tag, Value, Visual},
-        probe::{Hint, ProbeResult},
-        units::{Time, TimeBase},
-    },
-    default::get_codecs,
+use symphonia::core::{
+    audio::{AudioBufferRef, Signal},
+    codecs::{Decoder, DecoderOptions, CODEC_TYPE_MP3, CODEC_TYPE_NULL},
+    errors::Error,
+    formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+    io::MediaSourceStream,
+    meta::{MetadataOptions, StandardTagKey, StandardVisualKey, Tag, Value, Visual},
+    probe::{Hint, ProbeResult},
+    units::{Time, TimeBase},
 };
 
+use super::opus::codecs;
 use crate::media::{
     errors::{
         CloseError, FrameDurationError, MetadataError, OpenError, PlaybackReadError,
         PlaybackStartError, PlaybackStopError, SeekError, TrackDurationError,
     },
-    metadata::Metadata,
+    metadata::{pick_cover, CreditRole, EmbeddedImage, Metadata, PictureKind},
     playback::{PlaybackFrame, Samples},
     traits::{MediaPlugin, MediaProvider},
 };
 
+/// Adds every value in a (possibly multi-value) tag to the list. Formats that support repeated
+/// tags (like Vorbis comments) produce one tag per value, while ID3v2.4 separates values with null
+/// characters and many taggers fall back to semicolons.
+fn push_values(list: &mut Vec<String>, value: &Value) {
+    for value in value.to_string().split(['\0', ';']) {
+        let value = value.trim();
+
+        if !value.is_empty() && !list.iter().any(|v| v == value) {
+            list.push(value.to_string());
+        }
+    }
+}
+
+/// Adds every value in the tag to the credits, split the same way as [`push_values`].
+fn push_credits(credits: &mut Vec<(CreditRole, String)>, role: CreditRole, value: &Value) {
+    let mut names = Vec::new();
+    push_values(&mut names, value);
+
+    for name in names {
+        if !credits.iter().any(|(r, n)| *r == role && *n == name) {
+            credits.push((role, name));
+        }
+    }
+}
+
+/// Reads a ReplayGain peak, which is usually stored as a string like "0.988525".
+fn parse_peak(value: &Value) -> Option<f64> {
+    let peak = match value {
+        Value::Float(v) => Some(*v),
+        Value::String(v) => v.trim().parse().ok(),
+        _ => None,
+    };
+
+    peak.filter(|v: &f64| v.is_finite() && *v > 0.0)
+}
+
+/// Whether the tag's key is the specified name, ignoring case and any prefix (for example the
+/// "TXXX:" of ID3 user-defined text frames).
+fn key_is(key: &str, name: &str) -> bool {
+    key.rsplit(':')
+        .next()
+        .is_some_and(|v| v.eq_ignore_ascii_case(name))
+}
+
+fn picture_kind(usage: Option<StandardVisualKey>) -> PictureKind {
+    match usage {
+        Some(StandardVisualKey::FrontCover) => PictureKind::FrontCover,
+        Some(StandardVisualKey::BackCover) => PictureKind::BackCover,
+        Some(
+            StandardVisualKey::Leaflet | StandardVisualKey::Media | StandardVisualKey::Illustration,
+        ) => PictureKind::Booklet,
+        Some(
+            StandardVisualKey::LeadArtistPerformerSoloist
+            | StandardVisualKey::ArtistPerformer
+            | StandardVisualKey::Conductor
+            | StandardVisualKey::BandOrchestra
+            | StandardVisualKey::Composer
+            | StandardVisualKey::Lyricist,
+        ) => PictureKind::Artist,
+        Some(StandardVisualKey::FileIcon | StandardVisualKey::OtherIcon) => PictureKind::Icon,
+        Some(_) => PictureKind::Other,
+        None => PictureKind::Unknown,
+    }
+}
+
+fn embedded_images(visuals: &[Visual]) -> impl Iterator<Item = EmbeddedImage> + '_ {
+    visuals.iter().map(|visual| EmbeddedImage {
+        kind: picture_kind(visual.usage),
+        data: visual.data.clone(),
+    })
+}
+
 #[derive(Default)]
 pub struct SymphoniaProvider {
     format: Option<Box<dyn FormatReader>>,
@@ -36,7 +109,65 @@ pub struct SymphoniaProvider {
     current_timebase: Option<TimeBase>,
     decoder: Option<Box<dyn Decoder>>,
     pending_metadata_update: bool,
-    last_image: Option<Visual>,
+    /// Every picture in the current metadata.
+    images: Vec<EmbeddedImage>,
+    /// Whether the album art hasn't been read since the metadata changed.
+    pending_image: bool,
+    /// Whether the duration reported by the container is an estimate that has to be checked by
+    /// reading through the file.
+    estimated_duration: bool,
+    /// Where the current logical stream of a chained Ogg file starts, in seconds.
+    stream_offset: f64,
+    /// Where the last packet read from the current logical stream ends, relative to the start of
+    /// the stream, in seconds.
+    stream_end: f64,
+    /// Another handle to the open file (and its extension), so that it can be read from the
+    /// start again when seeking back to an earlier logical stream of a chained file.
+    source: Option<(File, Option<String>)>,
+}
+
+fn secs(time: Time) -> f64 {
+    time.seconds as f64 + time.frac
+}
+
+fn time(secs: f64) -> Time {
+    Time {
+        seconds: secs.trunc() as u64,
+        frac: secs.fract(),
+    }
+}
+
+/// Checks whether an MP3 file has a Xing, Info, or VBRI header, which contain the number of frames
+/// in the file. Without one, symphonia estimates the duration from the file size, which is only
+/// accurate for constant bitrate files. The file is returned to the start afterwards.
+fn has_frame_count_header(file: &mut File) -> bool {
+    let mut header = [0; 10];
+    let mut offset = 0;
+
+    // the header is in the first frame, which comes after the ID3v2 tag if there is one
+    if file.read_exact(&mut header).is_ok() && &header[0..3] == b"ID3" {
+        let size = header[6..10]
+            .iter()
+            .fold(0u64, |acc, v| (acc << 7) | (*v & 0x7F) as u64);
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+
+        offset = 10 + size + footer;
+    }
+
+    let mut frame = [0; 4096];
+    let found = file
+        .seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read(&mut frame))
+        .map(|read| {
+            frame[..read]
+                .windows(4)
+                .any(|v| v == b"Xing" || v == b"Info" || v == b"VBRI")
+        })
+        .unwrap_or(false);
+
+    let _ = file.seek(SeekFrom::Start(0));
+
+    found
 }
 
 impl SymphoniaProvider {
@@ -47,7 +178,7 @@ impl SymphoniaProvider {
                     self.current_metadata.name = Some(tag.value.to_string())
                 }
                 Some(StandardTagKey::Artist) => {
-                    self.current_metadata.artist = Some(tag.value.to_string())
+                    push_values(&mut self.current_metadata.artists, &tag.value)
                 }
                 Some(StandardTagKey::AlbumArtist) => {
                     self.current_metadata.album_artist = Some(tag.value.to_string())
@@ -56,13 +187,58 @@ impl SymphoniaProvider {
                     self.current_metadata.original_artist = Some(tag.value.to_string())
                 }
                 Some(StandardTagKey::Composer) => {
-                    self.current_metadata.composer = Some(tag.value.to_string())
+                    self.current_metadata.composer = Some(tag.value.to_string());
+                    push_credits(
+                        &mut self.current_metadata.credits,
+                        CreditRole::Composer,
+                        &tag.value,
+                    )
                 }
+                Some(StandardTagKey::Conductor) => push_credits(
+                    &mut self.current_metadata.credits,
+                    CreditRole::Conductor,
+                    &tag.value,
+                ),
+                Some(StandardTagKey::Performer) => push_credits(
+                    &mut self.current_metadata.credits,
+                    CreditRole::Performer,
+                    &tag.value,
+                ),
+                Some(StandardTagKey::Ensemble) => push_credits(
+                    &mut self.current_metadata.credits,
+                    CreditRole::Ensemble,
+                    &tag.value,
+                ),
+                Some(StandardTagKey::Arranger) => push_credits(
+                    &mut self.current_metadata.credits,
+                    CreditRole::Arranger,
+                    &tag.value,
+                ),
+                Some(StandardTagKey::Lyricist) => push_credits(
+                    &mut self.current_metadata.credits,
+                    CreditRole::Lyricist,
+                    &tag.value,
+                ),
+                Some(StandardTagKey::Producer) => push_credits(
+                    &mut self.current_metadata.credits,
+                    CreditRole::Producer,
+                    &tag.value,
+                ),
+                Some(StandardTagKey::Engineer) => push_credits(
+                    &mut self.current_metadata.credits,
+                    CreditRole::Engineer,
+                    &tag.value,
+                ),
+                Some(StandardTagKey::Remixer) => push_credits(
+                    &mut self.current_metadata.credits,
+                    CreditRole::Remixer,
+                    &tag.value,
+                ),
                 Some(StandardTagKey::Album) => {
                     self.current_metadata.album = Some(tag.value.to_string())
                 }
                 Some(StandardTagKey::Genre) => {
-                    self.current_metadata.genre = Some(tag.value.to_string())
+                    push_values(&mut self.current_metadata.genres, &tag.value)
                 }
                 Some(StandardTagKey::ContentGroup) => {
                     self.current_metadata.grouping = Some(tag.value.to_string())
@@ -113,6 +289,9 @@ impl SymphoniaProvider {
                         _ => None,
                     }
                 }
+                Some(StandardTagKey::DiscSubtitle) => {
+                    self.current_metadata.disc_subtitle = Some(tag.value.to_string())
+                }
                 Some(StandardTagKey::Label) => {
                     self.current_metadata.label = Some(tag.value.to_string())
                 }
@@ -125,9 +304,28 @@ impl SymphoniaProvider {
                 Some(StandardTagKey::SortAlbum) => {
                     self.current_metadata.sort_album = Some(tag.value.to_string())
                 }
+                Some(StandardTagKey::MusicBrainzAlbumId) => {
+                    self.current_metadata.mbid_album = Some(tag.value.to_string())
+                }
+                Some(StandardTagKey::MusicBrainzRecordingId) => {
+                    self.current_metadata.mbid_track = Some(tag.value.to_string())
+                }
                 Some(StandardTagKey::SortAlbumArtist) => {
                     self.current_metadata.artist_sort = Some(tag.value.to_string())
                 }
+                Some(StandardTagKey::ReplayGainTrackPeak) => {
+                    self.current_metadata.replaygain_track_peak = parse_peak(&tag.value)
+                }
+                Some(StandardTagKey::ReplayGainAlbumPeak) => {
+                    self.current_metadata.replaygain_album_peak = parse_peak(&tag.value)
+                }
+                // not every format's ReplayGain tags are recognized as standard ones
+                None if key_is(&tag.key, "REPLAYGAIN_TRACK_PEAK") => {
+                    self.current_metadata.replaygain_track_peak = parse_peak(&tag.value)
+                }
+                None if key_is(&tag.key, "REPLAYGAIN_ALBUM_PEAK") => {
+                    self.current_metadata.replaygain_album_peak = parse_peak(&tag.value)
+                }
                 _ => (),
             }
         }
@@ -135,28 +333,170 @@ impl SymphoniaProvider {
 
     fn read_base_metadata(&mut self, probed: &mut ProbeResult) {
         self.current_metadata = Metadata::default();
-        self.last_image = None;
+        self.images = Vec::new();
 
         if let Some(metadata) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
             self.break_metadata(metadata.tags());
-            if !metadata.visuals().is_empty() {
-                self.last_image = Some(metadata.visuals()[0].clone());
-            }
+            self.images.extend(embedded_images(metadata.visuals()));
         }
 
         if let Some(metadata) = probed.format.metadata().current() {
             self.break_metadata(metadata.tags());
-            if !metadata.visuals().is_empty() {
-                self.last_image = Some(metadata.visuals()[0].clone());
-            }
+            self.images.extend(embedded_images(metadata.visuals()));
+        }
+
+        self.pending_metadata_update = true;
+        self.pending_image = true;
+    }
+
+    /// Chained Ogg files (common for recorded radio streams) contain several logical streams one
+    /// after another, each with its own tracks and metadata. Symphonia asks for a reset when the
+    /// next one starts, after which it has to be decoded from scratch.
+    fn start_next_stream(&mut self) -> Result<(), PlaybackReadError> {
+        let Some(mut format) = self.format.take() else {
+            return Err(PlaybackReadError::NothingOpen);
+        };
+
+        if let Some(revision) = format.metadata().skip_to_latest() {
+            self.current_metadata = Metadata::default();
+            self.break_metadata(revision.tags());
+            self.images = embedded_images(revision.visuals()).collect();
         }
 
+        self.read_technical_metadata(format.as_ref(), None);
         self.pending_metadata_update = true;
+        self.pending_image = true;
+        self.format = Some(format);
+
+        let offset = self.stream_offset + self.stream_end;
+        let previous_length = self.current_length;
+
+        self.start_playback()
+            .map_err(|_| PlaybackReadError::DecodeFatal)?;
+
+        self.stream_offset = offset;
+        self.stream_end = 0.0;
+        self.current_position = offset as u64;
+        self.current_length = match self.stream_length() {
+            Some(length) => Some(previous_length.unwrap_or(0).max((offset + length) as u64)),
+            None => previous_length,
+        };
+
+        Ok(())
+    }
+
+    /// The length of the current logical stream in seconds, if the container knows it.
+    fn stream_length(&self) -> Option<f64> {
+        let format = self.format.as_ref()?;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|t| t.id == self.current_track)?;
+
+        Some(secs(
+            track
+                .codec_params
+                .time_base?
+                .calc_time(track.codec_params.n_frames?),
+        ))
+    }
+
+    /// Moves on to the next logical stream of a chained file without decoding the rest of the
+    /// current one. Returns false if the current stream is the last one, in which case the end of
+    /// the file has been reached.
+    fn skip_stream(&mut self) -> bool {
+        let length = self.stream_length();
+        let timebase = self.current_timebase;
+        let Some(format) = &mut self.format else {
+            return false;
+        };
+
+        // only the last second has to be read through to find where the stream ends
+        if let Some(length) = length {
+            if let Ok(seek) = format.seek(
+                SeekMode::Coarse,
+                SeekTo::Time {
+                    time: time((length - 1.0).max(0.0)),
+                    track_id: None,
+                },
+            ) {
+                if let Some(timebase) = timebase {
+                    self.stream_end = secs(timebase.calc_time(seek.actual_ts));
+                }
+            }
+        }
+
+        loop {
+            match format.next_packet() {
+                Ok(packet) => {
+                    if let (Some(timebase), true) =
+                        (timebase, packet.track_id() == self.current_track)
+                    {
+                        self.stream_end = secs(timebase.calc_time(packet.ts() + packet.dur));
+                    }
+                }
+                Err(Error::ResetRequired) => break,
+                Err(_) => return false,
+            }
+        }
+
+        self.start_next_stream().is_ok()
+    }
+
+    /// Opens the file again from the start, so that an earlier logical stream of a chained file
+    /// can be seeked to. The length of the whole file is kept, since it's only known once every
+    /// stream has been played.
+    fn reopen(&mut self) -> Result<(), SeekError> {
+        let (file, ext) = self.source.as_ref().ok_or(SeekError::Unknown)?;
+        let mut file = file.try_clone().map_err(|_| SeekError::Unknown)?;
+        let ext = ext.clone();
+        let length = self.current_length;
+
+        file.seek(SeekFrom::Start(0))
+            .map_err(|_| SeekError::Unknown)?;
+        self.open(file, ext).map_err(|_| SeekError::Unknown)?;
+        self.start_playback().map_err(|_| SeekError::Unknown)?;
+        self.current_length = self.current_length.max(length);
+
+        Ok(())
+    }
+
+    fn read_technical_metadata(&mut self, format: &dyn FormatReader, file_size: Option<u64>) {
+        let Some(track) = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        else {
+            return;
+        };
+
+        let params = &track.codec_params;
+
+        self.current_metadata.codec = codecs()
+            .get_codec(params.codec)
+            .map(|v| v.short_name.to_uppercase());
+        self.current_metadata.sample_rate = params.sample_rate;
+        self.current_metadata.bit_depth = params.bits_per_sample;
+        self.current_metadata.channels = params.channels.map(|v| v.count() as u16);
+
+        // symphonia doesn't report bitrates, so the average is calculated from the file size
+        if let (Some(size), Some(frames), Some(tb)) = (file_size, params.n_frames, params.time_base)
+        {
+            let time = tb.calc_time(frames);
+            let secs = time.seconds as f64 + time.frac;
+
+            if secs > 0.0 {
+                self.current_metadata.bitrate = Some((size as f64 * 8.0 / secs / 1000.0) as u64);
+            }
+        }
     }
 }
 
 impl MediaProvider for SymphoniaProvider {
-    fn open(&mut self, file: File, ext: Option<String>) -> Result<(), OpenError> {
+    fn open(&mut self, mut file: File, ext: Option<String>) -> Result<(), OpenError> {
+        let file_size = file.metadata().ok().map(|v| v.len());
+        let has_frame_count = has_frame_count_header(&mut file);
+        self.source = file.try_clone().ok().map(|v| (v, ext.clone()));
         let mss = MediaSourceStream::new(Box::new(file), Default::default());
         let meta_opts: MetadataOptions = Default::default();
         let fmt_opts: FormatOptions = Default::default();
@@ -177,9 +517,17 @@ impl MediaProvider for SymphoniaProvider {
         };
 
         self.read_base_metadata(&mut probed);
+        self.read_technical_metadata(probed.format.as_ref(), file_size);
+        self.estimated_duration = !has_frame_count
+            && probed
+                .format
+                .default_track()
+                .is_some_and(|t| t.codec_params.codec == CODEC_TYPE_MP3);
         self.current_position = 0;
         self.current_length = None;
         self.current_timebase = None;
+        self.stream_offset = 0.0;
+        self.stream_end = 0.0;
         self.format = Some(probed.format);
 
         Ok(())
@@ -189,6 +537,7 @@ impl MediaProvider for SymphoniaProvider {
         self.stop_playback().expect("invalid outcome");
         self.current_metadata = Metadata::default();
         self.format = None;
+        self.source = None;
         Ok(())
     }
 
@@ -211,7 +560,7 @@ impl MediaProvider for SymphoniaProvider {
 
             let dec_opts: DecoderOptions = Default::default();
             self.decoder = Some(
-                get_codecs()
+                codecs()
                     .make(&track.codec_params, &dec_opts)
                     .map_err(|_| PlaybackStartError::Undecodable)?,
             );
@@ -235,7 +584,7 @@ impl MediaProvider for SymphoniaProvider {
             loop {
                 let packet = match format.next_packet() {
                     Ok(packet) => packet,
-                    Err(Error::ResetRequired) => return Err(PlaybackReadError::Eof),
+                    Err(Error::ResetRequired) => break,
                     Err(_) => {
                         // TODO: Handle better
                         return Err(PlaybackReadError::Eof);
@@ -259,7 +608,14 @@ impl MediaProvider for SymphoniaProvider {
                             self.current_duration = decoded.capacity() as u64;
 
                             if let Some(tb) = &self.current_timebase {
-                                self.current_position = tb.calc_time(packet.ts()).seconds;
+                                let start = secs(tb.calc_time(packet.ts()));
+                                self.current_position = (self.stream_offset + start) as u64;
+                                self.stream_end = secs(tb.calc_time(packet.ts() + packet.dur));
+                            }
+
+                            // the whole packet may have been trimmed (like an Opus pre-skip)
+                            if decoded.frames() == 0 {
+                                continue;
                             }
 
                             match decoded {
@@ -433,8 +789,12 @@ impl MediaProvider for SymphoniaProvider {
                 }
             }
         } else {
-            Err(PlaybackReadError::NothingOpen)
+            return Err(PlaybackReadError::NothingOpen);
         }
+
+        // only reached when the next stream of a chained file starts
+        self.start_next_stream()?;
+        self.read_samples()
     }
 
     fn frame_duration(&self) -> Result<u64, FrameDurationError> {
@@ -463,10 +823,9 @@ impl MediaProvider for SymphoniaProvider {
 
     fn read_image(&mut self) -> Result<Option<Box<[u8]>>, MetadataError> {
         if self.format.is_some() {
-            if let Some(visual) = &self.last_image {
-                let data = Ok(Some(visual.data.clone()));
-                self.last_image = None;
-                data
+            if self.pending_image {
+                self.pending_image = false;
+                Ok(pick_cover(&self.images).map(|image| image.data.clone()))
             } else {
                 Ok(None)
             }
@@ -475,6 +834,14 @@ impl MediaProvider for SymphoniaProvider {
         }
     }
 
+    fn read_images(&mut self) -> Result<Vec<EmbeddedImage>, MetadataError> {
+        if self.format.is_some() {
+            Ok(self.images.clone())
+        } else {
+            Err(MetadataError::NothingOpen)
+        }
+    }
+
     fn duration_secs(&self) -> Result<u64, TrackDurationError> {
         if self.decoder.is_none() {
             Err(TrackDurationError::NothingOpen)
@@ -485,6 +852,55 @@ impl MediaProvider for SymphoniaProvider {
         }
     }
 
+    fn analyze_duration(&mut self) -> Result<u64, TrackDurationError> {
+        if !self.estimated_duration {
+            return self.duration_secs();
+        }
+
+        let Some(format) = &mut self.format else {
+            return Err(TrackDurationError::NothingOpen);
+        };
+
+        let timebase = format
+            .tracks()
+            .iter()
+            .find(|t| t.id == self.current_track)
+            .and_then(|t| t.codec_params.time_base)
+            .ok_or(TrackDurationError::Unknown)?;
+
+        let mut length = 0;
+
+        // reading stops at the end of the file, which symphonia reports as an IO error
+        while let Ok(packet) = format.next_packet() {
+            if packet.track_id() == self.current_track {
+                length += packet.dur;
+            }
+        }
+
+        format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::TimeStamp {
+                    ts: 0,
+                    track_id: self.current_track,
+                },
+            )
+            .map_err(|_| TrackDurationError::Unknown)?;
+
+        if let Some(decoder) = &mut self.decoder {
+            decoder.reset();
+        }
+
+        let secs = timebase.calc_time(length).seconds;
+
+        self.current_length = Some(secs);
+        self.current_timebase = Some(timebase);
+        self.current_position = 0;
+        self.estimated_duration = false;
+
+        Ok(secs)
+    }
+
     fn position_secs(&self) -> Result<u64, TrackDurationError> {
         if self.decoder.is_none() {
             Err(TrackDurationError::NothingOpen)
@@ -495,24 +911,43 @@ impl MediaProvider for SymphoniaProvider {
         }
     }
 
-    fn seek(&mut self, time: f64) -> Result<(), SeekError> {
+    fn seek(&mut self, to: f64) -> Result<(), SeekError> {
+        if self.format.is_none() {
+            return Err(SeekError::NothingOpen);
+        }
+
+        // positions in a chained file are relative to the start of the current stream, so the
+        // stream the position is in has to be started first
+        if to < self.stream_offset {
+            self.reopen()?;
+        }
+
+        while self
+            .stream_length()
+            .is_some_and(|length| to >= self.stream_offset + length)
+        {
+            if !self.skip_stream() {
+                break;
+            }
+        }
+
         let timebase = self.current_timebase;
+        let relative = (to - self.stream_offset).max(0.0);
+
         if let Some(format) = &mut self.format {
             let seek = format
                 .seek(
                     SeekMode::Accurate,
                     SeekTo::Time {
-                        time: Time {
-                            seconds: time.trunc() as u64,
-                            frac: time.fract(),
-                        },
+                        time: time(relative),
                         track_id: None,
                     },
                 )
                 .map_err(|_| SeekError::Unknown)?;
 
             if let Some(timebase) = timebase {
-                self.current_position = timebase.calc_time(seek.actual_ts).seconds;
+                self.stream_end = secs(timebase.calc_time(seek.actual_ts));
+                self.current_position = (self.stream_offset + self.stream_end) as u64;
             }
 
             Ok(())
@@ -520,6 +955,18 @@ impl MediaProvider for SymphoniaProvider {
             Err(SeekError::NothingOpen)
         }
     }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn supported_extensions(&self) -> &'static [&'static str] {
+        Self::SUPPORTED_EXTENSIONS
+    }
+
+    fn supported_mimetypes(&self) -> &'static [&'static str] {
+        Self::SUPPORTED_MIMETYPES
+    }
 }
 
 impl MediaPlugin for SymphoniaProvider {
@@ -529,6 +976,7 @@ impl MediaPlugin for SymphoniaProvider {
 
     const SUPPORTED_MIMETYPES: &'static [&'static str] = &[
         "audio/ogg",
+        "audio/opus",
         "audio/aac",
         "audio/x-flac",
         "audio/x-wav",
@@ -541,7 +989,243 @@ impl MediaPlugin for SymphoniaProvider {
     const PROVIDES_METADATA: bool = true;
     const ALWAYS_CHECK_METADATA: bool = false;
 
-    const SUPPORTED_EXTENSIONS: &'static [&'static str] =
-        &["ogg", "aac", "flac", "wav", "mp3", "m4a", "aiff"];
+    const SUPPORTED_EXTENSIONS: &'static [&'static str] = &[
+        "ogg", "opus", "oga", "aac", "flac", "wav", "mp3", "m4a", "aiff",
+    ];
     const INDEXING_SUPPORTED: bool = true;
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// Writes the bytes to a file in the temporary directory, and opens it.
+    fn temp_file(name: &str, ext: &str, bytes: &[u8]) -> File {
+        let path = std::env::temp_dir().join(format!(
+            "muzak-symphonia-{}-{}.{}",
+            name,
+            std::process::id(),
+            ext
+        ));
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+
+        let file = File::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        file
+    }
+
+    fn open(name: &str, ext: &str, bytes: &[u8]) -> SymphoniaProvider {
+        let mut provider = SymphoniaProvider::default();
+        provider
+            .open(temp_file(name, ext, bytes), Some(ext.to_string()))
+            .unwrap();
+        provider.start_playback().unwrap();
+        provider
+    }
+
+    /// A silent MPEG-1 layer III frame (mono, 44.1kHz) at the bitrate with the specified index.
+    /// Every frame contains 1152 samples, regardless of its bitrate.
+    fn mp3_frame(bitrate_index: u8) -> Vec<u8> {
+        const BITRATES: [usize; 15] = [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ];
+
+        let length = 144 * BITRATES[bitrate_index as usize] * 1000 / 44100;
+        let mut frame = vec![0xFF, 0xFB, bitrate_index << 4, 0xC0];
+        frame.resize(length, 0);
+        frame
+    }
+
+    #[test]
+    fn vbr_mp3_duration() {
+        // without a frame count header, the duration is estimated from the size of the first
+        // frame, which is way off when the bitrate drops afterwards
+        let mut bytes = Vec::new();
+
+        for _ in 0..20 {
+            bytes.extend(mp3_frame(14));
+        }
+
+        for _ in 0..400 {
+            bytes.extend(mp3_frame(1));
+        }
+
+        let mut provider = open("vbr", "mp3", &bytes);
+
+        assert!(provider.estimated_duration);
+        // 420 frames of 1152 samples at 44.1kHz is just under 11 seconds
+        assert_eq!(provider.analyze_duration(), Ok(10));
+        assert_eq!(provider.duration_secs(), Ok(10));
+
+        // playback starts from the beginning afterwards
+        assert_eq!(provider.position_secs(), Ok(0));
+        assert!(provider.read_samples().is_ok());
+    }
+
+    #[test]
+    fn frame_count_header() {
+        let mut frame = mp3_frame(9);
+        // the Xing header is in the side info of the first frame
+        frame[36..40].copy_from_slice(b"Xing");
+
+        let mut tagged = b"ID3\x04\0\0\0\0\0\x10".to_vec();
+        tagged.extend(std::iter::repeat(0).take(16));
+        tagged.extend(frame.clone());
+
+        assert!(has_frame_count_header(&mut temp_file(
+            "xing", "mp3", &frame
+        )));
+        assert!(has_frame_count_header(&mut temp_file(
+            "tagged", "mp3", &tagged
+        )));
+        assert!(!has_frame_count_header(&mut temp_file(
+            "none",
+            "mp3",
+            &mp3_frame(9)
+        )));
+    }
+
+    /// The checksum of an Ogg page: a CRC-32 (without reflection) of the whole page, with the
+    /// checksum itself zeroed.
+    fn ogg_crc(page: &[u8]) -> u32 {
+        let mut crc = 0u32;
+
+        for byte in page {
+            crc ^= (*byte as u32) << 24;
+
+            for _ in 0..8 {
+                crc = if crc & 0x8000_0000 != 0 {
+                    (crc << 1) ^ 0x04C1_1DB7
+                } else {
+                    crc << 1
+                };
+            }
+        }
+
+        crc
+    }
+
+    /// An Ogg page containing a single packet.
+    fn ogg_page(serial: u32, sequence: u32, flags: u8, granule: u64, packet: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS\0".to_vec();
+        page.push(flags);
+        page.extend(granule.to_le_bytes());
+        page.extend(serial.to_le_bytes());
+        page.extend(sequence.to_le_bytes());
+        page.extend([0; 4]);
+
+        let mut lacing = vec![255; packet.len() / 255];
+        lacing.push((packet.len() % 255) as u8);
+        page.push(lacing.len() as u8);
+        page.extend(lacing);
+        page.extend(packet);
+
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        page
+    }
+
+    const PRE_SKIP: u16 = 312;
+    /// 20ms at 48kHz.
+    const OPUS_FRAME: usize = 960;
+
+    /// A logical Ogg stream of 20ms Opus packets of silence (in mono), tagged with the title.
+    fn opus_stream(serial: u32, title: &str, packets: usize) -> Vec<u8> {
+        let mut head = b"OpusHead\x01\x01".to_vec();
+        head.extend(PRE_SKIP.to_le_bytes());
+        head.extend(48000u32.to_le_bytes());
+        // the output gain and channel mapping family
+        head.extend([0, 0, 0]);
+
+        let comment = format!("TITLE={}", title);
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend(5u32.to_le_bytes());
+        tags.extend(b"muzak");
+        tags.extend(1u32.to_le_bytes());
+        tags.extend((comment.len() as u32).to_le_bytes());
+        tags.extend(comment.as_bytes());
+
+        let mut stream = ogg_page(serial, 0, 0x02, 0, &head);
+        stream.extend(ogg_page(serial, 1, 0, 0, &tags));
+
+        let mut encoder =
+            ::opus::Encoder::new(48000, ::opus::Channels::Mono, ::opus::Application::Audio)
+                .unwrap();
+        let silence = [0.0; OPUS_FRAME];
+        let mut packet = [0; 1500];
+
+        for i in 0..packets {
+            let length = encoder.encode_float(&silence, &mut packet).unwrap();
+            // the last page ends the stream
+            let flags = if i == packets - 1 { 0x04 } else { 0 };
+            let granule = ((i + 1) * OPUS_FRAME) as u64;
+
+            stream.extend(ogg_page(
+                serial,
+                i as u32 + 2,
+                flags,
+                granule,
+                &packet[..length],
+            ));
+        }
+
+        stream
+    }
+
+    fn title(provider: &mut SymphoniaProvider) -> Option<String> {
+        provider.read_metadata().unwrap().name.clone()
+    }
+
+    fn frame_length(provider: &mut SymphoniaProvider) -> usize {
+        match provider.read_samples().unwrap().samples {
+            Samples::Float32(samples) => samples[0].len(),
+            _ => panic!("opus is decoded to floats"),
+        }
+    }
+
+    #[test]
+    fn opus_pre_skip() {
+        let mut provider = open("pre-skip", "opus", &opus_stream(1, "Song", 10));
+
+        // the start of the first packet only primes the decoder
+        assert_eq!(frame_length(&mut provider), OPUS_FRAME - PRE_SKIP as usize);
+        assert_eq!(frame_length(&mut provider), OPUS_FRAME);
+    }
+
+    #[test]
+    fn chained_ogg() {
+        // two streams of 3 seconds each, one after the other
+        let mut bytes = opus_stream(1, "First", 150);
+        bytes.extend(opus_stream(2, "Second", 150));
+
+        let mut provider = open("chained", "opus", &bytes);
+        assert_eq!(title(&mut provider).as_deref(), Some("First"));
+
+        // playback carries on into the second stream, which has its own metadata
+        while !provider.metadata_updated() {
+            provider.read_samples().unwrap();
+        }
+
+        assert_eq!(title(&mut provider).as_deref(), Some("Second"));
+        // the pre-skip makes each stream slightly shorter than 3 seconds
+        assert!(matches!(provider.position_secs(), Ok(2..=3)));
+        let duration = provider.duration_secs().unwrap();
+        assert!((5..=6).contains(&duration));
+
+        // seeking back into the first stream starts the file again
+        provider.seek(1.5).unwrap();
+        assert_eq!(title(&mut provider).as_deref(), Some("First"));
+        assert_eq!(provider.position_secs(), Ok(1));
+        assert_eq!(provider.duration_secs(), Ok(duration));
+        assert!(provider.read_samples().is_ok());
+
+        // and seeking into the second stream skips the rest of the first
+        provider.seek(4.5).unwrap();
+        assert_eq!(title(&mut provider).as_deref(), Some("Second"));
+        assert_eq!(provider.position_secs(), Ok(4));
+        assert_eq!(provider.duration_secs(), Ok(duration));
+        assert!(provider.read_samples().is_ok());
+    }
+}
@@ -1,16 +1,81 @@
 use chrono::{DateTime, Utc};
 
+/// Something a person or group is credited with on a track, besides being one of its artists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CreditRole {
+    Composer,
+    Conductor,
+    Performer,
+    Ensemble,
+    Arranger,
+    Lyricist,
+    Producer,
+    Engineer,
+    Remixer,
+}
+
+impl CreditRole {
+    /// The name the role is stored under in the library.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CreditRole::Composer => "composer",
+            CreditRole::Conductor => "conductor",
+            CreditRole::Performer => "performer",
+            CreditRole::Ensemble => "ensemble",
+            CreditRole::Arranger => "arranger",
+            CreditRole::Lyricist => "lyricist",
+            CreditRole::Producer => "producer",
+            CreditRole::Engineer => "engineer",
+            CreditRole::Remixer => "remixer",
+        }
+    }
+
+    /// The inverse of [`CreditRole::as_str`].
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "composer" => Some(CreditRole::Composer),
+            "conductor" => Some(CreditRole::Conductor),
+            "performer" => Some(CreditRole::Performer),
+            "ensemble" => Some(CreditRole::Ensemble),
+            "arranger" => Some(CreditRole::Arranger),
+            "lyricist" => Some(CreditRole::Lyricist),
+            "producer" => Some(CreditRole::Producer),
+            "engineer" => Some(CreditRole::Engineer),
+            "remixer" => Some(CreditRole::Remixer),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CreditRole::Composer => "Composer",
+            CreditRole::Conductor => "Conductor",
+            CreditRole::Performer => "Performer",
+            CreditRole::Ensemble => "Ensemble",
+            CreditRole::Arranger => "Arranger",
+            CreditRole::Lyricist => "Lyricist",
+            CreditRole::Producer => "Producer",
+            CreditRole::Engineer => "Engineer",
+            CreditRole::Remixer => "Remixer",
+        }
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct Metadata {
     pub name: Option<String>,
-    pub artist: Option<String>,
+    /// Every artist credited on the track, in tag order. Use `artist()` for display.
+    pub artists: Vec<String>,
     pub album_artist: Option<String>,
     pub artist_sort: Option<String>,
     pub original_artist: Option<String>,
     pub composer: Option<String>,
+    /// The composers, performers, conductors, and others credited on the track, in tag order.
+    pub credits: Vec<(CreditRole, String)>,
     pub album: Option<String>,
     pub sort_album: Option<String>,
-    pub genre: Option<String>,
+    /// Every genre the track is tagged with, in tag order. Use `genre()` for display.
+    pub genres: Vec<String>,
     pub grouping: Option<String>,
     pub bpm: Option<u64>,
     pub compilation: bool,
@@ -20,8 +85,107 @@ pub struct Metadata {
     pub track_max: Option<u64>,
     pub disc_current: Option<u64>,
     pub disc_max: Option<u64>,
+    /// The name of the disc in a multi-disc set, e.g. "Live in Tokyo".
+    pub disc_subtitle: Option<String>,
 
     pub label: Option<String>,
     pub catalog: Option<String>,
     pub isrc: Option<String>,
+    /// The MusicBrainz ID of the album's release.
+    pub mbid_album: Option<String>,
+    /// The MusicBrainz ID of the track's recording.
+    pub mbid_track: Option<String>,
+    /// The loudest sample of the track, from its ReplayGain tags, where 1.0 is full scale.
+    pub replaygain_track_peak: Option<f64>,
+    /// The loudest sample of the track's album, from its ReplayGain tags.
+    pub replaygain_album_peak: Option<f64>,
+
+    /// The short name of the codec, for example "FLAC" or "MP3".
+    pub codec: Option<String>,
+    /// The average bitrate of the file, in kbps.
+    pub bitrate: Option<u64>,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u32>,
+    pub channels: Option<u16>,
+}
+
+fn join(values: &[String]) -> Option<String> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.join(", "))
+    }
+}
+
+impl Metadata {
+    /// All of the track's artists joined into a single string, suitable for display.
+    pub fn artist(&self) -> Option<String> {
+        join(&self.artists)
+    }
+
+    /// All of the track's genres joined into a single string, suitable for display.
+    pub fn genre(&self) -> Option<String> {
+        join(&self.genres)
+    }
+
+    /// A short summary of the technical details of the file, like "FLAC 44.1/16" for lossless
+    /// formats or "MP3 320k" for lossy ones. Returns None if the codec isn't known.
+    pub fn quality(&self) -> Option<String> {
+        let codec = self.codec.as_ref()?;
+
+        match (self.sample_rate, self.bit_depth, self.bitrate) {
+            (Some(rate), Some(depth), _) => {
+                Some(format!("{} {}/{}", codec, rate as f64 / 1000.0, depth))
+            }
+            (_, _, Some(bitrate)) => Some(format!("{} {}k", codec, bitrate)),
+            _ => Some(codec.clone()),
+        }
+    }
+}
+
+/// What an embedded picture shows, following the ID3v2 picture types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PictureKind {
+    FrontCover,
+    BackCover,
+    /// A leaflet page, a picture of the disc itself, or an illustration related to the track.
+    Booklet,
+    /// A picture of the artist, a performer, or whoever else is credited.
+    Artist,
+    /// A small icon for the file, which is too small to be used as album art.
+    Icon,
+    Other,
+    /// The file doesn't say what the picture is, as is usual for MP4 files.
+    Unknown,
+}
+
+impl PictureKind {
+    /// How suitable a picture of this kind is as the album art, from 0 (the most suitable).
+    pub fn cover_priority(&self) -> u8 {
+        match self {
+            PictureKind::FrontCover => 0,
+            // files that don't mark their pictures usually only have the front cover
+            PictureKind::Unknown => 1,
+            PictureKind::Booklet => 2,
+            PictureKind::Other => 3,
+            PictureKind::BackCover => 4,
+            PictureKind::Artist => 5,
+            PictureKind::Icon => 6,
+        }
+    }
+}
+
+/// A picture embedded in a file's metadata, in whatever format it was stored in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedImage {
+    pub kind: PictureKind,
+    pub data: Box<[u8]>,
+}
+
+/// Picks the picture that is most suitable as the album art, preferring the front cover. Pictures
+/// of the same kind are picked in the order they're stored in.
+pub fn pick_cover(images: &[EmbeddedImage]) -> Option<&EmbeddedImage> {
+    images
+        .iter()
+        .min_by_key(|image| image.kind.cover_priority())
 }
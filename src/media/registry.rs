@@ -0,0 +1,206 @@
+use std::{fs::File, path::Path};
+
+use tracing::{debug, warn};
+
+use super::traits::MediaProvider;
+
+/// A file that has been opened ahead of time by its own instance of a provider.
+struct Prepared {
+    path: String,
+    /// The index of the provider in the registry that this is an instance of.
+    idx: usize,
+    provider: Box<dyn MediaProvider>,
+}
+
+/// A set of providers that files are opened with. When a file is opened, the first provider that
+/// supports its extension is used, so providers should be registered in order of preference.
+///
+/// Upcoming files can also be prepared: opened ahead of time by a separate instance of the
+/// provider, which takes the place of the registered one when the file is played.
+#[derive(Default)]
+pub struct ProviderRegistry {
+    providers: Vec<Box<dyn MediaProvider>>,
+    current: Option<usize>,
+    prepared: Vec<Prepared>,
+}
+
+impl ProviderRegistry {
+    pub fn new(providers: Vec<Box<dyn MediaProvider>>) -> Self {
+        Self {
+            providers,
+            current: None,
+            prepared: Vec::new(),
+        }
+    }
+
+    /// Returns the indices of every provider that supports the extension, in order of preference.
+    /// If no provider supports it (or there is no extension), every provider is returned, since
+    /// providers can try to determine the format from the file's contents.
+    fn candidates(&self, ext: Option<&str>) -> Vec<usize> {
+        let supporting: Vec<usize> = ext
+            .map(|ext| {
+                self.providers
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, provider)| {
+                        provider
+                            .supported_extensions()
+                            .iter()
+                            .any(|v| v.eq_ignore_ascii_case(ext))
+                    })
+                    .map(|(idx, _)| idx)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if supporting.is_empty() {
+            (0..self.providers.len()).collect()
+        } else {
+            supporting
+        }
+    }
+
+    fn set_current(&mut self, idx: usize) {
+        if let Some(previous) = self.current.filter(|v| *v != idx) {
+            // the provider may not have anything open, which is fine
+            let _ = self.providers[previous].close();
+        }
+
+        self.current = Some(idx);
+    }
+
+    /// Selects the provider that should be used to open the file at the specified path, closing
+    /// the previously selected provider if it is a different one.
+    pub fn select(&mut self, path: &str) -> Option<&mut dyn MediaProvider> {
+        let idx = *self.candidates(extension(path).as_deref()).first()?;
+
+        self.set_current(idx);
+        self.current_mut()
+    }
+
+    /// Opens the file at the specified path and starts playback, trying each capable provider in
+    /// turn until one succeeds. The provider that opened the file is selected and returned.
+    pub fn open(&mut self, path: &str) -> Result<&mut dyn MediaProvider, ()> {
+        let ext = extension(path);
+        let mut tried = Vec::new();
+
+        for idx in self.candidates(ext.as_deref()) {
+            let provider = &mut self.providers[idx];
+            tried.push(provider.name());
+
+            let file = File::open(path).map_err(|e| {
+                warn!("Failed to open file {}: {:?}", path, e);
+            })?;
+
+            if let Err(e) = provider.open(file, ext.clone()) {
+                debug!("{} couldn't open {}: {:?}", provider.name(), path, e);
+                continue;
+            }
+
+            if let Err(e) = provider.start_playback() {
+                debug!(
+                    "{} couldn't start playback of {}: {:?}",
+                    provider.name(),
+                    path,
+                    e
+                );
+                let _ = provider.close();
+                continue;
+            }
+
+            self.set_current(idx);
+            return self.current_mut().ok_or(());
+        }
+
+        warn!(
+            "No media provider could open {} (tried: {})",
+            path,
+            tried.join(", ")
+        );
+
+        Err(())
+    }
+
+    /// Opens the file at the specified path and starts playback ahead of time, using `providers`,
+    /// which must be new instances of the providers the registry was created with, in the same
+    /// order. Returns whether the file could be opened.
+    pub fn prepare(&mut self, path: &str, providers: Vec<Box<dyn MediaProvider>>) -> bool {
+        if self.is_prepared(path) {
+            return true;
+        }
+
+        let mut registry = ProviderRegistry::new(providers);
+
+        if registry.open(path).is_err() {
+            return false;
+        }
+
+        let Some(idx) = registry.current.filter(|idx| *idx < self.providers.len()) else {
+            return false;
+        };
+
+        debug!("prepared {}", path);
+        self.prepared.push(Prepared {
+            path: path.to_string(),
+            idx,
+            provider: registry.providers.swap_remove(idx),
+        });
+
+        true
+    }
+
+    pub fn is_prepared(&self, path: &str) -> bool {
+        self.prepared.iter().any(|v| v.path == path)
+    }
+
+    /// Closes the prepared files that aren't in `paths`.
+    pub fn retain_prepared(&mut self, paths: &[String]) {
+        self.prepared.retain_mut(|prepared| {
+            let keep = paths.contains(&prepared.path);
+
+            if !keep {
+                let _ = prepared.provider.close();
+            }
+
+            keep
+        });
+    }
+
+    /// If the file at the specified path has been prepared, selects the provider that opened it
+    /// in place of the registered one (closing whatever was open before) and returns it. The file
+    /// has already been opened, and playback started.
+    pub fn open_prepared(&mut self, path: &str) -> Option<&mut dyn MediaProvider> {
+        let position = self.prepared.iter().position(|v| v.path == path)?;
+        let prepared = self.prepared.remove(position);
+
+        if let Some(current) = self.current_mut() {
+            let _ = current.close();
+        }
+
+        self.providers[prepared.idx] = prepared.provider;
+        self.current = Some(prepared.idx);
+        self.current_mut()
+    }
+
+    /// The provider that was last selected.
+    pub fn current(&self) -> Option<&dyn MediaProvider> {
+        self.current
+            .and_then(|idx| self.providers.get(idx))
+            .map(|provider| provider.as_ref())
+    }
+
+    /// The provider that was last selected.
+    pub fn current_mut(&mut self) -> Option<&mut dyn MediaProvider> {
+        self.current
+            .and_then(|idx| self.providers.get_mut(idx))
+            .map(|provider| provider.as_mut())
+    }
+}
+
+/// Returns the extension of the file at the specified path, if it has one.
+pub fn extension(path: &str) -> Option<String> {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_string())
+}
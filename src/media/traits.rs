@@ -5,7 +5,7 @@ use super::{
         CloseError, FrameDurationError, MetadataError, OpenError, PlaybackReadError,
         PlaybackStartError, PlaybackStopError, SeekError, TrackDurationError,
     },
-    metadata::Metadata,
+    metadata::{EmbeddedImage, Metadata},
     playback::PlaybackFrame,
 };
 
@@ -93,14 +93,41 @@ pub trait MediaProvider {
     /// error.
     fn read_image(&mut self) -> Result<Option<Box<[u8]>>, MetadataError>;
 
+    /// Returns every picture in the track's metadata, in the order they're stored in. The picture
+    /// returned by `read_image` should be the most suitable of these as the album art, preferring
+    /// the front cover. By default, this returns no pictures.
+    fn read_images(&mut self) -> Result<Vec<EmbeddedImage>, MetadataError> {
+        Ok(Vec::new())
+    }
+
     /// Returns the duration of the currently opened file in seconds. If no file is opened, or
     /// playback has not started, this function should return an error. This function should be
     /// available immediately after playback has started, and should not require reading any
     /// samples.
     fn duration_secs(&self) -> Result<u64, TrackDurationError>;
 
+    /// Returns the duration of the currently opened file in seconds, determined as accurately as
+    /// possible even if that is slow (for example, by reading through the entire file). This is
+    /// used for library indexing, where the estimate returned by `duration_secs` may be wrong for
+    /// some files. After this function returns, playback should continue from the start of the
+    /// file. By default, this returns the same value as `duration_secs`.
+    fn analyze_duration(&mut self) -> Result<u64, TrackDurationError> {
+        self.duration_secs()
+    }
+
     /// Returns the current playback position in seconds. If no file is opened, or playback has not
     /// started, this function should return an error. This function should be available immediately
     /// after playback has started, and should not require reading any samples.
     fn position_secs(&self) -> Result<u64, TrackDurationError>;
+
+    /// Returns the name of the Provider, for logging.
+    fn name(&self) -> &'static str;
+
+    /// Returns the file extensions (without the leading dot) that the Provider supports. These are
+    /// used to decide which files are scanned into the library and accepted by the UI.
+    fn supported_extensions(&self) -> &'static [&'static str];
+
+    /// Returns the mime-types that the Provider supports, in the format described by
+    /// `MediaPlugin::SUPPORTED_MIMETYPES`.
+    fn supported_mimetypes(&self) -> &'static [&'static str];
 }
@@ -1,3 +1,16 @@
 pub mod events;
+pub mod fade;
+pub mod failure;
+pub mod gain;
+pub mod history;
 pub mod interface;
+pub mod level;
+pub mod monitor;
+pub mod prebuffer;
+pub mod resume;
+pub mod shuffle;
+pub mod skip;
+pub mod speed;
 pub mod thread;
+pub mod trim;
+pub mod volume;
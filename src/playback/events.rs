@@ -1,8 +1,15 @@
 #![allow(dead_code)]
 
-use crate::media::metadata::Metadata;
+use crate::{
+    devices::format::OutputInfo,
+    media::{
+        errors::{OpenError, PlaybackReadError, PlaybackStartError},
+        metadata::Metadata,
+    },
+    settings::playback::{ClippingMode, ShuffleMode},
+};
 
-use super::thread::PlaybackState;
+use super::{thread::PlaybackState, trim::TrackTrim};
 
 /// A command to the playback thread. This is used to control the playback thread from other
 /// threads. The playback thread recieves these commands from an MPSC channel, and processes them
@@ -31,10 +38,27 @@ pub enum PlaybackCommand {
     ClearQueue,
     /// Jumps to the specified position in the queue.
     Jump(usize),
+    /// Inserts a copy of the file at the specified position in the queue right after it.
+    DuplicateQueueItem(usize),
+    /// Moves the file at the first position in the queue to the second, shifting the files in
+    /// between. The current file keeps playing.
+    MoveQueueItem(usize, usize),
     /// Requests that the playback thread seek to the specified position in the current file.
     Seek(f64),
     /// Requests that the playback thread set the volume to the specified level.
     SetVolume(f64),
+    /// Requests that the playback thread apply the specified gain offset, in dB, to the current
+    /// file. The offset is combined with the volume, and reduced or limited to avoid clipping
+    /// unless clipping is allowed.
+    SetGain(f64),
+    /// Requests that the playback thread change whether the gain offset can make the audio clip.
+    SetClippingMode(ClippingMode),
+    /// Requests that the playback thread change the playback speed, without changing the pitch.
+    /// The speed is clamped between 0.5 and 2.0.
+    SetSpeed(f64),
+    /// Requests that the playback thread fade the audio in and out over the specified number of
+    /// milliseconds when starting, pausing, stopping, and seeking. 0 disables fading.
+    SetFadeDuration(u64),
     /// Requests that the playback thread replace the current queue with the specified queue.
     /// This will set the current playing track to the first item in the queue.
     ReplaceQueue(Vec<String>),
@@ -43,6 +67,38 @@ pub enum PlaybackCommand {
     /// Requests that the playback thread shuffle (or stop shuffling) the next tracks in the
     /// queue. Note that this currently results in duplication of the *entire* queue.
     ToggleShuffle,
+    /// Requests that the playback thread shuffle the tracks after the current one once, without
+    /// turning on shuffle.
+    ShuffleRemaining,
+    /// Changes how the queue is shuffled. This takes effect the next time the queue is shuffled.
+    SetShuffleMode(ShuffleMode),
+    /// Tells the playback thread which album each of the specified files belongs to, so that
+    /// albums can be kept together when shuffling by album.
+    AddAlbumKeys(Vec<(String, i64)>),
+    /// Sets whether the specified files are passed over when moving on to the next track.
+    SetSkipAlways(Vec<String>, bool),
+    /// Sets where playback of the specified files starts and ends. An empty trim plays the whole
+    /// file. This takes effect the next time each file is opened.
+    SetTrims(Vec<(String, TrackTrim)>),
+    /// Switches output to the device with the specified UID. If the flag is set, the new device's
+    /// stream is opened and given audio before the old one is closed, so that playback continues
+    /// without a gap.
+    SetOutputDevice(String, bool),
+    /// Asks for [`PlaybackEvent::DevicesListed`] to be sent, so that the list of output devices is
+    /// up to date.
+    ListDevices,
+    /// Sets which other devices, by UID, are given a copy of the audio. Streams are opened on
+    /// devices that weren't monitored before, and closed on devices that aren't listed anymore.
+    SetMonitors(Vec<String>),
+    /// Sets how many of the upcoming files in the queue have their start read ahead of time, up
+    /// to 8. 0 disables reading ahead.
+    SetPrebufferCount(usize),
+    /// Sets whether the peak level of the audio is measured and sent as
+    /// [`PlaybackEvent::LevelChanged`].
+    SetLevelMeter(bool),
+    /// Sets whether playback starts again from the beginning of the queue once the queue runs
+    /// out, instead of stopping.
+    SetRepeat(bool),
 }
 
 /// An event from the playback thread. This is used to communicate information from the playback
@@ -74,4 +130,50 @@ pub enum PlaybackEvent {
     ShuffleToggled(bool),
     /// Indicates that the volume has changed. The f64 is the new volume, from 0.0 to 1.0.
     VolumeChanged(f64),
+    /// Indicates that the playback speed has changed. The f64 is the new speed, where 1.0 is
+    /// normal speed.
+    SpeedChanged(f64),
+    /// Indicates that the output stream has been opened, with the format audio is being sent to
+    /// the device in.
+    OutputChanged(OutputInfo),
+    /// Indicates which output devices are available, as their UIDs and names. Sent whenever the
+    /// output stream is opened, and when asked for.
+    DevicesListed(Vec<(String, String)>),
+    /// Indicates that the smoothed peak level of the audio has changed, from 0.0 to 1.0. Only sent
+    /// while the level meter is enabled.
+    LevelChanged(f32),
+    /// Indicates that the specified file couldn't be played, and playback has stopped.
+    Error(String, PlaybackError),
+    /// Indicates that the specified output device couldn't be opened. Playback carries on through
+    /// the previous device, or the default device if that can't be opened again.
+    OutputFailed(String),
+}
+
+/// Why a file couldn't be played.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PlaybackError {
+    /// The file doesn't exist, or couldn't be read.
+    FileUnreadable,
+    Open(OpenError),
+    Start(PlaybackStartError),
+    Read(PlaybackReadError),
+}
+
+impl PlaybackError {
+    /// A short description of the error, suitable for display.
+    pub fn message(&self) -> &'static str {
+        match self {
+            PlaybackError::FileUnreadable => "The file couldn't be read",
+            PlaybackError::Open(OpenError::FileCorrupt)
+            | PlaybackError::Start(PlaybackStartError::BrokenContainer) => "The file is corrupt",
+            PlaybackError::Open(OpenError::UnsupportedFormat) => "The format isn't supported",
+            PlaybackError::Start(PlaybackStartError::ContainerSupportedButNotCodec) => {
+                "The codec isn't supported"
+            }
+            PlaybackError::Start(PlaybackStartError::NothingToPlay) => "The file has no audio",
+            PlaybackError::Start(PlaybackStartError::Undecodable)
+            | PlaybackError::Read(PlaybackReadError::DecodeFatal) => "The file couldn't be decoded",
+            _ => "The file couldn't be played",
+        }
+    }
 }
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use gpui::AppContext;
+
+use crate::{
+    devices::resample::convert_samples,
+    media::playback::{PlaybackFrame, Samples},
+    settings::SettingsGlobal,
+};
+
+use super::interface::GPUIPlaybackInterface;
+
+/// The longest fade that can be configured, in milliseconds.
+pub const MAX_FADE_DURATION: u64 = 500;
+
+/// A gain ramp applied to the audio sent to the device, so that starting, pausing, and seeking
+/// don't cause clicks. Audio is passed through untouched unless a fade is in progress or has
+/// faded out.
+#[derive(Debug, Clone, Copy)]
+pub struct Fade {
+    gain: f32,
+    target: f32,
+    /// How long a full fade from silence to full volume takes, in seconds.
+    duration: f32,
+}
+
+impl Default for Fade {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            target: 1.0,
+            duration: 0.0,
+        }
+    }
+}
+
+impl Fade {
+    /// Ramps up to full volume from the current gain.
+    pub fn fade_in(&mut self, duration: Duration) {
+        self.target = 1.0;
+        self.duration = duration.as_secs_f32();
+    }
+
+    /// Starts from silence and ramps up to full volume.
+    pub fn fade_in_from_silence(&mut self, duration: Duration) {
+        self.gain = 0.0;
+        self.fade_in(duration);
+    }
+
+    /// Ramps down to silence from the current gain.
+    pub fn fade_out(&mut self, duration: Duration) {
+        self.target = 0.0;
+        self.duration = duration.as_secs_f32();
+    }
+
+    /// Whether the audio has faded out completely.
+    pub fn is_silent(&self) -> bool {
+        self.gain == 0.0 && self.target == 0.0
+    }
+
+    pub fn apply(&mut self, frame: PlaybackFrame) -> PlaybackFrame {
+        // fading is disabled
+        if self.duration <= 0.0 {
+            self.gain = self.target;
+        }
+
+        if self.gain == 1.0 && self.target == 1.0 {
+            return frame;
+        }
+
+        let rate = frame.rate;
+        let mut samples: Vec<Vec<f32>> = convert_samples(frame.samples);
+        let step = 1.0 / (self.duration * rate as f32);
+
+        for i in 0..samples.first().map_or(0, Vec::len) {
+            for channel in samples.iter_mut() {
+                channel[i] *= self.gain;
+            }
+
+            self.gain = if self.target > self.gain {
+                (self.gain + step).min(self.target)
+            } else {
+                (self.gain - step).max(self.target)
+            };
+        }
+
+        PlaybackFrame {
+            samples: Samples::Float32(samples),
+            rate,
+        }
+    }
+}
+
+/// Keeps the playback thread's fade duration in sync with the settings.
+pub fn setup_fade(cx: &mut AppContext) {
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+    let duration = settings.read(cx).playback.fade_duration;
+    cx.global::<GPUIPlaybackInterface>()
+        .set_fade_duration(duration);
+
+    cx.observe(&settings, |settings, cx| {
+        let duration = settings.read(cx).playback.fade_duration;
+        cx.global::<GPUIPlaybackInterface>()
+            .set_fade_duration(duration);
+    })
+    .detach();
+}
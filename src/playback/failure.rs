@@ -0,0 +1,57 @@
+use std::{cell::Cell, rc::Rc};
+
+use gpui::AppContext;
+use tracing::warn;
+
+use crate::{
+    settings::SettingsGlobal,
+    ui::models::{Models, PlaybackInfo},
+};
+
+use super::interface::GPUIPlaybackInterface;
+
+/// How many files in a row can fail before giving up on skipping, so that a queue of files that
+/// can't be played (e.g. on a disconnected drive) doesn't get skipped through forever.
+const MAX_CONSECUTIVE_FAILURES: usize = 5;
+
+/// Moves on to the next track when a file can't be played, if `skip_on_error` is enabled.
+pub fn setup_failure_handling(cx: &mut AppContext) {
+    let playback_failure = cx.global::<Models>().playback_failure.clone();
+    let current_track = cx.global::<PlaybackInfo>().current_track.clone();
+    let failures = Rc::new(Cell::new(0));
+
+    let failures_clone = failures.clone();
+
+    // a track only becomes the current track once it has started playing
+    cx.observe(&current_track, move |_, _| failures_clone.set(0))
+        .detach();
+
+    cx.observe(&playback_failure, move |playback_failure, cx| {
+        if playback_failure.read(cx).is_none() {
+            return;
+        }
+
+        if !cx
+            .global::<SettingsGlobal>()
+            .model
+            .read(cx)
+            .playback
+            .skip_on_error
+        {
+            return;
+        }
+
+        failures.set(failures.get() + 1);
+
+        if failures.get() > MAX_CONSECUTIVE_FAILURES {
+            warn!(
+                "{} files in a row couldn't be played, stopping",
+                failures.get() - 1
+            );
+            return;
+        }
+
+        cx.global::<GPUIPlaybackInterface>().next();
+    })
+    .detach();
+}
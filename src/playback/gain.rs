@@ -0,0 +1,171 @@
+use gpui::AppContext;
+use tracing::error;
+
+use crate::{
+    devices::resample::convert_samples,
+    library::db::find_gain_offset,
+    media::playback::{PlaybackFrame, Samples},
+    settings::SettingsGlobal,
+    ui::{app::Pool, models::PlaybackInfo},
+};
+
+use super::interface::GPUIPlaybackInterface;
+
+/// The lowest gain offset that can be applied, in dB.
+pub const MIN_GAIN: f64 = -12.0;
+/// The highest gain offset that can be applied, in dB. Unless clipping is allowed, the playback
+/// thread reduces the offset so that the track's ReplayGain peak doesn't clip, or limits the
+/// samples if the peak isn't known.
+pub const MAX_GAIN: f64 = 12.0;
+/// How much the gain controls adjust the offset by, in dB.
+pub const GAIN_STEP: f64 = 0.5;
+
+pub fn clamp_gain(gain: f64) -> f64 {
+    gain.clamp(MIN_GAIN, MAX_GAIN)
+}
+
+pub fn gain_to_linear(gain: f64) -> f64 {
+    10.0_f64.powf(gain / 20.0)
+}
+
+/// Formats a gain offset for display, e.g. "+1.5 dB".
+pub fn format_gain(gain: f64) -> String {
+    format!("{:+.1} dB", gain)
+}
+
+/// Where the limiter starts to compress the samples, as a fraction of full scale.
+const LIMITER_KNEE: f32 = 0.8;
+
+/// Reduces the combined volume and gain offset so that the track's peak (with 1.0 being full
+/// scale) doesn't go past full scale. The volume is never reduced below unity, so that tracks
+/// with peaks above full scale aren't made quieter than they would be without an offset.
+pub fn limit_to_peak(volume: f64, peak: f64) -> f64 {
+    volume.min((1.0 / peak).max(1.0))
+}
+
+/// Leaves samples below `LIMITER_KNEE` alone, and smoothly compresses louder ones so that they
+/// approach full scale without ever reaching it.
+pub fn soft_limit(sample: f32) -> f32 {
+    let magnitude = sample.abs();
+
+    if magnitude <= LIMITER_KNEE {
+        return sample;
+    }
+
+    let range = 1.0 - LIMITER_KNEE;
+    let limited = LIMITER_KNEE + range * ((magnitude - LIMITER_KNEE) / range).tanh();
+
+    limited.copysign(sample)
+}
+
+/// Amplifies the samples past what the device's volume allows. Anything that ends up beyond full
+/// scale is either limited with [`soft_limit`] or clipped.
+pub fn amplify(frame: PlaybackFrame, factor: f32, limit: bool) -> PlaybackFrame {
+    if factor == 1.0 {
+        return frame;
+    }
+
+    let rate = frame.rate;
+    let mut samples: Vec<Vec<f32>> = convert_samples(frame.samples);
+
+    for sample in samples.iter_mut().flatten() {
+        *sample = if limit {
+            soft_limit(*sample * factor)
+        } else {
+            (*sample * factor).clamp(-1.0, 1.0)
+        };
+    }
+
+    PlaybackFrame {
+        samples: Samples::Float32(samples),
+        rate,
+    }
+}
+
+/// Applies the stored gain offset of each track as it starts playing, and keeps the playback
+/// thread's clipping mode in sync with the settings.
+pub fn setup_gain(cx: &mut AppContext) {
+    let current_track = cx.global::<PlaybackInfo>().current_track.clone();
+
+    cx.observe(&current_track, |_, cx| refresh_gain(cx))
+        .detach();
+
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+    let mode = settings.read(cx).playback.clipping;
+    cx.global::<GPUIPlaybackInterface>().set_clipping_mode(mode);
+
+    cx.observe(&settings, |settings, cx| {
+        let mode = settings.read(cx).playback.clipping;
+        cx.global::<GPUIPlaybackInterface>().set_clipping_mode(mode);
+    })
+    .detach();
+}
+
+/// Looks up the gain offset of the current track and sends it to the playback thread. This should
+/// be called whenever the offset of the current track or its album changes.
+pub fn refresh_gain(cx: &mut AppContext) {
+    let Some(track) = cx.global::<PlaybackInfo>().current_track.read(cx).clone() else {
+        return;
+    };
+
+    let pool = cx.global::<Pool>().0.clone();
+
+    cx.spawn(|cx| async move {
+        // files that aren't in the library don't have an offset
+        let gain = match find_gain_offset(&pool, &track).await {
+            Ok(gain) => gain.unwrap_or_default(),
+            Err(e) => {
+                error!("Could not retrieve gain offset for {}: {:?}", track, e);
+                0.0
+            }
+        };
+
+        cx.update(|cx| {
+            // the track may have changed while the offset was being retrieved
+            let current = cx.global::<PlaybackInfo>().current_track.read(cx);
+
+            if current.as_ref() == Some(&track) {
+                cx.global::<GPUIPlaybackInterface>().set_gain(gain);
+            }
+        })
+        .expect("failed to send gain offset");
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peaks_limit_positive_offsets() {
+        // a peak of 0.5 leaves 6 dB of headroom
+        assert_eq!(limit_to_peak(4.0, 0.5), 2.0);
+        assert_eq!(limit_to_peak(1.5, 0.5), 1.5);
+    }
+
+    #[test]
+    fn peaks_never_reduce_below_unity() {
+        assert_eq!(limit_to_peak(0.8, 1.2), 0.8);
+        assert_eq!(limit_to_peak(2.0, 1.2), 1.0);
+    }
+
+    #[test]
+    fn soft_limit_leaves_quiet_samples_alone() {
+        assert_eq!(soft_limit(0.5), 0.5);
+        assert_eq!(soft_limit(-LIMITER_KNEE), -LIMITER_KNEE);
+    }
+
+    #[test]
+    fn soft_limit_stays_below_full_scale() {
+        for sample in [0.9_f32, 1.0, 1.5] {
+            let limited = soft_limit(sample);
+
+            assert!(limited > LIMITER_KNEE && limited < 1.0);
+            assert_eq!(soft_limit(-sample), -limited);
+        }
+
+        assert!(soft_limit(1.5) > soft_limit(1.0));
+        assert!(soft_limit(100.0) <= 1.0);
+    }
+}
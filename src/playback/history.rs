@@ -0,0 +1,333 @@
+use std::{cell::Cell, collections::VecDeque, fs::File, path::PathBuf, rc::Rc, time::Duration};
+
+use gpui::{AppContext, Context, Global, Model};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::{
+    settings::SettingsGlobal,
+    ui::{
+        app::get_dirs,
+        models::{Models, PlaybackInfo},
+    },
+};
+
+use super::{interface::GPUIPlaybackInterface, thread::PlaybackState};
+
+/// The most tracks the history holds, older tracks are dropped first.
+const HISTORY_LENGTH: usize = 200;
+
+/// How long the history waits to be saved after it changes, so that skipping through tracks
+/// doesn't write it to disk for each one. It's also saved when Muzak quits.
+const SAVE_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+}
+
+/// The tracks that have been played, oldest first. This is kept separately from the queue, so
+/// that tracks can be found again after the queue has been replaced.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub entries: VecDeque<HistoryEntry>,
+    /// The entry that was last opened by navigating backwards through the history, if playback
+    /// hasn't moved on since.
+    cursor: Option<usize>,
+}
+
+impl History {
+    /// Records that the track has started playing. Returns whether the history changed.
+    fn record(&mut self, location: String) -> bool {
+        if let Some(cursor) = self.cursor {
+            // this is the track that was opened from the history, so it's already recorded
+            if self.entries.get(cursor).map(|v| &v.location) == Some(&location) {
+                return false;
+            }
+
+            self.cursor = None;
+        }
+
+        // replaying the same track shouldn't fill the history with duplicates
+        if self.entries.back().map(|v| &v.location) == Some(&location) {
+            return false;
+        }
+
+        self.entries.push_back(HistoryEntry {
+            location,
+            title: None,
+            artist: None,
+        });
+
+        if self.entries.len() > HISTORY_LENGTH {
+            self.entries.pop_front();
+        }
+
+        true
+    }
+}
+
+pub struct PlaybackHistory(pub Model<History>);
+
+impl Global for PlaybackHistory {}
+
+fn history_path() -> PathBuf {
+    get_dirs().data_dir().join("history.json")
+}
+
+fn load_history() -> VecDeque<HistoryEntry> {
+    let Ok(file) = File::open(history_path()) else {
+        return VecDeque::new();
+    };
+
+    serde_json::from_reader(std::io::BufReader::new(file)).unwrap_or_else(|e| {
+        warn!("Could not read playback history: {:?}", e);
+        VecDeque::new()
+    })
+}
+
+fn save_history(entries: &VecDeque<HistoryEntry>) {
+    let result = File::create(history_path())
+        .map_err(|e| e.to_string())
+        .and_then(|file| {
+            serde_json::to_writer(std::io::BufWriter::new(file), entries).map_err(|e| e.to_string())
+        });
+
+    if let Err(e) = result {
+        error!("Could not save playback history: {}", e);
+    }
+}
+
+fn persist_history(cx: &AppContext) -> bool {
+    cx.global::<SettingsGlobal>()
+        .model
+        .read(cx)
+        .playback
+        .persist_history
+}
+
+/// Records each track as it starts playing. The history is only read from and written to disk if
+/// `persist_history` is enabled.
+pub fn setup_history(cx: &mut AppContext) {
+    let persist = persist_history(cx);
+    let history = cx.new_model(|_| History {
+        entries: if persist {
+            load_history()
+        } else {
+            VecDeque::new()
+        },
+        cursor: None,
+    });
+
+    let current_track = cx.global::<PlaybackInfo>().current_track.clone();
+    let history_clone = history.clone();
+
+    cx.observe(&current_track, move |current_track, cx| {
+        let Some(track) = current_track.read(cx).clone() else {
+            return;
+        };
+
+        history_clone.update(cx, |history, cx| {
+            if history.record(track) {
+                cx.notify();
+            }
+        });
+    })
+    .detach();
+
+    let metadata = cx.global::<Models>().metadata.clone();
+    let history_clone = history.clone();
+
+    cx.observe(&metadata, move |metadata, cx| {
+        let Some(track) = cx.global::<PlaybackInfo>().current_track.read(cx).clone() else {
+            return;
+        };
+        let title = metadata.read(cx).name.clone();
+        let artist = metadata.read(cx).artist();
+
+        history_clone.update(cx, |history, cx| {
+            let cursor = history
+                .cursor
+                .unwrap_or(history.entries.len().saturating_sub(1));
+
+            if let Some(entry) = history.entries.get_mut(cursor) {
+                if entry.location == track && (entry.title != title || entry.artist != artist) {
+                    entry.title = title;
+                    entry.artist = artist;
+                    cx.notify();
+                }
+            }
+        });
+    })
+    .detach();
+
+    let save_pending = Rc::new(Cell::new(false));
+
+    cx.observe(&history, move |history, cx| {
+        if !persist_history(cx) || save_pending.get() {
+            return;
+        }
+
+        save_pending.set(true);
+        let history = history.clone();
+        let save_pending = save_pending.clone();
+
+        cx.spawn(|cx| async move {
+            cx.background_executor().timer(SAVE_DELAY).await;
+            save_pending.set(false);
+
+            cx.update(|cx| {
+                if persist_history(cx) {
+                    save_history(&history.read(cx).entries);
+                }
+            })
+            .ok();
+        })
+        .detach();
+    })
+    .detach();
+
+    let history_clone = history.clone();
+
+    cx.on_app_quit(move |cx| {
+        if persist_history(cx) {
+            save_history(&history_clone.read(cx).entries);
+        }
+
+        async {}
+    })
+    .detach();
+
+    cx.set_global(PlaybackHistory(history));
+}
+
+/// Plays a track from the history, jumping to it if it's still in the queue and adding it to the
+/// end of the queue otherwise.
+pub fn play_from_history(cx: &mut AppContext, location: &str) {
+    let queue = &cx.global::<Models>().queue.read(cx).0;
+    let position = queue.iter().position(|v| v == location);
+    let queue_length = queue.len();
+
+    let interface = cx.global::<GPUIPlaybackInterface>();
+
+    if let Some(position) = position {
+        interface.jump(position);
+    } else {
+        interface.queue(location);
+        interface.jump(queue_length);
+    }
+}
+
+/// Returns to the previous track, or restarts the current one if more than
+/// `previous_restart_threshold` seconds of it have played. If `previous_navigates_history` is
+/// enabled this goes back through the tracks that were actually played, rather than the order of
+/// the queue.
+pub fn previous(cx: &mut AppContext) {
+    let settings = &cx.global::<SettingsGlobal>().model.read(cx).playback;
+    let navigate_history = settings.previous_navigates_history;
+    let threshold = settings.previous_restart_threshold;
+
+    let playback_info = cx.global::<PlaybackInfo>();
+    let playing = *playback_info.playback_state.read(cx) != PlaybackState::Stopped;
+    let position = *playback_info.position.read(cx);
+
+    if threshold > 0 && playing && position > threshold {
+        cx.global::<GPUIPlaybackInterface>().seek(0.0);
+        return;
+    }
+
+    if !navigate_history {
+        cx.global::<GPUIPlaybackInterface>().previous();
+        return;
+    }
+
+    let history = cx.global::<PlaybackHistory>().0.clone();
+
+    let target = history.update(cx, |history, _| {
+        let current = history
+            .cursor
+            .unwrap_or(history.entries.len().saturating_sub(1));
+        let target = current.checked_sub(1)?;
+
+        history.cursor = Some(target);
+        history.entries.get(target).map(|v| v.location.clone())
+    });
+
+    match target {
+        Some(location) => play_from_history(cx, &location),
+        None => cx.global::<GPUIPlaybackInterface>().previous(),
+    }
+}
+
+/// Empties the history.
+pub fn clear_history(cx: &mut AppContext) {
+    let history = cx.global::<PlaybackHistory>().0.clone();
+
+    history.update(cx, |history, cx| {
+        *history = History::default();
+        cx.notify();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locations(history: &History) -> Vec<&str> {
+        history
+            .entries
+            .iter()
+            .map(|v| v.location.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut history = History::default();
+
+        for i in 0..HISTORY_LENGTH + 10 {
+            history.record(format!("/music/{}", i));
+        }
+
+        assert_eq!(history.entries.len(), HISTORY_LENGTH);
+        assert_eq!(history.entries[0].location, "/music/10");
+        assert_eq!(
+            history.entries.back().unwrap().location,
+            format!("/music/{}", HISTORY_LENGTH + 9)
+        );
+    }
+
+    #[test]
+    fn repeated_tracks_are_moved_to_the_top() {
+        let mut history = History::default();
+
+        assert!(history.record("/a".to_string()));
+        assert!(history.record("/b".to_string()));
+
+        // replaying the most recent track keeps it at the top without a duplicate
+        assert!(!history.record("/b".to_string()));
+        assert_eq!(locations(&history), ["/a", "/b"]);
+
+        // playing an earlier track again puts it back at the top
+        assert!(history.record("/a".to_string()));
+        assert_eq!(locations(&history), ["/a", "/b", "/a"]);
+    }
+
+    #[test]
+    fn tracks_opened_from_the_history_are_not_recorded_again() {
+        let mut history = History::default();
+        history.record("/a".to_string());
+        history.record("/b".to_string());
+
+        history.cursor = Some(0);
+        assert!(!history.record("/a".to_string()));
+        assert_eq!(history.cursor, Some(0));
+
+        // moving on to another track leaves the history
+        assert!(history.record("/c".to_string()));
+        assert_eq!(history.cursor, None);
+        assert_eq!(locations(&history), ["/a", "/b", "/c"]);
+    }
+}
@@ -5,21 +5,69 @@ use std::{
         mpsc::{Receiver, Sender},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use gpui::AppContext;
+use gpui::{AppContext, AsyncAppContext};
+use tracing::error;
 
 use crate::{
-    data::interface::GPUIDataInterface,
-    ui::models::{ImageEvent, MMBSEvent, Models, PlaybackInfo},
+    library::db::find_track_id,
+    settings::{
+        playback::{ClippingMode, ShuffleMode},
+        SettingsGlobal,
+    },
+    ui::{
+        app::Pool,
+        models::{ImageEvent, MMBSEvent, Models, PlaybackFailure, PlaybackInfo, ReplacedQueue},
+    },
 };
 
 use super::{
     events::{PlaybackCommand, PlaybackEvent},
     thread::PlaybackState,
+    trim::TrackTrim,
 };
 
+/// Holds back a frequently changing value so that it's passed on at most once per interval. The
+/// latest value is always passed on eventually.
+struct Throttle<T> {
+    pending: Option<T>,
+    last_sent: Option<Instant>,
+}
+
+impl<T> Default for Throttle<T> {
+    fn default() -> Self {
+        Self {
+            pending: None,
+            last_sent: None,
+        }
+    }
+}
+
+impl<T> Throttle<T> {
+    fn push(&mut self, value: T) {
+        self.pending = Some(value);
+    }
+
+    /// Drops the held back value without passing it on.
+    fn clear(&mut self) {
+        self.pending = None;
+    }
+
+    /// Returns the held back value, if there is one and `interval` has passed since the last one.
+    fn take(&mut self, interval: Duration) -> Option<T> {
+        if self.last_sent.is_some_and(|v| v.elapsed() < interval) {
+            return None;
+        }
+
+        let value = self.pending.take()?;
+        self.last_sent = Some(Instant::now());
+
+        Some(value)
+    }
+}
+
 /// The PlaybackInterface trait defines the method used to create the struct that will be used to
 /// communicate between the playback thread and the main thread.
 pub trait PlaybackInterface {
@@ -106,6 +154,18 @@ impl GPUIPlaybackInterface {
             .expect("could not send tx");
     }
 
+    pub fn duplicate_queue_item(&self, index: usize) {
+        self.commands_tx
+            .send(PlaybackCommand::DuplicateQueueItem(index))
+            .expect("could not send tx");
+    }
+
+    pub fn move_queue_item(&self, from: usize, to: usize) {
+        self.commands_tx
+            .send(PlaybackCommand::MoveQueueItem(from, to))
+            .expect("could not send tx");
+    }
+
     pub fn seek(&self, position: f64) {
         self.commands_tx
             .send(PlaybackCommand::Seek(position))
@@ -118,12 +178,72 @@ impl GPUIPlaybackInterface {
             .expect("could not send tx");
     }
 
+    pub fn set_speed(&self, speed: f64) {
+        self.commands_tx
+            .send(PlaybackCommand::SetSpeed(speed))
+            .expect("could not send tx");
+    }
+
+    pub fn set_fade_duration(&self, duration: u64) {
+        self.commands_tx
+            .send(PlaybackCommand::SetFadeDuration(duration))
+            .expect("could not send tx");
+    }
+
+    pub fn set_clipping_mode(&self, mode: ClippingMode) {
+        self.commands_tx
+            .send(PlaybackCommand::SetClippingMode(mode))
+            .expect("could not send tx");
+    }
+
+    pub fn set_gain(&self, gain: f64) {
+        self.commands_tx
+            .send(PlaybackCommand::SetGain(gain))
+            .expect("could not send tx");
+    }
+
     pub fn replace_queue(&self, paths: Vec<String>) {
         self.commands_tx
             .send(PlaybackCommand::ReplaceQueue(paths.clone()))
             .expect("could not send tx");
     }
 
+    pub fn set_monitors(&self, uids: Vec<String>) {
+        self.commands_tx
+            .send(PlaybackCommand::SetMonitors(uids))
+            .expect("could not send tx");
+    }
+
+    pub fn set_prebuffer_count(&self, count: usize) {
+        self.commands_tx
+            .send(PlaybackCommand::SetPrebufferCount(count))
+            .expect("could not send tx");
+    }
+
+    pub fn set_level_meter(&self, enabled: bool) {
+        self.commands_tx
+            .send(PlaybackCommand::SetLevelMeter(enabled))
+            .expect("could not send tx");
+    }
+
+    pub fn set_repeat(&self, repeat: bool) {
+        self.commands_tx
+            .send(PlaybackCommand::SetRepeat(repeat))
+            .expect("could not send tx");
+    }
+
+    pub fn set_output_device(&self, uid: String, seamless: bool) {
+        self.commands_tx
+            .send(PlaybackCommand::SetOutputDevice(uid, seamless))
+            .expect("could not send tx");
+    }
+
+    pub fn list_devices(&self) {
+        self.commands_tx
+            .send(PlaybackCommand::ListDevices)
+            .expect("could not send tx");
+    }
+
     pub fn stop(&self) {
         self.commands_tx
             .send(PlaybackCommand::Stop)
@@ -136,6 +256,36 @@ impl GPUIPlaybackInterface {
             .expect("could not send tx");
     }
 
+    pub fn shuffle_remaining(&self) {
+        self.commands_tx
+            .send(PlaybackCommand::ShuffleRemaining)
+            .expect("could not send tx");
+    }
+
+    pub fn set_shuffle_mode(&self, mode: ShuffleMode) {
+        self.commands_tx
+            .send(PlaybackCommand::SetShuffleMode(mode))
+            .expect("could not send tx");
+    }
+
+    pub fn add_album_keys(&self, keys: Vec<(String, i64)>) {
+        self.commands_tx
+            .send(PlaybackCommand::AddAlbumKeys(keys))
+            .expect("could not send tx");
+    }
+
+    pub fn set_skip_always(&self, paths: Vec<String>, skip: bool) {
+        self.commands_tx
+            .send(PlaybackCommand::SetSkipAlways(paths, skip))
+            .expect("could not send tx");
+    }
+
+    pub fn set_trims(&self, trims: Vec<(String, TrackTrim)>) {
+        self.commands_tx
+            .send(PlaybackCommand::SetTrims(trims))
+            .expect("could not send tx");
+    }
+
     /// Starts the broadcast loop that will read events from the playback thread and update data
     /// models accordingly. This function should be called once, and will panic if called more than
     /// once.
@@ -145,13 +295,22 @@ impl GPUIPlaybackInterface {
 
         let metadata_model = cx.global::<Models>().metadata.clone();
         let albumart_model = cx.global::<Models>().albumart.clone();
+        let albumart_color_model = cx.global::<Models>().albumart_color.clone();
         let queue_model = cx.global::<Models>().queue.clone();
         let mmbs_model = cx.global::<Models>().mmbs.clone();
+        let playback_failure_model = cx.global::<Models>().playback_failure.clone();
+        let output_failure_model = cx.global::<Models>().output_failure.clone();
 
         let playback_info = cx.global::<PlaybackInfo>().clone();
 
         if let Some(events_rx) = events_rx {
             cx.spawn(|mut cx| async move {
+                // the seek bar and the services don't need every position update
+                let mut ui_position = Throttle::default();
+                let mut service_position = Throttle::default();
+                // whether the services are told about the current track, see `is_scrobbled`
+                let mut scrobbled = true;
+
                 loop {
                     while let Ok(event) = events_rx.try_recv() {
                         match event {
@@ -165,13 +324,17 @@ impl GPUIPlaybackInterface {
                                     })
                                     .expect("failed to update metadata");
 
-                                mmbs_model
-                                    .update(&mut cx, |_, cx| {
-                                        cx.emit(MMBSEvent::MetadataRecieved(metadata));
-                                    })
-                                    .expect("failed to broadcast MMBS event MetadataRecieved");
+                                if scrobbled {
+                                    mmbs_model
+                                        .update(&mut cx, |_, cx| {
+                                            cx.emit(MMBSEvent::MetadataRecieved(metadata));
+                                        })
+                                        .expect("failed to broadcast MMBS event MetadataRecieved");
+                                }
                             }
                             PlaybackEvent::AlbumArtUpdate(v) => {
+                                let has_art = v.is_some();
+
                                 albumart_model
                                     .update(&mut cx, |m, cx| {
                                         if let Some(v) = v {
@@ -182,6 +345,15 @@ impl GPUIPlaybackInterface {
                                         }
                                     })
                                     .expect("failed to update albumart");
+
+                                if !has_art {
+                                    albumart_color_model
+                                        .update(&mut cx, |m, cx| {
+                                            *m = None;
+                                            cx.notify()
+                                        })
+                                        .expect("failed to update albumart color");
+                                }
                             }
                             PlaybackEvent::StateChanged(v) => {
                                 playback_info
@@ -209,18 +381,8 @@ impl GPUIPlaybackInterface {
                                     .expect("failed to broadcast MMBS event StateChanged");
                             }
                             PlaybackEvent::PositionChanged(v) => {
-                                playback_info
-                                    .position
-                                    .update(&mut cx, |m, cx| {
-                                        *m = v;
-                                        cx.notify()
-                                    })
-                                    .expect("failed to update position");
-                                mmbs_model
-                                    .update(&mut cx, |_, cx| {
-                                        cx.emit(MMBSEvent::PositionChanged(v));
-                                    })
-                                    .expect("failed to broadcast MMBS event PositionChanged");
+                                ui_position.push(v);
+                                service_position.push(v);
                             }
                             PlaybackEvent::DurationChanged(v) => {
                                 playback_info
@@ -237,6 +399,10 @@ impl GPUIPlaybackInterface {
                                     .expect("failed to broadcast MMBS event DurationChanged");
                             }
                             PlaybackEvent::SongChanged(v) => {
+                                // the services shouldn't get the last track's position
+                                service_position.clear();
+                                scrobbled = is_scrobbled(&mut cx, &v).await;
+
                                 let clone = v.clone();
                                 playback_info
                                     .current_track
@@ -275,10 +441,82 @@ impl GPUIPlaybackInterface {
                                     cx.notify()
                                 })
                                 .expect("failed to update volume model"),
+                            PlaybackEvent::SpeedChanged(v) => playback_info
+                                .speed
+                                .update(&mut cx, |m, cx| {
+                                    *m = v;
+                                    cx.notify()
+                                })
+                                .expect("failed to update speed model"),
+                            PlaybackEvent::OutputChanged(v) => playback_info
+                                .output
+                                .update(&mut cx, |m, cx| {
+                                    *m = Some(v);
+                                    cx.notify()
+                                })
+                                .expect("failed to update output model"),
+                            PlaybackEvent::DevicesListed(v) => playback_info
+                                .devices
+                                .update(&mut cx, |m, cx| {
+                                    *m = v;
+                                    cx.notify()
+                                })
+                                .expect("failed to update devices model"),
+                            PlaybackEvent::LevelChanged(v) => playback_info
+                                .level
+                                .update(&mut cx, |m, cx| {
+                                    *m = v;
+                                    cx.notify()
+                                })
+                                .expect("failed to update level model"),
+                            PlaybackEvent::Error(path, error) => playback_failure_model
+                                .update(&mut cx, |m, cx| {
+                                    *m = Some(PlaybackFailure { path, error });
+                                    cx.notify()
+                                })
+                                .expect("failed to update playback failure"),
+                            PlaybackEvent::OutputFailed(name) => output_failure_model
+                                .update(&mut cx, |m, cx| {
+                                    *m = Some(name);
+                                    cx.notify()
+                                })
+                                .expect("failed to update output failure"),
                             _ => (),
                         }
                     }
 
+                    let (ui_interval, service_interval) = cx
+                        .update(|cx| {
+                            let settings = &cx.global::<SettingsGlobal>().model.read(cx).playback;
+
+                            (
+                                Duration::from_millis(settings.position_update_interval),
+                                Duration::from_millis(settings.service_position_interval),
+                            )
+                        })
+                        .expect("failed to read settings");
+
+                    if let Some(v) = ui_position.take(ui_interval) {
+                        playback_info
+                            .position
+                            .update(&mut cx, |m, cx| {
+                                *m = v;
+                                cx.notify()
+                            })
+                            .expect("failed to update position");
+                    }
+
+                    if let Some(v) = service_position
+                        .take(service_interval)
+                        .filter(|_| scrobbled)
+                    {
+                        mmbs_model
+                            .update(&mut cx, |_, cx| {
+                                cx.emit(MMBSEvent::PositionChanged(v));
+                            })
+                            .expect("failed to broadcast MMBS event PositionChanged");
+                    }
+
                     cx.background_executor()
                         .timer(Duration::from_millis(10))
                         .await;
@@ -291,12 +529,123 @@ impl GPUIPlaybackInterface {
     }
 }
 
+/// Whether the services are told about the track at the specified path. With `library_only` on,
+/// files that aren't in the library are kept from them. The services still hear that a new track
+/// has started, so that the previous track is scrobbled, but without its metadata or position
+/// the new one is never scrobbled or shown as now playing.
+async fn is_scrobbled(cx: &mut AsyncAppContext, path: &str) -> bool {
+    let pool = cx
+        .update(|cx| {
+            let library_only = cx
+                .global::<SettingsGlobal>()
+                .model
+                .read(cx)
+                .lastfm
+                .library_only;
+
+            library_only.then(|| cx.try_global::<Pool>().map(|v| v.0.clone()))
+        })
+        .expect("failed to read settings");
+
+    let Some(pool) = pool else {
+        return true;
+    };
+
+    // without a library, nothing is in it
+    let Some(pool) = pool else {
+        return false;
+    };
+
+    match find_track_id(&pool, Some(path), None, None, None).await {
+        Ok(id) => id.is_some(),
+        Err(e) => {
+            error!("Could not look up {} in the library: {:?}", path, e);
+            false
+        }
+    }
+}
+
 // TODO: this should be in a trait for AppContext
 pub fn replace_queue(paths: Vec<String>, cx: &mut AppContext) {
+    stash_queue(cx);
+
     let playback_interface = cx.global::<GPUIPlaybackInterface>();
-    playback_interface.replace_queue(paths.clone());
+    playback_interface.replace_queue(paths);
+}
+
+/// Switches output to the device with the specified UID, keeping playback going through the switch
+/// if `seamless_device_switch` is enabled.
+pub fn set_output_device(uid: String, cx: &mut AppContext) {
+    let seamless = cx
+        .global::<SettingsGlobal>()
+        .model
+        .read(cx)
+        .playback
+        .seamless_device_switch;
+
+    cx.global::<GPUIPlaybackInterface>()
+        .set_output_device(uid, seamless);
+}
+
+/// Keeps the current queue so that replacing it can be undone, if `undo_queue_replacement` is
+/// enabled and the queue still has tracks that haven't been played.
+fn stash_queue(cx: &mut AppContext) {
+    let enabled = cx
+        .global::<SettingsGlobal>()
+        .model
+        .read(cx)
+        .playback
+        .undo_queue_replacement;
+
+    if !enabled {
+        return;
+    }
 
-    let data_interface = cx.global::<GPUIDataInterface>();
+    let queue = cx.global::<Models>().queue.read(cx).0.clone();
+    let playback_info = cx.global::<PlaybackInfo>();
+    let current = playback_info
+        .current_track
+        .read(cx)
+        .as_ref()
+        .and_then(|track| queue.iter().position(|v| v == track));
+    let position = *playback_info.position.read(cx);
 
-    data_interface.evict_cache();
+    let unplayed = current.map_or(queue.len(), |idx| queue.len() - idx - 1);
+
+    if unplayed == 0 {
+        return;
+    }
+
+    let replaced_queue = cx.global::<Models>().replaced_queue.clone();
+
+    replaced_queue.update(cx, |m, cx| {
+        *m = Some(ReplacedQueue {
+            paths: queue,
+            current,
+            position,
+        });
+        cx.notify();
+    });
+}
+
+/// Restores the queue that was last replaced, returning to the track that was playing and the
+/// position within it.
+pub fn undo_replace_queue(cx: &mut AppContext) {
+    let replaced_queue = cx.global::<Models>().replaced_queue.clone();
+    let Some(replaced) = replaced_queue.update(cx, |m, cx| {
+        cx.notify();
+        m.take()
+    }) else {
+        return;
+    };
+
+    // the queue the UI shows is already shuffled if shuffling is enabled, so the restored queue
+    // will be shuffled again, but the track that was playing is still returned to
+    let playback_interface = cx.global::<GPUIPlaybackInterface>();
+    playback_interface.replace_queue(replaced.paths);
+
+    if let Some(current) = replaced.current {
+        playback_interface.jump(current);
+        playback_interface.seek(replaced.position as f64);
+    }
 }
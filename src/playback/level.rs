@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use gpui::AppContext;
+
+use crate::{
+    devices::resample::SampleFrom,
+    media::playback::{PlaybackFrame, Samples},
+    settings::SettingsGlobal,
+};
+
+use super::interface::GPUIPlaybackInterface;
+
+/// How often the level is sent to the main thread.
+const INTERVAL: Duration = Duration::from_millis(50);
+/// How much of the level is kept each interval once the audio gets quieter, so that the meter
+/// falls gradually instead of flickering.
+const DECAY: f32 = 0.75;
+/// Levels below this are treated as silence.
+const FLOOR: f32 = 0.001;
+
+/// Follows the peak level of the audio sent to the device. Peaks are measured as frames are
+/// decoded, so the level runs slightly ahead of what's heard.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LevelMeter {
+    /// The highest peak since the level was last sent.
+    peak: f32,
+    level: f32,
+    last_sent: Option<Instant>,
+}
+
+impl LevelMeter {
+    pub fn measure(&mut self, frame: &PlaybackFrame) {
+        self.peak = self.peak.max(peak(&frame.samples));
+    }
+
+    /// Returns the smoothed level, from 0.0 to 1.0, once per interval. Returns None if it isn't
+    /// time to send it yet, or if it's still silent.
+    pub fn poll(&mut self) -> Option<f32> {
+        if self.last_sent.is_some_and(|v| v.elapsed() < INTERVAL) {
+            return None;
+        }
+
+        let previous = self.level;
+        self.level = self.peak.max(self.level * DECAY);
+        self.peak = 0.0;
+        self.last_sent = Some(Instant::now());
+
+        if self.level < FLOOR {
+            self.level = 0.0;
+        }
+
+        if self.level == 0.0 && previous == 0.0 {
+            return None;
+        }
+
+        Some(self.level.min(1.0))
+    }
+}
+
+fn channel_peak<T: Copy>(channels: &[Vec<T>]) -> f32
+where
+    f64: SampleFrom<T>,
+{
+    channels.iter().flatten().fold(0.0, |peak: f32, v| {
+        peak.max(f64::sample_from(*v).abs() as f32)
+    })
+}
+
+fn peak(samples: &Samples) -> f32 {
+    match samples {
+        Samples::Float64(v) => channel_peak(v),
+        Samples::Float32(v) => channel_peak(v),
+        Samples::Signed32(v) => channel_peak(v),
+        Samples::Unsigned32(v) => channel_peak(v),
+        Samples::Signed24(v) => channel_peak(v),
+        Samples::Unsigned24(v) => channel_peak(v),
+        Samples::Signed16(v) => channel_peak(v),
+        Samples::Unsigned16(v) => channel_peak(v),
+        Samples::Signed8(v) => channel_peak(v),
+        Samples::Unsigned8(v) => channel_peak(v),
+        Samples::Dsd(_) => 0.0,
+    }
+}
+
+/// The lowest level shown by the meter, in dB.
+const METER_RANGE: f32 = -48.0;
+
+/// Converts a level to how full the meter is, from 0.0 to 1.0, on a decibel scale.
+pub fn meter_fill(level: f32) -> f32 {
+    if level <= 0.0 {
+        return 0.0;
+    }
+
+    (1.0 - (20.0 * level.log10()) / METER_RANGE).clamp(0.0, 1.0)
+}
+
+/// Keeps the playback thread measuring the level only while the meter is shown.
+pub fn setup_level_meter(cx: &mut AppContext) {
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+    let enabled = settings.read(cx).interface.level_meter;
+    cx.global::<GPUIPlaybackInterface>()
+        .set_level_meter(enabled);
+
+    cx.observe(&settings, |settings, cx| {
+        let enabled = settings.read(cx).interface.level_meter;
+        cx.global::<GPUIPlaybackInterface>()
+            .set_level_meter(enabled);
+    })
+    .detach();
+}
@@ -0,0 +1,204 @@
+use gpui::AppContext;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    devices::{
+        format::{ChannelSpec, FormatInfo},
+        resample::{Resampler, SampleFrom},
+        traits::{Device, DeviceProvider, OutputStream},
+    },
+    media::playback::{PlaybackFrame, Samples},
+    settings::SettingsGlobal,
+};
+
+use super::interface::GPUIPlaybackInterface;
+
+/// An extra output stream that's given a copy of the audio sent to the main device, for listening
+/// on two devices at once. Each monitor is resampled to its own format and buffers separately, so
+/// monitors are only fed at the same time as the main device, not kept in sync with it.
+pub struct Monitor {
+    pub uid: String,
+    // the stream may need the device to stay open
+    _device: Box<dyn Device>,
+    stream: Box<dyn OutputStream>,
+    format: FormatInfo,
+    resampler: Option<Resampler>,
+    /// The sample rate the resampler was created for.
+    source_rate: u32,
+    /// Set once submitting to the stream fails, after which the monitor is skipped until it's
+    /// reopened.
+    failed: bool,
+}
+
+impl Monitor {
+    pub fn open(provider: &mut dyn DeviceProvider, uid: &str) -> Option<Self> {
+        let mut device = match provider.get_device_by_uid(uid) {
+            Ok(device) => device,
+            Err(e) => {
+                error!("Could not find monitor device {}: {:?}", uid, e);
+                return None;
+            }
+        };
+
+        let stream = device
+            .get_default_format()
+            .map_err(|e| format!("{:?}", e))
+            .and_then(|format| device.open_device(format).map_err(|e| format!("{:?}", e)));
+
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!("Could not open monitor device {}: {}", uid, e);
+                return None;
+            }
+        };
+
+        let Ok(format) = stream.get_current_format().cloned() else {
+            error!("Could not get format of monitor device {}", uid);
+            return None;
+        };
+
+        info!(
+            "Opened monitor device: {:?}, format: {:?}, rate: {}",
+            device.get_name(),
+            format.sample_type,
+            format.sample_rate
+        );
+
+        Some(Self {
+            uid: uid.to_string(),
+            _device: device,
+            stream,
+            format,
+            resampler: None,
+            source_rate: 0,
+            failed: false,
+        })
+    }
+
+    /// Converts a copy of the frame to the monitor's format and submits it. `duration` is the
+    /// usual length of the frames being played, which the resampler is created for.
+    pub fn submit(&mut self, frame: &PlaybackFrame, duration: u64) {
+        if self.failed {
+            return;
+        }
+
+        if self.resampler.is_none() || self.source_rate != frame.rate {
+            self.resampler = Some(Resampler::new(
+                frame.rate,
+                self.format.sample_rate,
+                duration,
+                match self.format.channels {
+                    ChannelSpec::Count(v) => v,
+                    _ => 2,
+                },
+            ));
+            self.source_rate = frame.rate;
+        }
+
+        let Some(samples) = copy_samples(&frame.samples) else {
+            // the monitor only falls behind by a frame, and catches up as it's fed more
+            debug!(
+                "Skipping frame for monitor {}, its samples can't be copied",
+                self.uid
+            );
+            return;
+        };
+
+        let copy = PlaybackFrame {
+            samples: Samples::Float32(samples),
+            rate: frame.rate,
+        };
+        let converted = self
+            .resampler
+            .as_mut()
+            .unwrap()
+            .convert_formats(copy, &self.format);
+
+        if let Err(e) = self.stream.submit_frame(converted) {
+            warn!("Could not submit audio to monitor {}: {:?}", self.uid, e);
+            self.failed = true;
+        }
+    }
+
+    /// Whether submitting to the stream has failed, e.g. because the device was unplugged.
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn play(&mut self) {
+        if let Err(e) = self.stream.play() {
+            warn!("Could not play monitor {}: {:?}", self.uid, e);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if let Err(e) = self.stream.pause() {
+            warn!("Could not pause monitor {}: {:?}", self.uid, e);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        if let Err(e) = self.stream.reset() {
+            warn!("Could not reset monitor {}: {:?}", self.uid, e);
+        }
+    }
+
+    pub fn set_volume(&mut self, volume: f64) {
+        if let Err(e) = self.stream.set_volume(volume) {
+            warn!("Could not set volume of monitor {}: {:?}", self.uid, e);
+        }
+    }
+
+    pub fn close(mut self) {
+        if let Err(e) = self.stream.close_stream() {
+            warn!("Could not close monitor {}: {:?}", self.uid, e);
+        }
+    }
+}
+
+fn copy_channels<T: Copy>(channels: &[Vec<T>]) -> Vec<Vec<f32>>
+where
+    f32: SampleFrom<T>,
+{
+    channels
+        .iter()
+        .map(|v| v.iter().map(|v| f32::sample_from(*v)).collect())
+        .collect()
+}
+
+/// Copies the samples as floats. Returns None for DSD, which can't be converted sample by sample.
+fn copy_samples(samples: &Samples) -> Option<Vec<Vec<f32>>> {
+    Some(match samples {
+        Samples::Float64(v) => copy_channels(v),
+        Samples::Float32(v) => copy_channels(v),
+        Samples::Signed32(v) => copy_channels(v),
+        Samples::Unsigned32(v) => copy_channels(v),
+        Samples::Signed24(v) => copy_channels(v),
+        Samples::Unsigned24(v) => copy_channels(v),
+        Samples::Signed16(v) => copy_channels(v),
+        Samples::Unsigned16(v) => copy_channels(v),
+        Samples::Signed8(v) => copy_channels(v),
+        Samples::Unsigned8(v) => copy_channels(v),
+        Samples::Dsd(_) => return None,
+    })
+}
+
+/// Keeps the playback thread's monitor devices in sync with the settings.
+pub fn setup_monitors(cx: &mut AppContext) {
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+    let mut last = settings.read(cx).playback.monitor_devices.clone();
+    cx.global::<GPUIPlaybackInterface>()
+        .set_monitors(last.clone());
+
+    cx.observe(&settings, move |settings, cx| {
+        let devices = &settings.read(cx).playback.monitor_devices;
+
+        if *devices != last {
+            last = devices.clone();
+            cx.global::<GPUIPlaybackInterface>()
+                .set_monitors(last.clone());
+        }
+    })
+    .detach();
+}
@@ -0,0 +1,175 @@
+use std::{fs::File, io::Read};
+
+use gpui::AppContext;
+use tracing::{debug, warn};
+
+use crate::settings::SettingsGlobal;
+
+use super::interface::GPUIPlaybackInterface;
+
+/// How much of each upcoming file is read ahead of time, so that it's already in the operating
+/// system's cache when it's opened. This covers the headers and the first frames of most files.
+const PREBUFFER_SIZE: u64 = 1024 * 1024;
+
+/// The most upcoming files that can be read ahead of time.
+pub const MAX_PREBUFFER_COUNT: usize = 8;
+
+/// Keeps the start of the next few files in the queue in the operating system's cache, so that
+/// opening them doesn't have to wait for the disk (or network share) once the current track ends
+/// or is skipped. The playback thread also opens each of them ahead of time, one at a time, with
+/// [`ProviderRegistry::prepare`](crate::media::registry::ProviderRegistry::prepare).
+pub struct Prebuffer {
+    count: usize,
+    /// The files that were last read ahead of time, so that each is only read once.
+    window: Vec<String>,
+    /// The files in the window that haven't been opened ahead of time yet, in the order they'll be
+    /// played.
+    unprepared: Vec<String>,
+}
+
+impl Default for Prebuffer {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            window: Vec::new(),
+            unprepared: Vec::new(),
+        }
+    }
+}
+
+impl Prebuffer {
+    /// How many upcoming files should be read ahead of time.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn set_count(&mut self, count: usize) {
+        self.count = count.min(MAX_PREBUFFER_COUNT);
+        self.window.truncate(self.count);
+
+        let window = &self.window;
+        self.unprepared.retain(|path| window.contains(path));
+    }
+
+    /// The files that are currently read ahead of time.
+    pub fn window(&self) -> &[String] {
+        &self.window
+    }
+
+    /// Returns the next file in the window that should be opened ahead of time, if there is one.
+    /// Each file is only returned once while it stays in the window.
+    pub fn take_unprepared(&mut self) -> Option<String> {
+        (!self.unprepared.is_empty()).then(|| self.unprepared.remove(0))
+    }
+
+    /// Moves the window to the specified upcoming files, in the order they'll be played, and reads
+    /// the ones that weren't in it before on another thread.
+    pub fn update(&mut self, upcoming: Vec<String>) {
+        if upcoming == self.window {
+            return;
+        }
+
+        let new: Vec<String> = upcoming
+            .iter()
+            .filter(|path| !self.window.contains(path))
+            .cloned()
+            .collect();
+
+        self.unprepared.retain(|path| upcoming.contains(path));
+        self.unprepared.extend(new.iter().cloned());
+        self.window = upcoming;
+
+        if new.is_empty() {
+            return;
+        }
+
+        let result = std::thread::Builder::new()
+            .name("prebuffer".to_string())
+            .spawn(move || {
+                // the next file is read first, since it's needed soonest
+                for path in new {
+                    let result = File::open(&path).and_then(|file| {
+                        std::io::copy(&mut file.take(PREBUFFER_SIZE), &mut std::io::sink())
+                    });
+
+                    match result {
+                        Ok(read) => debug!("prebuffered {} bytes of {}", read, path),
+                        Err(e) => debug!("could not prebuffer {}: {:?}", path, e),
+                    }
+                }
+            });
+
+        if let Err(e) = result {
+            warn!("Could not start prebuffering: {:?}", e);
+        }
+    }
+}
+
+/// Keeps the number of upcoming files the playback thread reads ahead of time in sync with the
+/// settings.
+pub fn setup_prebuffer(cx: &mut AppContext) {
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+    let mut last = settings.read(cx).playback.prebuffer_count;
+    cx.global::<GPUIPlaybackInterface>()
+        .set_prebuffer_count(last);
+
+    cx.observe(&settings, move |settings, cx| {
+        let count = settings.read(cx).playback.prebuffer_count;
+
+        if count != last {
+            last = count;
+            cx.global::<GPUIPlaybackInterface>()
+                .set_prebuffer_count(count);
+        }
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(names: &[&str]) -> Vec<String> {
+        names.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn files_are_prepared_once_in_order() {
+        let mut prebuffer = Prebuffer::default();
+        prebuffer.set_count(2);
+
+        prebuffer.update(paths(&["/nonexistent/a", "/nonexistent/b"]));
+        assert_eq!(
+            prebuffer.take_unprepared().as_deref(),
+            Some("/nonexistent/a")
+        );
+
+        // the window moves on before b has been prepared
+        prebuffer.update(paths(&["/nonexistent/b", "/nonexistent/c"]));
+        assert_eq!(
+            prebuffer.take_unprepared().as_deref(),
+            Some("/nonexistent/b")
+        );
+        assert_eq!(
+            prebuffer.take_unprepared().as_deref(),
+            Some("/nonexistent/c")
+        );
+        assert_eq!(prebuffer.take_unprepared(), None);
+    }
+
+    #[test]
+    fn files_leaving_the_window_are_not_prepared() {
+        let mut prebuffer = Prebuffer::default();
+        prebuffer.set_count(2);
+
+        prebuffer.update(paths(&["/nonexistent/a", "/nonexistent/b"]));
+        prebuffer.set_count(1);
+
+        assert_eq!(prebuffer.window(), paths(&["/nonexistent/a"]).as_slice());
+        assert_eq!(
+            prebuffer.take_unprepared().as_deref(),
+            Some("/nonexistent/a")
+        );
+        assert_eq!(prebuffer.take_unprepared(), None);
+    }
+}
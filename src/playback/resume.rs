@@ -0,0 +1,144 @@
+use std::{cell::RefCell, rc::Rc};
+
+use gpui::AppContext;
+use tracing::error;
+
+use crate::{
+    library::db::{clear_resume_position, find_album_position_by_location, set_resume_position},
+    ui::{app::Pool, models::PlaybackInfo},
+};
+
+use super::interface::{replace_queue, GPUIPlaybackInterface};
+
+/// How far playback has to move (in seconds) before the position within the album is saved again.
+const SAVE_INTERVAL: u64 = 10;
+/// How close to the end of an album's last track playback has to get for the album to count as
+/// finished, in seconds.
+const FINISH_MARGIN: u64 = 5;
+
+/// The track that is currently playing, and what's known about where it is in its album.
+struct ResumeTracker {
+    location: String,
+    /// The album of the track and whether it's the album's last track, once they've been looked
+    /// up. Tracks that aren't part of an album aren't tracked.
+    album: Option<(i64, bool)>,
+    last_saved: Option<u64>,
+    finished: bool,
+}
+
+/// Periodically saves the track and position within it of the album that's playing, so that it
+/// can be resumed later. The saved position is cleared once the album has been played to the end.
+pub fn setup_resume(cx: &mut AppContext) {
+    let tracker: Rc<RefCell<Option<ResumeTracker>>> = Rc::new(RefCell::new(None));
+
+    let current_track = cx.global::<PlaybackInfo>().current_track.clone();
+    let tracker_clone = tracker.clone();
+
+    cx.observe(&current_track, move |current_track, cx| {
+        let Some(track) = current_track.read(cx).clone() else {
+            *tracker_clone.borrow_mut() = None;
+            return;
+        };
+
+        if tracker_clone
+            .borrow()
+            .as_ref()
+            .is_some_and(|v| v.location == track)
+        {
+            return;
+        }
+
+        *tracker_clone.borrow_mut() = Some(ResumeTracker {
+            location: track.clone(),
+            album: None,
+            last_saved: None,
+            finished: false,
+        });
+
+        let Some(pool) = cx.try_global::<Pool>().map(|v| v.0.clone()) else {
+            return;
+        };
+        let tracker = tracker_clone.clone();
+
+        cx.spawn(|_| async move {
+            match find_album_position_by_location(&pool, &track).await {
+                Ok(album) => {
+                    if let Some(tracker) = tracker.borrow_mut().as_mut() {
+                        // the track may have changed while the album was being looked up
+                        if tracker.location == track {
+                            tracker.album = album;
+                        }
+                    }
+                }
+                Err(e) => error!("Could not find album position of {}: {:?}", track, e),
+            }
+        })
+        .detach();
+    })
+    .detach();
+
+    let position = cx.global::<PlaybackInfo>().position.clone();
+
+    cx.observe(&position, move |position, cx| {
+        let position = *position.read(cx);
+        let duration = *cx.global::<PlaybackInfo>().duration.read(cx);
+
+        let mut borrow = tracker.borrow_mut();
+        let Some(tracker) = borrow.as_mut() else {
+            return;
+        };
+        let Some((album_id, is_last)) = tracker.album else {
+            return;
+        };
+
+        if tracker.finished {
+            return;
+        }
+
+        let Some(pool) = cx.try_global::<Pool>().map(|v| v.0.clone()) else {
+            return;
+        };
+
+        if is_last && duration > 0 && position + FINISH_MARGIN >= duration {
+            tracker.finished = true;
+
+            cx.spawn(|_| async move {
+                if let Err(e) = clear_resume_position(&pool, album_id).await {
+                    error!("Could not clear resume position: {:?}", e);
+                }
+            })
+            .detach();
+        } else if tracker
+            .last_saved
+            .filter(|v| v.abs_diff(position) < SAVE_INTERVAL)
+            .is_none()
+        {
+            tracker.last_saved = Some(position);
+            let location = tracker.location.clone();
+
+            cx.spawn(|_| async move {
+                if let Err(e) =
+                    set_resume_position(&pool, album_id, &location, position as i64).await
+                {
+                    error!("Could not save resume position: {:?}", e);
+                }
+            })
+            .detach();
+        }
+    })
+    .detach();
+}
+
+/// Replaces the queue with the album's tracks and continues playback from the saved track and
+/// position.
+pub fn resume_album(cx: &mut AppContext, paths: Vec<String>, location: &str, position: i64) {
+    let Some(idx) = paths.iter().position(|v| v == location) else {
+        return;
+    };
+
+    replace_queue(paths, cx);
+
+    let playback_interface = cx.global::<GPUIPlaybackInterface>();
+    playback_interface.jump(idx);
+    playback_interface.seek(position as f64);
+}
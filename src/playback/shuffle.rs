@@ -0,0 +1,144 @@
+use ahash::AHashSet;
+use gpui::{AppContext, Global};
+use sqlx::SqlitePool;
+use tracing::error;
+
+use crate::{
+    library::db::find_album_id_by_location,
+    settings::{playback::ShuffleMode, SettingsGlobal},
+    ui::{
+        app::Pool,
+        models::{Models, PlaybackInfo},
+    },
+};
+
+use super::interface::GPUIPlaybackInterface;
+
+/// The queued files whose albums have been looked up. Lookups happen in the background, so a file
+/// can be requested well before its album has been sent to the playback thread.
+#[derive(Default)]
+struct ResolvedAlbums {
+    requested: AHashSet<String>,
+    sent: AHashSet<String>,
+}
+
+impl Global for ResolvedAlbums {}
+
+async fn find_album_keys(pool: &SqlitePool, paths: Vec<String>) -> Vec<(String, i64)> {
+    let mut keys = Vec::new();
+
+    for path in paths {
+        match find_album_id_by_location(pool, &path).await {
+            Ok(Some(album_id)) => keys.push((path, album_id)),
+            Ok(None) => (),
+            Err(e) => error!("Could not find album for {}: {:?}", path, e),
+        }
+    }
+
+    keys
+}
+
+/// Looks up the albums of the specified files in the background, sends them to the playback
+/// thread, and then calls `then`. Commands are handled in order, so anything `then` sends to the
+/// playback thread is handled after it knows about the albums.
+fn send_album_keys(
+    cx: &mut AppContext,
+    paths: Vec<String>,
+    then: impl FnOnce(&mut AppContext) + 'static,
+) {
+    let Some(pool) = cx.try_global::<Pool>().map(|v| v.0.clone()) else {
+        then(cx);
+        return;
+    };
+
+    cx.spawn(|cx| async move {
+        let keys = find_album_keys(&pool, paths.clone()).await;
+
+        cx.update(|cx| {
+            cx.global_mut::<ResolvedAlbums>().sent.extend(paths);
+            cx.global::<GPUIPlaybackInterface>().add_album_keys(keys);
+            then(cx);
+        })
+        .expect("failed to send album keys");
+    })
+    .detach();
+}
+
+/// Keeps the playback thread's shuffle mode in sync with the settings, and tells it which album
+/// each queued file belongs to so that albums can be shuffled as a whole.
+pub fn setup_shuffle(cx: &mut AppContext) {
+    cx.set_global(ResolvedAlbums::default());
+
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+    let mode = settings.read(cx).playback.shuffle_mode;
+    cx.global::<GPUIPlaybackInterface>().set_shuffle_mode(mode);
+
+    cx.observe(&settings, |settings, cx| {
+        let mode = settings.read(cx).playback.shuffle_mode;
+        cx.global::<GPUIPlaybackInterface>().set_shuffle_mode(mode);
+    })
+    .detach();
+
+    let queue = cx.global::<Models>().queue.clone();
+
+    cx.observe(&queue, move |queue, cx| {
+        let paths = queue.read(cx).0.clone();
+        let resolved = cx.global_mut::<ResolvedAlbums>();
+        let unresolved: Vec<String> = paths
+            .into_iter()
+            .filter(|path| resolved.requested.insert(path.clone()))
+            .collect();
+
+        if !unresolved.is_empty() {
+            send_album_keys(cx, unresolved, |_| ());
+        }
+    })
+    .detach();
+}
+
+/// Turns shuffle on or off. See [`toggle_shuffle_then`].
+pub fn toggle_shuffle(cx: &mut AppContext) {
+    toggle_shuffle_then(cx, |_| ());
+}
+
+/// Turns shuffle on or off, and then calls `then`. When shuffle is turned on by album, the albums
+/// of any queued files that haven't been sent to the playback thread yet are sent first, since
+/// otherwise the first shuffle would group them by directory instead.
+pub fn toggle_shuffle_then(cx: &mut AppContext, then: impl FnOnce(&mut AppContext) + 'static) {
+    let shuffling = *cx.global::<PlaybackInfo>().shuffling.read(cx);
+    let mode = cx
+        .global::<SettingsGlobal>()
+        .model
+        .read(cx)
+        .playback
+        .shuffle_mode;
+
+    let unsent: Vec<String> = if !shuffling && mode == ShuffleMode::Albums {
+        let sent = &cx.global::<ResolvedAlbums>().sent;
+
+        cx.global::<Models>()
+            .queue
+            .read(cx)
+            .0
+            .iter()
+            .filter(|path| !sent.contains(*path))
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let toggle = move |cx: &mut AppContext| {
+        cx.global::<GPUIPlaybackInterface>().toggle_shuffle();
+        then(cx);
+    };
+
+    if unsent.is_empty() {
+        toggle(cx);
+    } else {
+        cx.global_mut::<ResolvedAlbums>()
+            .requested
+            .extend(unsent.iter().cloned());
+        send_album_keys(cx, unsent, toggle);
+    }
+}
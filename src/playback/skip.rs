@@ -0,0 +1,73 @@
+use ahash::AHashSet;
+use gpui::{AppContext, Context, Global, Model};
+use tracing::error;
+
+use crate::{
+    library::db::{find_skipped_locations, LibraryAccess},
+    ui::app::Pool,
+};
+
+use super::interface::GPUIPlaybackInterface;
+
+/// The locations of the tracks that are always skipped. These are passed over when the queue
+/// advances, and never added by radio mode.
+#[derive(Clone)]
+pub struct SkippedTracks(pub Model<AHashSet<String>>);
+
+impl Global for SkippedTracks {}
+
+/// Loads the tracks that are always skipped and tells the playback thread about them.
+pub fn setup_skip(cx: &mut AppContext) {
+    let skipped: Model<AHashSet<String>> = cx.new_model(|_| AHashSet::new());
+    cx.set_global(SkippedTracks(skipped.clone()));
+
+    let Some(pool) = cx.try_global::<Pool>().map(|v| v.0.clone()) else {
+        return;
+    };
+
+    cx.spawn(|mut cx| async move {
+        let locations = match find_skipped_locations(&pool).await {
+            Ok(locations) => locations,
+            Err(e) => {
+                error!("Could not load skipped tracks: {:?}", e);
+                return;
+            }
+        };
+
+        skipped
+            .update(&mut cx, |m, cx| {
+                m.extend(locations.iter().cloned());
+                cx.notify();
+            })
+            .expect("failed to update skipped tracks");
+
+        cx.update(|cx| {
+            cx.global::<GPUIPlaybackInterface>()
+                .set_skip_always(locations, true)
+        })
+        .expect("failed to send skipped tracks");
+    })
+    .detach();
+}
+
+/// Sets whether the track is always skipped, saving it to the database.
+pub fn set_skip_always(cx: &mut AppContext, track_id: i64, location: String, skip: bool) {
+    if let Err(e) = cx.set_track_skip_always(track_id, skip) {
+        error!("Could not save skip flag: {:?}", e);
+        return;
+    }
+
+    let skipped = cx.global::<SkippedTracks>().0.clone();
+
+    skipped.update(cx, |m, cx| {
+        if skip {
+            m.insert(location.clone());
+        } else {
+            m.remove(&location);
+        }
+        cx.notify();
+    });
+
+    cx.global::<GPUIPlaybackInterface>()
+        .set_skip_always(vec![location], skip);
+}
@@ -0,0 +1,45 @@
+use gpui::AppContext;
+
+use crate::{settings::SettingsGlobal, ui::models::PlaybackInfo};
+
+use super::interface::GPUIPlaybackInterface;
+
+/// The speeds the speed control cycles through.
+pub const SPEEDS: &[f64] = &[0.5, 0.75, 1.0, 1.25, 1.5, 1.75, 2.0];
+
+/// Returns the speed after the specified one, wrapping around to the slowest speed.
+pub fn next_speed(speed: f64) -> f64 {
+    SPEEDS
+        .iter()
+        .copied()
+        .find(|v| *v > speed + f64::EPSILON)
+        .unwrap_or(SPEEDS[0])
+}
+
+/// Formats a playback speed for display, e.g. "1.25×".
+pub fn format_speed(speed: f64) -> String {
+    let formatted = format!("{:.2}", speed);
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+
+    format!("{}×", formatted)
+}
+
+/// Returns playback to normal speed whenever a new track starts, if `reset_speed_per_track` is
+/// enabled.
+pub fn setup_speed(cx: &mut AppContext) {
+    let current_track = cx.global::<PlaybackInfo>().current_track.clone();
+
+    cx.observe(&current_track, |_, cx| {
+        let reset = cx
+            .global::<SettingsGlobal>()
+            .model
+            .read(cx)
+            .playback
+            .reset_speed_per_track;
+
+        if reset && *cx.global::<PlaybackInfo>().speed.read(cx) != 1.0 {
+            cx.global::<GPUIPlaybackInterface>().set_speed(1.0);
+        }
+    })
+    .detach();
+}
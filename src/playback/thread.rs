@@ -1,10 +1,13 @@
 use std::{
+    path::{Path, PathBuf},
     sync::mpsc::{Receiver, Sender},
     thread::sleep,
+    time::{Duration, Instant},
 };
 
+use ahash::{AHashMap, AHashSet};
 use rand::{seq::SliceRandom, thread_rng};
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
 
 #[cfg(target_os = "linux")]
 use crate::devices::builtin::pulse::PulseProvider;
@@ -14,20 +17,44 @@ use crate::devices::builtin::cpal::CpalProvider;
 
 use crate::{
     devices::{
-        format::{ChannelSpec, FormatInfo},
+        errors::OpenError,
+        format::{ChannelSpec, FormatInfo, OutputInfo},
         resample::Resampler,
+        stretch::{TimeStretcher, MAX_SPEED, MIN_SPEED},
         traits::{Device, DeviceProvider, OutputStream},
     },
     media::{
-        builtin::symphonia::SymphoniaProvider, errors::PlaybackReadError, traits::MediaProvider,
+        builtin::providers, errors::PlaybackReadError, playback::PlaybackFrame,
+        registry::ProviderRegistry,
     },
+    settings::playback::{ClippingMode, ShuffleMode},
 };
 
 use super::{
-    events::{PlaybackCommand, PlaybackEvent},
+    events::{PlaybackCommand, PlaybackError, PlaybackEvent},
+    fade::{Fade, MAX_FADE_DURATION},
+    gain::{amplify, clamp_gain, gain_to_linear, limit_to_peak},
     interface::PlaybackInterface,
+    level::LevelMeter,
+    monitor::Monitor,
+    prebuffer::Prebuffer,
+    trim::TrackTrim,
 };
 
+#[derive(PartialEq)]
+enum AlbumKey {
+    Library(i64),
+    Directory(Option<PathBuf>),
+}
+
+/// Something that happens once the audio has faded out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FadeAction {
+    Pause,
+    Stop,
+    Seek(f64),
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PlaybackState {
     Stopped,
@@ -38,12 +65,24 @@ pub enum PlaybackState {
 pub struct PlaybackThread {
     commands_rx: Receiver<PlaybackCommand>,
     events_tx: Sender<PlaybackEvent>,
-    media_provider: Option<Box<dyn MediaProvider>>,
+    media_providers: ProviderRegistry,
     device_provider: Option<Box<dyn DeviceProvider>>,
     device: Option<Box<dyn Device>>,
     stream: Option<Box<dyn OutputStream>>,
+    /// Other devices that are given a copy of the audio.
+    monitors: Vec<Monitor>,
+    /// Monitor devices that couldn't be opened or stopped accepting audio, which are tried again
+    /// every `MONITOR_RETRY_INTERVAL`.
+    lost_monitors: Vec<String>,
+    monitor_retry_at: Option<Instant>,
     state: PlaybackState,
     resampler: Option<Resampler>,
+    stretcher: Option<TimeStretcher>,
+    speed: f64,
+    fade: Fade,
+    fade_duration: Duration,
+    /// What to do once the current fade out has finished.
+    pending_fade: Option<FadeAction>,
     format: Option<FormatInfo>,
     queue: Vec<String>,
     shuffled_queue: Vec<String>,
@@ -51,6 +90,54 @@ pub struct PlaybackThread {
     queue_next: usize,
     last_timestamp: u64,
     pending_reset: bool,
+    volume: f64,
+    gain: f64,
+    clipping: ClippingMode,
+    /// How much the samples are amplified by, on top of the stream's volume. This is only above 1
+    /// when the gain offset pushes the volume past unity.
+    amplification: f32,
+    /// The current file's ReplayGain peak, which limits how far the gain offset can amplify it.
+    peak: Option<f64>,
+    /// Whether amplified samples are limited rather than clipped.
+    limit: bool,
+    shuffle_mode: ShuffleMode,
+    /// The album each known file belongs to, used when shuffling by album.
+    album_keys: AHashMap<String, i64>,
+    /// Files that are passed over when moving on to the next track.
+    skipped: AHashSet<String>,
+    /// Where playback of trimmed files starts and ends.
+    trims: AHashMap<String, TrackTrim>,
+    /// The trim of the file that's currently open.
+    trim: TrackTrim,
+    repeat: bool,
+    /// Measures the level of the audio for the level meter, if it's enabled.
+    level: Option<LevelMeter>,
+    /// When the current file was opened, until its first samples have been submitted.
+    opened_at: Option<Instant>,
+    /// Reads the start of the upcoming files ahead of time.
+    prebuffer: Prebuffer,
+    /// Where the audio submitted to the stream so far ends in the current file, in seconds. Unlike
+    /// the provider's position this isn't rounded, so that playback can carry on from exactly the
+    /// same place.
+    submitted: f64,
+    /// The previous device's stream during a seamless device switch, which keeps playing what it
+    /// has buffered until the new stream has been given enough audio, along with where the audio
+    /// given to the new stream started.
+    draining: Option<(Box<dyn OutputStream>, f64)>,
+}
+
+/// How much audio the new stream has to be given during a seamless device switch before the old
+/// stream is closed, in seconds. Submitting blocks once the device's buffer is full, so by then the
+/// new device is playing.
+const SWITCH_OVERLAP: f64 = 0.5;
+
+/// How often monitor devices that were lost are tried again, e.g. after being plugged back in.
+const MONITOR_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Whether audio converted for one device format can be sent to a device with the other, so that
+/// the resampler doesn't have to be replaced.
+fn same_output_format(a: &FormatInfo, b: &FormatInfo) -> bool {
+    a.sample_type == b.sample_type && a.sample_rate == b.sample_rate && a.channels == b.channels
 }
 
 impl PlaybackThread {
@@ -65,12 +152,20 @@ impl PlaybackThread {
                 let mut thread = PlaybackThread {
                     commands_rx,
                     events_tx,
-                    media_provider: None,
+                    media_providers: ProviderRegistry::default(),
                     device_provider: None,
                     device: None,
                     stream: None,
+                    monitors: Vec::new(),
+                    lost_monitors: Vec::new(),
+                    monitor_retry_at: None,
                     state: PlaybackState::Stopped,
                     resampler: None,
+                    stretcher: None,
+                    speed: 1.0,
+                    fade: Fade::default(),
+                    fade_duration: Duration::ZERO,
+                    pending_fade: None,
                     format: None,
                     queue: Vec::new(),
                     shuffled_queue: Vec::new(),
@@ -78,6 +173,23 @@ impl PlaybackThread {
                     queue_next: 0,
                     last_timestamp: u64::MAX,
                     pending_reset: false,
+                    volume: 1.0,
+                    gain: 0.0,
+                    clipping: ClippingMode::Prevent,
+                    amplification: 1.0,
+                    peak: None,
+                    limit: false,
+                    shuffle_mode: ShuffleMode::Tracks,
+                    album_keys: AHashMap::new(),
+                    skipped: AHashSet::new(),
+                    trims: AHashMap::new(),
+                    trim: TrackTrim::default(),
+                    repeat: false,
+                    level: None,
+                    opened_at: None,
+                    prebuffer: Prebuffer::default(),
+                    submitted: 0.0,
+                    draining: None,
                 };
 
                 thread.run();
@@ -89,7 +201,7 @@ impl PlaybackThread {
 
     pub fn run(&mut self) {
         // for now just throw in the default Providers and pick the default Device
-        // TODO: Add a way to select the Device and MediaProvider
+        // TODO: Add a way to select the Device
         #[cfg(target_os = "linux")]
         {
             self.device_provider = Some(Box::new(PulseProvider::default()));
@@ -99,7 +211,7 @@ impl PlaybackThread {
             self.device_provider = Some(Box::new(CpalProvider::default()));
         }
 
-        self.media_provider = Some(Box::new(SymphoniaProvider::default()));
+        self.media_providers = ProviderRegistry::new(providers());
         self.device = Some(
             self.device_provider
                 .as_mut()
@@ -111,7 +223,8 @@ impl PlaybackThread {
         // TODO: proper error handling
         // TODO: allow the user to pick a format on supported platforms
         let format = self.device.as_ref().unwrap().get_default_format().unwrap();
-        self.stream = Some(self.device.as_mut().unwrap().open_device(format).unwrap());
+        self.open_stream(format)
+            .expect("could not open default output device");
 
         let format = self.device.as_ref().unwrap().get_default_format().unwrap();
 
@@ -127,6 +240,322 @@ impl PlaybackThread {
         }
     }
 
+    /// Opens the output stream on the current device, and tells the UI what format audio is being
+    /// output in.
+    fn open_stream(&mut self, format: FormatInfo) -> Result<(), OpenError> {
+        let device = self.device.as_mut().ok_or(OpenError::Unknown)?;
+        let mut stream = device.open_device(format)?;
+
+        let current_format = match stream.get_current_format() {
+            Ok(format) => format.clone(),
+            Err(_) => {
+                let _ = stream.close_stream();
+                return Err(OpenError::Unknown);
+            }
+        };
+
+        let output = OutputInfo {
+            format: current_format,
+            device_name: device.get_name().ok(),
+            device_uid: device.get_uid().ok(),
+        };
+
+        // the resampler has to be recreated for the new format
+        self.resampler = None;
+        self.stream = Some(stream);
+
+        self.events_tx
+            .send(PlaybackEvent::OutputChanged(output))
+            .expect("unable to send event");
+
+        self.list_devices();
+        Ok(())
+    }
+
+    /// Opens a stream on the current device again, or on the default device if that fails too.
+    /// Returns whether a stream was opened.
+    fn reopen_stream(&mut self) -> bool {
+        let format = self
+            .device
+            .as_ref()
+            .and_then(|device| device.get_default_format().ok());
+
+        if let Some(format) = format {
+            match self.open_stream(format) {
+                Ok(()) => return true,
+                Err(e) => error!("Could not reopen previous output device: {:?}", e),
+            }
+        }
+
+        let device = match self.device_provider.as_mut().unwrap().get_default_device() {
+            Ok(device) => device,
+            Err(e) => {
+                error!("Could not find default output device: {:?}", e);
+                return false;
+            }
+        };
+
+        let format = match device.get_default_format() {
+            Ok(format) => format,
+            Err(e) => {
+                error!("Could not get format of default output device: {:?}", e);
+                return false;
+            }
+        };
+
+        self.device = Some(device);
+
+        match self.open_stream(format) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("Could not open default output device: {:?}", e);
+                false
+            }
+        }
+    }
+
+    /// Tells the UI which output devices can be switched to.
+    fn list_devices(&mut self) {
+        let Some(provider) = self.device_provider.as_mut() else {
+            return;
+        };
+
+        match provider.get_devices() {
+            Ok(devices) => {
+                let devices = devices
+                    .iter()
+                    .filter_map(|device| Some((device.get_uid().ok()?, device.get_name().ok()?)))
+                    .collect();
+
+                self.events_tx
+                    .send(PlaybackEvent::DevicesListed(devices))
+                    .expect("unable to send event");
+            }
+            Err(e) => error!("Could not list output devices: {:?}", e),
+        }
+    }
+
+    /// Moves output to another device. Without `seamless`, the old stream is closed before the new
+    /// one is opened, which leaves a gap while the new device starts up. With it, the old stream
+    /// keeps playing what it has buffered until the new one has been given enough audio to play.
+    fn set_output_device(&mut self, uid: &str, seamless: bool) {
+        let device = match self
+            .device_provider
+            .as_mut()
+            .unwrap()
+            .get_device_by_uid(uid)
+        {
+            Ok(device) => device,
+            Err(e) => {
+                error!("Could not find output device {}: {:?}", uid, e);
+                return;
+            }
+        };
+
+        let format = match device.get_default_format() {
+            Ok(format) => format,
+            Err(e) => {
+                error!("Could not get format of output device {}: {:?}", uid, e);
+                return;
+            }
+        };
+
+        // a device can't be both the output and a monitor
+        if let Some(idx) = self.monitors.iter().position(|monitor| monitor.uid == uid) {
+            self.monitors.remove(idx).close();
+        }
+        self.lost_monitors.retain(|v| *v != uid);
+
+        // a switch that's still finishing doesn't have to wait any longer
+        if let Some((mut stream, _)) = self.draining.take() {
+            stream.close_stream().expect("unable to close stream");
+        }
+
+        let previous_format = self.format.clone();
+        let resampler = self.resampler.take();
+        let mut old_stream = self.stream.take();
+
+        if !seamless {
+            if let Some(mut stream) = old_stream.take() {
+                stream.close_stream().expect("unable to close stream");
+            }
+        }
+
+        let name = device.get_name().unwrap_or_else(|_| uid.to_string());
+        let previous_device = self.device.replace(device);
+
+        if let Err(e) = self.open_stream(format) {
+            error!("Could not open output device {}: {:?}", uid, e);
+            self.events_tx
+                .send(PlaybackEvent::OutputFailed(name))
+                .expect("unable to send event");
+            self.device = previous_device;
+
+            // after a seamless switch the previous stream is still open, so it just carries on
+            if let Some(stream) = old_stream {
+                self.stream = Some(stream);
+                self.resampler = resampler;
+                return;
+            }
+
+            if !self.reopen_stream() {
+                return;
+            }
+        }
+
+        self.apply_volume();
+
+        if self.state != PlaybackState::Playing {
+            if let Some(stream) = &mut self.stream {
+                stream.pause().expect("unable to pause stream");
+            }
+        }
+
+        let device_format = self
+            .stream
+            .as_ref()
+            .and_then(|stream| stream.get_current_format().ok().cloned());
+
+        match (previous_format, device_format) {
+            (Some(previous), Some(current)) if same_output_format(&previous, &current) => {
+                // the new device takes audio in the same format, so nothing has to be redone
+                self.resampler = resampler;
+                self.format = Some(current);
+            }
+            (Some(_), _) => {
+                // the resampler and stretcher are replaced once the next samples are read, which
+                // drops what the stretcher had buffered, so decoding carries on from exactly where
+                // the submitted audio ends
+                if let Some(provider) = self.media_providers.current_mut() {
+                    if let Err(e) = provider.seek(self.submitted) {
+                        warn!("Could not seek after switching devices: {:?}", e);
+                    }
+
+                    self.stretcher = None;
+                }
+            }
+            _ => (),
+        }
+
+        if let Some(mut stream) = old_stream {
+            if self.state == PlaybackState::Playing {
+                self.draining = Some((stream, self.submitted));
+            } else {
+                stream.close_stream().expect("unable to close stream");
+            }
+        }
+    }
+
+    /// Closes the previous device's stream after a seamless switch once the new stream has been
+    /// given enough audio to be playing, or as soon as nothing is playing any more.
+    fn finish_switch(&mut self) {
+        let done = match &self.draining {
+            Some((_, start)) => {
+                self.state != PlaybackState::Playing || self.submitted - start >= SWITCH_OVERLAP
+            }
+            None => return,
+        };
+
+        if done {
+            if let Some((mut stream, _)) = self.draining.take() {
+                stream.close_stream().expect("unable to close stream");
+            }
+        }
+    }
+
+    fn set_monitors(&mut self, uids: Vec<String>) {
+        let (kept, removed): (Vec<_>, Vec<_>) = std::mem::take(&mut self.monitors)
+            .into_iter()
+            .partition(|monitor| uids.contains(&monitor.uid));
+
+        for monitor in removed {
+            info!("Closing monitor device {}", monitor.uid);
+            monitor.close();
+        }
+
+        self.monitors = kept;
+        self.lost_monitors.clear();
+
+        let current_uid = self
+            .device
+            .as_ref()
+            .and_then(|device| device.get_uid().ok());
+
+        for uid in uids {
+            if self.monitors.iter().any(|monitor| monitor.uid == uid) {
+                continue;
+            }
+
+            if current_uid.as_ref() == Some(&uid) {
+                warn!("{} is already the output device, not monitoring it", uid);
+                continue;
+            }
+
+            let Some(provider) = self.device_provider.as_mut() else {
+                continue;
+            };
+
+            if let Some(mut monitor) = Monitor::open(provider.as_mut(), &uid) {
+                if self.state != PlaybackState::Playing {
+                    monitor.pause();
+                }
+
+                self.monitors.push(monitor);
+            } else {
+                self.lost_monitors.push(uid);
+            }
+        }
+
+        self.monitor_retry_at = Some(Instant::now() + MONITOR_RETRY_INTERVAL);
+        self.apply_volume();
+    }
+
+    /// Closes the monitors that stopped accepting audio, and tries to open the lost ones again
+    /// every `MONITOR_RETRY_INTERVAL`.
+    fn reopen_lost_monitors(&mut self) {
+        if self.monitors.iter().any(Monitor::failed) {
+            let (failed, working): (Vec<_>, Vec<_>) = std::mem::take(&mut self.monitors)
+                .into_iter()
+                .partition(Monitor::failed);
+
+            for monitor in failed {
+                warn!("Monitor device {} stopped working, closing it", monitor.uid);
+                self.lost_monitors.push(monitor.uid.clone());
+                monitor.close();
+            }
+
+            self.monitors = working;
+            self.monitor_retry_at = Some(Instant::now() + MONITOR_RETRY_INTERVAL);
+        }
+
+        if self.lost_monitors.is_empty()
+            || self.monitor_retry_at.is_some_and(|at| Instant::now() < at)
+        {
+            return;
+        }
+
+        let Some(provider) = self.device_provider.as_mut() else {
+            return;
+        };
+
+        for uid in std::mem::take(&mut self.lost_monitors) {
+            if let Some(mut monitor) = Monitor::open(provider.as_mut(), &uid) {
+                info!("Reopened monitor device {}", uid);
+
+                if self.state != PlaybackState::Playing {
+                    monitor.pause();
+                }
+
+                self.monitors.push(monitor);
+            } else {
+                self.lost_monitors.push(uid);
+            }
+        }
+
+        self.monitor_retry_at = Some(Instant::now() + MONITOR_RETRY_INTERVAL);
+        self.apply_volume();
+    }
+
     pub fn main_loop(&mut self) {
         self.command_intake();
 
@@ -136,11 +565,13 @@ impl PlaybackThread {
             sleep(std::time::Duration::from_millis(10));
         }
 
+        self.finish_switch();
+        self.reopen_lost_monitors();
         self.broadcast_events();
     }
 
     pub fn broadcast_events(&mut self) {
-        if let Some(provider) = &mut self.media_provider {
+        if let Some(provider) = self.media_providers.current_mut() {
             if provider.metadata_updated() {
                 // TODO: proper error handling
                 let metadata = provider.read_metadata().expect("failed to get metadata");
@@ -168,25 +599,112 @@ impl PlaybackThread {
                 PlaybackCommand::Previous => self.previous(),
                 PlaybackCommand::ClearQueue => self.clear_queue(),
                 PlaybackCommand::Jump(v) => self.jump(v),
+                PlaybackCommand::DuplicateQueueItem(v) => self.duplicate_queue_item(v),
+                PlaybackCommand::MoveQueueItem(from, to) => self.move_queue_item(from, to),
                 PlaybackCommand::Seek(v) => self.seek(v),
                 PlaybackCommand::SetVolume(v) => self.set_volume(v),
+                PlaybackCommand::SetGain(v) => self.set_gain(v),
+                PlaybackCommand::SetClippingMode(v) => {
+                    self.clipping = v;
+                    self.apply_volume();
+                }
+                PlaybackCommand::SetSpeed(v) => self.set_speed(v),
+                PlaybackCommand::SetFadeDuration(v) => {
+                    self.fade_duration = Duration::from_millis(v.min(MAX_FADE_DURATION))
+                }
                 PlaybackCommand::ReplaceQueue(v) => self.replace_queue(v),
                 PlaybackCommand::Stop => self.stop(),
                 PlaybackCommand::ToggleShuffle => self.toggle_shuffle(),
+                PlaybackCommand::ShuffleRemaining => self.shuffle_remaining(),
+                PlaybackCommand::SetShuffleMode(v) => self.shuffle_mode = v,
+                PlaybackCommand::AddAlbumKeys(v) => self.album_keys.extend(v),
+                PlaybackCommand::SetRepeat(v) => self.repeat = v,
+                PlaybackCommand::SetLevelMeter(v) => {
+                    self.level = v.then(LevelMeter::default);
+                }
+                PlaybackCommand::SetOutputDevice(uid, seamless) => {
+                    self.set_output_device(&uid, seamless)
+                }
+                PlaybackCommand::ListDevices => self.list_devices(),
+                PlaybackCommand::SetMonitors(uids) => self.set_monitors(uids),
+                PlaybackCommand::SetPrebufferCount(v) => self.prebuffer.set_count(v),
+                PlaybackCommand::SetSkipAlways(paths, skip) => {
+                    if skip {
+                        self.skipped.extend(paths);
+                    } else {
+                        for path in paths {
+                            self.skipped.remove(&path);
+                        }
+                    }
+                }
+                PlaybackCommand::SetTrims(trims) => {
+                    for (path, trim) in trims {
+                        if trim.is_empty() {
+                            self.trims.remove(&path);
+                        } else {
+                            self.trims.insert(path, trim);
+                        }
+                    }
+                }
             }
         }
     }
 
     pub fn pause(&mut self) {
-        if self.state == PlaybackState::Paused {
+        if self.state == PlaybackState::Playing {
+            self.fade_out_then(FadeAction::Pause);
+        }
+    }
+
+    /// Fades the audio out and then performs the action, or performs it immediately if fading is
+    /// disabled.
+    fn fade_out_then(&mut self, action: FadeAction) {
+        if self.fade_duration.is_zero() {
+            self.perform(action);
             return;
         }
 
+        match (self.pending_fade, action) {
+            // stopping takes priority over everything else
+            (Some(FadeAction::Stop), _) => (),
+            // seeking while paused is fine, so there's no need to wait for the pause
+            (Some(FadeAction::Pause), FadeAction::Seek(timestamp)) => self.seek_now(timestamp),
+            _ => self.pending_fade = Some(action),
+        }
+
+        self.fade.fade_out(self.fade_duration);
+    }
+
+    fn perform(&mut self, action: FadeAction) {
+        match action {
+            FadeAction::Pause => self.pause_now(),
+            FadeAction::Stop => self.stop_now(),
+            FadeAction::Seek(timestamp) => {
+                self.seek_now(timestamp);
+                self.fade.fade_in(self.fade_duration);
+            }
+        }
+    }
+
+    /// Performs the pending action once the audio has faded out.
+    fn finish_fade(&mut self) {
+        if self.fade.is_silent() {
+            if let Some(action) = self.pending_fade.take() {
+                self.perform(action);
+            }
+        }
+    }
+
+    fn pause_now(&mut self) {
         if self.state == PlaybackState::Playing {
             if let Some(stream) = &mut self.stream {
                 stream.pause().expect("unable to pause stream");
             }
 
+            for monitor in &mut self.monitors {
+                monitor.pause();
+            }
+
             self.state = PlaybackState::Paused;
 
             self.events_tx
@@ -197,10 +715,26 @@ impl PlaybackThread {
 
     pub fn play(&mut self) {
         if self.state == PlaybackState::Playing {
+            // playing again before a pause has faded out cancels it
+            if matches!(self.pending_fade, Some(FadeAction::Pause)) {
+                self.pending_fade = None;
+                self.fade.fade_in(self.fade_duration);
+            }
+
             return;
         }
 
         if self.state == PlaybackState::Paused {
+            self.fade.fade_in_from_silence(self.fade_duration);
+
+            for monitor in &mut self.monitors {
+                if self.pending_reset {
+                    monitor.reset();
+                }
+
+                monitor.play();
+            }
+
             if let Some(stream) = &mut self.stream {
                 if self.pending_reset {
                     stream.reset().expect("unable to reset stream");
@@ -227,6 +761,7 @@ impl PlaybackThread {
 
     fn open(&mut self, path: &String) {
         info!("Opening: {}", path);
+        self.opened_at = Some(Instant::now());
 
         if self.state == PlaybackState::Paused {
             self.stream
@@ -242,13 +777,55 @@ impl PlaybackThread {
             .play()
             .expect("unable to play stream");
 
-        // TODO: handle multiple media providers
-        if let Some(provider) = &mut self.media_provider {
-            // TODO: proper error handling
+        for monitor in &mut self.monitors {
+            if self.state == PlaybackState::Paused {
+                monitor.reset();
+            }
+
+            monitor.play();
+        }
+
+        // files that were opened ahead of time are ready to play straight away
+        let prepared = self.media_providers.open_prepared(path).is_some();
+        let provider = if prepared {
+            debug!("{} was opened ahead of time", path);
+            self.media_providers.current_mut()
+        } else {
+            self.media_providers.select(path)
+        };
+
+        if let Some(provider) = provider {
             self.resampler = None;
-            let src = std::fs::File::open(path).expect("failed to open media");
-            provider.open(src, None).expect("unable to open file");
-            provider.start_playback().expect("unable to start playback");
+            self.stretcher = None;
+            self.pending_fade = None;
+            self.fade.fade_in_from_silence(self.fade_duration);
+
+            let result = if prepared {
+                Ok(())
+            } else {
+                std::fs::File::open(path)
+                    .map_err(|_| PlaybackError::FileUnreadable)
+                    .and_then(|src| provider.open(src, None).map_err(PlaybackError::Open))
+                    .and_then(|_| provider.start_playback().map_err(PlaybackError::Start))
+            };
+
+            if let Err(e) = result {
+                self.fail(path.clone(), e);
+                return;
+            }
+
+            self.trim = self.trims.get(path).copied().unwrap_or_default();
+            self.submitted = self.trim.start.unwrap_or(0) as f64;
+            self.peak = provider
+                .read_metadata()
+                .ok()
+                .and_then(|v| v.replaygain_track_peak.or(v.replaygain_album_peak));
+
+            if let Some(start) = self.trim.start {
+                if let Err(e) = provider.seek(start as f64) {
+                    warn!("Could not seek to the start of the trimmed track: {:?}", e);
+                }
+            }
 
             self.state = PlaybackState::Playing;
             self.events_tx
@@ -266,6 +843,7 @@ impl PlaybackThread {
             }
 
             self.update_ts();
+            self.apply_volume();
 
             self.events_tx
                 .send(PlaybackEvent::StateChanged(PlaybackState::Playing))
@@ -273,19 +851,52 @@ impl PlaybackThread {
         }
     }
 
+    /// Moves on to the next track once the current one has ended. The next track follows straight
+    /// on from the current one, so it isn't faded in, and any fade in progress carries over.
+    fn advance(&mut self) {
+        let fade = self.fade;
+        let pending_fade = self.pending_fade.take();
+
+        self.next(false);
+
+        if self.state == PlaybackState::Playing {
+            self.fade = fade;
+            // a seek within the track that just ended no longer makes sense
+            self.pending_fade = pending_fade.filter(|v| !matches!(v, FadeAction::Seek(_)));
+        }
+    }
+
+    /// Returns the index of the first file in the queue at or after `from` that isn't always
+    /// skipped.
+    fn next_playable(&self, from: usize) -> Option<usize> {
+        let queue = if self.shuffle {
+            &self.shuffled_queue
+        } else {
+            &self.queue
+        };
+
+        (from..queue.len()).find(|idx| !self.skipped.contains(&queue[*idx]))
+    }
+
     fn next(&mut self, user_initiated: bool) {
-        if self.queue_next < self.queue.len() {
+        let idx = self.next_playable(self.queue_next).or_else(|| {
+            // with repeat on, the queue starts over once it runs out
+            self.repeat.then(|| self.next_playable(0)).flatten()
+        });
+
+        if let Some(idx) = idx {
             info!("Opening next file in queue");
             let next_path = if self.shuffle {
-                self.shuffled_queue[self.queue_next].clone()
+                self.shuffled_queue[idx].clone()
             } else {
-                self.queue[self.queue_next].clone()
+                self.queue[idx].clone()
             };
             self.open(&next_path);
-            self.queue_next += 1;
+            self.queue_next = idx + 1;
         } else if !user_initiated {
             info!("Playback queue is empty, stopping playback");
-            self.stop();
+            // the track has already ended, so there's nothing to fade out
+            self.stop_now();
         }
     }
 
@@ -340,13 +951,14 @@ impl PlaybackThread {
     }
 
     fn queue_list(&mut self, mut paths: Vec<String>) {
-        info!("Adding files to queue: {:?}", paths);
+        info!("Adding {} files to queue", paths.len());
+        debug!("Adding files to queue: {:?}", paths);
         let pre_len = self.queue.len();
         let first = paths.first().cloned();
 
         if self.shuffle {
             let mut shuffled_paths = paths.clone();
-            shuffled_paths.shuffle(&mut thread_rng());
+            self.shuffle_paths(&mut shuffled_paths);
 
             self.shuffled_queue.append(&mut shuffled_paths);
         }
@@ -371,8 +983,16 @@ impl PlaybackThread {
         }
     }
 
+    fn update_level(&mut self) {
+        if let Some(level) = self.level.as_mut().and_then(LevelMeter::poll) {
+            self.events_tx
+                .send(PlaybackEvent::LevelChanged(level))
+                .expect("unable to send event");
+        }
+    }
+
     fn update_ts(&mut self) {
-        if let Some(provider) = &self.media_provider {
+        if let Some(provider) = self.media_providers.current() {
             if let Ok(timestamp) = provider.position_secs() {
                 if timestamp == self.last_timestamp {
                     return;
@@ -388,9 +1008,26 @@ impl PlaybackThread {
     }
 
     fn seek(&mut self, timestamp: f64) {
-        if let Some(provider) = &mut self.media_provider {
+        // seeking into the trimmed start of the track plays from where it starts instead
+        let timestamp = timestamp.max(self.trim.start.unwrap_or(0) as f64);
+
+        if self.state == PlaybackState::Playing {
+            self.fade_out_then(FadeAction::Seek(timestamp));
+        } else {
+            self.seek_now(timestamp);
+        }
+    }
+
+    fn seek_now(&mut self, timestamp: f64) {
+        if let Some(provider) = self.media_providers.current_mut() {
             provider.seek(timestamp).expect("unable to seek");
             self.pending_reset = true;
+            self.submitted = timestamp;
+
+            if let Some(stretcher) = &mut self.stretcher {
+                stretcher.reset();
+            }
+
             self.update_ts();
         }
     }
@@ -406,19 +1043,110 @@ impl PlaybackThread {
         }
     }
 
+    /// Inserts a copy of the file at `index` right after it. While shuffling, the copy is also
+    /// added after the file in the original order, so that it's kept when shuffle is turned off.
+    fn duplicate_queue_item(&mut self, index: usize) {
+        let queue = if self.shuffle {
+            &mut self.shuffled_queue
+        } else {
+            &mut self.queue
+        };
+
+        let Some(path) = queue.get(index).cloned() else {
+            return;
+        };
+
+        queue.insert(index + 1, path.clone());
+
+        if self.shuffle {
+            if let Some(original) = self.queue.iter().position(|v| *v == path) {
+                self.queue.insert(original + 1, path);
+            }
+        }
+
+        // the current file only moves if it comes after the copy
+        if self.queue_next > index + 1 {
+            self.queue_next += 1;
+        }
+
+        if self.shuffle {
+            self.events_tx
+                .send(PlaybackEvent::QueueUpdated(self.shuffled_queue.clone()))
+                .expect("unable to send event");
+        } else {
+            self.events_tx
+                .send(PlaybackEvent::QueueUpdated(self.queue.clone()))
+                .expect("unable to send event");
+        }
+    }
+
+    /// Moves the file at `from` to `to`, shifting the files in between. While shuffling, only the
+    /// shuffled order changes.
+    fn move_queue_item(&mut self, from: usize, to: usize) {
+        let queue = if self.shuffle {
+            &mut self.shuffled_queue
+        } else {
+            &mut self.queue
+        };
+
+        if from >= queue.len() {
+            return;
+        }
+
+        let to = to.min(queue.len() - 1);
+
+        if from == to {
+            return;
+        }
+
+        let path = queue.remove(from);
+        queue.insert(to, path);
+
+        // queue_next is one past the current file, which has to be followed to its new position
+        if let Some(current) = self.queue_next.checked_sub(1) {
+            let current = if current == from {
+                to
+            } else {
+                let current = if from < current { current - 1 } else { current };
+
+                if to <= current {
+                    current + 1
+                } else {
+                    current
+                }
+            };
+
+            self.queue_next = current + 1;
+        }
+
+        if self.shuffle {
+            self.events_tx
+                .send(PlaybackEvent::QueueUpdated(self.shuffled_queue.clone()))
+                .expect("unable to send event");
+        } else {
+            self.events_tx
+                .send(PlaybackEvent::QueueUpdated(self.queue.clone()))
+                .expect("unable to send event");
+        }
+    }
+
     fn replace_queue(&mut self, paths: Vec<String>) {
-        info!("Replacing queue with: {:?}", paths);
+        info!("Replacing queue with {} files", paths.len());
+        debug!("Replacing queue with: {:?}", paths);
 
         if self.shuffle {
             let mut shuffled_paths = paths.clone();
-            shuffled_paths.shuffle(&mut thread_rng());
+            self.shuffle_paths(&mut shuffled_paths);
 
             self.shuffled_queue = shuffled_paths;
         }
 
         self.queue = paths;
         self.queue_next = 0;
-        self.jump(0);
+
+        // tracks that are always skipped are skipped at the start of the queue too, unless every
+        // track is, in which case they were presumably picked to be played anyways
+        self.jump(self.next_playable(0).unwrap_or(0));
 
         if self.shuffle {
             self.events_tx
@@ -440,7 +1168,101 @@ impl PlaybackThread {
     }
 
     fn stop(&mut self) {
-        if let Some(provider) = &mut self.media_provider {
+        if self.state == PlaybackState::Playing {
+            self.fade_out_then(FadeAction::Stop);
+        } else {
+            self.stop_now();
+        }
+    }
+
+    /// The file that's currently open, if any.
+    fn current_path(&self) -> Option<String> {
+        let queue = if self.shuffle {
+            &self.shuffled_queue
+        } else {
+            &self.queue
+        };
+
+        self.queue_next
+            .checked_sub(1)
+            .and_then(|idx| queue.get(idx))
+            .cloned()
+    }
+
+    /// Reads the start of the next few files in the queue ahead of time, following the shuffled
+    /// order if shuffle is on, and starting over from the beginning of the queue with repeat on.
+    fn prebuffer_upcoming(&mut self) {
+        let queue = if self.shuffle {
+            &self.shuffled_queue
+        } else {
+            &self.queue
+        };
+
+        let mut upcoming = Vec::new();
+        let mut from = self.queue_next;
+
+        while upcoming.len() < self.prebuffer.count() {
+            let idx = match self.next_playable(from) {
+                Some(idx) => idx,
+                None if self.repeat && from > 0 => match self.next_playable(0) {
+                    Some(idx) => idx,
+                    None => break,
+                },
+                None => break,
+            };
+
+            // short queues wrap around onto files that are already in the window
+            if upcoming.contains(&queue[idx]) {
+                break;
+            }
+
+            upcoming.push(queue[idx].clone());
+            from = idx + 1;
+        }
+
+        self.prebuffer.update(upcoming);
+        self.media_providers
+            .retain_prepared(self.prebuffer.window());
+
+        // one file is opened per frame at most, so that opening several doesn't hold up playback
+        if let Some(path) = self.prebuffer.take_unprepared() {
+            if !self.media_providers.prepare(&path, providers()) {
+                debug!("could not open {} ahead of time", path);
+            }
+        }
+    }
+
+    /// Stops playback after the file couldn't be played, and tells the main thread why.
+    fn fail(&mut self, path: String, error: PlaybackError) {
+        warn!("Could not play {}: {:?}", path, error);
+
+        self.pending_fade = None;
+
+        if let Some(provider) = self.media_providers.current_mut() {
+            let _ = provider.close();
+        }
+
+        if let Some(stream) = self.stream.as_mut() {
+            let _ = stream.pause();
+        }
+
+        for monitor in &mut self.monitors {
+            monitor.pause();
+        }
+
+        self.state = PlaybackState::Stopped;
+        self.events_tx
+            .send(PlaybackEvent::StateChanged(PlaybackState::Stopped))
+            .expect("unable to send event");
+        self.events_tx
+            .send(PlaybackEvent::Error(path, error))
+            .expect("unable to send event");
+    }
+
+    fn stop_now(&mut self) {
+        self.pending_fade = None;
+
+        if let Some(provider) = self.media_providers.current_mut() {
             provider.stop_playback().expect("unable to stop playback");
             provider.close().expect("unable to close media");
         }
@@ -470,8 +1292,10 @@ impl PlaybackThread {
                 .expect("unable to send event");
         } else {
             self.shuffled_queue = self.queue.clone();
-            let length = self.shuffled_queue.len();
-            self.shuffled_queue[self.queue_next..length].shuffle(&mut thread_rng());
+            let mut upcoming = self.shuffled_queue.split_off(self.queue_next);
+
+            self.shuffle_paths(&mut upcoming);
+            self.shuffled_queue.append(&mut upcoming);
             self.shuffle = true;
 
             self.events_tx
@@ -483,9 +1307,65 @@ impl PlaybackThread {
         }
     }
 
+    /// Shuffles the tracks after the current one in place. Unlike turning on shuffle, the new order
+    /// is kept, and there is no original order to go back to.
+    fn shuffle_remaining(&mut self) {
+        let queue = if self.shuffle {
+            &mut self.shuffled_queue
+        } else {
+            &mut self.queue
+        };
+
+        let mut paths = std::mem::take(queue);
+        let mut upcoming = paths.split_off(self.queue_next.min(paths.len()));
+
+        self.shuffle_paths(&mut upcoming);
+        paths.append(&mut upcoming);
+
+        self.events_tx
+            .send(PlaybackEvent::QueueUpdated(paths.clone()))
+            .expect("unable to send event");
+
+        if self.shuffle {
+            self.shuffled_queue = paths;
+        } else {
+            self.queue = paths;
+        }
+    }
+
+    /// Shuffles the paths according to the current shuffle mode. When shuffling by album, files
+    /// the library doesn't know about are grouped by the directory they're in.
+    fn shuffle_paths(&self, paths: &mut Vec<String>) {
+        match self.shuffle_mode {
+            ShuffleMode::Tracks => paths.shuffle(&mut thread_rng()),
+            ShuffleMode::Albums => {
+                let mut groups: Vec<(AlbumKey, Vec<String>)> = Vec::new();
+
+                for path in paths.drain(..) {
+                    let key = match self.album_keys.get(&path) {
+                        Some(id) => AlbumKey::Library(*id),
+                        None => {
+                            AlbumKey::Directory(Path::new(&path).parent().map(|v| v.to_path_buf()))
+                        }
+                    };
+
+                    match groups.iter_mut().find(|(k, _)| *k == key) {
+                        Some((_, group)) => group.push(path),
+                        None => groups.push((key, vec![path])),
+                    }
+                }
+
+                groups.shuffle(&mut thread_rng());
+                paths.extend(groups.into_iter().flat_map(|(_, group)| group));
+            }
+        }
+    }
+
     fn set_volume(&mut self, volume: f64) {
-        if let Some(stream) = self.stream.as_mut() {
-            stream.set_volume(volume).expect("failed to set volume");
+        self.volume = volume;
+
+        if self.stream.is_some() {
+            self.apply_volume();
 
             self.events_tx
                 .send(PlaybackEvent::VolumeChanged(volume))
@@ -493,87 +1373,148 @@ impl PlaybackThread {
         }
     }
 
-    fn play_audio(&mut self) {
-        if let Some(stream) = &mut self.stream {
-            if let Some(provider) = &mut self.media_provider {
-                if self.resampler.is_none() {
-                    // TODO: proper error handling
-                    let first_samples = match provider.read_samples() {
-                        Ok(samples) => samples,
-                        Err(e) => match e {
-                            PlaybackReadError::NothingOpen => {
-                                panic!("thread state is invalid: no file open")
-                            }
-                            PlaybackReadError::NeverStarted => {
-                                panic!("thread state is invalid: playback never started")
-                            }
-                            PlaybackReadError::Eof => {
-                                info!("EOF, moving to next song");
-                                self.next(false);
-                                return;
-                            }
-                            PlaybackReadError::Unknown => return,
-                            PlaybackReadError::DecodeFatal => panic!("fatal decoding error"),
-                        },
-                    };
-                    let duration = provider.frame_duration().expect("can't get duration");
-                    let device_format = stream.get_current_format().unwrap();
-
-                    self.resampler = Some(Resampler::new(
-                        first_samples.rate,
-                        device_format.sample_rate,
-                        duration,
-                        // TODO: support getting channels from the bitmask
-                        match device_format.channels {
-                            ChannelSpec::Count(v) => v,
-                            _ => 2,
-                        },
-                    ));
-                    self.format = Some(device_format.clone());
-
-                    let converted = self
-                        .resampler
-                        .as_mut()
-                        .unwrap()
-                        .convert_formats(first_samples, self.format.as_ref().unwrap());
-
-                    stream
-                        .submit_frame(converted)
-                        .expect("failed to submit frames to stream");
-
-                    self.update_ts();
-                } else {
-                    let samples = match provider.read_samples() {
-                        Ok(samples) => samples,
-                        Err(e) => match e {
-                            PlaybackReadError::NothingOpen => {
-                                panic!("thread state is invalid: no file open")
-                            }
-                            PlaybackReadError::NeverStarted => {
-                                panic!("thread state is invalid: playback never started")
-                            }
-                            PlaybackReadError::Eof => {
-                                info!("EOF, moving to next song");
-                                self.next(false);
-                                return;
-                            }
-                            PlaybackReadError::Unknown => return,
-                            PlaybackReadError::DecodeFatal => panic!("fatal decoding error"),
-                        },
-                    };
-                    let converted = self
-                        .resampler
-                        .as_mut()
-                        .unwrap()
-                        .convert_formats(samples, self.format.as_ref().unwrap());
+    fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+
+        self.events_tx
+            .send(PlaybackEvent::SpeedChanged(self.speed))
+            .expect("unable to send event");
+    }
+
+    fn set_gain(&mut self, gain: f64) {
+        self.gain = clamp_gain(gain);
+        self.apply_volume();
+    }
+
+    /// Applies the volume and gain offset to the stream. The stream's volume can't go past unity,
+    /// so the rest is made up by amplifying the samples. Unless clipping is allowed, the offset is
+    /// reduced to fit the file's ReplayGain peak, or the samples are limited if it isn't known.
+    fn apply_volume(&mut self) {
+        let mut volume = self.volume * gain_to_linear(self.gain);
+
+        if let (ClippingMode::Prevent, Some(peak)) = (self.clipping, self.peak) {
+            volume = limit_to_peak(volume, peak);
+        }
+
+        self.amplification = volume.max(1.0) as f32;
+        self.limit = self.clipping == ClippingMode::Prevent && self.peak.is_none();
 
-                    stream
-                        .submit_frame(converted)
-                        .expect("failed to submit frames to stream");
+        if let Some(stream) = self.stream.as_mut() {
+            stream
+                .set_volume(volume.min(1.0))
+                .expect("failed to set volume");
+        }
+
+        for monitor in &mut self.monitors {
+            monitor.set_volume(volume.min(1.0));
+        }
+    }
 
-                    self.update_ts();
+    fn play_audio(&mut self) {
+        let Some(stream) = &self.stream else {
+            return;
+        };
+        let Some(provider) = self.media_providers.current_mut() else {
+            return;
+        };
+
+        // TODO: proper error handling
+        let samples = match provider.read_samples() {
+            Ok(samples) => samples,
+            Err(e) => match e {
+                PlaybackReadError::NothingOpen => {
+                    panic!("thread state is invalid: no file open")
+                }
+                PlaybackReadError::NeverStarted => {
+                    panic!("thread state is invalid: playback never started")
                 }
+                PlaybackReadError::Eof => {
+                    info!("EOF, moving to next song");
+                    self.advance();
+                    return;
+                }
+                PlaybackReadError::Unknown => return,
+                PlaybackReadError::DecodeFatal => {
+                    if let Some(path) = self.current_path() {
+                        self.fail(path, PlaybackError::Read(e));
+                    }
+                    return;
+                }
+            },
+        };
+        let duration = provider.frame_duration().expect("can't get duration");
+        let first = self.resampler.is_none();
+
+        if first {
+            let device_format = stream.get_current_format().unwrap().clone();
+
+            self.resampler = Some(Resampler::new(
+                samples.rate,
+                device_format.sample_rate,
+                duration,
+                // TODO: support getting channels from the bitmask
+                match device_format.channels {
+                    ChannelSpec::Count(v) => v,
+                    _ => 2,
+                },
+            ));
+            self.format = Some(device_format);
+            self.stretcher = Some(TimeStretcher::new(duration as usize));
+        }
+
+        self.process_and_submit(samples, duration);
+
+        self.update_ts();
+        self.update_level();
+        self.finish_fade();
+
+        if first {
+            if let Some(opened_at) = self.opened_at.take() {
+                debug!(
+                    "first samples submitted {:?} after opening",
+                    opened_at.elapsed()
+                );
+            }
+
+            self.prebuffer_upcoming();
+        } else if self.trim.end.is_some_and(|end| self.last_timestamp >= end) {
+            info!("Reached the end of the trimmed track, moving to next song");
+            self.advance();
+        }
+    }
+
+    /// Runs decoded samples through the stretcher, fade, gain, level meter, monitors, and
+    /// resampler, and submits them to the output stream.
+    fn process_and_submit(&mut self, samples: PlaybackFrame, duration: u64) {
+        let frames = match &mut self.stretcher {
+            Some(stretcher) => stretcher.process(samples, self.speed),
+            None => vec![samples],
+        };
+
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+
+        for frame in frames {
+            self.submitted += frame.samples.frames() as f64 * self.speed / frame.rate as f64;
+            let frame = amplify(self.fade.apply(frame), self.amplification, self.limit);
+            if let Some(level) = &mut self.level {
+                level.measure(&frame);
             }
+
+            for monitor in &mut self.monitors {
+                monitor.submit(&frame, duration);
+            }
+
+            let converted = self
+                .resampler
+                .as_mut()
+                .unwrap()
+                .convert_formats(frame, self.format.as_ref().unwrap());
+
+            stream
+                .submit_frame(converted)
+                .expect("failed to submit frames to stream");
         }
     }
 }
@@ -0,0 +1,99 @@
+use ahash::AHashMap;
+use gpui::{AppContext, Context, Global, Model};
+use tracing::error;
+
+use crate::{
+    library::db::{find_track_trims, LibraryAccess},
+    ui::app::Pool,
+};
+
+use super::interface::GPUIPlaybackInterface;
+
+/// Where playback of a track starts and ends, in seconds, for skipping silence or applause at
+/// either end of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TrackTrim {
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+}
+
+impl TrackTrim {
+    pub fn is_empty(&self) -> bool {
+        self.start.is_none() && self.end.is_none()
+    }
+}
+
+/// The trims of every trimmed track, by location.
+#[derive(Clone)]
+pub struct TrackTrims(pub Model<AHashMap<String, TrackTrim>>);
+
+impl Global for TrackTrims {}
+
+/// Loads the trimmed tracks and tells the playback thread about them.
+pub fn setup_trim(cx: &mut AppContext) {
+    let trims: Model<AHashMap<String, TrackTrim>> = cx.new_model(|_| AHashMap::new());
+    cx.set_global(TrackTrims(trims.clone()));
+
+    let Some(pool) = cx.try_global::<Pool>().map(|v| v.0.clone()) else {
+        return;
+    };
+
+    cx.spawn(|mut cx| async move {
+        let rows = match find_track_trims(&pool).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                error!("Could not load track trims: {:?}", e);
+                return;
+            }
+        };
+
+        let loaded: Vec<(String, TrackTrim)> = rows
+            .into_iter()
+            .map(|(location, start, end)| {
+                let trim = TrackTrim {
+                    start: start.map(|v| v.max(0) as u64),
+                    end: end.map(|v| v.max(0) as u64),
+                };
+
+                (location, trim)
+            })
+            .collect();
+
+        trims
+            .update(&mut cx, |m, cx| {
+                m.extend(loaded.iter().cloned());
+                cx.notify();
+            })
+            .expect("failed to update track trims");
+
+        cx.update(|cx| cx.global::<GPUIPlaybackInterface>().set_trims(loaded))
+            .expect("failed to send track trims");
+    })
+    .detach();
+}
+
+/// Sets where the track starts and ends, saving it to the database. The trim takes effect the
+/// next time the track is opened.
+pub fn set_track_trim(cx: &mut AppContext, track_id: i64, location: String, trim: TrackTrim) {
+    let start = trim.start.map(|v| v as i64);
+    let end = trim.end.map(|v| v as i64);
+
+    if let Err(e) = cx.set_track_trim(track_id, start, end) {
+        error!("Could not save track trim: {:?}", e);
+        return;
+    }
+
+    let trims = cx.global::<TrackTrims>().0.clone();
+
+    trims.update(cx, |m, cx| {
+        if trim.is_empty() {
+            m.remove(&location);
+        } else {
+            m.insert(location.clone(), trim);
+        }
+        cx.notify();
+    });
+
+    cx.global::<GPUIPlaybackInterface>()
+        .set_trims(vec![(location, trim)]);
+}
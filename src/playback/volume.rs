@@ -0,0 +1,90 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use gpui::AppContext;
+
+use crate::{
+    settings::{save_settings, SettingsGlobal},
+    ui::models::PlaybackInfo,
+};
+
+use super::interface::GPUIPlaybackInterface;
+
+/// How long the volume has to stay the same before it's saved, so that dragging the volume slider
+/// doesn't write the settings file over and over.
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Remembers the volume of each output device, restoring it whenever playback switches to that
+/// device.
+pub fn setup_volume(cx: &mut AppContext) {
+    let output = cx.global::<PlaybackInfo>().output.clone();
+    let volume = cx.global::<PlaybackInfo>().volume.clone();
+    // incremented whenever the volume changes, so that only the last change is saved
+    let generation = Rc::new(RefCell::new(0usize));
+    let current_device: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+    let current_device_clone = current_device.clone();
+
+    cx.observe(&output, move |output, cx| {
+        let uid = output.read(cx).as_ref().and_then(|v| v.device_uid.clone());
+
+        if *current_device_clone.borrow() == uid {
+            return;
+        }
+
+        *current_device_clone.borrow_mut() = uid.clone();
+
+        // unknown devices keep the current volume, which is saved for them once it's changed
+        let saved = uid.and_then(|uid| {
+            cx.global::<SettingsGlobal>()
+                .model
+                .read(cx)
+                .playback
+                .device_volumes
+                .get(&uid)
+                .copied()
+        });
+
+        if let Some(saved) = saved {
+            cx.global::<GPUIPlaybackInterface>().set_volume(saved);
+        }
+    })
+    .detach();
+
+    cx.observe(&volume, move |volume, cx| {
+        let Some(uid) = current_device.borrow().clone() else {
+            return;
+        };
+
+        let volume = *volume.read(cx);
+        *generation.borrow_mut() += 1;
+        let expected = *generation.borrow();
+        let generation = generation.clone();
+
+        cx.spawn(|mut cx| async move {
+            cx.background_executor().timer(SAVE_DELAY).await;
+
+            if *generation.borrow() != expected {
+                return;
+            }
+
+            cx.update(|cx| {
+                let saved = cx
+                    .global::<SettingsGlobal>()
+                    .model
+                    .read(cx)
+                    .playback
+                    .device_volumes
+                    .get(&uid)
+                    .copied();
+
+                if saved != Some(volume) {
+                    save_settings(cx, |settings| {
+                        settings.playback.device_volumes.insert(uid, volume);
+                    });
+                }
+            })
+            .ok();
+        })
+        .detach();
+    })
+    .detach();
+}
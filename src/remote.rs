@@ -0,0 +1,435 @@
+//! A minimal HTTP API for controlling playback from other devices, such as a phone or a stream
+//! deck. The server is disabled by default and only listens on localhost unless configured
+//! otherwise.
+//!
+//! | Method | Path           | Description                                                      |
+//! |--------|----------------|------------------------------------------------------------------|
+//! | GET    | `/now-playing` | The current track and playback state, as a [`NowPlaying`].       |
+//! | POST   | `/play`        | Resumes playback.                                                |
+//! | POST   | `/pause`       | Pauses playback.                                                 |
+//! | POST   | `/play-pause`  | Toggles between playing and paused.                              |
+//! | POST   | `/next`        | Skips to the next track.                                         |
+//! | POST   | `/previous`    | Goes back to the previous track.                                 |
+//! | POST   | `/enqueue`     | Adds the files in the body (a JSON array of paths) to the queue. |
+//!
+//! Commands respond with `{"ok": true}`, and errors with `{"error": "..."}`.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    sync::mpsc::{channel, Receiver, Sender},
+    time::Duration,
+};
+
+use gpui::AppContext;
+use serde::Serialize;
+use serde_json::json;
+use tracing::{debug, info, warn};
+
+use crate::{
+    playback::{interface::GPUIPlaybackInterface, thread::PlaybackState},
+    settings::SettingsGlobal,
+    ui::models::{PlaybackInfo, PlaybackSnapshot},
+};
+
+/// How long the server waits for a request to be sent, or for the main thread to handle it.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// The largest request body that is accepted, in bytes.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+/// The most bytes the request line and headers can take up together.
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+enum RemoteCommand {
+    NowPlaying,
+    Play,
+    Pause,
+    PlayPause,
+    Next,
+    Previous,
+    Enqueue(Vec<String>),
+}
+
+/// A status code and a JSON body.
+type Response = (u16, String);
+
+type Request = (RemoteCommand, Sender<Response>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NowPlaying {
+    /// "playing", "paused", or "stopped".
+    pub state: &'static str,
+    /// None if nothing is playing.
+    pub track: Option<NowPlayingTrack>,
+    /// The position within the track, in seconds.
+    pub position: u64,
+    /// The duration of the track, in seconds.
+    pub duration: u64,
+    pub volume: f64,
+    pub shuffling: bool,
+    pub repeat: bool,
+    /// The number of tracks in the queue, including the ones that have been played.
+    pub queue_length: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NowPlayingTrack {
+    pub path: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+/// Starts the HTTP server, if it's enabled in the settings. Requests are received on a separate
+/// thread and handled on the main thread.
+pub fn start_remote_server(cx: &mut AppContext) {
+    let settings = cx.global::<SettingsGlobal>().model.read(cx).remote.clone();
+
+    if !settings.enabled {
+        return;
+    }
+
+    let Some(requests_rx) = listen(&settings.address, settings.port) else {
+        return;
+    };
+
+    cx.spawn(|mut cx| async move {
+        loop {
+            while let Ok((command, response_tx)) = requests_rx.try_recv() {
+                let Ok(response) = cx.update(|cx| handle_command(command, cx)) else {
+                    return;
+                };
+
+                // the connection may have timed out
+                let _ = response_tx.send(response);
+            }
+
+            cx.background_executor()
+                .timer(Duration::from_millis(10))
+                .await;
+        }
+    })
+    .detach();
+}
+
+fn listen(address: &str, port: u16) -> Option<Receiver<Request>> {
+    let listener = match TcpListener::bind((address, port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!(
+                "Couldn't start remote control server on {}:{}: {:?}",
+                address, port, e
+            );
+            return None;
+        }
+    };
+
+    let (requests_tx, requests_rx) = channel::<Request>();
+
+    std::thread::Builder::new()
+        .name("remote".to_string())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let response = match read_request(&stream) {
+                    Ok(command) => {
+                        let (response_tx, response_rx) = channel();
+                        requests_tx
+                            .send((command, response_tx))
+                            .expect("could not send tx");
+
+                        response_rx
+                            .recv_timeout(REQUEST_TIMEOUT)
+                            .unwrap_or_else(|_| error(503, "timed out waiting for a response"))
+                    }
+                    Err(response) => response,
+                };
+
+                if let Err(e) = write_response(&stream, response) {
+                    debug!("Couldn't send remote control response: {:?}", e);
+                }
+            }
+        })
+        .expect("could not start remote thread");
+
+    info!("Remote control server listening on {}:{}", address, port);
+
+    Some(requests_rx)
+}
+
+fn error(status: u16, message: &str) -> Response {
+    (status, json!({ "error": message }).to_string())
+}
+
+fn ok() -> Response {
+    (200, json!({ "ok": true }).to_string())
+}
+
+/// Reads an HTTP request from the stream and determines the command it's for.
+fn read_request(stream: &TcpStream) -> Result<RemoteCommand, Response> {
+    stream
+        .set_read_timeout(Some(REQUEST_TIMEOUT))
+        .map_err(|_| error(500, "couldn't set timeout"))?;
+
+    parse_request(&mut BufReader::new(stream))
+}
+
+/// Reads a line of the request line or headers, counting it against the bytes they have left, so
+/// that a client can't make the line grow without bound.
+fn read_header_line(reader: &mut impl BufRead, remaining: &mut usize) -> Result<String, Response> {
+    let mut line = String::new();
+    let read = reader
+        .by_ref()
+        .take(*remaining as u64 + 1)
+        .read_line(&mut line)
+        .map_err(|_| error(400, "couldn't read headers"))?;
+
+    if read > *remaining {
+        return Err(error(431, "request headers are too large"));
+    }
+
+    *remaining -= read;
+    Ok(line)
+}
+
+fn parse_request(reader: &mut impl BufRead) -> Result<RemoteCommand, Response> {
+    let mut remaining = MAX_HEADER_SIZE;
+    let request_line = read_header_line(reader, &mut remaining)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(error(400, "malformed request"));
+    };
+    let method = method.to_string();
+    let path = path.split('?').next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+
+    loop {
+        let header = read_header_line(reader, &mut remaining)?;
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(error(400, "malformed header"));
+        };
+
+        // browsers send the origin with requests made by web pages, which shouldn't be able to
+        // control playback
+        if name.eq_ignore_ascii_case("origin") {
+            return Err(error(403, "requests from web pages aren't allowed"));
+        }
+
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value
+                .trim()
+                .parse()
+                .map_err(|_| error(400, "invalid content length"))?;
+        }
+    }
+
+    if content_length > MAX_BODY_SIZE {
+        return Err(error(413, "request body is too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|_| error(400, "couldn't read request body"))?;
+
+    let command = match path.as_str() {
+        "/now-playing" => RemoteCommand::NowPlaying,
+        "/play" => RemoteCommand::Play,
+        "/pause" => RemoteCommand::Pause,
+        "/play-pause" => RemoteCommand::PlayPause,
+        "/next" => RemoteCommand::Next,
+        "/previous" => RemoteCommand::Previous,
+        "/enqueue" => {
+            let paths: Vec<String> = serde_json::from_slice(&body)
+                .map_err(|_| error(400, "expected a JSON array of paths"))?;
+
+            if let Some(missing) = paths.iter().find(|v| !Path::new(v).is_file()) {
+                return Err(error(400, &format!("no such file: {}", missing)));
+            }
+
+            RemoteCommand::Enqueue(paths)
+        }
+        _ => return Err(error(404, "not found")),
+    };
+
+    let expected = if command == RemoteCommand::NowPlaying {
+        "GET"
+    } else {
+        "POST"
+    };
+
+    if method != expected {
+        return Err(error(405, "method not allowed"));
+    }
+
+    Ok(command)
+}
+
+fn write_response(mut stream: &TcpStream, (status, body): Response) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )
+}
+
+fn now_playing(cx: &AppContext) -> NowPlaying {
+    let snapshot = PlaybackSnapshot::read(cx);
+    let metadata = &snapshot.metadata;
+
+    let track = snapshot.track.clone().map(|path| NowPlayingTrack {
+        path,
+        title: metadata.name.clone(),
+        artist: metadata.artist(),
+        album: metadata.album.clone(),
+    });
+
+    NowPlaying {
+        state: match snapshot.state {
+            PlaybackState::Playing => "playing",
+            PlaybackState::Paused => "paused",
+            PlaybackState::Stopped => "stopped",
+        },
+        track,
+        position: snapshot.position,
+        duration: snapshot.duration,
+        volume: snapshot.volume,
+        shuffling: snapshot.shuffling,
+        repeat: snapshot.repeat,
+        queue_length: snapshot.queue_length,
+    }
+}
+
+fn handle_command(command: RemoteCommand, cx: &mut AppContext) -> Response {
+    debug!("Received remote control command: {:?}", command);
+
+    let interface = cx.global::<GPUIPlaybackInterface>();
+
+    match command {
+        RemoteCommand::NowPlaying => {
+            let body = serde_json::to_string(&now_playing(cx)).expect("couldn't serialize");
+            return (200, body);
+        }
+        RemoteCommand::Play => interface.play(),
+        RemoteCommand::Pause => interface.pause(),
+        RemoteCommand::PlayPause => match cx.global::<PlaybackInfo>().playback_state.read(cx) {
+            PlaybackState::Playing => interface.pause(),
+            _ => interface.play(),
+        },
+        RemoteCommand::Next => interface.next(),
+        RemoteCommand::Previous => crate::playback::history::previous(cx),
+        RemoteCommand::Enqueue(paths) => interface.queue_list(paths),
+    }
+
+    ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn parse(request: &str) -> Result<RemoteCommand, Response> {
+        parse_request(&mut Cursor::new(request.as_bytes()))
+    }
+
+    fn status(request: &str) -> u16 {
+        parse(request).expect_err("request should be rejected").0
+    }
+
+    #[test]
+    fn routes_requests() {
+        assert_eq!(
+            parse("GET /now-playing HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Ok(RemoteCommand::NowPlaying)
+        );
+        assert_eq!(
+            parse("POST /next?from=deck HTTP/1.1\r\n\r\n"),
+            Ok(RemoteCommand::Next)
+        );
+        assert_eq!(status("GET /unknown HTTP/1.1\r\n\r\n"), 404);
+        assert_eq!(status("GET /play HTTP/1.1\r\n\r\n"), 405);
+    }
+
+    #[test]
+    fn rejects_bad_request_lines() {
+        assert_eq!(status(""), 400);
+        assert_eq!(status("GARBAGE\r\n\r\n"), 400);
+        assert_eq!(status("POST /play HTTP/1.1\r\nnot a header\r\n\r\n"), 400);
+    }
+
+    #[test]
+    fn rejects_web_pages() {
+        assert_eq!(
+            status("POST /play HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n"),
+            403
+        );
+    }
+
+    #[test]
+    fn checks_content_length() {
+        // without a length there's no body, which isn't a list of paths
+        assert_eq!(status("POST /enqueue HTTP/1.1\r\n\r\n[]"), 400);
+        assert_eq!(
+            status("POST /enqueue HTTP/1.1\r\nContent-Length: many\r\n\r\n[]"),
+            400
+        );
+        assert_eq!(
+            status(&format!(
+                "POST /enqueue HTTP/1.1\r\nContent-Length: {}\r\n\r\n[]",
+                MAX_BODY_SIZE + 1
+            )),
+            413
+        );
+        assert_eq!(
+            parse("POST /enqueue HTTP/1.1\r\nContent-Length: 2\r\n\r\n[]"),
+            Ok(RemoteCommand::Enqueue(Vec::new()))
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_bodies() {
+        assert_eq!(
+            status("POST /enqueue HTTP/1.1\r\nContent-Length: 20\r\n\r\n[\"/a\"]"),
+            400
+        );
+    }
+
+    #[test]
+    fn limits_header_size() {
+        let header = "a".repeat(MAX_HEADER_SIZE * 4);
+        let request = format!("POST /play HTTP/1.1\r\nX-Long: {}\r\n\r\n", header);
+        let mut reader = Cursor::new(request.as_bytes());
+
+        assert_eq!(parse_request(&mut reader).unwrap_err().0, 431);
+        // reading stops at the limit, instead of buffering the whole header first
+        assert!(reader.position() as usize <= MAX_HEADER_SIZE + 1);
+
+        let many = "X-Header: value\r\n".repeat(MAX_HEADER_SIZE / 16);
+        assert_eq!(status(&format!("POST /play HTTP/1.1\r\n{}\r\n", many)), 431);
+    }
+}
@@ -1 +1,8 @@
+pub mod continuation;
+pub mod media_keys;
 pub mod mmb;
+#[cfg(target_os = "linux")]
+pub mod notifications;
+pub mod radio;
+#[cfg(target_os = "linux")]
+pub mod tray;
@@ -0,0 +1,163 @@
+use ahash::AHashSet;
+use gpui::AppContext;
+use tracing::{debug, error, info};
+
+use crate::{
+    library::db::{find_album_id_by_location, find_next_album, list_tracks_in_album},
+    playback::interface::GPUIPlaybackInterface,
+    services::{
+        mmb::lastfm::{client::LastFMClient, LASTFM_API_KEY, LASTFM_API_SECRET},
+        radio::{continue_with_radio, find_similar_in_library, Radio},
+    },
+    settings::{playback::EndOfQueue, SettingsGlobal},
+    ui::{
+        app::Pool,
+        models::{Models, PlaybackInfo},
+    },
+};
+
+/// How many similar library tracks are considered when looking for a related album.
+const RELATED_CANDIDATES: usize = 10;
+
+/// Does what `end_of_queue` asks for once the queue runs out. Repeating is handled by the playback
+/// thread. Otherwise the radio is started, or another album is queued: the next album by the same
+/// artist is preferred, falling back to the album of a similar track from last.fm.
+///
+/// The radio or album is started as soon as the last track in the queue starts, so that playback
+/// continues without a gap. Nothing is done while the radio is running, since it extends the
+/// queue itself.
+pub fn setup_continuation(cx: &mut AppContext) {
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+    let repeat = settings.read(cx).playback.end_of_queue == EndOfQueue::RepeatAll;
+    cx.global::<GPUIPlaybackInterface>().set_repeat(repeat);
+
+    cx.observe(&settings, |settings, cx| {
+        let repeat = settings.read(cx).playback.end_of_queue == EndOfQueue::RepeatAll;
+        cx.global::<GPUIPlaybackInterface>().set_repeat(repeat);
+    })
+    .detach();
+
+    let current_track = cx.global::<PlaybackInfo>().current_track.clone();
+    let fetching = cx.global::<Models>().finding_continuation.clone();
+
+    cx.observe(&current_track, move |current_track, cx| {
+        let end_of_queue = cx
+            .global::<SettingsGlobal>()
+            .model
+            .read(cx)
+            .playback
+            .end_of_queue;
+        let radio_running = cx.global::<Radio>().0.read(cx).is_some();
+        let enabled = matches!(end_of_queue, EndOfQueue::Radio | EndOfQueue::SimilarAlbum);
+
+        if !enabled || radio_running || *fetching.read(cx) {
+            return;
+        }
+
+        let Some(track) = current_track.read(cx).clone() else {
+            return;
+        };
+
+        let queue = &cx.global::<Models>().queue.read(cx).0;
+
+        if queue.last() != Some(&track) {
+            return;
+        }
+
+        let queued: AHashSet<String> = queue.iter().cloned().collect();
+        let metadata = cx.global::<Models>().metadata.read(cx);
+        let seed = metadata.artists.first().cloned().zip(metadata.name.clone());
+
+        if end_of_queue == EndOfQueue::Radio {
+            match seed {
+                Some((artist, title)) => {
+                    info!("Continuing playback with the radio");
+                    continue_with_radio(cx, artist, title);
+                }
+                None => info!("The last track has no artist or title to start the radio from"),
+            }
+
+            return;
+        }
+
+        fetching.update(cx, |m, cx| {
+            *m = true;
+            cx.notify();
+        });
+
+        let fetching = fetching.clone();
+        let pool = cx.global::<Pool>().0.clone();
+
+        cx.spawn(|mut cx| async move {
+            let album_id = match find_next_album(&pool, &track).await {
+                Ok(Some(album_id)) => Some(album_id),
+                Ok(None) => {
+                    debug!("No next album by the same artist, looking for a similar track");
+                    find_related_album(&pool, &track, seed, &queued).await
+                }
+                Err(e) => {
+                    error!("Database error while finding the next album: {:?}", e);
+                    None
+                }
+            };
+
+            let paths: Vec<String> = if let Some(album_id) = album_id {
+                match list_tracks_in_album(&pool, album_id).await {
+                    Ok(tracks) => tracks.iter().map(|v| v.location.clone()).collect(),
+                    Err(e) => {
+                        error!("Could not list tracks in album {}: {:?}", album_id, e);
+                        Vec::new()
+                    }
+                }
+            } else {
+                Vec::new()
+            };
+
+            cx.update(|cx| {
+                fetching.update(cx, |m, cx| {
+                    *m = false;
+                    cx.notify();
+                });
+
+                if paths.is_empty() {
+                    info!("Couldn't find another album to continue playback with");
+                } else {
+                    info!("Continuing playback with album {:?}", album_id);
+                    cx.global::<GPUIPlaybackInterface>().queue_list(paths);
+                }
+            })
+            .expect("failed to update continuation state");
+        })
+        .detach();
+    })
+    .detach();
+}
+
+/// Finds the album of a library track that last.fm considers similar to the seed, other than the
+/// album the current track belongs to.
+async fn find_related_album(
+    pool: &sqlx::SqlitePool,
+    track: &str,
+    seed: Option<(String, String)>,
+    queued: &AHashSet<String>,
+) -> Option<i64> {
+    let (Some(key), Some(secret)) = (LASTFM_API_KEY, LASTFM_API_SECRET) else {
+        return None;
+    };
+    let seed = seed?;
+
+    let current_album = find_album_id_by_location(pool, track).await.ok().flatten();
+    let mut client = LastFMClient::new(key.to_string(), secret);
+
+    for (location, _, _) in
+        find_similar_in_library(&mut client, pool, vec![seed], queued, RELATED_CANDIDATES).await
+    {
+        match find_album_id_by_location(pool, &location).await {
+            Ok(Some(album_id)) if Some(album_id) != current_album => return Some(album_id),
+            Ok(_) => (),
+            Err(e) => error!("Database error while finding a related album: {:?}", e),
+        }
+    }
+
+    None
+}
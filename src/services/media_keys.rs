@@ -0,0 +1,136 @@
+use std::{
+    sync::mpsc::{channel, TryRecvError},
+    time::Duration,
+};
+
+use gpui::{AppContext, Global};
+use souvlaki::{MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, PlatformConfig};
+use tracing::{debug, warn};
+
+use crate::{
+    playback::{interface::GPUIPlaybackInterface, thread::PlaybackState},
+    ui::{
+        global_actions::{Next, PlayPause, Previous},
+        models::{Models, PlaybackInfo},
+    },
+};
+
+/// The operating system's media controls (MPRIS on Linux, the Now Playing center on macOS), which
+/// receive the media keys even while Muzak isn't focused.
+struct MediaKeys(MediaControls);
+
+impl Global for MediaKeys {}
+
+/// Registers with the operating system's media controls, so that the media keys control
+/// playback while Muzak is in the background, and keeps them informed of what's playing.
+///
+/// Only Linux and macOS are supported. Windows' media controls have to be attached to a window's
+/// HWND, which gpui doesn't expose, so nothing is registered there.
+pub fn setup_media_keys(cx: &mut AppContext) {
+    // TODO: register with the system media transport controls once gpui exposes the HWND
+    if cfg!(target_os = "windows") {
+        warn!("Background media keys aren't supported on Windows");
+        return;
+    }
+
+    let config = PlatformConfig {
+        dbus_name: "muzak",
+        display_name: "Muzak",
+        hwnd: None,
+    };
+
+    let mut controls = match MediaControls::new(config) {
+        Ok(controls) => controls,
+        Err(e) => {
+            warn!("Could not register media controls: {:?}", e);
+            return;
+        }
+    };
+
+    let (events_tx, events_rx) = channel();
+
+    // the handler is called from another thread, so events are passed back to be handled here
+    if let Err(e) = controls.attach(move |event| {
+        events_tx.send(event).ok();
+    }) {
+        warn!("Could not listen for media keys: {:?}", e);
+        return;
+    }
+
+    cx.set_global(MediaKeys(controls));
+
+    cx.spawn(|mut cx| async move {
+        loop {
+            match events_rx.try_recv() {
+                Ok(event) => {
+                    debug!("media control event: {:?}", event);
+                    cx.update(|cx| handle_event(cx, event))
+                        .expect("failed to handle media control event");
+                }
+                Err(TryRecvError::Empty) => {
+                    cx.background_executor()
+                        .timer(Duration::from_millis(50))
+                        .await
+                }
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+    })
+    .detach();
+
+    let playback_state = cx.global::<PlaybackInfo>().playback_state.clone();
+
+    cx.observe(&playback_state, |playback_state, cx| {
+        let playback = match playback_state.read(cx) {
+            PlaybackState::Playing => MediaPlayback::Playing { progress: None },
+            PlaybackState::Paused => MediaPlayback::Paused { progress: None },
+            PlaybackState::Stopped => MediaPlayback::Stopped,
+        };
+
+        cx.update_global::<MediaKeys, _>(|keys, _| {
+            if let Err(e) = keys.0.set_playback(playback) {
+                warn!("Could not update media controls: {:?}", e);
+            }
+        });
+    })
+    .detach();
+
+    let metadata = cx.global::<Models>().metadata.clone();
+
+    cx.observe(&metadata, |metadata, cx| {
+        let metadata = metadata.read(cx);
+        let artist = metadata.artist();
+        let title = metadata.name.clone();
+        let album = metadata.album.clone();
+        let duration = *cx.global::<PlaybackInfo>().duration.read(cx);
+        let duration = (duration > 0).then(|| Duration::from_secs(duration));
+
+        cx.update_global::<MediaKeys, _>(|keys, _| {
+            let metadata = MediaMetadata {
+                title: title.as_deref(),
+                artist: artist.as_deref(),
+                album: album.as_deref(),
+                duration,
+                ..Default::default()
+            };
+
+            if let Err(e) = keys.0.set_metadata(metadata) {
+                warn!("Could not update media controls: {:?}", e);
+            }
+        });
+    })
+    .detach();
+}
+
+/// The keys are handled the same way as the corresponding actions.
+fn handle_event(cx: &mut AppContext, event: MediaControlEvent) {
+    match event {
+        MediaControlEvent::Play => cx.global::<GPUIPlaybackInterface>().play(),
+        MediaControlEvent::Pause => cx.global::<GPUIPlaybackInterface>().pause(),
+        MediaControlEvent::Toggle => cx.dispatch_action(&PlayPause),
+        MediaControlEvent::Next => cx.dispatch_action(&Next),
+        MediaControlEvent::Previous => cx.dispatch_action(&Previous),
+        MediaControlEvent::Stop => cx.global::<GPUIPlaybackInterface>().stop(),
+        _ => (),
+    }
+}
@@ -1,16 +1,26 @@
-use std::sync::Arc;
+use std::{
+    sync::{mpsc::Sender, Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use async_std::task;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use client::LastFMClient;
+use gpui::Global;
+use pending::{PendingScrobble, SharedPendingScrobbles};
 use tracing::{debug, warn};
 
-use crate::{media::metadata::Metadata, playback::thread::PlaybackState};
+use crate::{
+    media::metadata::Metadata,
+    playback::thread::PlaybackState,
+    settings::lastfm::{LastFMSettings, ScrobbleMode},
+};
 
 use super::MediaMetadataBroadcastService;
 
 pub mod client;
+pub mod pending;
 mod requests;
 pub mod types;
 mod util;
@@ -18,8 +28,28 @@ mod util;
 pub const LASTFM_API_KEY: Option<&'static str> = option_env!("LASTFM_API_KEY");
 pub const LASTFM_API_SECRET: Option<&'static str> = option_env!("LASTFM_API_SECRET");
 
+/// The outcome of the most recent scrobble attempt, reported back to the UI.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ScrobbleStatus {
+    Submitted,
+    Failed,
+    /// The scrobble was queued to be submitted later, because of the scrobble mode.
+    Queued,
+}
+
+/// The last.fm settings, shared with the service so that it sees changes to them.
+#[derive(Clone)]
+pub struct SharedLastFMSettings(pub Arc<Mutex<LastFMSettings>>);
+
+impl Global for SharedLastFMSettings {}
+
 pub struct LastFM {
     client: LastFMClient,
+    status_tx: Sender<ScrobbleStatus>,
+    pending: SharedPendingScrobbles,
+    settings: SharedLastFMSettings,
+    /// When queued scrobbles were last submitted on schedule.
+    last_sync: Option<Instant>,
     start_timestamp: Option<DateTime<Utc>>,
     accumulated_time: u64,
     duration: u64,
@@ -29,9 +59,18 @@ pub struct LastFM {
 }
 
 impl LastFM {
-    pub fn new(client: LastFMClient) -> Self {
+    pub fn new(
+        client: LastFMClient,
+        status_tx: Sender<ScrobbleStatus>,
+        pending: SharedPendingScrobbles,
+        settings: SharedLastFMSettings,
+    ) -> Self {
         LastFM {
             client,
+            status_tx,
+            pending,
+            settings,
+            last_sync: None,
             start_timestamp: None,
             accumulated_time: 0,
             metadata: None,
@@ -42,26 +81,193 @@ impl LastFM {
     }
 
     pub async fn scrobble(&mut self) {
-        if let Some(info) = &self.metadata {
-            if let (Some(artist), Some(track)) = (info.artist.clone(), info.name.clone()) {
-                if let Err(e) = self
-                    .client
-                    .scrobble(
-                        artist,
-                        track,
-                        self.start_timestamp.unwrap(),
-                        info.album.clone(),
-                        None,
-                    )
-                    .await
-                {
-                    warn!("Could not scrobble: {}", e)
-                }
-            }
+        let Some(info) = &self.metadata else {
+            return;
+        };
+        // last.fm expects a single artist, and other artists would split the track's history
+        let (Some(artist), Some(track)) = (info.artists.first().cloned(), info.name.clone()) else {
+            return;
+        };
+
+        let mut entry = PendingScrobble {
+            artist,
+            track,
+            album: info.album.clone(),
+            mbid: info.mbid_track.clone(),
+            timestamp: self.start_timestamp.unwrap().timestamp(),
+            attempts: 0,
+        };
+
+        let mode = self
+            .settings
+            .0
+            .lock()
+            .expect("could not lock last.fm settings")
+            .scrobble_mode;
+
+        let status = if mode != ScrobbleMode::Immediate {
+            debug!("queueing scrobble to be submitted later");
+            self.pending
+                .0
+                .lock()
+                .expect("could not lock pending scrobbles")
+                .add(entry);
+            ScrobbleStatus::Queued
+        } else if let Err(e) = submit(&mut self.client, &entry).await {
+            warn!("Could not scrobble, it will be retried later: {}", e);
+            entry.attempts = 1;
+            self.pending
+                .0
+                .lock()
+                .expect("could not lock pending scrobbles")
+                .add(entry);
+            ScrobbleStatus::Failed
+        } else {
+            // last.fm is reachable again, so this is a good time to submit the backlog
+            submit_pending(&mut self.client, &self.pending).await;
+            ScrobbleStatus::Submitted
+        };
+
+        // the UI may have gone away, which is fine
+        self.status_tx.send(status).ok();
+    }
+
+    /// Submits the scrobbles left pending by the previous session, unless they're only meant to be
+    /// submitted manually. Scheduled scrobbles are submitted by the first sync instead.
+    pub async fn retry_pending(&mut self) {
+        let mode = self
+            .settings
+            .0
+            .lock()
+            .expect("could not lock last.fm settings")
+            .scrobble_mode;
+
+        if mode != ScrobbleMode::Immediate
+            || self
+                .pending
+                .0
+                .lock()
+                .expect("could not lock pending scrobbles")
+                .is_empty()
+        {
+            return;
         }
+
+        debug!("retrying scrobbles left pending by the previous session");
+        let status = if submit_pending(&mut self.client, &self.pending).await {
+            ScrobbleStatus::Failed
+        } else {
+            ScrobbleStatus::Submitted
+        };
+        self.status_tx.send(status).ok();
+    }
+
+    /// Submits the queued scrobbles if the scrobble mode is scheduled and the sync interval has
+    /// passed since they were last submitted. This is called on a timer, so that queued scrobbles
+    /// are submitted even when nothing is playing.
+    pub async fn sync_if_due(&mut self) {
+        let settings = self
+            .settings
+            .0
+            .lock()
+            .expect("could not lock last.fm settings")
+            .clone();
+
+        if settings.scrobble_mode != ScrobbleMode::Scheduled
+            || self
+                .last_sync
+                .is_some_and(|v| v.elapsed() < Duration::from_secs(settings.sync_interval * 60))
+        {
+            return;
+        }
+
+        self.last_sync = Some(Instant::now());
+
+        if self
+            .pending
+            .0
+            .lock()
+            .expect("could not lock pending scrobbles")
+            .is_empty()
+        {
+            return;
+        }
+
+        debug!("submitting queued scrobbles on schedule");
+        let failed = submit_pending(&mut self.client, &self.pending).await;
+
+        let status = if failed {
+            ScrobbleStatus::Failed
+        } else {
+            ScrobbleStatus::Submitted
+        };
+        self.status_tx.send(status).ok();
     }
 }
 
+async fn submit(client: &mut LastFMClient, entry: &PendingScrobble) -> anyhow::Result<()> {
+    let timestamp = DateTime::from_timestamp(entry.timestamp, 0)
+        .ok_or_else(|| anyhow::Error::msg("invalid timestamp"))?;
+
+    client
+        .scrobble(
+            entry.artist.clone(),
+            entry.track.clone(),
+            timestamp,
+            entry.album.clone(),
+            None,
+            entry.mbid.clone(),
+        )
+        .await
+}
+
+/// Submits the scrobbles that are pending. Each one is tried once, and those that fail are kept
+/// for next time until they run out of attempts. Each scrobble is only removed once it has been
+/// submitted. Returns true if any of them failed.
+pub async fn submit_pending(client: &mut LastFMClient, pending: &SharedPendingScrobbles) -> bool {
+    let entries = pending
+        .0
+        .lock()
+        .expect("could not lock pending scrobbles")
+        .entries();
+    let mut any_failed = false;
+
+    for entry in entries {
+        let result = submit(client, &entry).await;
+        let mut pending = pending.0.lock().expect("could not lock pending scrobbles");
+
+        if let Err(e) = result {
+            debug!(
+                "Could not submit pending scrobble of {} - {}: {}",
+                entry.artist, entry.track, e
+            );
+            pending.record_failure(&entry);
+            any_failed = true;
+        } else {
+            pending.remove(&entry);
+        }
+    }
+
+    any_failed
+}
+
+/// Adds the time played since the last position update to `accumulated`. Jumps of 2 seconds or
+/// more (seeking forwards) don't count as played, and neither does seeking backwards.
+pub fn accumulate(accumulated: u64, last_position: u64, position: u64) -> u64 {
+    if position >= last_position && position < last_position + 2 {
+        accumulated + (position - last_position)
+    } else {
+        accumulated
+    }
+}
+
+/// Whether a track has been played for long enough to be scrobbled, following last.fm's rules:
+/// the track must be at least 30 seconds long, and must have been played for more than half of
+/// its duration or for more than 4 minutes.
+pub fn should_scrobble(duration: u64, accumulated: u64, has_scrobbled: bool) -> bool {
+    !has_scrobbled && duration >= 30 && (accumulated > duration / 2 || accumulated > 240)
+}
+
 #[async_trait]
 impl MediaMetadataBroadcastService for LastFM {
     async fn new_track(&mut self, _: String) {
@@ -77,10 +283,16 @@ impl MediaMetadataBroadcastService for LastFM {
     }
 
     async fn metadata_recieved(&mut self, info: Arc<Metadata>) {
-        if let (Some(artist), Some(track)) = (info.artist.clone(), info.name.clone()) {
+        if let (Some(artist), Some(track)) = (info.artists.first().cloned(), info.name.clone()) {
             if let Err(e) = self
                 .client
-                .now_playing(artist, track, info.album.clone(), None)
+                .now_playing(
+                    artist,
+                    track,
+                    info.album.clone(),
+                    None,
+                    info.mbid_track.clone(),
+                )
                 .await
             {
                 warn!("Could not set now playing: {}", e)
@@ -99,19 +311,13 @@ impl MediaMetadataBroadcastService for LastFM {
     }
 
     async fn position_changed(&mut self, position: u64) {
-        if position < self.last_postion + 2 {
-            self.accumulated_time += position - self.last_postion;
-        }
-
+        self.accumulated_time = accumulate(self.accumulated_time, self.last_postion, position);
         self.last_postion = position;
 
-        if self.duration >= 30
-            && (self.accumulated_time > self.duration / 2 || self.accumulated_time > 240)
-            && !self.should_scrobble
+        if self.metadata.is_some()
+            && should_scrobble(self.duration, self.accumulated_time, self.should_scrobble)
         {
-            if let Some(info) = &self.metadata {
-                self.should_scrobble = true;
-            }
+            self.should_scrobble = true;
         }
     }
 
@@ -128,3 +334,49 @@ impl Drop for LastFM {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulate_counts_playback() {
+        assert_eq!(accumulate(10, 5, 6), 11);
+        assert_eq!(accumulate(10, 5, 5), 10);
+    }
+
+    #[test]
+    fn accumulate_ignores_seeking() {
+        // jumps of 2 seconds or more are seeks, not playback
+        assert_eq!(accumulate(10, 5, 7), 10);
+        assert_eq!(accumulate(10, 5, 60), 10);
+        // and so is going backwards
+        assert_eq!(accumulate(10, 5, 4), 10);
+        assert_eq!(accumulate(10, 5, 0), 10);
+    }
+
+    #[test]
+    fn short_tracks_are_not_scrobbled() {
+        assert!(!should_scrobble(29, 29, false));
+        assert!(should_scrobble(30, 16, false));
+    }
+
+    #[test]
+    fn scrobbled_after_half_the_duration() {
+        assert!(!should_scrobble(200, 100, false));
+        assert!(should_scrobble(200, 101, false));
+    }
+
+    #[test]
+    fn scrobbled_after_four_minutes() {
+        // long tracks don't have to be played for half their duration
+        assert!(!should_scrobble(1200, 240, false));
+        assert!(should_scrobble(1200, 241, false));
+    }
+
+    #[test]
+    fn scrobbled_only_once() {
+        assert!(!should_scrobble(200, 150, true));
+        assert!(!should_scrobble(1200, 600, true));
+    }
+}
@@ -2,7 +2,8 @@ use chrono::{DateTime, Utc};
 
 use super::{
     requests::LFMRequestBuilder,
-    types::{GetSession, GetToken, Session},
+    types::{GetSession, GetSimilar, GetToken, Session},
+    util::retry,
 };
 
 pub struct LastFMClient {
@@ -27,28 +28,63 @@ impl LastFMClient {
     }
 
     pub async fn get_token(&mut self) -> anyhow::Result<String> {
-        let token = LFMRequestBuilder::new(self.api_key.clone())
+        let request = LFMRequestBuilder::new(self.api_key.clone())
             .add_param("method", "auth.gettoken".to_string())
             .read()
-            .sign(self.api_secret)
-            .send_request::<GetToken>()
-            .await?;
+            .sign(self.api_secret);
+
+        let token = retry("auth.gettoken", || {
+            request.clone().send_request::<GetToken>()
+        })
+        .await?;
 
         Ok(token.token)
     }
 
     pub async fn get_session(&mut self, token: String) -> anyhow::Result<Session> {
-        let session = LFMRequestBuilder::new(self.api_key.clone())
+        let request = LFMRequestBuilder::new(self.api_key.clone())
             .add_param("method", "auth.getsession".to_string())
             .add_param("token", token)
             .write()
-            .sign(self.api_secret)
-            .send_request::<GetSession>()
-            .await?;
+            .sign(self.api_secret);
+
+        let session = retry("auth.getsession", || {
+            request.clone().send_request::<GetSession>()
+        })
+        .await?;
 
         Ok(session.session)
     }
 
+    /// Returns up to `limit` tracks similar to the specified track, as (artist, title) pairs.
+    pub async fn get_similar(
+        &mut self,
+        artist: String,
+        track: String,
+        limit: u32,
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        let request = LFMRequestBuilder::new(self.api_key.clone())
+            .add_param("method", "track.getsimilar".to_string())
+            .add_param("artist", artist)
+            .add_param("track", track)
+            .add_param("autocorrect", "1".to_string())
+            .add_param("limit", limit.to_string())
+            .read()
+            .sign(self.api_secret);
+
+        let similar = retry("track.getsimilar", || {
+            request.clone().send_request::<GetSimilar>()
+        })
+        .await?;
+
+        Ok(similar
+            .similartracks
+            .track
+            .into_iter()
+            .map(|v| (v.artist.name, v.name))
+            .collect())
+    }
+
     pub async fn scrobble(
         &mut self,
         artist: String,
@@ -56,20 +92,22 @@ impl LastFMClient {
         timestamp: DateTime<Utc>,
         album: Option<String>,
         duration: Option<u64>,
+        mbid: Option<String>,
     ) -> anyhow::Result<()> {
         if let Some(session) = self.auth_session.clone() {
-            LFMRequestBuilder::new(self.api_key.clone())
+            let request = LFMRequestBuilder::new(self.api_key.clone())
                 .add_param("method", "track.scrobble".to_string())
                 .add_param("artist[0]", artist)
                 .add_param("track[0]", track)
                 .add_param("timestamp[0]", timestamp.timestamp().to_string())
                 .add_optional_param("album[0]", album)
                 .add_optional_param("duration[0]", duration.map(|a| u64::to_string(&a)))
+                .add_optional_param("mbid[0]", mbid)
                 .add_param("sk", session)
                 .write()
-                .sign(self.api_secret)
-                .send_write_request_ns()
-                .await?;
+                .sign(self.api_secret);
+
+            retry("track.scrobble", || request.clone().send_write_request_ns()).await?;
 
             Ok(())
         } else {
@@ -83,19 +121,24 @@ impl LastFMClient {
         track: String,
         album: Option<String>,
         duration: Option<u64>,
+        mbid: Option<String>,
     ) -> anyhow::Result<()> {
         if let Some(session) = self.auth_session.clone() {
-            LFMRequestBuilder::new(self.api_key.clone())
+            let request = LFMRequestBuilder::new(self.api_key.clone())
                 .add_param("method", "track.updateNowPlaying".to_string())
                 .add_param("artist", artist)
                 .add_param("track", track)
                 .add_optional_param("album", album)
                 .add_optional_param("duration", duration.map(|a| u64::to_string(&a)))
+                .add_optional_param("mbid", mbid)
                 .add_param("sk", session)
                 .write()
-                .sign(self.api_secret)
-                .send_write_request_ns()
-                .await?;
+                .sign(self.api_secret);
+
+            retry("track.updateNowPlaying", || {
+                request.clone().send_write_request_ns()
+            })
+            .await?;
 
             Ok(())
         } else {
@@ -0,0 +1,275 @@
+//! Scrobbles that couldn't be submitted, saved to disk so that they can be retried once last.fm
+//! is reachable again.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+    sync::{mpsc::Sender, Arc, Mutex},
+};
+
+use ahash::AHashSet;
+use gpui::Global;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::ui::app::get_dirs;
+
+/// How many times submitting a scrobble can fail before it's given up on, so that a scrobble
+/// last.fm will never accept isn't retried forever.
+const MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingScrobble {
+    pub artist: String,
+    pub track: String,
+    pub album: Option<String>,
+    /// The MusicBrainz ID of the recording, which helps last.fm match the track.
+    #[serde(default)]
+    pub mbid: Option<String>,
+    /// When the track started playing, as a unix timestamp.
+    pub timestamp: i64,
+    /// How many times submitting the scrobble has failed.
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+impl PendingScrobble {
+    /// Scrobbles of the same track starting at the same time are duplicates.
+    fn key(&self) -> (String, String, i64) {
+        (
+            self.artist.to_lowercase(),
+            self.track.to_lowercase(),
+            self.timestamp,
+        )
+    }
+}
+
+/// The pending scrobbles, shared between the last.fm service and the UI.
+#[derive(Clone)]
+pub struct SharedPendingScrobbles(pub Arc<Mutex<PendingScrobbles>>);
+
+impl Global for SharedPendingScrobbles {}
+
+pub struct PendingScrobbles {
+    entries: Vec<PendingScrobble>,
+    /// Where the scrobbles are saved, or None if they're only kept in memory.
+    path: Option<PathBuf>,
+    /// Sent the number of pending scrobbles whenever it changes, so that the UI can show it.
+    count_tx: Sender<usize>,
+}
+
+impl PendingScrobbles {
+    /// Loads the pending scrobbles from the data directory, discarding any duplicates.
+    pub fn load(count_tx: Sender<usize>) -> Self {
+        let path = get_dirs().data_dir().join("pending_scrobbles.json");
+
+        let entries: Vec<PendingScrobble> = match File::open(&path) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|e| {
+                warn!("Could not read pending scrobbles, discarding them: {:?}", e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+
+        let mut pending = PendingScrobbles {
+            entries: Vec::new(),
+            path: Some(path),
+            count_tx,
+        };
+
+        let count = entries.len();
+        pending.extend(entries);
+
+        if pending.entries.len() != count {
+            pending.save();
+        }
+
+        pending.count_tx.send(pending.entries.len()).ok();
+        pending
+    }
+
+    fn save(&self) {
+        // the UI may have gone away, which is fine
+        self.count_tx.send(self.entries.len()).ok();
+
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(path);
+
+        match file {
+            Ok(file) => {
+                if let Err(e) = serde_json::to_writer(BufWriter::new(file), &self.entries) {
+                    error!("Could not write pending scrobbles: {:?}", e);
+                }
+            }
+            Err(e) => error!("Could not open pending scrobbles file: {:?}", e),
+        }
+    }
+
+    fn extend(&mut self, entries: Vec<PendingScrobble>) {
+        let mut keys: AHashSet<_> = self.entries.iter().map(PendingScrobble::key).collect();
+
+        for entry in entries {
+            if entry.attempts >= MAX_ATTEMPTS {
+                warn!(
+                    "Giving up on scrobbling {} - {} after {} attempts",
+                    entry.artist, entry.track, entry.attempts
+                );
+            } else if keys.insert(entry.key()) {
+                self.entries.push(entry);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds the scrobble, unless the same scrobble is already pending.
+    pub fn add(&mut self, entry: PendingScrobble) {
+        self.extend(vec![entry]);
+        self.save();
+    }
+
+    /// A copy of the pending scrobbles, so that they can be submitted without holding the lock.
+    /// They stay pending until they're removed, so that none are lost if Muzak exits while they're
+    /// being submitted.
+    pub fn entries(&self) -> Vec<PendingScrobble> {
+        self.entries.clone()
+    }
+
+    /// Removes a scrobble that has been submitted.
+    pub fn remove(&mut self, entry: &PendingScrobble) {
+        let key = entry.key();
+        let count = self.entries.len();
+        self.entries.retain(|v| v.key() != key);
+
+        if self.entries.len() != count {
+            self.save();
+        }
+    }
+
+    /// Records that submitting a scrobble failed, giving up on it if it has run out of attempts.
+    pub fn record_failure(&mut self, entry: &PendingScrobble) {
+        let key = entry.key();
+        let Some(index) = self.entries.iter().position(|v| v.key() == key) else {
+            return;
+        };
+
+        let entry = &mut self.entries[index];
+        entry.attempts += 1;
+
+        if entry.attempts >= MAX_ATTEMPTS {
+            warn!(
+                "Giving up on scrobbling {} - {} after {} attempts",
+                entry.artist, entry.track, entry.attempts
+            );
+            self.entries.remove(index);
+        }
+
+        self.save();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+
+    fn in_memory() -> PendingScrobbles {
+        let (count_tx, _) = channel();
+
+        PendingScrobbles {
+            entries: Vec::new(),
+            path: None,
+            count_tx,
+        }
+    }
+
+    fn scrobble(artist: &str, track: &str, timestamp: i64) -> PendingScrobble {
+        PendingScrobble {
+            artist: artist.to_string(),
+            track: track.to_string(),
+            album: None,
+            mbid: None,
+            timestamp,
+            attempts: 0,
+        }
+    }
+
+    #[test]
+    fn duplicates_are_dropped() {
+        let mut pending = in_memory();
+
+        pending.extend(vec![
+            scrobble("Artist", "Track", 100),
+            scrobble("artist", "TRACK", 100),
+            scrobble("Artist", "Track", 200),
+            scrobble("Artist", "Other", 100),
+        ]);
+        pending.add(scrobble("Artist", "Track", 200));
+
+        assert_eq!(pending.len(), 3);
+    }
+
+    #[test]
+    fn scrobbles_are_given_up_on_after_max_attempts() {
+        let mut pending = in_memory();
+        let entry = scrobble("Artist", "Track", 100);
+        pending.add(entry.clone());
+
+        for _ in 0..MAX_ATTEMPTS - 1 {
+            pending.record_failure(&entry);
+        }
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending.entries()[0].attempts, MAX_ATTEMPTS - 1);
+
+        pending.record_failure(&entry);
+        assert!(pending.is_empty());
+
+        // scrobbles that ran out of attempts in an earlier session aren't loaded again
+        let mut exhausted = scrobble("Artist", "Track", 100);
+        exhausted.attempts = MAX_ATTEMPTS;
+        pending.extend(vec![exhausted]);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn only_submitted_scrobbles_are_removed() {
+        let mut pending = in_memory();
+        let submitted = scrobble("Artist", "Track", 100);
+        pending.extend(vec![
+            submitted.clone(),
+            scrobble("Artist", "Track", 200),
+            scrobble("Artist", "Other", 100),
+        ]);
+
+        pending.remove(&submitted);
+
+        let remaining: Vec<_> = pending.entries().iter().map(PendingScrobble::key).collect();
+        assert_eq!(
+            remaining,
+            vec![
+                ("artist".to_string(), "track".to_string(), 200),
+                ("artist".to_string(), "other".to_string(), 100),
+            ]
+        );
+    }
+}
@@ -1,7 +1,22 @@
-use isahc::prelude::*;
+use isahc::{http::StatusCode, prelude::*};
 use serde::Deserialize;
 use smallvec::SmallVec;
 
+use super::{types::ErrorResponse, util::LFMError};
+
+fn check_response(status: StatusCode, body: &str) -> anyhow::Result<()> {
+    if status.is_server_error() {
+        return Err(LFMError::Server(status.as_u16()).into());
+    }
+
+    if let Ok(error) = serde_json::from_str::<ErrorResponse>(body) {
+        return Err(LFMError::Api(error.error, error.message).into());
+    }
+
+    Ok(())
+}
+
+#[derive(Clone)]
 pub struct LFMRequestBuilder {
     api_key: String,
     params: SmallVec<[(&'static str, String); 5]>,
@@ -90,7 +105,7 @@ impl LFMRequestBuilder {
         for (k, v) in self.params.iter() {
             url.push_str(k);
             url.push('=');
-            url.push_str(v);
+            url.push_str(&urlencoding::encode(v));
             url.push('&');
         }
 
@@ -103,6 +118,7 @@ impl LFMRequestBuilder {
 
         let mut response = isahc::get_async(url).await?;
         let body = response.text().await?;
+        check_response(response.status(), &body)?;
         serde_json::from_str(&body).map_err(anyhow::Error::from)
     }
 
@@ -126,6 +142,7 @@ impl LFMRequestBuilder {
 
         let mut response = isahc::post_async(self.endpoint, body).await?;
         let body = response.text().await?;
+        check_response(response.status(), &body)?;
 
         Ok(body)
     }
@@ -10,9 +10,36 @@ pub struct GetSession {
     pub session: Session,
 }
 
+#[derive(Deserialize)]
+pub struct GetSimilar {
+    pub similartracks: SimilarTracks,
+}
+
+#[derive(Deserialize)]
+pub struct SimilarTracks {
+    pub track: Vec<SimilarTrack>,
+}
+
+#[derive(Deserialize)]
+pub struct SimilarTrack {
+    pub name: String,
+    pub artist: SimilarArtist,
+}
+
+#[derive(Deserialize)]
+pub struct SimilarArtist {
+    pub name: String,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Session {
     pub name: String,
     pub key: String,
     pub subscriber: i8,
 }
+
+#[derive(Deserialize)]
+pub struct ErrorResponse {
+    pub error: u32,
+    pub message: String,
+}
@@ -0,0 +1,78 @@
+use std::{fmt, future::Future, time::Duration};
+
+use async_std::task;
+use tracing::debug;
+
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+/// An error returned by last.fm itself, as opposed to a network or parsing error.
+#[derive(Debug)]
+pub enum LFMError {
+    /// The server responded with a 5xx status code.
+    Server(u16),
+    /// The API returned an error object. See https://www.last.fm/api/errorcodes for the codes.
+    Api(u32, String),
+}
+
+impl LFMError {
+    /// Whether the request could succeed if it was sent again. Authentication and parameter
+    /// errors will never succeed on retry, while outages and rate limiting might.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LFMError::Server(_) => true,
+            // 8: operation failed, 11: service offline, 16: temporarily unavailable,
+            // 29: rate limit exceeded
+            LFMError::Api(code, _) => matches!(code, 8 | 11 | 16 | 29),
+        }
+    }
+}
+
+impl fmt::Display for LFMError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LFMError::Server(status) => write!(f, "last.fm returned status {}", status),
+            LFMError::Api(code, message) => write!(f, "last.fm error {}: {}", code, message),
+        }
+    }
+}
+
+impl std::error::Error for LFMError {}
+
+fn is_retryable(error: &anyhow::Error) -> bool {
+    if let Some(error) = error.downcast_ref::<LFMError>() {
+        error.is_retryable()
+    } else {
+        // network errors, anything else (like a response we couldn't parse) won't get better
+        error.downcast_ref::<isahc::Error>().is_some()
+            || error.downcast_ref::<std::io::Error>().is_some()
+    }
+}
+
+/// Runs the request returned by `request`, retrying with exponential backoff if it fails with a
+/// retryable error. Only the final error is returned, intermediate failures are logged at debug
+/// level so that flaky connections don't flood the logs.
+pub async fn retry<T, F, Fut>(name: &str, mut request: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let mut delay = INITIAL_DELAY;
+    let mut attempt = 1;
+
+    loop {
+        match request().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < MAX_ATTEMPTS && is_retryable(&e) => {
+                debug!(
+                    "{} failed (attempt {}/{}), retrying in {:?}: {}",
+                    name, attempt, MAX_ATTEMPTS, delay, e
+                );
+                task::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc, time::Duration};
+
+use gpui::AppContext;
+use notify_rust::{Hint, Notification};
+use tracing::{debug, warn};
+
+use crate::{
+    playback::thread::PlaybackState,
+    settings::SettingsGlobal,
+    ui::{
+        app::get_dirs,
+        models::{ImageEvent, Models, PlaybackInfo},
+    },
+};
+
+/// How long to wait for the album art after the metadata changes, since both are read when the
+/// track is opened but arrive separately.
+const ART_DELAY: Duration = Duration::from_millis(300);
+
+#[derive(Default)]
+struct NotificationState {
+    /// The encoded album art of the track that is about to be announced.
+    art: Option<Box<[u8]>>,
+    /// Incremented whenever the metadata changes, so that only the last track is announced.
+    generation: usize,
+    /// The ID of the notification that was last shown, so that it can be replaced.
+    last_id: Option<u32>,
+}
+
+/// Shows a desktop notification whenever a new track starts, if `track_notifications` is enabled.
+/// Notifications are sent over D-Bus, so this is only available on Linux.
+// TODO: macOS and Windows
+pub fn setup_notifications(cx: &mut AppContext) {
+    let state = Rc::new(RefCell::new(NotificationState::default()));
+    let albumart = cx.global::<Models>().albumart.clone();
+    let metadata = cx.global::<Models>().metadata.clone();
+
+    let state_clone = state.clone();
+
+    cx.subscribe(&albumart, move |_, ev: &ImageEvent, _| {
+        state_clone.borrow_mut().art = Some(ev.0.clone());
+    })
+    .detach();
+
+    cx.observe(&metadata, move |_, cx| {
+        let expected = {
+            let mut state = state.borrow_mut();
+            state.generation += 1;
+            state.generation
+        };
+        let state = state.clone();
+
+        cx.spawn(|mut cx| async move {
+            cx.background_executor().timer(ART_DELAY).await;
+
+            if state.borrow().generation != expected {
+                return;
+            }
+
+            let Ok(Some(notification)) = cx.update(|cx| build_notification(cx, &state)) else {
+                return;
+            };
+
+            let id = cx
+                .background_executor()
+                .spawn(async move {
+                    match notification.show() {
+                        Ok(handle) => Some(handle.id()),
+                        Err(e) => {
+                            warn!("Could not show notification: {:?}", e);
+                            None
+                        }
+                    }
+                })
+                .await;
+
+            if id.is_some() {
+                state.borrow_mut().last_id = id;
+            }
+        })
+        .detach();
+    })
+    .detach();
+}
+
+/// Builds the notification for the current track, taking the album art that was received for it.
+/// Returns None if there's nothing to announce.
+fn build_notification(
+    cx: &AppContext,
+    state: &Rc<RefCell<NotificationState>>,
+) -> Option<Notification> {
+    let art = state.borrow_mut().art.take();
+    let settings = &cx.global::<SettingsGlobal>().model.read(cx).interface;
+
+    if !settings.track_notifications {
+        return None;
+    }
+
+    if settings.suppress_notifications_when_focused && cx.active_window().is_some() {
+        debug!("window is focused, not showing notification");
+        return None;
+    }
+
+    // tracks that are restored paused at startup aren't announced
+    if *cx.global::<PlaybackInfo>().playback_state.read(cx) != PlaybackState::Playing {
+        return None;
+    }
+
+    let metadata = cx.global::<Models>().metadata.read(cx);
+    let title = metadata.name.clone()?;
+    let body = match (metadata.artist(), metadata.album.clone()) {
+        (Some(artist), Some(album)) => format!("{} — {}", artist, album),
+        (Some(artist), None) => artist,
+        (None, Some(album)) => album,
+        (None, None) => String::new(),
+    };
+
+    let mut notification = Notification::new();
+    notification
+        .appname("Muzak")
+        .summary(&title)
+        .body(&body)
+        .icon("audio-x-generic")
+        // keeps the notifications out of the notification history
+        .hint(Hint::Transient(true));
+
+    if let Some(id) = state.borrow().last_id {
+        notification.id(id);
+    }
+
+    if let Some(path) = art.and_then(|art| write_art(&art)) {
+        notification.image_path(&path.to_string_lossy());
+    }
+
+    Some(notification)
+}
+
+/// Notification servers read images from disk, so the album art is written to the cache directory,
+/// replacing the art of the previous track.
+fn write_art(art: &[u8]) -> Option<PathBuf> {
+    let directory = get_dirs().cache_dir().to_path_buf();
+    let path = directory.join("now-playing");
+
+    match std::fs::create_dir_all(&directory).and_then(|_| std::fs::write(&path, art)) {
+        Ok(_) => Some(path),
+        Err(e) => {
+            warn!("Could not write album art for notification: {:?}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use ahash::AHashSet;
+use gpui::{AppContext, Context, Global, Model};
+use sqlx::SqlitePool;
+use tracing::{error, info, warn};
+
+use crate::{
+    library::db::find_track_location,
+    playback::{
+        interface::{replace_queue, GPUIPlaybackInterface},
+        skip::SkippedTracks,
+    },
+    services::mmb::lastfm::{client::LastFMClient, LASTFM_API_KEY, LASTFM_API_SECRET},
+    ui::{
+        app::Pool,
+        models::{Models, PlaybackInfo},
+    },
+};
+
+/// How many tracks can be left in the queue before more are fetched.
+const REFILL_THRESHOLD: usize = 3;
+/// The maximum number of tracks added to the queue per refill.
+const TRACKS_PER_REFILL: usize = 10;
+/// How many similar tracks are requested from last.fm. Most of these probably won't be in the
+/// library, so this is much larger than TRACKS_PER_REFILL.
+const SIMILAR_LIMIT: u32 = 100;
+/// How many of the most recent seeds are tried before giving up on a refill.
+const SEED_ATTEMPTS: usize = 3;
+
+pub struct RadioState {
+    /// (artist, title) pairs of the tracks similar tracks are fetched for, most recent last.
+    seeds: Vec<(String, String)>,
+    /// Every path the radio has added to the queue. This is used to avoid repeats, and to notice
+    /// when the queue has been changed by something else (which ends the radio).
+    queued: AHashSet<String>,
+    fetching: bool,
+}
+
+/// Radio mode continuously extends the queue with tracks from the library that last.fm considers
+/// similar to the tracks already played. Tracks that aren't in the library are skipped.
+#[derive(Clone)]
+pub struct Radio(pub Model<Option<RadioState>>);
+
+impl Global for Radio {}
+
+pub fn radio_available() -> bool {
+    LASTFM_API_KEY.is_some() && LASTFM_API_SECRET.is_some()
+}
+
+pub fn setup_radio(cx: &mut AppContext) {
+    let state: Model<Option<RadioState>> = cx.new_model(|_| None);
+    let queue = cx.global::<Models>().queue.clone();
+    let current_track = cx.global::<PlaybackInfo>().current_track.clone();
+
+    let state_clone = state.clone();
+
+    cx.observe(&queue, move |queue, cx| {
+        let foreign = state_clone.read(cx).as_ref().is_some_and(|radio| {
+            queue
+                .read(cx)
+                .0
+                .iter()
+                .any(|path| !radio.queued.contains(path))
+        });
+
+        if foreign {
+            info!("Queue was changed, stopping radio");
+            state_clone.update(cx, |m, cx| {
+                *m = None;
+                cx.notify();
+            });
+        }
+    })
+    .detach();
+
+    let state_clone = state.clone();
+
+    cx.observe(&current_track, move |current_track, cx| {
+        if state_clone.read(cx).is_none() {
+            return;
+        }
+
+        let queue = &cx.global::<Models>().queue.read(cx).0;
+        let remaining = current_track
+            .read(cx)
+            .as_ref()
+            .and_then(|track| queue.iter().position(|path| path == track))
+            .map(|idx| queue.len() - idx - 1)
+            .unwrap_or(0);
+
+        if remaining < REFILL_THRESHOLD {
+            refill(state_clone.clone(), cx);
+        }
+    })
+    .detach();
+
+    cx.set_global(Radio(state));
+}
+
+/// Replaces the queue with the specified track and starts radio mode from it.
+pub fn start_radio(cx: &mut AppContext, path: String, artist: String, title: String) {
+    if !radio_available() {
+        warn!("Muzak was built without last.fm API keys, radio mode is unavailable.");
+        return;
+    }
+
+    let state = cx.global::<Radio>().0.clone();
+
+    state.update(cx, |m, cx| {
+        let mut queued = AHashSet::new();
+        queued.insert(path.clone());
+
+        *m = Some(RadioState {
+            seeds: vec![(artist, title)],
+            queued,
+            fetching: false,
+        });
+        cx.notify();
+    });
+
+    replace_queue(vec![path], cx);
+    refill(state, cx);
+}
+
+/// Starts radio mode from the specified track without replacing the queue, so that the queue is
+/// extended with similar tracks once it runs out.
+pub fn continue_with_radio(cx: &mut AppContext, artist: String, title: String) {
+    if !radio_available() {
+        warn!("Muzak was built without last.fm API keys, radio mode is unavailable.");
+        return;
+    }
+
+    let queued: AHashSet<String> = cx
+        .global::<Models>()
+        .queue
+        .read(cx)
+        .0
+        .iter()
+        .cloned()
+        .collect();
+    let state = cx.global::<Radio>().0.clone();
+
+    state.update(cx, |m, cx| {
+        *m = Some(RadioState {
+            seeds: vec![(artist, title)],
+            queued,
+            fetching: false,
+        });
+        cx.notify();
+    });
+
+    refill(state, cx);
+}
+
+fn refill(state: Model<Option<RadioState>>, cx: &mut AppContext) {
+    let (Some(key), Some(secret)) = (LASTFM_API_KEY, LASTFM_API_SECRET) else {
+        return;
+    };
+
+    let Some(radio) = state.read(cx).as_ref() else {
+        return;
+    };
+
+    if radio.fetching {
+        return;
+    }
+
+    let seeds: Vec<(String, String)> = radio
+        .seeds
+        .iter()
+        .rev()
+        .take(SEED_ATTEMPTS)
+        .cloned()
+        .collect();
+    // tracks that are always skipped are never added
+    let mut exclude = radio.queued.clone();
+    exclude.extend(cx.global::<SkippedTracks>().0.read(cx).iter().cloned());
+
+    state.update(cx, |m, _| {
+        if let Some(m) = m {
+            m.fetching = true;
+        }
+    });
+
+    let pool = cx.global::<Pool>().0.clone();
+
+    cx.spawn(|mut cx| async move {
+        let mut client = LastFMClient::new(key.to_string(), secret);
+        let found =
+            find_similar_in_library(&mut client, &pool, seeds, &exclude, TRACKS_PER_REFILL).await;
+
+        cx.update(|cx| {
+            let mut paths = Vec::new();
+
+            state.update(cx, |m, cx| {
+                // the radio may have been stopped while the request was running
+                let Some(radio) = m else {
+                    return;
+                };
+
+                radio.fetching = false;
+
+                for (location, artist, title) in found {
+                    radio.queued.insert(location.clone());
+                    radio.seeds.push((artist, title));
+                    paths.push(location);
+                }
+
+                cx.notify();
+            });
+
+            if paths.is_empty() {
+                info!("Radio couldn't find any more similar tracks in the library");
+            } else {
+                cx.global::<GPUIPlaybackInterface>().queue_list(paths);
+            }
+        })
+        .expect("failed to update radio state");
+    })
+    .detach();
+}
+
+/// Finds up to `limit` tracks in the library that last.fm considers similar to the seeds, trying
+/// each seed in order until one produces results. Tracks in `exclude` are skipped. Returns
+/// (location, artist, title) for each track found.
+pub async fn find_similar_in_library(
+    client: &mut LastFMClient,
+    pool: &SqlitePool,
+    seeds: Vec<(String, String)>,
+    exclude: &AHashSet<String>,
+    limit: usize,
+) -> Vec<(String, String, String)> {
+    let mut found: Vec<(String, String, String)> = Vec::new();
+
+    for (artist, title) in seeds {
+        let similar = match client.get_similar(artist, title, SIMILAR_LIMIT).await {
+            Ok(similar) => similar,
+            Err(e) => {
+                warn!("Could not fetch similar tracks: {}", e);
+                continue;
+            }
+        };
+
+        for (artist, title) in similar {
+            if found.len() >= limit {
+                break;
+            }
+
+            match find_track_location(pool, &artist, &title).await {
+                Ok(Some(location)) => {
+                    if !exclude.contains(&location) && !found.iter().any(|v| v.0 == location) {
+                        found.push((location, artist, title));
+                    }
+                }
+                Ok(None) => (),
+                Err(e) => error!("Database error while matching similar track: {:?}", e),
+            }
+        }
+
+        if !found.is_empty() {
+            break;
+        }
+    }
+
+    found
+}
@@ -0,0 +1,219 @@
+use std::{
+    sync::mpsc::{channel, Sender, TryRecvError},
+    time::Duration,
+};
+
+use gpui::{AppContext, Global, WindowContext};
+use ksni::{
+    menu::{MenuItem, StandardItem},
+    Handle, ToolTip, Tray, TrayService,
+};
+use tracing::debug;
+
+use crate::{
+    playback::thread::PlaybackState,
+    settings::SettingsGlobal,
+    ui::{
+        global_actions::{Next, PlayPause, Previous, Quit},
+        models::{Models, PlaybackInfo},
+    },
+};
+
+#[derive(Debug, Clone, Copy)]
+enum TrayEvent {
+    Show,
+    PlayPause,
+    Next,
+    Previous,
+    Quit,
+}
+
+/// The tray icon's state. The tray runs on its own thread, so the menu sends events back to the
+/// main thread instead of acting on them directly.
+struct MuzakTray {
+    events_tx: Sender<TrayEvent>,
+    /// The current track, as "Artist - Title".
+    now_playing: Option<String>,
+    playing: bool,
+}
+
+impl MuzakTray {
+    fn send(&self, event: TrayEvent) {
+        self.events_tx.send(event).ok();
+    }
+}
+
+impl Tray for MuzakTray {
+    fn id(&self) -> String {
+        "muzak".to_string()
+    }
+
+    fn title(&self) -> String {
+        "Muzak".to_string()
+    }
+
+    fn icon_name(&self) -> String {
+        "audio-x-generic".to_string()
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: self
+                .now_playing
+                .clone()
+                .unwrap_or_else(|| "Muzak".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn activate(&mut self, _: i32, _: i32) {
+        self.send(TrayEvent::Show);
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        vec![
+            StandardItem {
+                label: self
+                    .now_playing
+                    .clone()
+                    .unwrap_or_else(|| "Nothing playing".to_string()),
+                enabled: false,
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: if self.playing { "Pause" } else { "Play" }.to_string(),
+                activate: Box::new(|this: &mut Self| this.send(TrayEvent::PlayPause)),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Next".to_string(),
+                activate: Box::new(|this: &mut Self| this.send(TrayEvent::Next)),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Previous".to_string(),
+                activate: Box::new(|this: &mut Self| this.send(TrayEvent::Previous)),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: "Show Muzak".to_string(),
+                activate: Box::new(|this: &mut Self| this.send(TrayEvent::Show)),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Quit".to_string(),
+                activate: Box::new(|this: &mut Self| this.send(TrayEvent::Quit)),
+                ..Default::default()
+            }
+            .into(),
+        ]
+    }
+}
+
+struct TrayHandle(Handle<MuzakTray>);
+
+impl Global for TrayHandle {}
+
+/// Shows the tray icon if it's enabled, and keeps its menu up to date with what's playing. The
+/// tray uses the StatusNotifierItem protocol, so this is only available on Linux.
+// TODO: macOS and Windows
+pub fn setup_tray(cx: &mut AppContext) {
+    if !cx
+        .global::<SettingsGlobal>()
+        .model
+        .read(cx)
+        .interface
+        .tray_icon
+    {
+        return;
+    }
+
+    let (events_tx, events_rx) = channel();
+
+    let service = TrayService::new(MuzakTray {
+        events_tx,
+        now_playing: None,
+        playing: false,
+    });
+    cx.set_global(TrayHandle(service.handle()));
+    service.spawn();
+
+    cx.spawn(|mut cx| async move {
+        loop {
+            match events_rx.try_recv() {
+                Ok(event) => {
+                    debug!("tray event: {:?}", event);
+                    cx.update(|cx| handle_event(cx, event))
+                        .expect("failed to handle tray event");
+                }
+                Err(TryRecvError::Empty) => {
+                    cx.background_executor()
+                        .timer(Duration::from_millis(50))
+                        .await
+                }
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+    })
+    .detach();
+
+    let playback_state = cx.global::<PlaybackInfo>().playback_state.clone();
+
+    cx.observe(&playback_state, |playback_state, cx| {
+        let playing = *playback_state.read(cx) == PlaybackState::Playing;
+
+        cx.global::<TrayHandle>().0.update(move |tray| {
+            tray.playing = playing;
+        });
+    })
+    .detach();
+
+    let metadata = cx.global::<Models>().metadata.clone();
+
+    cx.observe(&metadata, |metadata, cx| {
+        let metadata = metadata.read(cx);
+        let now_playing = match (metadata.artist(), metadata.name.clone()) {
+            (Some(artist), Some(title)) => Some(format!("{} - {}", artist, title)),
+            (None, Some(title)) => Some(title),
+            _ => None,
+        };
+
+        cx.global::<TrayHandle>().0.update(move |tray| {
+            tray.now_playing = now_playing;
+        });
+    })
+    .detach();
+}
+
+/// The menu items are handled the same way as the corresponding actions.
+fn handle_event(cx: &mut AppContext, event: TrayEvent) {
+    match event {
+        TrayEvent::Show => {
+            for window in cx.windows() {
+                window.update(cx, |_, cx| cx.activate_window()).ok();
+            }
+        }
+        TrayEvent::PlayPause => cx.dispatch_action(&PlayPause),
+        TrayEvent::Next => cx.dispatch_action(&Next),
+        TrayEvent::Previous => cx.dispatch_action(&Previous),
+        TrayEvent::Quit => cx.dispatch_action(&Quit),
+    }
+}
+
+/// Called when the window is about to close. With the tray icon enabled, the window is minimized
+/// instead, so that Muzak keeps playing from the tray. Returns whether the window should close.
+pub fn should_close(cx: &mut WindowContext) -> bool {
+    if cx.has_global::<TrayHandle>() {
+        cx.minimize_window();
+        false
+    } else {
+        true
+    }
+}
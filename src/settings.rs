@@ -1,3 +1,7 @@
+pub mod interface;
+pub mod lastfm;
+pub mod playback;
+pub mod remote;
 pub mod scan;
 
 use std::{fs::File, path::PathBuf, sync::mpsc::channel, time::Duration};
@@ -7,10 +11,20 @@ use notify::{Event, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
 
+use crate::{logging::set_log_level, util::set_image_layout};
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Settings {
     #[serde(default)]
     pub scanning: scan::ScanSettings,
+    #[serde(default)]
+    pub interface: interface::InterfaceSettings,
+    #[serde(default)]
+    pub playback: playback::PlaybackSettings,
+    #[serde(default)]
+    pub remote: remote::RemoteSettings,
+    #[serde(default)]
+    pub lastfm: lastfm::LastFMSettings,
 }
 
 pub fn create_settings(path: &PathBuf) -> Settings {
@@ -30,6 +44,7 @@ pub fn create_settings(path: &PathBuf) -> Settings {
 
 pub struct SettingsGlobal {
     pub model: Model<Settings>,
+    pub path: PathBuf,
     pub watcher: Option<Box<dyn Watcher>>,
 }
 
@@ -39,6 +54,15 @@ pub fn setup_settings(cx: &mut AppContext, path: PathBuf) {
     let settings = cx.new_model(|_| create_settings(&path));
     let settings_model = settings.clone(); // for the closure
 
+    set_image_layout(settings.read(cx).interface.image_layout);
+    set_log_level(settings.read(cx).interface.log_level.as_deref());
+
+    cx.observe(&settings, |settings, cx| {
+        set_image_layout(settings.read(cx).interface.image_layout);
+        set_log_level(settings.read(cx).interface.log_level.as_deref());
+    })
+    .detach();
+
     // create and setup file watcher
     let (tx, rx) = channel::<notify::Result<Event>>();
 
@@ -49,6 +73,8 @@ pub fn setup_settings(cx: &mut AppContext, path: PathBuf) {
             warn!("failed to watch settings file: {:?}", e);
         }
 
+        let path_clone = path.clone();
+
         cx.spawn(|mut cx: AsyncAppContext| async move {
             loop {
                 while let Ok(event) = rx.try_recv() {
@@ -58,18 +84,20 @@ pub fn setup_settings(cx: &mut AppContext, path: PathBuf) {
                                 match v.kind {
                                     notify::EventKind::Create(_) | notify::EventKind::Modify(_) => {
                                         info!("Settings changed, updating...");
-                                        let settings = create_settings(&path);
+                                        let settings = create_settings(&path_clone);
                                         settings_model
-                                            .update(&mut cx, |v, _| {
+                                            .update(&mut cx, |v, cx| {
                                                 *v = settings;
+                                                cx.notify();
                                             })
                                             .expect("settings model could not be updated");
                                     }
                                     notify::EventKind::Remove(_) => {
                                         info!("Settings file removed, using default settings");
                                         settings_model
-                                            .update(&mut cx, |v, _| {
+                                            .update(&mut cx, |v, cx| {
                                                 *v = Settings::default();
+                                                cx.notify();
                                             })
                                             .expect("settings model could not be updated");
                                     }
@@ -90,6 +118,7 @@ pub fn setup_settings(cx: &mut AppContext, path: PathBuf) {
 
         let global = SettingsGlobal {
             model: settings,
+            path,
             watcher: Some(Box::new(watcher)),
         };
 
@@ -99,9 +128,32 @@ pub fn setup_settings(cx: &mut AppContext, path: PathBuf) {
 
         let global = SettingsGlobal {
             model: settings,
+            path,
             watcher: None,
         };
 
         cx.set_global(global);
     }
 }
+
+/// Applies a change to the settings and writes them to disk.
+pub fn save_settings(cx: &mut AppContext, update: impl FnOnce(&mut Settings)) {
+    let global = cx.global::<SettingsGlobal>();
+    let model = global.model.clone();
+    let path = global.path.clone();
+
+    model.update(cx, |settings, cx| {
+        update(settings);
+        cx.notify();
+
+        // the watcher will reload the file, which is harmless
+        match File::create(&path) {
+            Ok(file) => {
+                if let Err(e) = serde_json::to_writer_pretty(file, settings) {
+                    warn!("Failed to write settings file: {:?}", e);
+                }
+            }
+            Err(e) => warn!("Failed to open settings file for writing: {:?}", e),
+        }
+    });
+}
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{data::events::ImageLayout, library::db::AlbumSortMethod};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ListDensity {
+    /// Smaller rows and text, to fit more on screen.
+    Compact,
+    #[default]
+    Comfortable,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickToPlay {
+    /// Clicking a track plays it.
+    #[default]
+    SingleClick,
+    /// Clicking a track selects it, and double-clicking plays it.
+    DoubleClick,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InterfaceSettings {
+    /// Keeps the original aspect ratio of album art (letterboxing it) instead of center-cropping
+    /// it to a square.
+    #[serde(default)]
+    pub preserve_album_art_aspect: bool,
+    /// Renders a blurred copy of the album art behind the release header.
+    #[serde(default = "default_true")]
+    pub album_art_backdrop: bool,
+    /// Shows the codec and sample rate/bit depth (or bitrate) of each track in the queue.
+    #[serde(default)]
+    pub show_audio_quality: bool,
+    /// Skips decoding and displaying album art everywhere, rendering placeholders instead.
+    #[serde(default)]
+    pub disable_album_art: bool,
+    /// The row height and text size of the track, album, and queue lists.
+    #[serde(default)]
+    pub list_density: ListDensity,
+    /// Whether tracks in track lists are played with a single click or a double click.
+    #[serde(default)]
+    pub click_to_play: ClickToPlay,
+    /// Overrides the theme's primary color, in the "#RRGGBB" format.
+    #[serde(default)]
+    pub accent_color: Option<String>,
+    /// Tints the accent color to match the album art of the current track, taking priority over
+    /// `accent_color`.
+    #[serde(default)]
+    pub dynamic_accent: bool,
+    /// Patterns used to guess the title and artist of files without tags from their paths, tried
+    /// in order. `{title}`, `{artist}`, `{album}`, and `{track}` match parts of the path, and `/`
+    /// separates folders.
+    #[serde(default = "default_filename_patterns")]
+    pub filename_patterns: Vec<String>,
+    /// How large the on-disk cache of queue thumbnails can grow, in megabytes. 0 disables it.
+    #[serde(default = "default_thumbnail_cache_size")]
+    pub thumbnail_cache_size: u64,
+    /// Replaces the seek bar with an overview of the current track's waveform. Peaks are computed
+    /// the first time a track is played, and stored in the library.
+    #[serde(default)]
+    pub waveform_seek_bar: bool,
+    /// Plays animations, such as the pulsing scan indicator. Turning this off shows everything in
+    /// its final state instead.
+    // TODO: follow the OS's reduce motion setting once gpui exposes it
+    #[serde(default = "default_true")]
+    pub animations_enabled: bool,
+    /// Shows an icon in the system tray with playback controls. Closing the window minimizes it
+    /// instead while the icon is shown. Only supported on Linux, and read at startup.
+    #[serde(default)]
+    pub tray_icon: bool,
+    /// How dates are shown, as a strftime-style pattern (e.g. `%Y-%m-%d`). If unset, the system
+    /// locale's date format is used.
+    #[serde(default)]
+    pub date_format: Option<String>,
+    /// Fetches covers from the Cover Art Archive for queued files without embedded art, using
+    /// their release's MusicBrainz ID. Fetched covers are kept on disk.
+    #[serde(default)]
+    pub remote_album_art: bool,
+    /// Shows a desktop notification with the title, artist, album, and art of each new track.
+    /// Only supported on Linux.
+    #[serde(default)]
+    pub track_notifications: bool,
+    /// Doesn't show track notifications while Muzak's window is focused.
+    #[serde(default = "default_true")]
+    pub suppress_notifications_when_focused: bool,
+    /// The channel order images are decoded into, `"bgr"` or `"rgb"`. If unset, the order gpui
+    /// expects is used. Only needs to be set if album art shows with red and blue swapped.
+    #[serde(default)]
+    pub image_layout: Option<ImageLayout>,
+    /// The order albums are listed in in the library. Can be changed from the library header.
+    #[serde(default)]
+    pub album_sort: AlbumSortMethod,
+    /// Which events are logged and shown in the log viewer, e.g. `"debug"` or
+    /// `"muzak=debug,warn"`. Ignored if `MUZAK_LOG` or `RUST_LOG` is set.
+    #[serde(default)]
+    pub log_level: Option<String>,
+    /// Shows a small meter of the current output level next to the volume slider.
+    #[serde(default = "default_true")]
+    pub level_meter: bool,
+    /// What the controls show on the first line for the current track, as a template like
+    /// `{title}`. See [`crate::ui::util::format_metadata`] for the syntax.
+    #[serde(default = "default_controls_title")]
+    pub controls_title: String,
+    /// What the controls show on the second line, like `controls_title`.
+    #[serde(default = "default_controls_subtitle")]
+    pub controls_subtitle: String,
+}
+
+impl Default for InterfaceSettings {
+    fn default() -> Self {
+        Self {
+            preserve_album_art_aspect: false,
+            album_art_backdrop: true,
+            show_audio_quality: false,
+            disable_album_art: false,
+            list_density: ListDensity::Comfortable,
+            click_to_play: ClickToPlay::SingleClick,
+            accent_color: None,
+            dynamic_accent: false,
+            filename_patterns: default_filename_patterns(),
+            thumbnail_cache_size: default_thumbnail_cache_size(),
+            waveform_seek_bar: false,
+            animations_enabled: true,
+            tray_icon: false,
+            date_format: None,
+            remote_album_art: false,
+            track_notifications: false,
+            suppress_notifications_when_focused: true,
+            image_layout: None,
+            album_sort: AlbumSortMethod::TitleAsc,
+            log_level: None,
+            level_meter: true,
+            controls_title: default_controls_title(),
+            controls_subtitle: default_controls_subtitle(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_filename_patterns() -> Vec<String> {
+    [
+        "{track} - {artist} - {title}",
+        "{artist}/{album}/{track} - {title}",
+        "{artist}/{album}/{track} {title}",
+        "{artist} - {title}",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+fn default_controls_title() -> String {
+    "{title}".to_string()
+}
+
+fn default_controls_subtitle() -> String {
+    "{artist}".to_string()
+}
+
+fn default_thumbnail_cache_size() -> u64 {
+    64
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrobbleMode {
+    /// Scrobbles are submitted as soon as a track has been listened to.
+    #[default]
+    Immediate,
+    /// Scrobbles are queued, and submitted together every `sync_interval` minutes.
+    Scheduled,
+    /// Scrobbles are queued until they're submitted from the last.fm menu.
+    Manual,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LastFMSettings {
+    #[serde(default)]
+    pub scrobble_mode: ScrobbleMode,
+    /// How often queued scrobbles are submitted when `scrobble_mode` is scheduled, in minutes.
+    #[serde(default = "default_sync_interval")]
+    pub sync_interval: u64,
+    /// Only scrobbles tracks that are in the library, so that loose files aren't scrobbled.
+    #[serde(default)]
+    pub library_only: bool,
+}
+
+fn default_sync_interval() -> u64 {
+    60
+}
+
+impl Default for LastFMSettings {
+    fn default() -> Self {
+        Self {
+            scrobble_mode: ScrobbleMode::Immediate,
+            sync_interval: default_sync_interval(),
+            library_only: false,
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ShuffleMode {
+    /// Shuffles every track individually.
+    #[default]
+    Tracks,
+    /// Shuffles the order of albums, keeping the tracks of each album in order.
+    Albums,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EndOfQueue {
+    /// Stops playback.
+    #[default]
+    Stop,
+    /// Starts again from the first track in the queue.
+    RepeatAll,
+    /// Starts the radio from the last track in the queue, which extends the queue with similar
+    /// tracks from the library. Requires last.fm.
+    Radio,
+    /// Queues the next album by the same artist (or an album containing a similar track, if
+    /// last.fm is available).
+    SimilarAlbum,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClippingMode {
+    /// Reduces positive gain offsets so that the track's ReplayGain peak doesn't clip, and softly
+    /// limits loud passages of tracks without a peak tag instead.
+    #[default]
+    Prevent,
+    /// Applies the whole offset, even if that makes loud passages clip.
+    Allow,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlaybackSettings {
+    /// What happens once the last track in the queue has been played. Continuing with the radio
+    /// or another album happens as soon as the last track starts, so that there's no gap.
+    #[serde(default)]
+    pub end_of_queue: EndOfQueue,
+    #[serde(default)]
+    pub shuffle_mode: ShuffleMode,
+    /// Saves the history of played tracks, so that it's kept between sessions.
+    #[serde(default)]
+    pub persist_history: bool,
+    /// Makes Previous go back through the history of played tracks instead of the queue.
+    #[serde(default)]
+    pub previous_navigates_history: bool,
+    /// How far into a track Previous restarts it instead of going back to the previous track, in
+    /// seconds. 0 always goes back.
+    #[serde(default = "default_previous_restart_threshold")]
+    pub previous_restart_threshold: u64,
+    /// Returns playback to normal speed whenever a new track starts.
+    #[serde(default)]
+    pub reset_speed_per_track: bool,
+    /// Offers to undo replacing a queue that still has unplayed tracks.
+    #[serde(default)]
+    pub undo_queue_replacement: bool,
+    /// How long playback fades in and out when starting, pausing, stopping, and seeking, in
+    /// milliseconds. 0 disables fading.
+    #[serde(default = "default_fade_duration")]
+    pub fade_duration: u64,
+    /// The volume last used with each output device, by the device's UID. Devices that aren't
+    /// listed keep whatever volume was in use before switching to them.
+    #[serde(default)]
+    pub device_volumes: AHashMap<String, f64>,
+    /// Opens the new device before closing the old one when switching output devices, so that
+    /// playback continues without a gap.
+    #[serde(default)]
+    pub seamless_device_switch: bool,
+    /// Other output devices that are given the same audio as the current one, by the device's
+    /// UID. These play at the same volume, but aren't kept in sync with each other.
+    #[serde(default)]
+    pub monitor_devices: Vec<String>,
+    /// How many of the upcoming tracks have their start read ahead of time, so that they open
+    /// without waiting for the disk. Up to 8, and 0 disables reading ahead.
+    #[serde(default = "default_prebuffer_count")]
+    pub prebuffer_count: usize,
+    /// The least time between updates of the position shown in the interface, in milliseconds.
+    #[serde(default = "default_position_update_interval")]
+    pub position_update_interval: u64,
+    /// The least time between position updates sent to services like last.fm, in milliseconds.
+    #[serde(default = "default_service_position_interval")]
+    pub service_position_interval: u64,
+    /// Moves on to the next track when a file can't be played, instead of stopping.
+    #[serde(default = "default_true")]
+    pub skip_on_error: bool,
+    /// Whether gain offsets can push the audio past full scale.
+    #[serde(default)]
+    pub clipping: ClippingMode,
+}
+
+impl Default for PlaybackSettings {
+    fn default() -> Self {
+        Self {
+            end_of_queue: EndOfQueue::Stop,
+            shuffle_mode: ShuffleMode::Tracks,
+            persist_history: false,
+            previous_navigates_history: false,
+            previous_restart_threshold: default_previous_restart_threshold(),
+            reset_speed_per_track: false,
+            undo_queue_replacement: false,
+            fade_duration: default_fade_duration(),
+            device_volumes: AHashMap::new(),
+            seamless_device_switch: false,
+            monitor_devices: Vec::new(),
+            prebuffer_count: default_prebuffer_count(),
+            position_update_interval: default_position_update_interval(),
+            service_position_interval: default_service_position_interval(),
+            skip_on_error: true,
+            clipping: ClippingMode::Prevent,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_fade_duration() -> u64 {
+    30
+}
+
+fn default_previous_restart_threshold() -> u64 {
+    3
+}
+
+fn default_prebuffer_count() -> usize {
+    1
+}
+
+fn default_position_update_interval() -> u64 {
+    16
+}
+
+fn default_service_position_interval() -> u64 {
+    1000
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RemoteSettings {
+    /// Starts an HTTP server that playback can be controlled through.
+    #[serde(default)]
+    pub enabled: bool,
+    /// The address the server listens on. Only local connections are accepted by default.
+    #[serde(default = "default_address")]
+    pub address: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+}
+
+fn default_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_port() -> u16 {
+    7245
+}
+
+impl Default for RemoteSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: default_address(),
+            port: default_port(),
+        }
+    }
+}
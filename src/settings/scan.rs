@@ -3,16 +3,48 @@ use std::{fs::exists, path::PathBuf};
 use serde::{Deserialize, Serialize};
 use tracing::{error, warn};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What decides which tracks belong to the same album.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AlbumGrouping {
+    /// Tracks with the same album title and album artist.
+    #[default]
+    ArtistAndTitle,
+    /// Tracks with the same MusicBrainz album ID, falling back to the album title and artist for
+    /// tracks without one.
+    MusicbrainzId,
+    /// Tracks in the same folder.
+    Folder,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ScanSettings {
     #[serde(default = "retrieve_default_paths")]
     pub paths: Vec<PathBuf>,
+    /// Pauses scanning while music is playing, to avoid stuttering on slower machines.
+    #[serde(default)]
+    pub pause_while_playing: bool,
+    /// Changing this rescans the whole library.
+    #[serde(default)]
+    pub album_grouping: AlbumGrouping,
+    /// Scans the folders and files that symlinks inside the library point to. Folders are only
+    /// scanned once, so symlinks that loop back are safe.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Pre-renders the queue thumbnails of every album with art into the disk thumbnail cache
+    /// after scanning, so that they don't have to be decoded when the album is first queued.
+    #[serde(default)]
+    pub prerender_thumbnails: bool,
 }
 
 impl Default for ScanSettings {
     fn default() -> Self {
         Self {
             paths: retrieve_default_paths(),
+            pause_while_playing: false,
+            album_grouping: AlbumGrouping::default(),
+            follow_symlinks: false,
+            prerender_thumbnails: false,
         }
     }
 }
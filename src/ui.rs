@@ -1,13 +1,21 @@
+mod about;
+mod accent;
 pub mod app;
-mod arguments;
+pub mod arguments;
 mod assets;
 mod components;
 mod constants;
 mod controls;
-mod global_actions;
+pub mod global_actions;
 mod header;
 mod library;
+mod logs;
 pub mod models;
+mod palette;
 mod queue;
 mod theme;
+mod toast;
+mod track_details;
+mod trim;
+mod unreadable;
 mod util;
@@ -0,0 +1,150 @@
+use gpui::*;
+use tracing::error;
+
+use crate::services::mmb::lastfm::{LASTFM_API_KEY, LASTFM_API_SECRET};
+
+use super::{
+    components::button::{button, ButtonIntent},
+    constants::FONT_AWESOME,
+    global_actions::ShowLogs,
+    models::Models,
+    theme::Theme,
+};
+
+const REPOSITORY_URL: &str = "https://github.com/143mailliw/muzak";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const COMMIT: Option<&str> = option_env!("MUZAK_COMMIT");
+
+pub struct AboutDialog {
+    show_about: Model<bool>,
+}
+
+impl AboutDialog {
+    pub fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
+        cx.new_view(|cx| {
+            let show_about = cx.global::<Models>().show_about.clone();
+
+            cx.observe(&show_about, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            Self { show_about }
+        })
+    }
+}
+
+pub(super) fn info_row(label: &'static str, value: impl Into<SharedString>, theme: &Theme) -> Div {
+    div()
+        .flex()
+        .text_sm()
+        .child(div().text_color(theme.text_secondary).child(label))
+        .child(div().ml_auto().child(value.into()))
+}
+
+impl Render for AboutDialog {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        if !*self.show_about.read(cx) {
+            return div().id("about-dialog");
+        }
+
+        let theme = cx.global::<Theme>();
+        let show_about = self.show_about.clone();
+
+        let profile = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+        let lastfm = if LASTFM_API_KEY.is_some() && LASTFM_API_SECRET.is_some() {
+            "Included"
+        } else {
+            "Not included"
+        };
+
+        div()
+            .id("about-dialog")
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full()
+            .flex()
+            .items_center()
+            .justify_center()
+            .occlude()
+            .child(
+                div()
+                    .id("about-dialog-content")
+                    .w(px(320.0))
+                    .p(px(16.0))
+                    .flex()
+                    .flex_col()
+                    .gap(px(6.0))
+                    .border_1()
+                    .shadow_sm()
+                    .rounded(px(4.0))
+                    .border_color(theme.elevated_border_color)
+                    .bg(theme.elevated_background)
+                    .on_mouse_down_out(move |_, cx| {
+                        show_about.update(cx, |m, cx| {
+                            *m = false;
+                            cx.notify();
+                        })
+                    })
+                    .child(
+                        div()
+                            .font_weight(FontWeight::EXTRA_BOLD)
+                            .text_size(px(26.0))
+                            .line_height(px(26.0))
+                            .child("Muzak"),
+                    )
+                    .child(
+                        div()
+                            .mb(px(10.0))
+                            .text_sm()
+                            .text_color(theme.text_secondary)
+                            .child(format!("Version {}", VERSION)),
+                    )
+                    .child(info_row(
+                        "Commit",
+                        COMMIT.unwrap_or("unknown").to_string(),
+                        theme,
+                    ))
+                    .child(info_row("Build profile", profile, theme))
+                    .child(info_row(
+                        "Target",
+                        format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS),
+                        theme,
+                    ))
+                    .child(info_row("last.fm keys", lastfm, theme))
+                    .child(
+                        div()
+                            .mt(px(10.0))
+                            .flex()
+                            .gap(px(6.0))
+                            .child(
+                                button()
+                                    .intent(ButtonIntent::Secondary)
+                                    .child(div().font_family(FONT_AWESOME).child(""))
+                                    .child("Repository")
+                                    .id("about-repository")
+                                    .on_click(|_, _| {
+                                        if open::that(REPOSITORY_URL).is_err() {
+                                            error!(
+                                                "Failed to open web browser to {}",
+                                                REPOSITORY_URL
+                                            );
+                                        }
+                                    }),
+                            )
+                            .child(
+                                button()
+                                    .intent(ButtonIntent::Secondary)
+                                    .child("Logs")
+                                    .id("about-logs")
+                                    .on_click(|_, cx| cx.dispatch_action(Box::new(ShowLogs))),
+                            ),
+                    ),
+            )
+    }
+}
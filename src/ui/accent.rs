@@ -0,0 +1,153 @@
+use gpui::*;
+use prelude::FluentBuilder;
+
+use crate::settings::{save_settings, SettingsGlobal};
+
+use super::{
+    components::button::{button, ButtonIntent},
+    constants::FONT_AWESOME,
+    models::Models,
+    theme::{parse_accent, Theme},
+};
+
+/// The colors offered by the accent picker.
+const SWATCHES: [u32; 10] = [
+    0x0667B2, 0x2563EB, 0x7C3AED, 0xC026D3, 0xDB2777, 0xDC2626, 0xEA580C, 0xCA8A04, 0x16A34A,
+    0x0D9488,
+];
+
+pub struct AccentPicker {
+    show_accent_picker: Model<bool>,
+}
+
+impl AccentPicker {
+    pub fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
+        cx.new_view(|cx| {
+            let show_accent_picker = cx.global::<Models>().show_accent_picker.clone();
+            let settings = cx.global::<SettingsGlobal>().model.clone();
+
+            cx.observe(&show_accent_picker, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            cx.observe(&settings, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            Self { show_accent_picker }
+        })
+    }
+}
+
+fn set_accent(accent: Option<String>, cx: &mut WindowContext) {
+    save_settings(cx, |settings| {
+        settings.interface.accent_color = accent;
+    });
+}
+
+impl Render for AccentPicker {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        if !*self.show_accent_picker.read(cx) {
+            return div().id("accent-picker");
+        }
+
+        let theme = cx.global::<Theme>();
+        let show_accent_picker = self.show_accent_picker.clone();
+        let interface = &cx.global::<SettingsGlobal>().model.read(cx).interface;
+        let current = interface.accent_color.as_deref().and_then(parse_accent);
+        let dynamic_accent = interface.dynamic_accent;
+
+        div()
+            .id("accent-picker")
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full()
+            .flex()
+            .items_center()
+            .justify_center()
+            .occlude()
+            .child(
+                div()
+                    .id("accent-picker-content")
+                    .w(px(320.0))
+                    .p(px(16.0))
+                    .flex()
+                    .flex_col()
+                    .gap(px(10.0))
+                    .border_1()
+                    .shadow_sm()
+                    .rounded(px(4.0))
+                    .border_color(theme.elevated_border_color)
+                    .bg(theme.elevated_background)
+                    .on_mouse_down_out(move |_, cx| {
+                        show_accent_picker.update(cx, |m, cx| {
+                            *m = false;
+                            cx.notify();
+                        })
+                    })
+                    .child(div().font_weight(FontWeight::BOLD).child("Accent color"))
+                    .child(
+                        div()
+                            .flex()
+                            .flex_wrap()
+                            .gap(px(8.0))
+                            .children(SWATCHES.iter().map(|hex| {
+                                let hex = *hex;
+                                let color = rgb(hex);
+                                let selected = current == Some(color);
+
+                                div()
+                                    .id(("accent-swatch", hex as usize))
+                                    .size(px(40.0))
+                                    .flex()
+                                    .items_center()
+                                    .justify_center()
+                                    .rounded(px(4.0))
+                                    .bg(color)
+                                    .border_2()
+                                    .border_color(if selected { theme.text } else { color })
+                                    .hover(|this| this.border_color(theme.text_secondary))
+                                    .when(selected, |this| {
+                                        this.text_color(rgb(0xFFFFFF))
+                                            .font_family(FONT_AWESOME)
+                                            .text_sm()
+                                            .child("")
+                                    })
+                                    .on_click(move |_, cx| {
+                                        set_accent(Some(format!("#{:06X}", hex)), cx);
+                                    })
+                            })),
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .gap(px(6.0))
+                            .child(
+                                button()
+                                    .intent(ButtonIntent::Secondary)
+                                    .child("Reset to theme default")
+                                    .id("accent-picker-reset")
+                                    .on_click(|_, cx| set_accent(None, cx)),
+                            )
+                            .child(
+                                button()
+                                    .intent(if dynamic_accent {
+                                        ButtonIntent::Primary
+                                    } else {
+                                        ButtonIntent::Secondary
+                                    })
+                                    .child("Match album art")
+                                    .id("accent-picker-dynamic")
+                                    .on_click(move |_, cx| {
+                                        save_settings(cx, |settings| {
+                                            settings.interface.dynamic_accent = !dynamic_accent;
+                                        })
+                                    }),
+                            ),
+                    ),
+            )
+    }
+}
@@ -1,40 +1,86 @@
 use core::panic;
-use std::{cell::RefCell, fs, rc::Rc, sync::Arc};
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
+use ahash::{AHashMap, AHashSet};
 
 use directories::ProjectDirs;
 use gpui::*;
 use prelude::FluentBuilder;
 use sqlx::SqlitePool;
-use tracing::{debug, error};
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    data::{interface::GPUIDataInterface, thread::DataThread},
+    data::{interface::GPUIDataInterface, peaks::setup_peaks, thread::DataThread},
+    ipc::start_server,
     library::{
         db::{create_cache, create_pool},
-        scan::{ScanInterface, ScanThread},
+        scan::{
+            setup_scan_pausing, setup_scan_settings, setup_thumbnail_prerender, ScanInterface,
+            ScanThread,
+        },
+    },
+    playback::{
+        fade::setup_fade, failure::setup_failure_handling, gain::setup_gain,
+        history::setup_history, interface::GPUIPlaybackInterface, level::setup_level_meter,
+        monitor::setup_monitors, prebuffer::setup_prebuffer, resume::setup_resume,
+        shuffle::setup_shuffle, skip::setup_skip, speed::setup_speed, thread::PlaybackThread,
+        trim::setup_trim, volume::setup_volume,
+    },
+    remote::start_remote_server,
+    services::{
+        continuation::setup_continuation, media_keys::setup_media_keys, radio::setup_radio,
     },
-    playback::{interface::GPUIPlaybackInterface, thread::PlaybackThread},
     settings::{setup_settings, SettingsGlobal},
 };
 
+#[cfg(target_os = "linux")]
+use crate::services::{
+    notifications::setup_notifications,
+    tray::{setup_tray, should_close},
+};
+
 use super::{
-    arguments::parse_args_and_prepare,
+    about::AboutDialog,
+    accent::AccentPicker,
+    arguments::{data_dir_argument, parse_args_and_prepare},
     assets::Assets,
     constants::APP_ROUNDING,
     controls::Controls,
     global_actions::register_actions,
     header::Header,
     library::Library,
-    models::build_models,
+    logs::LogViewer,
+    models::{build_models, Models},
+    palette::CommandPalette,
     queue::Queue,
-    theme::{setup_theme, Theme},
+    theme::{setup_accent, setup_theme, Theme},
+    toast::{ErrorToast, UndoToast},
+    track_details::TrackDetailsDialog,
+    trim::TrimDialog,
+    unreadable::UnreadableFilesDialog,
 };
 
 struct WindowShadow {
+    pub about: View<AboutDialog>,
+    pub trim: View<TrimDialog>,
+    pub unreadable_files: View<UnreadableFilesDialog>,
+    pub logs: View<LogViewer>,
+    pub accent_picker: View<AccentPicker>,
     pub controls: View<Controls>,
     pub queue: View<Queue>,
     pub library: View<Library>,
     pub header: View<Header>,
+    pub undo_toast: View<UndoToast>,
+    pub error_toast: View<ErrorToast>,
+    pub track_details: View<TrackDetailsDialog>,
+    pub palette: View<CommandPalette>,
     pub show_queue: Model<bool>,
 }
 
@@ -165,6 +211,7 @@ impl Render for WindowShadow {
                         cx.stop_propagation();
                     })
                     .overflow_hidden()
+                    .relative()
                     .bg(theme.background_primary)
                     .size_full()
                     .flex()
@@ -182,7 +229,16 @@ impl Render for WindowShadow {
                             .child(self.library.clone())
                             .when(*self.show_queue.read(cx), |this| this.child(queue)),
                     )
-                    .child(self.controls.clone()),
+                    .child(self.controls.clone())
+                    .child(self.undo_toast.clone())
+                    .child(self.error_toast.clone())
+                    .child(self.about.clone())
+                    .child(self.logs.clone())
+                    .child(self.accent_picker.clone())
+                    .child(self.track_details.clone())
+                    .child(self.trim.clone())
+                    .child(self.unreadable_files.clone())
+                    .child(self.palette.clone()),
             )
     }
 }
@@ -247,29 +303,211 @@ pub struct Pool(pub SqlitePool);
 
 impl Global for Pool {}
 
+/// How often unused images are dropped from the sprite atlas, regardless of how many are pending.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+/// The number of pending images that triggers a flush before the interval has elapsed.
+const FLUSH_THRESHOLD: usize = 64;
+
 #[derive(Debug, PartialEq, Clone, Default)]
-pub struct DropOnNavigateQueue(Rc<RefCell<Vec<Arc<RenderImage>>>>);
+pub struct DropOnNavigateQueue {
+    pending: Rc<RefCell<Vec<Arc<RenderImage>>>>,
+    /// Album art currently displayed by the queue, keyed by queue index.
+    queue_images: Rc<RefCell<AHashMap<usize, (String, Arc<RenderImage>)>>>,
+}
 
 impl DropOnNavigateQueue {
-    pub fn drop_all(&self, cx: &mut WindowContext) {
-        let mut borrow = self.0.borrow_mut();
-
-        while let Some(item) = borrow.pop() {
+    /// Drops every pending image that is no longer held by a view (or the data thread's cache),
+    /// leaving the rest queued. The same image can be shown in several places at once, like the
+    /// album art of every queue item from the same album, so images are only ever dropped once
+    /// nothing else holds them.
+    pub fn drop_unused(&self, cx: &mut WindowContext) {
+        let mut borrow = self.pending.borrow_mut();
+        let (unused, used): (Vec<_>, Vec<_>) = borrow
+            .drain(..)
+            .partition(|item| Arc::strong_count(item) == 1);
+
+        *borrow = used;
+
+        for item in unused {
             cx.drop_image(item).expect("bruh");
         }
     }
 
     pub fn add(&self, item: Arc<RenderImage>) {
-        let mut borrow = self.0.borrow_mut();
+        let mut borrow = self.pending.borrow_mut();
+
+        // a second copy in the queue would keep the image from ever looking unused
+        if !borrow.iter().any(|v| Arc::ptr_eq(v, &item)) {
+            borrow.push(item);
+        }
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.borrow().len()
+    }
+
+    /// Records the album art displayed for the specified queue index, so that it can be dropped
+    /// once the item leaves the queue.
+    pub fn track_queue_image(&self, idx: usize, path: String, item: Arc<RenderImage>) {
+        let previous = self
+            .queue_images
+            .borrow_mut()
+            .insert(idx, (path, item.clone()));
+
+        if let Some((_, previous)) = previous {
+            if !Arc::ptr_eq(&previous, &item) {
+                self.add(previous);
+            }
+        }
+    }
+
+    /// Queues the album art displayed for the specified queue index for dropping, once its item is
+    /// no longer visible. Nothing happens if the index has been given another file since.
+    pub fn untrack_queue_image(&self, idx: usize, path: &str) {
+        let mut queue_images = self.queue_images.borrow_mut();
+
+        if queue_images.get(&idx).is_some_and(|(v, _)| v == path) {
+            if let Some((_, item)) = queue_images.remove(&idx) {
+                drop(queue_images);
+                self.add(item);
+            }
+        }
+    }
+
+    /// Queues the album art of any queue index that no longer holds the same file for dropping.
+    pub fn untrack_removed(&self, queue: &[String]) {
+        let mut removed = Vec::new();
 
-        borrow.push(item);
+        self.queue_images.borrow_mut().retain(|idx, (path, item)| {
+            let keep = queue.get(*idx) == Some(path);
+
+            if !keep {
+                removed.push(item.clone());
+            }
+
+            keep
+        });
+
+        for item in removed {
+            self.add(item);
+        }
     }
 }
 
+/// Periodically drops images whose views are gone. Images are otherwise only dropped on
+/// navigation, which never happens for album art shown in the queue.
+fn start_image_flush<V: 'static>(cx: &mut ViewContext<V>) {
+    cx.spawn(|_, mut cx| async move {
+        let mut last_flush = Instant::now();
+
+        loop {
+            cx.background_executor().timer(Duration::from_secs(1)).await;
+
+            let result = cx.update(|cx| {
+                let queue = cx.global::<DropOnNavigateQueue>().clone();
+
+                if queue.pending_count() >= FLUSH_THRESHOLD
+                    || last_flush.elapsed() >= FLUSH_INTERVAL
+                {
+                    debug!("flushing {} pending images", queue.pending_count());
+                    queue.drop_unused(cx);
+                    last_flush = Instant::now();
+                }
+            });
+
+            if result.is_err() {
+                break;
+            }
+        }
+    })
+    .detach();
+}
+
 impl Global for DropOnNavigateQueue {}
 
-pub fn get_dirs() -> ProjectDirs {
-    directories::ProjectDirs::from("me", "william341", "muzak").expect("couldn't find project dirs")
+/// The data directory chosen by [`setup_data_dir`], if it isn't the platform default.
+static DATA_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// The environment variable that overrides the data directory, if `--data-dir` isn't passed.
+const DATA_DIR_VAR: &str = "MUZAK_DATA_DIR";
+
+/// Where the library, settings, theme, and caches are kept.
+pub struct Dirs {
+    data: PathBuf,
+    cache: PathBuf,
+    runtime: Option<PathBuf>,
+}
+
+impl Dirs {
+    pub fn data_dir(&self) -> &Path {
+        &self.data
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache
+    }
+
+    pub fn runtime_dir(&self) -> Option<&Path> {
+        self.runtime.as_deref()
+    }
+}
+
+pub fn get_dirs() -> Dirs {
+    if let Some(Some(directory)) = DATA_DIR.get() {
+        // everything is kept together, so that separate data directories don't share anything
+        return Dirs {
+            data: directory.clone(),
+            cache: directory.join("cache"),
+            runtime: None,
+        };
+    }
+
+    let dirs = ProjectDirs::from("me", "william341", "muzak").expect("couldn't find project dirs");
+
+    Dirs {
+        data: dirs.data_dir().to_path_buf(),
+        cache: dirs.cache_dir().to_path_buf(),
+        runtime: dirs.runtime_dir().map(Path::to_path_buf),
+    }
+}
+
+/// Picks the data directory, from the `--data-dir` argument or `MUZAK_DATA_DIR`. If neither is
+/// set, or the directory can't be written to, the platform default is used. This has to be
+/// called before anything uses [`get_dirs`].
+pub fn setup_data_dir() {
+    let requested = data_dir_argument().or_else(|| {
+        std::env::var_os(DATA_DIR_VAR)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    });
+
+    let directory = requested.and_then(|directory| match prepare_data_dir(&directory) {
+        Ok(directory) => {
+            info!("Using data directory {:?}", directory);
+            Some(directory)
+        }
+        Err(e) => {
+            warn!(
+                "Data directory {:?} isn't writable, using the default instead: {:?}",
+                directory, e
+            );
+            None
+        }
+    });
+
+    DATA_DIR.set(directory).ok();
+}
+
+/// Creates the directory if needed and checks that files can be written to it. Returns the
+/// absolute path to the directory.
+fn prepare_data_dir(directory: &Path) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(directory)?;
+
+    let probe = directory.join(".write_test");
+    fs::write(&probe, [])?;
+    fs::remove_file(&probe)?;
+
+    fs::canonicalize(directory)
 }
 
 pub async fn run() {
@@ -295,6 +533,7 @@ pub async fn run() {
 
             setup_theme(cx, directory.join("theme.json"));
             setup_settings(cx, directory.join("settings.json"));
+            setup_accent(cx);
 
             if let Ok(pool) = pool {
                 let settings = cx.global::<SettingsGlobal>().model.read(cx);
@@ -310,8 +549,12 @@ pub async fn run() {
                 panic!("fatal: unable to create database pool");
             }
 
+            let settings = cx.global::<SettingsGlobal>().model.clone();
+            let interface_settings = settings.read(cx).interface.clone();
+
             let mut playback_interface: GPUIPlaybackInterface = PlaybackThread::start();
-            let mut data_interface: GPUIDataInterface = DataThread::start();
+            let pool = cx.global::<Pool>().0.clone();
+            let mut data_interface: GPUIDataInterface = DataThread::start(interface_settings, pool);
 
             playback_interface.start_broadcast(cx);
             data_interface.start_broadcast(cx);
@@ -323,6 +566,57 @@ pub async fn run() {
             cx.set_global(create_cache());
             cx.set_global(DropOnNavigateQueue::default());
 
+            setup_radio(cx);
+            setup_continuation(cx);
+            setup_gain(cx);
+            setup_shuffle(cx);
+            setup_history(cx);
+            setup_speed(cx);
+            setup_volume(cx);
+            setup_fade(cx);
+            setup_level_meter(cx);
+            setup_monitors(cx);
+            setup_prebuffer(cx);
+            setup_resume(cx);
+            setup_skip(cx);
+            setup_trim(cx);
+            setup_failure_handling(cx);
+            setup_scan_pausing(cx);
+            setup_scan_settings(cx);
+            setup_thumbnail_prerender(cx);
+            setup_media_keys(cx);
+            setup_peaks(cx);
+            #[cfg(target_os = "linux")]
+            setup_tray(cx);
+            #[cfg(target_os = "linux")]
+            setup_notifications(cx);
+            start_server(cx);
+            start_remote_server(cx);
+
+            let queue = cx.global::<Models>().queue.clone();
+
+            cx.observe(&queue, |queue, cx| {
+                let paths = queue.read(cx).0.clone();
+                cx.global::<DropOnNavigateQueue>().untrack_removed(&paths);
+
+                let queued: AHashSet<&String> = paths.iter().collect();
+                let queue_metadata = cx.global::<Models>().queue_metadata.clone();
+
+                queue_metadata.update(cx, |m, _| {
+                    m.retain(|path, _| queued.contains(path));
+                });
+            })
+            .detach();
+
+            cx.observe(&settings, |settings, cx| {
+                let interface_settings = settings.read(cx).interface.clone();
+                cx.global::<GPUIDataInterface>()
+                    .update_settings(interface_settings);
+                // views read interface settings during render, redraw them so changes apply
+                cx.refresh();
+            })
+            .detach();
+
             cx.activate(true);
 
             cx.open_window(
@@ -350,13 +644,27 @@ pub async fn run() {
                         })
                         .detach();
 
-                        let show_queue = cx.new_model(|_| true);
+                        #[cfg(target_os = "linux")]
+                        cx.on_window_should_close(should_close);
+
+                        start_image_flush(cx);
+
+                        let show_queue = cx.global::<Models>().show_queue.clone();
 
                         WindowShadow {
+                            about: AboutDialog::new(cx),
+                            logs: LogViewer::new(cx),
+                            accent_picker: AccentPicker::new(cx),
                             controls: Controls::new(cx, show_queue.clone()),
                             queue: Queue::new(cx, show_queue.clone()),
                             library: Library::new(cx),
                             header: Header::new(cx),
+                            undo_toast: UndoToast::new(cx),
+                            error_toast: ErrorToast::new(cx),
+                            track_details: TrackDetailsDialog::new(cx),
+                            trim: TrimDialog::new(cx),
+                            unreadable_files: UnreadableFilesDialog::new(cx),
+                            palette: CommandPalette::new(cx),
                             show_queue,
                         }
                     })
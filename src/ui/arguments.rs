@@ -1,33 +1,218 @@
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
 
-use clap::Parser;
-use tracing::info;
+use clap::{Parser, Subcommand};
+use tracing::{info, warn};
 
-use crate::playback::interface::GPUIPlaybackInterface;
+use crate::{
+    ipc::{self, IpcCommand, IpcResponse},
+    media::{
+        builtin::{has_supported_extension, providers, supported_extensions},
+        registry::ProviderRegistry,
+    },
+    playback::interface::GPUIPlaybackInterface,
+};
 
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     #[arg()]
     files: Option<Vec<PathBuf>>,
+    /// Keeps the library, settings, theme, and caches in this directory instead of the default
+    /// one. Can also be set with MUZAK_DATA_DIR
+    #[arg(long, value_name = "DIR", global = true)]
+    data_dir: Option<PathBuf>,
+}
+
+/// Commands that control an already running instance.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Searches the library and plays the best matching track
+    Play {
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
+    /// Toggles between playing and paused
+    PlayPause,
+    /// Pauses playback
+    Pause,
+    /// Skips to the next track
+    Next,
+    /// Returns to the previous track
+    Previous,
+}
+
+impl From<Command> for IpcCommand {
+    fn from(command: Command) -> Self {
+        match command {
+            Command::Play { query } => IpcCommand::Play(query.join(" ")),
+            Command::PlayPause => IpcCommand::PlayPause,
+            Command::Pause => IpcCommand::Pause,
+            Command::Next => IpcCommand::Next,
+            Command::Previous => IpcCommand::Previous,
+        }
+    }
+}
+
+/// Forwards subcommands, and any files passed as arguments, to the running instance. This exits
+/// the process if there was anything to forward, with a nonzero code if no instance is running or
+/// the command failed. Otherwise, it returns and the application should start as usual.
+pub fn forward_to_running_instance() {
+    let args = Args::parse();
+
+    if let Some(command) = args.command {
+        let code = match ipc::send(&command.into()) {
+            Ok(IpcResponse::Ok) => 0,
+            Ok(IpcResponse::NotFound) => {
+                eprintln!("No matching track was found in the library");
+                1
+            }
+            Ok(IpcResponse::Error(e)) => {
+                eprintln!("The running instance couldn't handle the command: {}", e);
+                1
+            }
+            Err(_) => {
+                eprintln!("Muzak isn't running");
+                1
+            }
+        };
+
+        std::process::exit(code);
+    }
+
+    if let Some(files) = args.files {
+        // paths are relative to this process, not the running instance
+        let paths = expand_files(&files)
+            .into_iter()
+            .map(|v| std::fs::canonicalize(&v).map(path_to_string).unwrap_or(v))
+            .collect();
+
+        // the same as when the files are opened by a new instance
+        let command = if plays_as_album(&files) {
+            IpcCommand::ReplaceQueue(paths)
+        } else {
+            IpcCommand::Enqueue(paths)
+        };
+
+        if let Ok(response) = ipc::send(&command) {
+            if response != IpcResponse::Ok {
+                warn!(
+                    "The running instance couldn't queue the files: {:?}",
+                    response
+                );
+            }
+
+            std::process::exit(0);
+        }
+    }
+}
+
+/// The data directory passed with `--data-dir`, if any.
+pub fn data_dir_argument() -> Option<PathBuf> {
+    Args::parse().data_dir
 }
 
 pub fn parse_args_and_prepare(interface: &GPUIPlaybackInterface) {
     let args = Args::parse();
 
     if let Some(files) = args.files {
-        info!("Queueing files found in arguments: {:?}", files);
-
-        interface.queue_list(
-            files
-                .iter()
-                .map(|v| {
-                    v.clone()
-                        .into_os_string()
-                        .into_string()
-                        .expect("Invalid path")
-                })
-                .collect(),
-        );
+        let paths = expand_files(&files);
+
+        if plays_as_album(&files) {
+            info!(
+                "Playing directories found in arguments as albums: {:?}",
+                files
+            );
+            interface.replace_queue(paths);
+        } else {
+            info!("Queueing files found in arguments: {:?}", files);
+            interface.queue_list(paths);
+        }
     }
 }
+
+/// Whether the files passed as arguments replace the queue, which happens if any of them is a
+/// directory, rather than being added to the end of it.
+fn plays_as_album(files: &[PathBuf]) -> bool {
+    files.iter().any(|v| v.is_dir())
+}
+
+/// Replaces any directories with the files of the album they contain.
+fn expand_files(files: &[PathBuf]) -> Vec<String> {
+    files
+        .iter()
+        .flat_map(|v| {
+            if v.is_dir() {
+                collect_album(v)
+            } else {
+                vec![v.clone()]
+            }
+        })
+        .map(path_to_string)
+        .collect()
+}
+
+fn path_to_string(path: PathBuf) -> String {
+    path.into_os_string().into_string().expect("Invalid path")
+}
+
+/// Collects every playable file in the directory (and any nested directories, for multi-disc
+/// releases) and sorts them by disc and track number. If any file is missing a track number,
+/// the files are sorted by path instead.
+fn collect_album(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect_files(dir, &supported_extensions(), &mut files);
+
+    let mut media_providers = ProviderRegistry::new(providers());
+    let mut tracks: Vec<(Option<u64>, Option<u64>, PathBuf)> = files
+        .into_iter()
+        .map(|path| {
+            let (disc, track) = read_position(&mut media_providers, &path).unwrap_or_default();
+            (disc, track, path)
+        })
+        .collect();
+
+    if tracks.iter().all(|(_, track, _)| track.is_some()) {
+        tracks.sort_by(|a, b| (a.0.unwrap_or(1), a.1, &a.2).cmp(&(b.0.unwrap_or(1), b.1, &b.2)));
+    } else {
+        tracks.sort_by(|a, b| a.2.cmp(&b.2));
+    }
+
+    tracks.into_iter().map(|(_, _, path)| path).collect()
+}
+
+fn collect_files(dir: &Path, extensions: &[&str], files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        warn!("Could not read directory {:?}", dir);
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect_files(&path, extensions, files);
+        } else if has_supported_extension(&path, extensions) {
+            files.push(path);
+        }
+    }
+}
+
+fn read_position(
+    media_providers: &mut ProviderRegistry,
+    path: &Path,
+) -> Result<(Option<u64>, Option<u64>), ()> {
+    let provider = media_providers.select(path.to_str().ok_or(())?).ok_or(())?;
+    let src = File::open(path).map_err(|_| ())?;
+    provider.open(src, None).map_err(|_| ())?;
+    provider.start_playback().map_err(|_| ())?;
+    let metadata = provider.read_metadata().map_err(|_| ())?;
+    let position = (metadata.disc_current, metadata.track_current);
+    provider.close().map_err(|_| ())?;
+
+    Ok(position)
+}
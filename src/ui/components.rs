@@ -3,3 +3,4 @@ pub mod context;
 pub mod menu;
 pub mod slider;
 pub mod styling;
+pub mod tooltip;
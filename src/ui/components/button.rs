@@ -1,8 +1,8 @@
-use gpui::*;
+use gpui::{prelude::FluentBuilder, *};
 
 use crate::ui::theme::Theme;
 
-use super::styling::AdditionalStyleUtil;
+use super::styling::{focus_ring, AdditionalStyleUtil};
 
 #[derive(Clone, Copy)]
 pub enum ButtonSize {
@@ -39,6 +39,13 @@ impl ButtonStyle {
         }
     }
 
+    fn rounding(&self) -> Pixels {
+        match self {
+            ButtonStyle::Regular | ButtonStyle::Minimal => px(4.0),
+            ButtonStyle::MinimalNoRounding => px(0.0),
+        }
+    }
+
     fn hover<T>(&self, dest: T) -> T
     where
         T: Styled,
@@ -164,6 +171,7 @@ impl Button {
             size: self.size,
             style: self.style,
             intent: self.intent,
+            focused: false,
         }
     }
 }
@@ -196,6 +204,7 @@ pub struct InteractiveButton {
     pub(self) style: ButtonStyle,
     pub(self) size: ButtonSize,
     pub(self) intent: ButtonIntent,
+    pub(self) focused: bool,
 }
 
 impl InteractiveButton {
@@ -214,6 +223,13 @@ impl InteractiveButton {
         self
     }
 
+    /// Draws the focus ring around the button. Keyboard navigation is handled by the view that
+    /// owns the button, which knows which of its elements currently has focus.
+    pub fn focused(mut self, focused: bool) -> Self {
+        self.focused = focused;
+        self
+    }
+
     pub fn on_click(mut self, fun: impl Fn(&ClickEvent, &mut WindowContext) + 'static) -> Self {
         self.div = self.div.on_click(fun);
         self
@@ -237,17 +253,24 @@ impl RenderOnce for InteractiveButton {
         let style = self.style;
         let size = self.size;
         let intent = self.intent;
-
-        style.base(
-            size.base(
-                intent.base(
-                    self.div
-                        .hover(|v| style.hover(intent.hover(v, cx)))
-                        .active(|v| style.active(size.active(intent.active(v, cx)))),
-                    cx,
+        let focused = self.focused;
+        let focus_ring_color = cx.global::<Theme>().focus_ring;
+
+        style
+            .base(
+                size.base(
+                    intent.base(
+                        self.div
+                            .hover(|v| style.hover(intent.hover(v, cx)))
+                            .active(|v| style.active(size.active(intent.active(v, cx)))),
+                        cx,
+                    ),
                 ),
-            ),
-        )
+            )
+            .relative()
+            .when(focused, |this| {
+                this.child(focus_ring(focus_ring_color, style.rounding()))
+            })
     }
 }
 
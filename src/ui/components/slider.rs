@@ -1,4 +1,4 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, rc::Rc, sync::Arc};
 
 use gpui::*;
 
@@ -12,6 +12,8 @@ pub struct Slider {
     pub(self) value: f32,
     pub(self) on_change: Option<Rc<RefCell<ClickHandler>>>,
     pub(self) hitbox: Option<Hitbox>,
+    /// If set, the slider is drawn as a waveform made of these peaks instead of a bar.
+    pub(self) peaks: Option<Arc<Vec<u8>>>,
 }
 
 impl Slider {
@@ -25,6 +27,11 @@ impl Slider {
         self
     }
 
+    pub fn peaks(mut self, peaks: Option<Arc<Vec<u8>>>) -> Self {
+        self.peaks = peaks;
+        self
+    }
+
     pub fn on_change(mut self, func: impl FnMut(f32, &mut WindowContext) + 'static) -> Self {
         self.on_change = Some(Rc::new(RefCell::new(func)));
         self
@@ -94,32 +101,73 @@ impl Element for Slider {
 
         cx.set_cursor_style(CursorStyle::PointingHand, self.hitbox.as_ref().unwrap());
 
-        cx.paint_quad(quad(
-            bounds,
-            corners.to_pixels(bounds.size, cx.rem_size()),
-            self.style
+        if let Some(peaks) = self.peaks.as_ref().filter(|v| !v.is_empty()) {
+            let background = self
+                .style
                 .background
                 .clone()
                 .and_then(|v| v.color())
-                .unwrap_or(default_background.into()),
-            Edges::all(px(0.0)),
-            rgb(0x000000),
-        ));
-
-        let mut borders = Edges::default();
-        borders.refine(&self.style.border_widths);
-
-        cx.paint_quad(quad(
-            inner_bounds,
-            corners.to_pixels(bounds.size, cx.rem_size()),
-            self.style
+                .unwrap_or(default_background.into());
+            let foreground = self
+                .style
                 .text
                 .clone()
                 .and_then(|v| v.color)
-                .unwrap_or(default_foreground.into()),
-            borders.to_pixels(cx.rem_size()),
-            self.style.border_color.unwrap_or_default(),
-        ));
+                .unwrap_or(default_foreground.into());
+
+            let slot = bounds.size.width / peaks.len() as f32;
+            // leave a gap between the bars, unless they're too narrow for one
+            let bar_width = if slot > px(2.0) { slot - px(1.0) } else { slot };
+            let played = inner_bounds.size.width;
+
+            for (i, peak) in peaks.iter().enumerate() {
+                // quiet sections still get a sliver, so that the bar doesn't disappear
+                let height = (bounds.size.height * (*peak as f32 / 255.0)).max(px(2.0));
+                let x = slot * i as f32;
+
+                cx.paint_quad(fill(
+                    Bounds {
+                        origin: point(
+                            bounds.origin.x + x,
+                            bounds.origin.y + (bounds.size.height - height) / 2.0,
+                        ),
+                        size: size(bar_width, height),
+                    },
+                    if x + slot / 2.0 <= played {
+                        foreground
+                    } else {
+                        background
+                    },
+                ));
+            }
+        } else {
+            cx.paint_quad(quad(
+                bounds,
+                corners.to_pixels(bounds.size, cx.rem_size()),
+                self.style
+                    .background
+                    .clone()
+                    .and_then(|v| v.color())
+                    .unwrap_or(default_background.into()),
+                Edges::all(px(0.0)),
+                rgb(0x000000),
+            ));
+
+            let mut borders = Edges::default();
+            borders.refine(&self.style.border_widths);
+
+            cx.paint_quad(quad(
+                inner_bounds,
+                corners.to_pixels(bounds.size, cx.rem_size()),
+                self.style
+                    .text
+                    .clone()
+                    .and_then(|v| v.color)
+                    .unwrap_or(default_foreground.into()),
+                borders.to_pixels(cx.rem_size()),
+                self.style.border_color.unwrap_or_default(),
+            ));
+        }
 
         if let Some(func) = self.on_change.as_ref() {
             cx.with_optional_element_state(id, move |v: Option<Option<Rc<RefCell<bool>>>>, cx| {
@@ -178,5 +226,6 @@ pub fn slider() -> Slider {
         value: 0.0,
         on_change: None,
         hitbox: None,
+        peaks: None,
     }
 }
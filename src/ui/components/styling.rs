@@ -1,4 +1,18 @@
-use gpui::{Fill, Styled};
+use gpui::{div, Div, Fill, Hsla, Pixels, Styled};
+
+/// An outline showing that an element has keyboard focus. Unlike a border, it's drawn over the
+/// element without changing its size, so the element it's added to has to be `relative` (and
+/// should add it as its last child, so that it's drawn on top).
+pub fn focus_ring(color: impl Into<Hsla>, rounding: Pixels) -> Div {
+    div()
+        .absolute()
+        .top_0()
+        .left_0()
+        .size_full()
+        .border_1()
+        .border_color(color)
+        .rounded(rounding)
+}
 
 pub trait AdditionalStyleUtil {
     fn background_opacity(self, opacity: f32) -> Self;
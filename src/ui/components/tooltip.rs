@@ -0,0 +1,118 @@
+use std::{cell::Cell, rc::Rc};
+
+use gpui::{prelude::FluentBuilder, *};
+
+use crate::ui::theme::Theme;
+
+pub struct Tooltip {
+    text: Option<SharedString>,
+    secondary: Option<SharedString>,
+}
+
+impl Tooltip {
+    pub fn new(
+        cx: &mut WindowContext,
+        text: impl Into<SharedString>,
+        secondary: Option<SharedString>,
+    ) -> AnyView {
+        cx.new_view(|_| Tooltip {
+            text: Some(text.into()),
+            secondary,
+        })
+        .into()
+    }
+
+    /// A tooltip that doesn't render anything, for when GPUI requires a tooltip but there's
+    /// nothing worth showing.
+    pub fn empty(cx: &mut WindowContext) -> AnyView {
+        cx.new_view(|_| Tooltip {
+            text: None,
+            secondary: None,
+        })
+        .into()
+    }
+}
+
+impl Render for Tooltip {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+
+        let Some(text) = self.text.clone() else {
+            return div();
+        };
+
+        div()
+            .bg(theme.elevated_background)
+            .border_1()
+            .border_color(theme.elevated_border_color)
+            .rounded(px(4.0))
+            .shadow_sm()
+            .px(px(8.0))
+            .py(px(4.0))
+            .max_w(px(400.0))
+            .text_sm()
+            .font_weight(FontWeight::SEMIBOLD)
+            .text_color(theme.text)
+            .child(text)
+            .when_some(self.secondary.clone(), |this, secondary| {
+                this.child(
+                    div()
+                        .font_weight(FontWeight::NORMAL)
+                        .text_color(theme.text_secondary)
+                        .child(secondary),
+                )
+            })
+    }
+}
+
+/// Single line text that is truncated with an ellipsis, showing the full text (and optionally
+/// some secondary text, like an artist name) in a tooltip when it doesn't fit.
+pub fn truncated_text(
+    id: impl Into<ElementId>,
+    text: impl Into<SharedString>,
+    secondary: Option<SharedString>,
+) -> Stateful<Div> {
+    let text = text.into();
+    let truncated = Rc::new(Cell::new(false));
+    let truncated_clone = truncated.clone();
+    let measured = text.clone();
+
+    div()
+        .id(id)
+        .relative()
+        .min_w_0()
+        .overflow_x_hidden()
+        .whitespace_nowrap()
+        .text_ellipsis()
+        .child(text.clone())
+        .child(
+            canvas(
+                move |bounds, cx| {
+                    // GPUI doesn't tell us whether the text was truncated, so the text is shaped
+                    // again with the inherited style and compared to the space available
+                    let style = cx.text_style();
+                    let font_size = style.font_size.to_pixels(cx.rem_size());
+                    let run = style.to_run(measured.len());
+                    let width = cx
+                        .text_system()
+                        .shape_line(measured, font_size, &[run])
+                        .map(|line| line.width)
+                        .unwrap_or_default();
+
+                    truncated_clone.set(width > bounds.size.width);
+                },
+                |_, _, _| {},
+            )
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full(),
+        )
+        .tooltip(move |cx| {
+            if truncated.get() {
+                Tooltip::new(cx, text.clone(), secondary.clone())
+            } else {
+                Tooltip::empty(cx)
+            }
+        })
+}
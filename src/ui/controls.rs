@@ -1,14 +1,31 @@
+use std::sync::Arc;
+
 use gpui::*;
 use prelude::FluentBuilder;
 
-use crate::playback::{interface::GPUIPlaybackInterface, thread::PlaybackState};
+use crate::{
+    playback::{
+        interface::{set_output_device, GPUIPlaybackInterface},
+        level::meter_fill,
+        shuffle::toggle_shuffle,
+        speed::{format_speed, next_speed},
+        thread::PlaybackState,
+    },
+    settings::SettingsGlobal,
+};
 
 use super::{
-    components::slider::slider,
+    components::{
+        context::context,
+        menu::{menu, menu_item},
+        slider::slider,
+        tooltip::Tooltip,
+    },
     constants::{APP_ROUNDING, FONT_AWESOME},
     global_actions::{Next, PlayPause, Previous},
     models::{Models, PlaybackInfo},
     theme::Theme,
+    util::{format_metadata, format_output},
 };
 
 pub struct Controls {
@@ -73,12 +90,26 @@ impl InfoSection {
             })
             .detach();
 
-            cx.observe(&metadata_model, |this: &mut Self, m, cx| {
-                let metadata = m.read(cx);
+            cx.observe(&playback_info.output, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            cx.observe(&playback_info.devices, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            cx.observe(&metadata_model, |this: &mut Self, _, cx| {
+                this.update_text(cx);
+                cx.notify();
+            })
+            .detach();
 
-                this.track_name = metadata.name.clone().map(SharedString::from);
-                this.artist_name = metadata.artist.clone().map(SharedString::from);
+            let settings = cx.global::<SettingsGlobal>().model.clone();
 
+            cx.observe(&settings, |this: &mut Self, _, cx| {
+                this.update_text(cx);
                 cx.notify();
             })
             .detach();
@@ -99,12 +130,53 @@ impl InfoSection {
             }
         })
     }
+
+    /// Fills in the templates from the settings with the current track's metadata. Lines that
+    /// come out empty show a placeholder instead.
+    fn update_text(&mut self, cx: &mut ViewContext<Self>) {
+        let settings = &cx.global::<SettingsGlobal>().model.read(cx).interface;
+        let metadata = cx.global::<Models>().metadata.read(cx);
+
+        let format = |template: &str| {
+            Some(format_metadata(template, metadata))
+                .filter(|v| !v.is_empty())
+                .map(SharedString::from)
+        };
+
+        self.track_name = format(&settings.controls_title);
+        self.artist_name = format(&settings.controls_subtitle);
+    }
 }
 
 impl Render for InfoSection {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
         let state = self.playback_info.playback_state.read(cx);
+        // what's actually being sent to the device, to check that resampling isn't happening
+        let current_uid = self
+            .playback_info
+            .output
+            .read(cx)
+            .as_ref()
+            .and_then(|output| output.device_uid.clone());
+        let devices = self.playback_info.devices.read(cx).clone();
+        let output = self.playback_info.output.read(cx).as_ref().map(|output| {
+            (
+                output
+                    .device_name
+                    .clone()
+                    .map(SharedString::from)
+                    .unwrap_or("Unknown device".into()),
+                SharedString::from(format_output(output)),
+            )
+        });
+        let albumart = self.albumart_actual.clone().filter(|_| {
+            !cx.global::<SettingsGlobal>()
+                .model
+                .read(cx)
+                .interface
+                .disable_album_art
+        });
 
         div()
             .id("info-section")
@@ -123,22 +195,43 @@ impl Render for InfoSection {
                     .flex()
                     .overflow_x_hidden()
                     .child(
-                        div()
-                            .id("album-art")
-                            .rounded(px(4.0))
-                            .bg(theme.album_art_background)
-                            .shadow_sm()
-                            .w(px(36.0))
-                            .h(px(36.0))
-                            .mb(px(6.0))
-                            .when(self.albumart_actual.is_some(), |div| {
-                                div.child(
-                                    img(self.albumart_actual.clone().unwrap())
-                                        .w(px(36.0))
-                                        .h(px(36.0))
-                                        .rounded(px(4.0)),
-                                )
-                            }),
+                        // right clicking the album art switches output devices
+                        context("output-device-context")
+                            .with(
+                                div()
+                                    .id("album-art")
+                                    .rounded(px(4.0))
+                                    .bg(theme.album_art_background)
+                                    .shadow_sm()
+                                    .w(px(36.0))
+                                    .h(px(36.0))
+                                    .mb(px(6.0))
+                                    .on_mouse_down(MouseButton::Right, |_, cx| {
+                                        cx.global::<GPUIPlaybackInterface>().list_devices();
+                                    })
+                                    .when_some(albumart, |div, albumart| {
+                                        div.child(
+                                            img(albumart).w(px(36.0)).h(px(36.0)).rounded(px(4.0)),
+                                        )
+                                    })
+                                    .when_some(output, |div, (device, format)| {
+                                        div.tooltip(move |cx| {
+                                            Tooltip::new(cx, device.clone(), Some(format.clone()))
+                                        })
+                                    }),
+                            )
+                            .child(div().bg(theme.elevated_background).child(
+                                devices.into_iter().fold(menu(), |menu, (uid, name)| {
+                                    let icon = (current_uid.as_ref() == Some(&uid)).then_some("");
+
+                                    menu.item(menu_item(
+                                        SharedString::from(format!("output-device-{}", uid)),
+                                        icon,
+                                        name,
+                                        move |_, cx| set_output_device(uid.clone(), cx),
+                                    ))
+                                }),
+                            )),
                     )
                     .when(*state == PlaybackState::Stopped, |e| {
                         e.child(
@@ -251,9 +344,7 @@ impl Render for PlaybackSection {
                         cx.stop_propagation();
                         cx.prevent_default();
                     })
-                    .on_click(|_, cx| {
-                        cx.global::<GPUIPlaybackInterface>().toggle_shuffle();
-                    })
+                    .on_click(|_, cx| toggle_shuffle(cx))
                     .when(*shuffling, |this| this.child(""))
                     .when(!shuffling, |this| this.child("")),
             )
@@ -340,7 +431,11 @@ impl Render for PlaybackSection {
 pub struct Scrubber {
     position: Model<u64>,
     duration: Model<u64>,
+    peaks: Model<Option<Arc<Vec<u8>>>>,
     playback_section: View<PlaybackSection>,
+    /// Whether the waveform seek bar is enabled, in which case its height is reserved even before
+    /// the peaks have loaded.
+    waveform: bool,
 }
 
 impl Scrubber {
@@ -348,6 +443,19 @@ impl Scrubber {
         cx.new_view(|cx| {
             let position_model = cx.global::<PlaybackInfo>().position.clone();
             let duration_model = cx.global::<PlaybackInfo>().duration.clone();
+            let peaks_model = cx.global::<Models>().peaks.clone();
+            let settings = cx.global::<SettingsGlobal>().model.clone();
+
+            cx.observe(&peaks_model, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            cx.observe(&settings, |this: &mut Self, settings, cx| {
+                this.waveform = settings.read(cx).interface.waveform_seek_bar;
+                cx.notify();
+            })
+            .detach();
 
             cx.observe(&position_model, |_, _, cx| {
                 cx.notify();
@@ -362,7 +470,9 @@ impl Scrubber {
             Self {
                 position: position_model,
                 duration: duration_model,
+                peaks: peaks_model,
                 playback_section: PlaybackSection::new(cx),
+                waveform: settings.read(cx).interface.waveform_seek_bar,
             }
         })
     }
@@ -374,6 +484,7 @@ impl Render for Scrubber {
         let position = *self.position.read(cx);
         let duration = *self.duration.read(cx);
         let remaining = duration - position;
+        let peaks = self.peaks.read(cx).clone();
 
         div()
             .pl(px(13.0))
@@ -416,25 +527,108 @@ impl Render for Scrubber {
                     ))),
             )
             .child(
-                slider()
+                div()
                     .w_full()
-                    .h(px(6.0))
-                    .rounded(px(3.0))
-                    .id("scrubber-back")
-                    .value(position as f32 / duration as f32)
-                    .on_change(move |v, cx| {
-                        if duration > 0 {
-                            cx.global::<GPUIPlaybackInterface>()
-                                .seek(v as f64 * duration as f64);
-                        }
-                    }),
+                    .h(px(if self.waveform { 24.0 } else { 6.0 }))
+                    .flex()
+                    .items_center()
+                    .child(
+                        slider()
+                            .w_full()
+                            .h(px(if peaks.is_some() { 24.0 } else { 6.0 }))
+                            .rounded(px(3.0))
+                            .id("scrubber-back")
+                            .peaks(peaks)
+                            .value(position as f32 / duration as f32)
+                            .on_change(move |v, cx| {
+                                if duration > 0 {
+                                    cx.global::<GPUIPlaybackInterface>()
+                                        .seek(v as f64 * duration as f64);
+                                }
+                            }),
+                    ),
             )
     }
 }
 
+/// How much the arrow keys change the volume by.
+const VOLUME_STEP: f64 = 0.01;
+/// How much Page Up and Page Down change the volume by.
+const VOLUME_PAGE_STEP: f64 = 0.1;
+
+/// A small meter of the output level. This is its own view so that updating the level, which
+/// happens many times a second, only redraws the meter.
+pub struct LevelMeter {
+    level: Model<f32>,
+    playback_state: Model<PlaybackState>,
+    enabled: bool,
+}
+
+impl LevelMeter {
+    pub fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
+        cx.new_view(|cx| {
+            let info = cx.global::<PlaybackInfo>().clone();
+            let settings = cx.global::<SettingsGlobal>().model.clone();
+
+            cx.observe(&info.level, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            cx.observe(&info.playback_state, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            cx.observe(&settings, |this: &mut Self, settings, cx| {
+                this.enabled = settings.read(cx).interface.level_meter;
+                cx.notify();
+            })
+            .detach();
+
+            Self {
+                level: info.level,
+                playback_state: info.playback_state,
+                enabled: settings.read(cx).interface.level_meter,
+            }
+        })
+    }
+}
+
+impl Render for LevelMeter {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        if !self.enabled {
+            return div().id("level-meter");
+        }
+
+        let theme = cx.global::<Theme>();
+        let fill = if *self.playback_state.read(cx) == PlaybackState::Playing {
+            meter_fill(*self.level.read(cx))
+        } else {
+            0.0
+        };
+
+        div()
+            .id("level-meter")
+            .w(px(6.0))
+            .h(px(20.0))
+            .mt(px(5.0))
+            .rounded(px(3.0))
+            .overflow_hidden()
+            .flex()
+            .flex_col()
+            .justify_end()
+            .bg(theme.slider_background)
+            .child(div().w_full().h(relative(fill)).bg(theme.slider_foreground))
+            .tooltip(|cx| Tooltip::new(cx, "Output level", None))
+    }
+}
+
 pub struct SecondaryControls {
     info: PlaybackInfo,
     show_queue: Model<bool>,
+    volume_focus: FocusHandle,
+    level_meter: View<LevelMeter>,
 }
 
 impl SecondaryControls {
@@ -442,21 +636,58 @@ impl SecondaryControls {
         cx.new_view(|cx| {
             let info = cx.global::<PlaybackInfo>().clone();
             let volume = info.volume.clone();
+            let speed = info.speed.clone();
 
             cx.observe(&volume, |_, _, cx| {
                 cx.notify();
             })
             .detach();
 
-            Self { info, show_queue }
+            cx.observe(&speed, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            Self {
+                info,
+                show_queue,
+                volume_focus: cx.focus_handle(),
+                level_meter: LevelMeter::new(cx),
+            }
         })
     }
+
+    /// Adjusts the volume from the keyboard while the volume slider is focused.
+    fn volume_key_down(&mut self, event: &KeyDownEvent, cx: &mut ViewContext<Self>) {
+        let keystroke = &event.keystroke;
+
+        if keystroke.modifiers.control || keystroke.modifiers.platform {
+            return;
+        }
+
+        let volume = *self.info.volume.read(cx);
+
+        let new_volume = match keystroke.key.as_str() {
+            "left" | "down" => volume - VOLUME_STEP,
+            "right" | "up" => volume + VOLUME_STEP,
+            "pagedown" => volume - VOLUME_PAGE_STEP,
+            "pageup" => volume + VOLUME_PAGE_STEP,
+            "home" => 0.0,
+            "end" => 1.0,
+            _ => return,
+        };
+
+        cx.stop_propagation();
+        cx.global::<GPUIPlaybackInterface>()
+            .set_volume(new_volume.clamp(0.0, 1.0));
+    }
 }
 
 impl Render for SecondaryControls {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
         let volume = self.info.volume.read(cx);
+        let speed = *self.info.speed.read(cx);
         let show_queue = self.show_queue.clone();
 
         div().px(px(18.0)).flex().child(
@@ -465,6 +696,7 @@ impl Render for SecondaryControls {
                 .my_auto()
                 .pb(px(2.0))
                 .gap(px(8.0))
+                .child(self.level_meter.clone())
                 .child(
                     div()
                         .rounded(px(3.0))
@@ -484,15 +716,49 @@ impl Render for SecondaryControls {
                         .child(""),
                 )
                 .child(
-                    slider()
-                        .w(px(80.0))
-                        .h(px(6.0))
-                        .mt(px(11.0))
+                    div()
+                        .id("volume-container")
+                        .track_focus(&self.volume_focus)
+                        .on_key_down(cx.listener(Self::volume_key_down))
+                        // the slider stops mouse events from propagating, so this has to happen
+                        // before it handles them
+                        .capture_any_mouse_down(
+                            cx.listener(|this, _, cx| cx.focus(&this.volume_focus)),
+                        )
+                        .child(
+                            slider()
+                                .w(px(80.0))
+                                .h(px(6.0))
+                                .mt(px(11.0))
+                                .rounded(px(3.0))
+                                .id("volume")
+                                .value((*volume) as f32)
+                                .on_change(move |v, cx| {
+                                    cx.global::<GPUIPlaybackInterface>().set_volume(v as f64);
+                                }),
+                        ),
+                )
+                .child(
+                    div()
                         .rounded(px(3.0))
-                        .id("volume")
-                        .value((*volume) as f32)
-                        .on_change(move |v, cx| {
-                            cx.global::<GPUIPlaybackInterface>().set_volume(v as f64);
+                        .min_w(px(40.0))
+                        .h(px(25.0))
+                        .mt(px(2.0))
+                        .px(px(4.0))
+                        .text_size(px(12.0))
+                        .font_weight(FontWeight::SEMIBOLD)
+                        .flex()
+                        .items_center()
+                        .justify_center()
+                        .border_color(theme.playback_button_border)
+                        .id("speed-button")
+                        .bg(theme.playback_button)
+                        .hover(|this| this.bg(theme.playback_button_hover))
+                        .active(|this| this.bg(theme.playback_button_active))
+                        .child(format_speed(speed))
+                        .on_click(move |_, cx| {
+                            cx.global::<GPUIPlaybackInterface>()
+                                .set_speed(next_speed(speed));
                         }),
                 )
                 .child(
@@ -1,11 +1,81 @@
-use gpui::{actions, AppContext, KeyBinding, Menu, MenuItem, SharedString};
-use tracing::{debug, info};
+use std::time::Duration;
 
-use crate::playback::{interface::GPUIPlaybackInterface, thread::PlaybackState};
+use async_std::future::timeout;
+use gpui::{
+    actions, Action, AppContext, AsyncAppContext, KeyBinding, Menu, MenuItem, NoAction,
+    PathPromptOptions, PromptLevel, SharedString,
+};
+use sqlx::SqlitePool;
+use tracing::{debug, error, info, warn};
 
-use super::models::PlaybackInfo;
+use crate::{
+    data::interface::GPUIDataInterface,
+    library::{
+        db::create_cache,
+        import::{import_stats as import_stats_from, read_export},
+        reset::reset_database,
+        scan::ScanInterface,
+    },
+    playback::{
+        history,
+        interface::GPUIPlaybackInterface,
+        skip::SkippedTracks,
+        thread::PlaybackState,
+        trim::{TrackTrim, TrackTrims},
+    },
+    services::mmb::MediaMetadataBroadcastService,
+};
 
-actions!(muzak, [Quit, PlayPause, Next, Previous]);
+use super::{
+    app::{get_dirs, Pool},
+    library::release_view::{Activate, FocusNext, FocusPrevious},
+    models::{Models, PlaybackInfo},
+    palette::{Confirm, Dismiss, InsertSpace, SelectNext, SelectPrevious},
+    trim::{Cancel, Save, SwitchField},
+};
+
+/// How long quitting waits for playback to fade out and the last scrobble to be submitted, so that
+/// a hung request can't keep Muzak from quitting.
+const QUIT_TIMEOUT: Duration = Duration::from_secs(3);
+
+actions!(
+    muzak,
+    [
+        Quit,
+        PlayPause,
+        Next,
+        Previous,
+        ToggleQueue,
+        About,
+        ShowLogs,
+        ShowUnreadableFiles,
+        TogglePalette,
+        FocusSearch,
+        ImportStats,
+        ShuffleRemaining,
+        ResetLibrary
+    ]
+);
+
+/// The actions that can be run from the command palette, and their names.
+pub fn palette_actions() -> Vec<(&'static str, Box<dyn Action>)> {
+    vec![
+        ("Play/pause", Box::new(PlayPause)),
+        ("Next track", Box::new(Next)),
+        ("Previous track", Box::new(Previous)),
+        ("Toggle queue", Box::new(ToggleQueue)),
+        ("Shuffle remaining queue", Box::new(ShuffleRemaining)),
+        ("About Muzak", Box::new(About)),
+        ("Show logs", Box::new(ShowLogs)),
+        (
+            "Show files that couldn't be read",
+            Box::new(ShowUnreadableFiles),
+        ),
+        ("Import play counts and ratings", Box::new(ImportStats)),
+        ("Reset library", Box::new(ResetLibrary)),
+        ("Quit", Box::new(Quit)),
+    ]
+}
 
 pub fn register_actions(cx: &mut AppContext) {
     debug!("registering actions");
@@ -13,27 +83,122 @@ pub fn register_actions(cx: &mut AppContext) {
     cx.on_action(play_pause);
     cx.on_action(next);
     cx.on_action(previous);
+    cx.on_action(toggle_queue);
+    cx.on_action(about);
+    cx.on_action(show_logs);
+    cx.on_action(show_unreadable_files);
+    cx.on_action(toggle_palette);
+    cx.on_action(focus_search);
+    cx.on_action(import_stats);
+    cx.on_action(shuffle_remaining);
+    cx.on_action(reset_library);
     debug!("actions: {:?}", cx.all_action_names());
     debug!("action available: {:?}", cx.is_action_available(&Quit));
     if cfg!(target_os = "macos") {
         cx.bind_keys([KeyBinding::new("cmd-q", Quit, None)]);
         cx.bind_keys([KeyBinding::new("cmd-right", Next, None)]);
         cx.bind_keys([KeyBinding::new("cmd-left", Previous, None)]);
+        cx.bind_keys([KeyBinding::new("cmd-u", ToggleQueue, None)]);
+        cx.bind_keys([KeyBinding::new("cmd-k", TogglePalette, None)]);
+        cx.bind_keys([KeyBinding::new("cmd-f", FocusSearch, None)]);
+        cx.bind_keys([KeyBinding::new("cmd-f", NoAction, Some("TrimDialog"))]);
     } else {
         cx.bind_keys([KeyBinding::new("ctrl-w", Quit, None)]);
         cx.bind_keys([KeyBinding::new("ctrl-right", Next, None)]);
         cx.bind_keys([KeyBinding::new("ctrl-left", Previous, None)]);
+        cx.bind_keys([KeyBinding::new("ctrl-u", ToggleQueue, None)]);
+        cx.bind_keys([KeyBinding::new("ctrl-k", TogglePalette, None)]);
+        cx.bind_keys([KeyBinding::new("ctrl-f", FocusSearch, None)]);
+        // the trim dialog's time fields are typed into, so searching doesn't take their focus
+        cx.bind_keys([KeyBinding::new("ctrl-f", NoAction, Some("TrimDialog"))]);
     }
     cx.bind_keys([KeyBinding::new("space", PlayPause, None)]);
+    cx.bind_keys([
+        KeyBinding::new("tab", FocusNext, Some("ReleaseView")),
+        KeyBinding::new("shift-tab", FocusPrevious, Some("ReleaseView")),
+        KeyBinding::new("enter", Activate, Some("ReleaseView")),
+    ]);
+    // these take precedence over the bindings above while the palette is focused
+    cx.bind_keys([
+        KeyBinding::new("space", InsertSpace, Some("CommandPalette")),
+        KeyBinding::new("down", SelectNext, Some("CommandPalette")),
+        KeyBinding::new("up", SelectPrevious, Some("CommandPalette")),
+        KeyBinding::new("enter", Confirm, Some("CommandPalette")),
+        KeyBinding::new("escape", Dismiss, Some("CommandPalette")),
+    ]);
+    cx.bind_keys([
+        KeyBinding::new("tab", SwitchField, Some("TrimDialog")),
+        KeyBinding::new("enter", Save, Some("TrimDialog")),
+        KeyBinding::new("escape", Cancel, Some("TrimDialog")),
+    ]);
     cx.set_menus(vec![Menu {
         name: SharedString::from("Muzak"),
-        items: vec![MenuItem::action("Quit", Quit)],
+        items: vec![
+            MenuItem::action("About Muzak", About),
+            MenuItem::action("Quit", Quit),
+        ],
     }]);
 }
 
 fn quit(_: &Quit, cx: &mut AppContext) {
     info!("Quitting...");
-    cx.quit();
+
+    cx.spawn(|mut cx| async move {
+        if timeout(QUIT_TIMEOUT, shut_down(&mut cx)).await.is_err() {
+            warn!("Timed out shutting down, quitting anyway");
+        }
+
+        cx.update(|cx| cx.quit()).ok();
+    })
+    .detach();
+}
+
+/// Stops playback, letting it fade out, and then tells the services that playback has stopped so
+/// that the current track is scrobbled (or queued to be) if it's been listened to.
+async fn shut_down(cx: &mut AsyncAppContext) {
+    let Ok(playback_state) = cx.update(|cx| {
+        let playback_state = cx.global::<PlaybackInfo>().playback_state.clone();
+
+        if *playback_state.read(cx) != PlaybackState::Stopped {
+            cx.global::<GPUIPlaybackInterface>().stop();
+        }
+
+        playback_state
+    }) else {
+        return;
+    };
+
+    loop {
+        match playback_state.read_with(cx, |state, _| *state) {
+            Ok(PlaybackState::Stopped) | Err(_) => break,
+            Ok(_) => {
+                cx.background_executor()
+                    .timer(Duration::from_millis(10))
+                    .await
+            }
+        }
+    }
+
+    let Ok(services) = cx.update(|cx| {
+        cx.global::<Models>()
+            .mmbs
+            .read(cx)
+            .0
+            .values()
+            .cloned()
+            .collect::<Vec<_>>()
+    }) else {
+        return;
+    };
+
+    // the services may not have handled the state change yet, and handling it twice is harmless
+    for service in services {
+        service
+            .lock()
+            .await
+            .state_changed(PlaybackState::Stopped)
+            .await;
+    }
 }
 
 fn play_pause(_: &PlayPause, cx: &mut AppContext) {
@@ -58,6 +223,247 @@ fn next(_: &Next, cx: &mut AppContext) {
 }
 
 fn previous(_: &Previous, cx: &mut AppContext) {
-    let interface = cx.global::<GPUIPlaybackInterface>();
-    interface.previous();
+    history::previous(cx);
+}
+
+fn toggle_queue(_: &ToggleQueue, cx: &mut AppContext) {
+    let show_queue = cx.global::<Models>().show_queue.clone();
+    show_queue.update(cx, |m, cx| {
+        *m = !*m;
+        cx.notify();
+    });
+}
+
+fn about(_: &About, cx: &mut AppContext) {
+    let show_about = cx.global::<Models>().show_about.clone();
+    show_about.update(cx, |m, cx| {
+        *m = true;
+        cx.notify();
+    });
+}
+
+fn show_logs(_: &ShowLogs, cx: &mut AppContext) {
+    let show_about = cx.global::<Models>().show_about.clone();
+    show_about.update(cx, |m, cx| {
+        *m = false;
+        cx.notify();
+    });
+
+    let show_logs = cx.global::<Models>().show_logs.clone();
+    show_logs.update(cx, |m, cx| {
+        *m = true;
+        cx.notify();
+    });
+}
+
+fn show_unreadable_files(_: &ShowUnreadableFiles, cx: &mut AppContext) {
+    let show_unreadable_files = cx.global::<Models>().show_unreadable_files.clone();
+    show_unreadable_files.update(cx, |m, cx| {
+        *m = true;
+        cx.notify();
+    });
+}
+
+fn toggle_palette(_: &TogglePalette, cx: &mut AppContext) {
+    let show_palette = cx.global::<Models>().show_palette.clone();
+    show_palette.update(cx, |m, cx| {
+        *m = !*m;
+        cx.notify();
+    });
+}
+
+/// Opens the command palette with an empty query, or empties it if it's already open.
+fn focus_search(_: &FocusSearch, cx: &mut AppContext) {
+    let show_palette = cx.global::<Models>().show_palette.clone();
+    show_palette.update(cx, |m, cx| {
+        *m = true;
+        cx.notify();
+    });
+}
+
+fn shuffle_remaining(_: &ShuffleRemaining, cx: &mut AppContext) {
+    cx.global::<GPUIPlaybackInterface>().shuffle_remaining();
+}
+
+/// Asks for an export from another player, and imports the play counts, ratings, and last-played
+/// dates of the tracks in it that are in the library.
+fn import_stats(_: &ImportStats, cx: &mut AppContext) {
+    let paths = cx.prompt_for_paths(PathPromptOptions {
+        files: true,
+        directories: false,
+        multiple: false,
+    });
+    let pool = cx.global::<Pool>().0.clone();
+
+    cx.spawn(|mut cx| async move {
+        let Ok(Ok(Some(paths))) = paths.await else {
+            return;
+        };
+        let Some(path) = paths.into_iter().next() else {
+            return;
+        };
+
+        let tracks = match cx
+            .background_executor()
+            .spawn(async move { read_export(&path) })
+            .await
+        {
+            Ok(tracks) => tracks,
+            Err(e) => {
+                error!("Could not read export: {:?}", e);
+                show_prompt(&mut cx, "Couldn't read the export", Some(e.to_string()));
+                return;
+            }
+        };
+
+        let report = import_stats_from(&pool, tracks).await;
+
+        info!(
+            "Imported stats for {} tracks, {} unmatched",
+            report.matched,
+            report.unmatched.len()
+        );
+        for unmatched in &report.unmatched {
+            info!("Not in the library: {}", unmatched);
+        }
+
+        // the full list is logged, since it can be very long
+        let detail = (!report.unmatched.is_empty()).then(|| {
+            let mut detail = report
+                .unmatched
+                .iter()
+                .take(10)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if report.unmatched.len() > 10 {
+                detail.push_str(&format!("\nand {} more", report.unmatched.len() - 10));
+            }
+
+            format!("Not in the library:\n{}", detail)
+        });
+
+        show_prompt(
+            &mut cx,
+            &format!(
+                "Imported {} track{}",
+                report.matched,
+                if report.matched == 1 { "" } else { "s" }
+            ),
+            detail,
+        );
+    })
+    .detach();
+}
+
+/// Asks for confirmation, then deletes the library database and scans the library into a new one.
+fn reset_library(_: &ResetLibrary, cx: &mut AppContext) {
+    let Some(window) = cx.active_window() else {
+        return;
+    };
+
+    let Ok(answer) = window.update(cx, |_, cx| {
+        cx.prompt(
+            PromptLevel::Warning,
+            "Reset the library?",
+            Some(
+                "Play counts, ratings, pinned albums, and everything else changed in Muzak will be \
+                 lost, and the library will be scanned again.",
+            ),
+            &["Reset library", "Cancel"],
+        )
+    }) else {
+        return;
+    };
+
+    cx.spawn(|mut cx| async move {
+        if !matches!(answer.await, Ok(0)) {
+            return;
+        }
+
+        let Ok(pool) = cx.update(|cx| {
+            cx.global::<ScanInterface>().stop();
+            cx.global::<Pool>().0.clone()
+        }) else {
+            return;
+        };
+
+        let path = get_dirs().data_dir().join("library.db");
+
+        match reset_database(pool, &path).await {
+            Ok(pool) => {
+                cx.update(|cx| library_reset(cx, pool)).ok();
+            }
+            Err(e) => {
+                error!("Could not reset the library: {:?}", e);
+                show_prompt(
+                    &mut cx,
+                    "Couldn't reset the library",
+                    Some(format!("{}\nRestart Muzak to try again.", e)),
+                );
+            }
+        }
+    })
+    .detach();
+}
+
+/// Switches everything over to the new database, and scans the library into it.
+fn library_reset(cx: &mut AppContext, pool: SqlitePool) {
+    cx.set_global(Pool(pool.clone()));
+    cx.set_global(create_cache());
+
+    // the skipped and trimmed tracks were stored in the old database
+    let skipped = cx.global::<SkippedTracks>().0.clone();
+    let skipped_paths: Vec<String> = skipped.update(cx, |m, cx| {
+        cx.notify();
+        m.drain().collect()
+    });
+    cx.global::<GPUIPlaybackInterface>()
+        .set_skip_always(skipped_paths, false);
+
+    let trims = cx.global::<TrackTrims>().0.clone();
+    let trimmed: Vec<(String, TrackTrim)> = trims.update(cx, |m, cx| {
+        cx.notify();
+        m.drain()
+            .map(|(path, _)| (path, TrackTrim::default()))
+            .collect()
+    });
+    cx.global::<GPUIPlaybackInterface>().set_trims(trimmed);
+
+    let data_interface = cx.global::<GPUIDataInterface>();
+    data_interface.replace_pool(pool.clone());
+    data_interface.clear_image_cache();
+
+    let scan_interface = cx.global::<ScanInterface>();
+    scan_interface.replace_pool(pool);
+    scan_interface.rescan();
+
+    // the library views reload whatever they're showing
+    let pinned_albums = cx.global::<Models>().pinned_albums.clone();
+    pinned_albums.update(cx, |m, cx| {
+        *m += 1;
+        cx.notify();
+    });
+    let metadata_reloaded = cx.global::<Models>().metadata_reloaded.clone();
+    metadata_reloaded.update(cx, |m, cx| {
+        *m += 1;
+        cx.notify();
+    });
+
+    info!("Library reset, rescanning");
+}
+
+fn show_prompt(cx: &mut AsyncAppContext, message: &str, detail: Option<String>) {
+    cx.update(|cx| {
+        if let Some(window) = cx.active_window() {
+            window
+                .update(cx, |_, cx| {
+                    // the only answer is OK, so it isn't waited for
+                    let _ = cx.prompt(PromptLevel::Info, message, detail.as_deref(), &["OK"]);
+                })
+                .ok();
+        }
+    })
+    .ok();
 }
@@ -1,18 +1,23 @@
 mod lastfm;
 
+use std::time::Duration;
+
 use gpui::*;
 use prelude::FluentBuilder;
 
 use crate::{
-    library::scan::ScanEvent,
+    library::scan::{LastScan, ScanEvent, ScanInterface},
     services::mmb::lastfm::{LASTFM_API_KEY, LASTFM_API_SECRET},
+    settings::SettingsGlobal,
 };
 
 use super::{
+    components::tooltip::Tooltip,
     constants::{APP_ROUNDING, FONT_AWESOME},
-    global_actions::Quit,
+    global_actions::{About, Quit, ToggleQueue},
     models::Models,
     theme::Theme,
+    util::format_time_ago,
 };
 
 pub struct Header {
@@ -86,11 +91,65 @@ impl Render for Header {
                     .pt(px(5.0))
                     .flex()
                     .when(cfg!(not(target_os = "macos")), |this| {
-                        this.child(div().child("Muzak").mr(px(16.0)))
+                        this.child(
+                            div()
+                                .id("about-button")
+                                .child("Muzak")
+                                .mr(px(16.0))
+                                .on_mouse_down(MouseButton::Left, |_, cx| {
+                                    cx.prevent_default();
+                                    cx.stop_propagation();
+                                })
+                                .on_click(|_, cx| cx.dispatch_action(Box::new(About))),
+                        )
                     })
                     .child(self.scan_status.clone()),
             )
             .child(div().ml_auto())
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .px(px(12.0))
+                    .text_color(theme.text_secondary)
+                    .bg(theme.window_button)
+                    .id("accent-picker-button")
+                    .hover(|this| this.bg(theme.window_button_hover))
+                    .active(|this| this.bg(theme.window_button_active))
+                    .font_family(FONT_AWESOME)
+                    .text_size(px(11.0))
+                    .on_mouse_down(MouseButton::Left, |_, cx| {
+                        cx.prevent_default();
+                        cx.stop_propagation();
+                    })
+                    .on_click(|_, cx| {
+                        let show_accent_picker = cx.global::<Models>().show_accent_picker.clone();
+                        show_accent_picker.update(cx, |m, cx| {
+                            *m = true;
+                            cx.notify();
+                        });
+                    })
+                    .child(""),
+            )
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .px(px(12.0))
+                    .text_color(theme.text_secondary)
+                    .bg(theme.window_button)
+                    .id("queue-toggle-button")
+                    .hover(|this| this.bg(theme.window_button_hover))
+                    .active(|this| this.bg(theme.window_button_active))
+                    .font_family(FONT_AWESOME)
+                    .text_size(px(11.0))
+                    .on_mouse_down(MouseButton::Left, |_, cx| {
+                        cx.prevent_default();
+                        cx.stop_propagation();
+                    })
+                    .on_click(|_, cx| cx.dispatch_action(Box::new(ToggleQueue)))
+                    .child(""),
+            )
             .when_some(self.lastfm.clone(), |this, lastfm| this.child(lastfm))
             .when(cfg!(not(target_os = "macos")), |this| {
                 this.child(
@@ -106,11 +165,13 @@ impl Render for Header {
 
 pub struct ScanStatus {
     scan_model: Model<ScanEvent>,
+    last_scan: Model<Option<LastScan>>,
 }
 
 impl ScanStatus {
     pub fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
         let scan_model = cx.global::<Models>().scan_state.clone();
+        let last_scan = cx.global::<Models>().last_scan.clone();
 
         cx.new_view(|cx| {
             cx.observe(&scan_model, |_, _, cx| {
@@ -118,7 +179,29 @@ impl ScanStatus {
             })
             .detach();
 
-            Self { scan_model }
+            cx.observe(&last_scan, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            // keeps the time since the last scan up to date
+            cx.spawn(|this, mut cx| async move {
+                loop {
+                    cx.background_executor()
+                        .timer(Duration::from_secs(60))
+                        .await;
+
+                    if this.update(&mut cx, |_, cx| cx.notify()).is_err() {
+                        break;
+                    }
+                }
+            })
+            .detach();
+
+            Self {
+                scan_model,
+                last_scan,
+            }
         })
     }
 }
@@ -127,25 +210,50 @@ impl Render for ScanStatus {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
         let status = self.scan_model.read(cx);
+        let scanning = !matches!(
+            status,
+            ScanEvent::ScanCompleteIdle | ScanEvent::ScanCompleteWatching
+        );
+        let last_scan = *self.last_scan.read(cx);
+        let animations_enabled = cx
+            .global::<SettingsGlobal>()
+            .model
+            .read(cx)
+            .interface
+            .animations_enabled;
+
+        let icon = div()
+            .mr(px(8.0))
+            .pt(px(5.0))
+            .text_size(px(9.0))
+            .h_full()
+            .font_family(FONT_AWESOME)
+            .child(if scanning { "" } else { "" });
 
         div()
             .flex()
             .text_sm()
-            .child(
-                div()
-                    .mr(px(8.0))
-                    .pt(px(5.0))
-                    .text_size(px(9.0))
-                    .h_full()
-                    .font_family(FONT_AWESOME)
-                    .child(match status {
-                        ScanEvent::ScanCompleteIdle | ScanEvent::ScanCompleteWatching => "",
-                        _ => "",
-                    }),
-            )
+            .child(if scanning && animations_enabled {
+                icon.with_animation(
+                    "scan-spinner",
+                    Animation::new(Duration::from_secs(1)).repeat(),
+                    |this, delta| this.opacity(0.4 + 0.6 * (delta * std::f32::consts::PI).sin()),
+                )
+                .into_any_element()
+            } else {
+                icon.into_any_element()
+            })
             .text_color(theme.text_secondary)
             .child(match status {
-                ScanEvent::ScanCompleteIdle => "".to_string(),
+                ScanEvent::ScanCompleteIdle => last_scan
+                    .map(|v| {
+                        format!(
+                            "Last scanned {} • {} tracks",
+                            format_time_ago(v.timestamp),
+                            v.tracks
+                        )
+                    })
+                    .unwrap_or_default(),
                 ScanEvent::ScanProgress { current, total } => {
                     format!(
                         "Scanning ({}%)",
@@ -155,9 +263,27 @@ impl Render for ScanStatus {
                 ScanEvent::DiscoverProgress(progress) => {
                     format!("Discovering files ({})", progress)
                 }
-                ScanEvent::Cleaning => "".to_string(),
+                ScanEvent::Cleaning | ScanEvent::MetadataReloaded => "".to_string(),
                 ScanEvent::ScanCompleteWatching => "Watching for updates".to_string(),
             })
+            .when(!scanning, |this| {
+                this.child(
+                    div()
+                        .id("scan-now")
+                        .ml(px(8.0))
+                        .px(px(6.0))
+                        .rounded(px(4.0))
+                        .font_family(FONT_AWESOME)
+                        .text_size(px(10.0))
+                        .pt(px(4.0))
+                        .cursor_pointer()
+                        .hover(|this| this.bg(theme.nav_button_hover))
+                        .active(|this| this.bg(theme.nav_button_active))
+                        .child("")
+                        .tooltip(|cx| Tooltip::new(cx, "Scan now", None))
+                        .on_click(|_, cx| cx.global::<ScanInterface>().scan()),
+                )
+            })
     }
 }
 
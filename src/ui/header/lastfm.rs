@@ -1,10 +1,18 @@
 use gpui::*;
-use tracing::error;
+use prelude::FluentBuilder;
+use tracing::{error, warn};
 
 use crate::{
-    services::mmb::lastfm::{client::LastFMClient, LASTFM_API_KEY, LASTFM_API_SECRET},
+    services::mmb::lastfm::{
+        client::LastFMClient, pending::SharedPendingScrobbles, submit_pending, ScrobbleStatus,
+        LASTFM_API_KEY, LASTFM_API_SECRET,
+    },
     ui::{
-        constants::FONT_AWESOME_BRANDS,
+        components::{
+            context::context,
+            menu::{menu, menu_item},
+        },
+        constants::{FONT_AWESOME, FONT_AWESOME_BRANDS},
         models::{LastFMState, Models},
         theme::Theme,
     },
@@ -12,6 +20,8 @@ use crate::{
 
 pub struct LastFM {
     state: Model<LastFMState>,
+    scrobble_status: Model<Option<ScrobbleStatus>>,
+    pending_scrobbles: Model<usize>,
     name: Option<SharedString>,
 }
 
@@ -20,6 +30,18 @@ impl LastFM {
         cx.new_view(|cx| {
             let models = cx.global::<Models>();
             let state = models.lastfm.clone();
+            let scrobble_status = models.scrobble_status.clone();
+            let pending_scrobbles = models.pending_scrobbles.clone();
+
+            cx.observe(&scrobble_status, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            cx.observe(&pending_scrobbles, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
 
             cx.observe(&state, |this: &mut LastFM, m, cx| {
                 this.name = match m.read(cx) {
@@ -35,6 +57,8 @@ impl LastFM {
                     _ => None,
                 },
                 state,
+                scrobble_status,
+                pending_scrobbles,
             }
         })
     }
@@ -44,8 +68,14 @@ impl Render for LastFM {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
         let state = self.state.clone();
+        let pending = cx.global::<SharedPendingScrobbles>().clone();
+        let session_key = match self.state.read(cx) {
+            LastFMState::Connected(session) => Some(session.key.clone()),
+            _ => None,
+        };
+        let pending_count = *self.pending_scrobbles.read(cx);
 
-        div()
+        let button = div()
             .flex()
             .text_sm()
             .px(px(12.0))
@@ -82,6 +112,29 @@ impl Render for LastFM {
                         .into_any_element(),
                 }),
             )
+            .when_some(*self.scrobble_status.read(cx), |this, status| {
+                this.child(
+                    div()
+                        .font_family(FONT_AWESOME)
+                        .ml(px(8.0))
+                        .pt(px(3.0))
+                        .text_size(px(9.0))
+                        .h_full()
+                        .child(match status {
+                            ScrobbleStatus::Submitted => "",
+                            ScrobbleStatus::Failed => "",
+                            ScrobbleStatus::Queued => "",
+                        }),
+                )
+            })
+            .when(pending_count > 0, |this| {
+                this.child(
+                    div()
+                        .ml(px(6.0))
+                        .text_color(theme.text_secondary)
+                        .child(format!("{} pending", pending_count)),
+                )
+            })
             .on_click(move |_, cx| {
                 let state = state.clone();
                 let read = state.read(cx).clone();
@@ -91,23 +144,84 @@ impl Render for LastFM {
                     LastFMState::AwaitingFinalization(token) => confirm(cx, state, token),
                     LastFMState::Connected(_) => (),
                 }
-            })
+            });
+
+        context("lastfm-context").with(button).child(
+            div().bg(theme.elevated_background).child(
+                menu()
+                    .when_some(
+                        session_key.filter(|_| pending_count > 0),
+                        |this, session_key| {
+                            let pending = pending.clone();
+
+                            this.item(menu_item(
+                                "lastfm-sync-pending",
+                                Some(""),
+                                "Submit pending scrobbles now",
+                                move |_, cx| sync_now(cx, session_key.clone(), pending.clone()),
+                            ))
+                        },
+                    )
+                    .item(menu_item(
+                        "lastfm-clear-pending",
+                        Some(""),
+                        format!(
+                            "Clear {} pending scrobble{}",
+                            pending_count,
+                            if pending_count == 1 { "" } else { "s" }
+                        ),
+                        move |_, _| {
+                            pending
+                                .0
+                                .lock()
+                                .expect("could not lock pending scrobbles")
+                                .clear();
+                        },
+                    )),
+            ),
+        )
     }
 }
 
+/// Submits the pending scrobbles right away, regardless of the scrobble mode.
+fn sync_now(cx: &mut WindowContext<'_>, session_key: String, pending: SharedPendingScrobbles) {
+    let (Some(key), Some(secret)) = (LASTFM_API_KEY, LASTFM_API_SECRET) else {
+        return;
+    };
+
+    let scrobble_status = cx.global::<Models>().scrobble_status.clone();
+
+    cx.spawn(|mut cx| async move {
+        let mut client = LastFMClient::new(key.to_string(), secret);
+        client.set_session(session_key);
+
+        let status = if submit_pending(&mut client, &pending).await {
+            ScrobbleStatus::Failed
+        } else {
+            ScrobbleStatus::Submitted
+        };
+
+        scrobble_status
+            .update(&mut cx, |m, cx| {
+                *m = Some(status);
+                cx.notify();
+            })
+            .expect("failed to update scrobble status");
+    })
+    .detach();
+}
+
 fn get_token(cx: &mut WindowContext<'_>, state: Model<LastFMState>) {
+    let (Some(key), Some(secret)) = (LASTFM_API_KEY, LASTFM_API_SECRET) else {
+        warn!("Muzak was built without last.fm API keys, not signing in.");
+        return;
+    };
+
     cx.spawn(|mut cx| async move {
-        let mut client = LastFMClient::new(
-            LASTFM_API_KEY.unwrap().to_string(),
-            LASTFM_API_SECRET.unwrap(),
-        );
+        let mut client = LastFMClient::new(key.to_string(), secret);
 
         if let Ok(token) = client.get_token().await {
-            let path = format!(
-                "http://last.fm/api/auth/?api_key={}&token={}",
-                LASTFM_API_KEY.unwrap(),
-                token
-            );
+            let path = format!("http://last.fm/api/auth/?api_key={}&token={}", key, token);
             if open::that(&path).is_err() {
                 error!(
                     "Failed to open web browser to {}; you'll need to navigate to it manually.",
@@ -128,11 +242,13 @@ fn get_token(cx: &mut WindowContext<'_>, state: Model<LastFMState>) {
 }
 
 fn confirm(cx: &mut WindowContext<'_>, state: Model<LastFMState>, token: String) {
+    let (Some(key), Some(secret)) = (LASTFM_API_KEY, LASTFM_API_SECRET) else {
+        warn!("Muzak was built without last.fm API keys, not confirming sign in.");
+        return;
+    };
+
     cx.spawn(|mut cx| async move {
-        let mut client = LastFMClient::new(
-            LASTFM_API_KEY.unwrap().to_string(),
-            LASTFM_API_SECRET.unwrap(),
-        );
+        let mut client = LastFMClient::new(key.to_string(), secret);
 
         if let Ok(session) = client.get_session(token).await {
             state
@@ -6,9 +6,13 @@ use navigation::NavigationView;
 use release_view::ReleaseView;
 use tracing::debug;
 
+use crate::library::scan::ScanInterface;
+
+use super::{models::Models, theme::Theme};
+
 mod album_view;
 mod navigation;
-mod release_view;
+pub mod release_view;
 
 #[derive(Clone)]
 enum LibraryView {
@@ -30,14 +34,21 @@ enum ViewSwitchMessage {
 
 impl EventEmitter<ViewSwitchMessage> for VecDeque<ViewSwitchMessage> {}
 
+/// Creates the view for the message. Returns None if the view couldn't be created, e.g. because
+/// the album couldn't be found in the library.
 fn make_view(
     message: &ViewSwitchMessage,
     cx: &mut ViewContext<'_, Library>,
     model: Model<VecDeque<ViewSwitchMessage>>,
-) -> LibraryView {
+) -> Option<LibraryView> {
     match message {
-        ViewSwitchMessage::Albums => LibraryView::Album(AlbumView::new(cx, model.clone())),
-        ViewSwitchMessage::Release(id) => LibraryView::Release(ReleaseView::new(cx, *id)),
+        ViewSwitchMessage::Albums => Some(LibraryView::Album(AlbumView::new(cx, model.clone()))),
+        ViewSwitchMessage::Release(id) => {
+            let view = ReleaseView::new(cx, *id)?;
+            // the release view is navigated with the keyboard, so it takes focus when opened
+            cx.focus_view(&view);
+            Some(LibraryView::Release(view))
+        }
         ViewSwitchMessage::Back => panic!("improper use of make_view (cannot make Back)"),
     }
 }
@@ -68,14 +79,19 @@ impl Library {
                                 }
                             });
 
-                            if let Some(message) = last {
+                            let view = last.and_then(|message| {
                                 debug!("{:?}", message);
                                 make_view(&message, cx, m)
-                            } else {
-                                this.view.clone()
-                            }
+                            });
+
+                            view.unwrap_or_else(|| this.view.clone())
                         }
                         _ => {
+                            let Some(view) = make_view(message, cx, m.clone()) else {
+                                // the current view stays, so it isn't added to the history
+                                return;
+                            };
+
                             m.update(cx, |v, cx| {
                                 if v.len() > 99 {
                                     v.pop_front();
@@ -85,7 +101,7 @@ impl Library {
                                 cx.notify();
                             });
 
-                            make_view(message, cx, m)
+                            view
                         }
                     };
 
@@ -94,6 +110,39 @@ impl Library {
             )
             .detach();
 
+            let open_release = cx.global::<Models>().open_release.clone();
+            let switcher_model_clone = switcher_model.clone();
+
+            cx.observe(&open_release, move |_, open_release, cx| {
+                let Some(id) = *open_release.read(cx) else {
+                    return;
+                };
+
+                switcher_model_clone.update(cx, |_, cx| cx.emit(ViewSwitchMessage::Release(id)));
+                open_release.update(cx, |m, _| *m = None);
+            })
+            .detach();
+
+            // the release being shown may have been changed by reloading its metadata
+            let metadata_reloaded = cx.global::<Models>().metadata_reloaded.clone();
+            let switcher_model_clone = switcher_model.clone();
+
+            cx.observe(&metadata_reloaded, move |this: &mut Library, _, cx| {
+                let current = switcher_model_clone.read(cx).back().cloned();
+
+                if let Some(message @ ViewSwitchMessage::Release(_)) = current {
+                    match make_view(&message, cx, switcher_model_clone.clone()) {
+                        Some(view) => this.view = view,
+                        // the album may have been removed by reloading its files
+                        None => switcher_model_clone
+                            .update(cx, |_, cx| cx.emit(ViewSwitchMessage::Back)),
+                    }
+
+                    cx.notify();
+                }
+            })
+            .detach();
+
             Library {
                 navigation_view: NavigationView::new(cx, switcher_model.clone()),
                 view,
@@ -103,8 +152,20 @@ impl Library {
 }
 
 impl Render for Library {
-    fn render(&mut self, _: &mut ViewContext<Self>) -> impl IntoElement {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let drop_background = theme.nav_button_hover;
+
         div()
+            // folders dropped onto the library are scanned again
+            .drag_over::<ExternalPaths>(move |style, _, _| style.bg(drop_background))
+            .on_drop(|paths: &ExternalPaths, cx| {
+                let scan_interface = cx.global::<ScanInterface>();
+
+                for path in paths.paths().iter().filter(|v| v.is_dir()) {
+                    scan_interface.scan_folder(path.clone());
+                }
+            })
             .w_full()
             .h_full()
             .flex()
@@ -11,8 +11,14 @@ use crate::{
         scan::ScanEvent,
         types::Album,
     },
+    settings::{interface::ListDensity, save_settings, SettingsGlobal},
     ui::{
         app::DropOnNavigateQueue,
+        components::{
+            button::button,
+            context::context,
+            menu::{menu, menu_item},
+        },
         models::Models,
         theme::Theme,
         util::{create_or_retrieve_view, prune_views},
@@ -21,12 +27,83 @@ use crate::{
 
 use super::ViewSwitchMessage;
 
+#[derive(Clone, Copy)]
+enum AlbumListEntry {
+    Heading(&'static str),
+    /// An album, and whether it's in the pinned section.
+    Album(i64, bool),
+}
+
+/// Lists the pinned albums (if there are any) followed by every album in the library.
+fn list_entries(cx: &AppContext, sort: AlbumSortMethod) -> Option<Vec<AlbumListEntry>> {
+    let albums = cx
+        .list_albums(sort)
+        .map_err(|e| {
+            error!("Failed to retrieve album IDs from SQLite: {:?}", e);
+        })
+        .ok()?;
+    let pinned = cx.list_pinned_albums().unwrap_or_else(|e| {
+        error!("Failed to retrieve pinned albums from SQLite: {:?}", e);
+        Vec::new()
+    });
+
+    let mut entries = Vec::with_capacity(albums.len() + pinned.len() + 2);
+
+    if !pinned.is_empty() {
+        entries.push(AlbumListEntry::Heading("Pinned"));
+        entries.extend(
+            pinned
+                .into_iter()
+                .map(|(id, _)| AlbumListEntry::Album(id as i64, true)),
+        );
+        entries.push(AlbumListEntry::Heading("All albums"));
+    }
+
+    entries.extend(
+        albums
+            .into_iter()
+            .map(|(id, _)| AlbumListEntry::Album(id as i64, false)),
+    );
+
+    Some(entries)
+}
+
+/// Pins the album to the top of the library, or unpins it.
+pub(super) fn pin_album(cx: &mut AppContext, album_id: i64, pinned: bool) {
+    if let Err(e) = cx.set_album_pinned(album_id, pinned) {
+        error!("Could not pin album: {:?}", e);
+        return;
+    }
+
+    pinned_albums_changed(cx);
+}
+
+/// Moves a pinned album up (with a negative offset) or down the pinned section.
+fn move_pin(cx: &mut AppContext, album_id: i64, offset: isize) {
+    if let Err(e) = cx.move_pinned_album(album_id, offset) {
+        error!("Could not move pinned album: {:?}", e);
+        return;
+    }
+
+    pinned_albums_changed(cx);
+}
+
+fn pinned_albums_changed(cx: &mut AppContext) {
+    let pinned_albums = cx.global::<Models>().pinned_albums.clone();
+    pinned_albums.update(cx, |m, cx| {
+        *m += 1;
+        cx.notify();
+    });
+}
+
 #[derive(Clone)]
 pub struct AlbumView {
     views_model: Model<AHashMap<usize, View<AlbumItem>>>,
     render_counter: Model<usize>,
     list_state: ListState,
     view_switch_model: Model<VecDeque<ViewSwitchMessage>>,
+    density: ListDensity,
+    sort: AlbumSortMethod,
 }
 
 impl AlbumView {
@@ -35,14 +112,14 @@ impl AlbumView {
         view_switch_model: Model<VecDeque<ViewSwitchMessage>>,
     ) -> View<Self> {
         cx.new_view(|cx| {
-            let album_ids = cx.list_albums(AlbumSortMethod::TitleAsc).map_err(|e| {
-                error!("Failed to retrieve album IDs from SQLite: {:?}", e);
-            });
+            let settings = cx.global::<SettingsGlobal>().model.clone();
+            let sort = settings.read(cx).interface.album_sort;
+            let entries = list_entries(cx, sort);
             let views_model = cx.new_model(|_| AHashMap::new());
             let render_counter = cx.new_model(|_| 0);
 
             let list_state = AlbumView::make_list_state(
-                album_ids.clone().ok(),
+                entries,
                 views_model.clone(),
                 render_counter.clone(),
                 view_switch_model.clone(),
@@ -66,29 +143,53 @@ impl AlbumView {
             })
             .detach();
 
+            let pinned_albums = cx.global::<Models>().pinned_albums.clone();
+
+            cx.observe(&pinned_albums, |this: &mut AlbumView, _, cx| {
+                this.regenerate_list_state(cx);
+            })
+            .detach();
+
+            let density = settings.read(cx).interface.list_density;
+
+            cx.observe(&settings, |this: &mut AlbumView, settings, cx| {
+                let density = settings.read(cx).interface.list_density;
+                let sort = settings.read(cx).interface.album_sort;
+
+                // album rows change height with the density, so they have to be measured again
+                if density != this.density || sort != this.sort {
+                    this.density = density;
+                    this.sort = sort;
+                    this.regenerate_list_state(cx);
+                }
+            })
+            .detach();
+
             let queue = cx.global::<DropOnNavigateQueue>().clone();
 
-            queue.drop_all(cx);
+            // the view being navigated away from still holds its images until this one replaces
+            // it, so they're dropped afterwards (or by the next periodic flush)
+            WindowContext::defer(cx, move |cx| queue.drop_unused(cx));
 
             AlbumView {
                 views_model,
                 render_counter,
                 list_state,
                 view_switch_model,
+                density,
+                sort,
             }
         })
     }
 
     fn regenerate_list_state<V: 'static>(&mut self, cx: &mut ViewContext<V>) {
         let curr_scroll = self.list_state.logical_scroll_top();
-        let album_ids = cx.list_albums(AlbumSortMethod::TitleAsc).map_err(|e| {
-            error!("Failed to retrieve album IDs from SQLite: {:?}", e);
-        });
+        let entries = list_entries(cx, self.sort);
         self.views_model = cx.new_model(|_| AHashMap::new());
         self.render_counter = cx.new_model(|_| 0);
 
         self.list_state = AlbumView::make_list_state(
-            album_ids.ok(),
+            entries,
             self.views_model.clone(),
             self.render_counter.clone(),
             self.view_switch_model.clone(),
@@ -100,21 +201,40 @@ impl AlbumView {
     }
 
     fn make_list_state(
-        album_ids: Option<Vec<(u32, String)>>,
+        entries: Option<Vec<AlbumListEntry>>,
         views_model: Model<AHashMap<usize, View<AlbumItem>>>,
         render_counter: Model<usize>,
         view_switch_model: Model<VecDeque<ViewSwitchMessage>>,
     ) -> ListState {
-        match album_ids {
-            Some(album_ids) => {
-                let album_ids_copy = Rc::new(album_ids.clone());
+        match entries {
+            Some(entries) => {
+                let entries = Rc::new(entries);
 
                 ListState::new(
-                    album_ids.len(),
+                    entries.len(),
                     ListAlignment::Top,
                     px(300.0),
                     move |idx, cx| {
-                        let album_ids = album_ids_copy.clone();
+                        let (album_id, pinned) = match entries[idx] {
+                            AlbumListEntry::Heading(heading) => {
+                                let theme = cx.global::<Theme>();
+
+                                return div()
+                                    .w_full()
+                                    .px(px(24.0))
+                                    .pt(px(12.0))
+                                    .pb(px(6.0))
+                                    .border_b_1()
+                                    .border_color(theme.border_color)
+                                    .text_sm()
+                                    .font_weight(FontWeight::BOLD)
+                                    .text_color(theme.text_secondary)
+                                    .child(heading)
+                                    .into_any_element();
+                            }
+                            AlbumListEntry::Album(album_id, pinned) => (album_id, pinned),
+                        };
+
                         let view_switch_model = view_switch_model.clone();
 
                         prune_views(views_model.clone(), render_counter.clone(), idx, cx);
@@ -124,9 +244,7 @@ impl AlbumView {
                             .child(create_or_retrieve_view(
                                 views_model.clone(),
                                 idx,
-                                move |cx| {
-                                    AlbumItem::new(cx, album_ids[idx].0 as i64, view_switch_model)
-                                },
+                                move |cx| AlbumItem::new(cx, album_id, pinned, view_switch_model),
                                 cx,
                             ))
                             .into_any_element()
@@ -143,6 +261,7 @@ impl AlbumView {
 impl Render for AlbumView {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
+        let sort = self.sort;
 
         div()
             .flex()
@@ -156,12 +275,28 @@ impl Render for AlbumView {
             .child(
                 div()
                     .w_full()
+                    .flex()
+                    .items_center()
                     .pb(px(11.0))
                     .px(px(24.0))
-                    .line_height(px(26.0))
-                    .font_weight(FontWeight::BOLD)
-                    .text_size(px(26.0))
-                    .child("Albums"),
+                    .child(
+                        div()
+                            .line_height(px(26.0))
+                            .font_weight(FontWeight::BOLD)
+                            .text_size(px(26.0))
+                            .child("Albums"),
+                    )
+                    .child(
+                        button()
+                            .ml_auto()
+                            .child(format!("Sort: {}", sort.label()))
+                            .id("album-sort")
+                            .on_click(move |_, cx| {
+                                save_settings(cx, |settings| {
+                                    settings.interface.album_sort = sort.next();
+                                });
+                            }),
+                    ),
             )
             .child(
                 div()
@@ -203,12 +338,15 @@ pub struct AlbumItem {
     artist: Option<Arc<String>>,
     view_switch_model: Model<VecDeque<ViewSwitchMessage>>,
     id: SharedString,
+    /// Whether the item is in the pinned section, where it can be moved.
+    in_pinned_section: bool,
 }
 
 impl AlbumItem {
     pub(self) fn new(
         cx: &mut WindowContext,
         album_id: i64,
+        in_pinned_section: bool,
         view_switch_model: Model<VecDeque<ViewSwitchMessage>>,
     ) -> View<Self> {
         debug!("Creating AlbumItem view for album ID: {}", album_id);
@@ -222,22 +360,32 @@ impl AlbumItem {
             .and_then(|album| cx.get_artist_name_by_id(album.artist_id).ok());
         cx.new_view(|_| AlbumItem {
             id: SharedString::from(format!(
-                "album-item-{}",
+                "{}album-item-{}",
+                if in_pinned_section { "pinned-" } else { "" },
                 album.as_ref().map(|album| album.id).unwrap_or_default()
             )),
             album,
             artist,
             view_switch_model,
+            in_pinned_section,
         })
     }
 }
 
 impl Render for AlbumItem {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let settings = &cx.global::<SettingsGlobal>().model.read(cx).interface;
+        let show_art = !settings.disable_album_art;
+        let compact = settings.list_density == ListDensity::Compact;
         let theme = cx.global::<Theme>();
 
         if let Some(album) = &self.album {
-            div()
+            let thumb = album.thumb.clone().filter(|_| show_art);
+            let album_id = album.id;
+            let pinned = album.pinned_position.is_some();
+            let in_pinned_section = self.in_pinned_section;
+
+            let row = div()
                 .id(self.id.clone())
                 .w_full()
                 .flex()
@@ -255,15 +403,10 @@ impl Render for AlbumItem {
                         .shadow_sm()
                         .w(px(22.0))
                         .h(px(22.0))
-                        .my(px(8.0))
+                        .my(px(if compact { 4.0 } else { 8.0 }))
                         .flex_shrink_0()
-                        .when(album.thumb.is_some(), |div| {
-                            div.child(
-                                img(album.thumb.clone().unwrap().0)
-                                    .w(px(22.0))
-                                    .h(px(22.0))
-                                    .rounded(px(2.0)),
-                            )
+                        .when_some(thumb, |div, thumb| {
+                            div.child(img(thumb.0).w(px(22.0)).h(px(22.0)).rounded(px(2.0)))
                         }),
                 )
                 .child(
@@ -297,7 +440,35 @@ impl Render for AlbumItem {
                     this.view_switch_model.update(cx, |_, cx| {
                         cx.emit(ViewSwitchMessage::Release(this.album.as_ref().unwrap().id))
                     })
-                }))
+                }));
+
+            context(SharedString::from(format!("{}-context", self.id)))
+                .with(row)
+                .child(
+                    div().bg(theme.elevated_background).child(
+                        menu()
+                            .item(menu_item(
+                                "album_pin",
+                                Some(""),
+                                if pinned { "Unpin" } else { "Pin" },
+                                move |_, cx| pin_album(cx, album_id, !pinned),
+                            ))
+                            .when(in_pinned_section, |this| {
+                                this.item(menu_item(
+                                    "album_pin_move_up",
+                                    Some(""),
+                                    "Move up",
+                                    move |_, cx| move_pin(cx, album_id, -1),
+                                ))
+                                .item(menu_item(
+                                    "album_pin_move_down",
+                                    Some(""),
+                                    "Move down",
+                                    move |_, cx| move_pin(cx, album_id, 1),
+                                ))
+                            }),
+                    ),
+                )
                 .into_any_element()
         } else {
             div().into_any_element()
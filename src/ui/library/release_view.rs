@@ -1,63 +1,191 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
 
+use ahash::{AHashMap, AHashSet};
 use gpui::*;
 use prelude::FluentBuilder;
-use tracing::debug;
+use tracing::{debug, error, warn};
 
 use crate::{
-    data::{
-        events::{ImageLayout, ImageType},
-        interface::GPUIDataInterface,
-    },
+    data::{events::ImageType, interface::GPUIDataInterface},
     library::{
         db::{AlbumMethod, LibraryAccess},
-        types::{Album, Artist, Track},
+        scan::{reload_metadata, ScanInterface},
+        types::{Album, Artist, DBString, ResumePosition, Track},
+    },
+    media::metadata::CreditRole,
+    playback::{
+        gain::{clamp_gain, format_gain, refresh_gain, GAIN_STEP},
+        interface::{replace_queue, GPUIPlaybackInterface},
+        resume::resume_album,
+        shuffle::toggle_shuffle_then,
+        skip::{set_skip_always, SkippedTracks},
+    },
+    services::radio::{radio_available, start_radio},
+    settings::{
+        interface::{ClickToPlay, ListDensity},
+        SettingsGlobal,
     },
-    playback::interface::{replace_queue, GPUIPlaybackInterface},
     ui::{
         app::DropOnNavigateQueue,
         components::{
             button::{button, ButtonIntent, ButtonSize},
             context::context,
-            menu::{menu, menu_item},
+            menu::{menu, menu_item, CMenuItem},
+            styling::focus_ring,
+            tooltip::truncated_text,
         },
         constants::FONT_AWESOME,
-        models::{Models, PlaybackInfo},
+        models::{
+            ImageTransfer, ImageTransferFailed, Models, PlaybackInfo, TrackDetails, TrimTarget,
+        },
         theme::Theme,
+        util::{cover_size, format_date, format_duration, format_remaining},
     },
+    util::image_layout,
 };
 
+use super::album_view::pin_album;
+
+actions!(release_view, [FocusNext, FocusPrevious, Activate]);
+
+/// The play, add to queue, and shuffle buttons come first in the focus order, followed by the
+/// tracks. GPUI doesn't expose an accessibility tree yet, so this only covers keyboard navigation.
+const BUTTON_COUNT: usize = 3;
+
 pub struct ReleaseView {
+    focus_handle: FocusHandle,
+    focused: Model<Option<usize>>,
     album: Arc<Album>,
     image: Option<Arc<RenderImage>>,
+    backdrop: Option<Arc<RenderImage>>,
     artist: Option<Arc<Artist>>,
     tracks: Arc<Vec<Track>>,
     track_list_state: ListState,
     release_info: Option<SharedString>,
+    /// The number of tracks and how long the release is, e.g. "12 tracks, 45m".
+    length: SharedString,
+    /// Manual gain offsets of the album and each of its tracks (by ID), in dB.
+    album_gain: Model<f64>,
+    track_gains: Model<AHashMap<i64, f64>>,
+    density: ListDensity,
+    /// Where playback of the album was last stopped, if it wasn't played to the end.
+    resume: Option<ResumePosition>,
+    pinned: bool,
+    /// The album's credits by role, as summarized by [`summarize_credits`].
+    credits: Vec<(CreditRole, SharedString)>,
+    show_credits: bool,
+}
+
+/// Groups the credits of an album's tracks by role. Names that aren't credited on every track are
+/// followed by the tracks they're credited on, e.g. "Jane Doe (1, 3)".
+fn summarize_credits(
+    tracks: &[Track],
+    credits: &[(i64, CreditRole, String)],
+) -> Vec<(CreditRole, SharedString)> {
+    let mut grouped: BTreeMap<CreditRole, Vec<(&str, Vec<i64>)>> = BTreeMap::new();
+
+    for (track_id, role, name) in credits {
+        let names = grouped.entry(*role).or_default();
+
+        match names.iter_mut().find(|(v, _)| v == name) {
+            Some((_, ids)) => ids.push(*track_id),
+            None => names.push((name, vec![*track_id])),
+        }
+    }
+
+    let multi_disc = tracks
+        .iter()
+        .any(|track| track.disc_number.is_some_and(|v| v > 1));
+
+    grouped
+        .into_iter()
+        .map(|(role, names)| {
+            let names = names
+                .into_iter()
+                .map(|(name, ids)| {
+                    if ids.len() == tracks.len() {
+                        return name.to_string();
+                    }
+
+                    let numbers: Vec<String> = tracks
+                        .iter()
+                        .filter(|track| ids.contains(&track.id))
+                        .map(|track| {
+                            let number = track
+                                .track_number
+                                .map(|v| v.to_string())
+                                .unwrap_or_else(|| "?".to_string());
+
+                            match track.disc_number {
+                                Some(disc) if multi_disc => format!("{}-{}", disc, number),
+                                _ => number,
+                            }
+                        })
+                        .collect();
+
+                    format!("{} ({})", name, numbers.join(", "))
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            (role, SharedString::from(names))
+        })
+        .collect()
+}
+
+/// The tracks selected with shift or ctrl-click. The anchor is the index of the last track that
+/// was clicked, which shift-click selects a range from.
+#[derive(Default)]
+struct TrackSelection {
+    ids: AHashSet<i64>,
+    anchor: Option<usize>,
 }
 
 impl ReleaseView {
-    pub(super) fn new<V: 'static>(cx: &mut ViewContext<V>, album_id: i64) -> View<Self> {
-        cx.new_view(|cx| {
+    /// Creates the view of the album, or returns None if the album or its tracks couldn't be
+    /// retrieved.
+    pub(super) fn new<V: 'static>(cx: &mut ViewContext<V>, album_id: i64) -> Option<View<Self>> {
+        let album = cx
+            .get_album_by_id(album_id, AlbumMethod::Cached)
+            .map_err(|e| error!("Could not retrieve album {}: {:?}", album_id, e))
+            .ok()?;
+        let tracks = cx
+            .list_tracks_in_album(album_id)
+            .map_err(|e| error!("Could not retrieve tracks of album {}: {:?}", album_id, e))
+            .ok()?;
+
+        Some(cx.new_view(|cx| {
             let image = None;
-            // TODO: error handling
-            let album = cx
-                .get_album_by_id(album_id, AlbumMethod::Cached)
-                .expect("Failed to retrieve album");
-            let tracks = cx
-                .list_tracks_in_album(album_id)
-                .expect("Failed to retrieve tracks");
             let artist = cx.get_artist_by_id(album.artist_id).ok();
+            let album_credits = Arc::new(cx.list_album_credits(album_id).unwrap_or_else(|e| {
+                error!("Could not retrieve album credits: {:?}", e);
+                Vec::new()
+            }));
+            let credits = summarize_credits(&tracks, &album_credits);
+            let resume = cx
+                .get_resume_position(album_id)
+                .unwrap_or_else(|e| {
+                    error!("Could not retrieve resume position: {:?}", e);
+                    None
+                })
+                // the track may have been removed from the album since
+                .filter(|resume| tracks.iter().any(|track| track.location == resume.location));
 
             let image_transfer_model = cx.global::<Models>().image_transfer_model.clone();
 
             cx.subscribe(
                 &image_transfer_model,
-                move |this: &mut ReleaseView, _, image, cx| {
+                move |this: &mut ReleaseView, _, image: &ImageTransfer, cx| {
                     if image.0 == ImageType::AlbumArt(album_id) {
                         debug!("captured decoded image for album ID: {}", album_id);
                         this.image = Some(image.1.clone());
 
+                        cx.global::<DropOnNavigateQueue>().add(image.1.clone());
+                        cx.notify();
+                    } else if image.0 == ImageType::AlbumArtBackdrop(album_id) {
+                        debug!("captured decoded backdrop for album ID: {}", album_id);
+                        this.backdrop = Some(image.1.clone());
+
                         cx.global::<DropOnNavigateQueue>().add(image.1.clone());
                         cx.notify();
                     }
@@ -65,21 +193,105 @@ impl ReleaseView {
             )
             .detach();
 
-            if let Some(image) = album.image.clone() {
+            cx.subscribe(
+                &image_transfer_model,
+                move |this: &mut ReleaseView, _, failed: &ImageTransferFailed, cx| {
+                    if failed.0 == ImageType::AlbumArt(album_id) {
+                        warn!("could not decode image for album ID: {}", album_id);
+                        this.image = None;
+                        cx.notify();
+                    } else if failed.0 == ImageType::AlbumArtBackdrop(album_id) {
+                        // the view is shown without a backdrop rather than an empty one
+                        warn!("could not decode backdrop for album ID: {}", album_id);
+                        this.backdrop = None;
+                        cx.notify();
+                    }
+                },
+            )
+            .detach();
+
+            let settings = &cx.global::<SettingsGlobal>().model.read(cx).interface;
+            let show_backdrop = settings.album_art_backdrop;
+            let image = album.image.clone().filter(|_| !settings.disable_album_art);
+
+            if let Some(image) = image {
+                if show_backdrop {
+                    cx.global::<GPUIDataInterface>().decode_backdrop(
+                        image.clone(),
+                        ImageType::AlbumArtBackdrop(album_id),
+                        image_layout(),
+                    );
+                }
+
                 cx.global::<GPUIDataInterface>().decode_image(
                     image,
                     ImageType::AlbumArt(album_id),
-                    ImageLayout::BGR,
+                    image_layout(),
                     false,
                 );
             }
 
+            let focus_handle = cx.focus_handle();
+
+            let focused: Model<Option<usize>> = cx.new_model(|_| None);
+            let focused_clone = focused.clone();
+
+            let album_gain: Model<f64> = cx.new_model(|_| album.gain_offset);
+            let track_gains: Model<AHashMap<i64, f64>> = cx.new_model(|_| {
+                tracks
+                    .iter()
+                    .map(|track| (track.id, track.gain_offset))
+                    .collect()
+            });
+
+            cx.observe(&album_gain, |_, _, cx| cx.notify()).detach();
+            cx.observe(&track_gains, |_, _, cx| cx.notify()).detach();
+
+            let skipped = cx.global::<SkippedTracks>().0.clone();
+            cx.observe(&skipped, |_, _, cx| cx.notify()).detach();
+
+            let selection: Model<TrackSelection> = cx.new_model(|_| TrackSelection::default());
+            cx.observe(&selection, |_, _, cx| cx.notify()).detach();
+
+            let settings_model = cx.global::<SettingsGlobal>().model.clone();
+            let density = settings_model.read(cx).interface.list_density;
+
+            cx.observe(&settings_model, |this: &mut Self, settings, cx| {
+                let density = settings.read(cx).interface.list_density;
+
+                // rows change height with the density, so they have to be measured again
+                if density != this.density {
+                    this.density = density;
+                    this.track_list_state.reset(this.tracks.len());
+                }
+
+                cx.notify();
+            })
+            .detach();
+
+            let album_gain_clone = album_gain.clone();
+            let track_gains_clone = track_gains.clone();
+            let selection_clone = selection.clone();
+
             let tracks_clone = tracks.clone();
+            let album_credits_clone = album_credits.clone();
+            let album_mbid = album.mbid.clone();
+            let artist_name: Option<SharedString> = artist
+                .as_ref()
+                .and_then(|v| v.name.clone())
+                .map(SharedString::from);
 
-            let state =
-                ListState::new(tracks.len(), ListAlignment::Top, px(25.0), move |idx, _| {
+            let state = ListState::new(
+                tracks.len(),
+                ListAlignment::Top,
+                px(25.0),
+                move |idx, cx| {
                     TrackItem {
+                        focused: *focused_clone.read(cx) == Some(idx + BUTTON_COUNT),
                         track: tracks_clone[idx].clone(),
+                        idx,
+                        selected: selection_clone.read(cx).ids.contains(&tracks_clone[idx].id),
+                        selection: selection_clone.clone(),
                         is_start: if idx > 0 {
                             if let Some(track) = tracks_clone.get(idx - 1) {
                                 track.disc_number != tracks_clone[idx].disc_number
@@ -90,9 +302,55 @@ impl ReleaseView {
                             true
                         },
                         tracks: tracks_clone.clone(),
+                        album_credits: album_credits_clone.clone(),
+                        artist: artist_name.clone(),
+                        album_mbid: album_mbid.clone(),
+                        album_gain: *album_gain_clone.read(cx),
+                        track_gains: track_gains_clone.clone(),
+                        skipped: cx
+                            .global::<SkippedTracks>()
+                            .0
+                            .read(cx)
+                            .contains(&tracks_clone[idx].location),
+                        compact: cx
+                            .global::<SettingsGlobal>()
+                            .model
+                            .read(cx)
+                            .interface
+                            .list_density
+                            == ListDensity::Compact,
+                        double_click_to_play: cx
+                            .global::<SettingsGlobal>()
+                            .model
+                            .read(cx)
+                            .interface
+                            .click_to_play
+                            == ClickToPlay::DoubleClick,
                     }
                     .into_any_element()
-                });
+                },
+            );
+
+            // tracks without a duration are left out of the total, and their duration is worked
+            // out in the background
+            let unknown: Vec<PathBuf> = tracks
+                .iter()
+                .filter(|track| track.duration <= 0)
+                .map(|track| PathBuf::from(&track.location))
+                .collect();
+            let total: i64 = tracks.iter().map(|track| track.duration.max(0)).sum();
+
+            let length = SharedString::from(format!(
+                "{} track{}, {}{}",
+                tracks.len(),
+                if tracks.len() == 1 { "" } else { "s" },
+                if unknown.is_empty() { "" } else { "~" },
+                format_remaining(total as u64)
+            ));
+
+            if !unknown.is_empty() {
+                cx.global::<ScanInterface>().compute_durations(unknown);
+            }
 
             let release_info = {
                 let mut info = String::default();
@@ -117,23 +375,152 @@ impl ReleaseView {
             };
 
             ReleaseView {
+                focus_handle,
+                focused,
+                pinned: album.pinned_position.is_some(),
                 album,
                 image,
+                backdrop: None,
                 artist,
                 tracks,
                 track_list_state: state,
                 release_info,
+                length,
+                album_gain,
+                track_gains,
+                density,
+                resume,
+                credits,
+                show_credits: false,
             }
-        })
+        }))
+    }
+}
+
+impl ReleaseView {
+    fn paths(&self) -> Vec<String> {
+        self.tracks
+            .iter()
+            .map(|track| track.location.clone())
+            .collect()
+    }
+
+    fn play(&mut self, cx: &mut ViewContext<Self>) {
+        replace_queue(self.paths(), cx)
+    }
+
+    fn resume(&mut self, cx: &mut ViewContext<Self>) {
+        let Some(resume) = self.resume.take() else {
+            return;
+        };
+
+        resume_album(cx, self.paths(), &resume.location, resume.position);
+        cx.notify();
+    }
+
+    fn add_to_queue(&mut self, cx: &mut ViewContext<Self>) {
+        cx.global::<GPUIPlaybackInterface>()
+            .queue_list(self.paths());
+    }
+
+    fn shuffle(&mut self, cx: &mut ViewContext<Self>) {
+        let paths = self.paths();
+
+        if !(*cx.global::<PlaybackInfo>().shuffling.read(cx)) {
+            toggle_shuffle_then(cx, move |cx| replace_queue(paths, cx));
+        } else {
+            replace_queue(paths, cx)
+        }
+    }
+
+    fn set_album_gain(&mut self, gain: f64, cx: &mut ViewContext<Self>) {
+        let gain = clamp_gain(gain);
+
+        if let Err(e) = cx.set_album_gain(self.album.id, gain) {
+            error!("Could not save album gain offset: {:?}", e);
+            return;
+        }
+
+        self.album_gain.update(cx, |m, cx| {
+            *m = gain;
+            cx.notify();
+        });
+
+        refresh_gain(cx);
+    }
+
+    fn move_focus(&mut self, forward: bool, cx: &mut ViewContext<Self>) {
+        let count = BUTTON_COUNT + self.tracks.len();
+        let next = match (*self.focused.read(cx), forward) {
+            (None, true) => 0,
+            (None, false) => count - 1,
+            (Some(idx), true) => (idx + 1) % count,
+            (Some(idx), false) => (idx + count - 1) % count,
+        };
+
+        if next >= BUTTON_COUNT {
+            self.track_list_state
+                .scroll_to_reveal_item(next - BUTTON_COUNT);
+        }
+
+        self.focused.update(cx, |m, cx| {
+            *m = Some(next);
+            cx.notify();
+        });
+        cx.notify();
+    }
+
+    fn focus_next(&mut self, _: &FocusNext, cx: &mut ViewContext<Self>) {
+        self.move_focus(true, cx);
+    }
+
+    fn focus_previous(&mut self, _: &FocusPrevious, cx: &mut ViewContext<Self>) {
+        self.move_focus(false, cx);
+    }
+
+    fn activate(&mut self, _: &Activate, cx: &mut ViewContext<Self>) {
+        match *self.focused.read(cx) {
+            Some(0) => self.play(cx),
+            Some(1) => self.add_to_queue(cx),
+            Some(2) => self.shuffle(cx),
+            Some(idx) => {
+                if let Some(track) = self.tracks.get(idx - BUTTON_COUNT) {
+                    play_from_track(cx, &self.tracks, track.id);
+                }
+            }
+            None => (),
+        }
+    }
+}
+
+impl FocusableView for ReleaseView {
+    fn focus_handle(&self, _: &AppContext) -> FocusHandle {
+        self.focus_handle.clone()
     }
 }
 
 impl Render for ReleaseView {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let settings = &cx.global::<SettingsGlobal>().model.read(cx).interface;
+        let preserve_aspect = settings.preserve_album_art_aspect;
+        let show_art = !settings.disable_album_art;
+        let date_format = settings.date_format.clone();
+        // the settings are checked here as well so that turning them off takes effect immediately
+        let backdrop = self
+            .backdrop
+            .clone()
+            .filter(|_| settings.album_art_backdrop && show_art);
+        let image = self.image.clone().filter(|_| show_art);
+        let focused = *self.focused.read(cx);
+        let album_gain = *self.album_gain.read(cx);
+        let artist_name = self
+            .artist
+            .as_ref()
+            .and_then(|v| v.name.clone())
+            .map(SharedString::from);
         let theme = cx.global::<Theme>();
 
         div()
-            .mt(px(24.0))
             .w_full()
             .flex_shrink()
             .overflow_x_hidden()
@@ -146,9 +533,30 @@ impl Render for ReleaseView {
                 div()
                     .flex_shrink()
                     .flex()
-                    .overflow_x_hidden()
+                    .relative()
+                    .overflow_hidden()
                     .px(px(24.0))
+                    .pt(px(24.0))
                     .w_full()
+                    .when_some(backdrop, |this, backdrop| {
+                        this.pb(px(18.0))
+                            .child(
+                                img(backdrop)
+                                    .absolute()
+                                    .top_0()
+                                    .left_0()
+                                    .size_full()
+                                    .object_fit(ObjectFit::Cover),
+                            )
+                            .child(
+                                div()
+                                    .absolute()
+                                    .top_0()
+                                    .left_0()
+                                    .size_full()
+                                    .bg(theme.album_art_backdrop_overlay),
+                            )
+                    })
                     .child(
                         div()
                             .rounded(px(4.0))
@@ -157,19 +565,29 @@ impl Render for ReleaseView {
                             .w(px(160.0))
                             .h(px(160.0))
                             .flex_shrink_0()
+                            .relative()
                             .overflow_hidden()
-                            .when(self.image.is_some(), |div| {
+                            .when_some(image, |div, image| {
+                                if preserve_aspect {
+                                    return div.child(
+                                        img(image)
+                                            .w(px(160.0))
+                                            .h(px(160.0))
+                                            .object_fit(ObjectFit::Contain),
+                                    );
+                                }
+
+                                // ObjectFit::Cover makes the element bigger than its container,
+                                // so we scale the image ourselves and let the container clip it
+                                let size = cover_size(&image, px(160.0));
+
                                 div.child(
-                                    img(self.image.clone().unwrap())
-                                        .min_w(px(160.0))
-                                        .min_h(px(160.0))
-                                        .max_w(px(160.0))
-                                        .max_h(px(160.0))
-                                        .overflow_hidden()
-                                        .flex()
-                                        // TODO: Ideally this should be ObjectFit::Cover, but for
-                                        // some reason that makes the element bigger
-                                        // FIXME: Is this a GPUI bug?
+                                    img(image)
+                                        .absolute()
+                                        .left((px(160.0) - size.width) / 2.0)
+                                        .top((px(160.0) - size.height) / 2.0)
+                                        .w(size.width)
+                                        .h(size.height)
                                         .object_fit(ObjectFit::Fill)
                                         .rounded(px(4.0)),
                                 )
@@ -189,15 +607,15 @@ impl Render for ReleaseView {
                                 |this, artist| this.child(artist.unwrap()),
                             ))
                             .child(
-                                div()
-                                    .font_weight(FontWeight::EXTRA_BOLD)
-                                    .text_size(rems(2.5))
-                                    .line_height(rems(2.75))
-                                    .overflow_x_hidden()
-                                    .pb(px(10.0))
-                                    .min_w_0()
-                                    .text_ellipsis()
-                                    .child(self.album.title.clone()),
+                                truncated_text(
+                                    "release-title",
+                                    self.album.title.clone(),
+                                    artist_name,
+                                )
+                                .font_weight(FontWeight::EXTRA_BOLD)
+                                .text_size(rems(2.5))
+                                .line_height(rems(2.75))
+                                .pb(px(10.0)),
                             )
                             .child(
                                 div()
@@ -210,17 +628,10 @@ impl Render for ReleaseView {
                                             .size(ButtonSize::Large)
                                             .font_weight(FontWeight::BOLD)
                                             .intent(ButtonIntent::Primary)
-                                            .on_click(cx.listener(
-                                                |this: &mut ReleaseView, _, cx| {
-                                                    let paths = this
-                                                        .tracks
-                                                        .iter()
-                                                        .map(|track| track.location.clone())
-                                                        .collect();
-
-                                                    replace_queue(paths, cx)
-                                                },
-                                            ))
+                                            .focused(focused == Some(0))
+                                            .on_click(
+                                                cx.listener(|this: &mut Self, _, cx| this.play(cx)),
+                                            )
                                             .child(div().font_family(FONT_AWESOME).child(""))
                                             .child(div().child("Play")),
                                     )
@@ -230,18 +641,10 @@ impl Render for ReleaseView {
                                             .size(ButtonSize::Large)
                                             .font_weight(FontWeight::BOLD)
                                             .flex_none()
-                                            .on_click(cx.listener(
-                                                |this: &mut ReleaseView, _, cx| {
-                                                    let paths = this
-                                                        .tracks
-                                                        .iter()
-                                                        .map(|track| track.location.clone())
-                                                        .collect();
-
-                                                    cx.global::<GPUIPlaybackInterface>()
-                                                        .queue_list(paths);
-                                                },
-                                            ))
+                                            .focused(focused == Some(1))
+                                            .on_click(cx.listener(|this: &mut Self, _, cx| {
+                                                this.add_to_queue(cx)
+                                            }))
                                             .child(div().font_family(FONT_AWESOME).child("")),
                                     )
                                     .child(
@@ -250,27 +653,86 @@ impl Render for ReleaseView {
                                             .size(ButtonSize::Large)
                                             .font_weight(FontWeight::BOLD)
                                             .flex_none()
-                                            .on_click(cx.listener(
-                                                |this: &mut ReleaseView, _, cx| {
-                                                    let paths = this
-                                                        .tracks
-                                                        .iter()
-                                                        .map(|track| track.location.clone())
-                                                        .collect();
-
-                                                    if !(*cx
-                                                        .global::<PlaybackInfo>()
-                                                        .shuffling
-                                                        .read(cx))
-                                                    {
-                                                        cx.global::<GPUIPlaybackInterface>()
-                                                            .toggle_shuffle();
-                                                    }
-
-                                                    replace_queue(paths, cx)
-                                                },
-                                            ))
+                                            .focused(focused == Some(2))
+                                            .on_click(cx.listener(|this: &mut Self, _, cx| {
+                                                this.shuffle(cx)
+                                            }))
                                             .child(div().font_family(FONT_AWESOME).child("")),
+                                    )
+                                    .child(
+                                        button()
+                                            .id("release-pin-button")
+                                            .size(ButtonSize::Large)
+                                            .font_weight(FontWeight::BOLD)
+                                            .flex_none()
+                                            .when(self.pinned, |this| {
+                                                this.intent(ButtonIntent::Primary)
+                                            })
+                                            .on_click(cx.listener(|this: &mut Self, _, cx| {
+                                                this.pinned = !this.pinned;
+                                                pin_album(cx, this.album.id, this.pinned);
+                                                cx.notify();
+                                            }))
+                                            .child(div().font_family(FONT_AWESOME).child("")),
+                                    )
+                                    .when(self.resume.is_some(), |this| {
+                                        this.child(
+                                            button()
+                                                .id("release-resume-button")
+                                                .size(ButtonSize::Large)
+                                                .font_weight(FontWeight::BOLD)
+                                                .flex_none()
+                                                .on_click(cx.listener(|this: &mut Self, _, cx| {
+                                                    this.resume(cx)
+                                                }))
+                                                .child(div().font_family(FONT_AWESOME).child(""))
+                                                .child(div().child("Resume")),
+                                        )
+                                    })
+                                    .child(
+                                        div()
+                                            .ml_auto()
+                                            .my_auto()
+                                            .flex()
+                                            .items_center()
+                                            .gap(px(6.0))
+                                            .text_sm()
+                                            .font_weight(FontWeight::SEMIBOLD)
+                                            .child(
+                                                button()
+                                                    .id("release-gain-down")
+                                                    .font_family(FONT_AWESOME)
+                                                    .on_click(cx.listener(
+                                                        move |this: &mut Self, _, cx| {
+                                                            this.set_album_gain(
+                                                                album_gain - GAIN_STEP,
+                                                                cx,
+                                                            )
+                                                        },
+                                                    ))
+                                                    .child(""),
+                                            )
+                                            .child(
+                                                div()
+                                                    .min_w(px(64.0))
+                                                    .flex()
+                                                    .justify_center()
+                                                    .child(format_gain(album_gain)),
+                                            )
+                                            .child(
+                                                button()
+                                                    .id("release-gain-up")
+                                                    .font_family(FONT_AWESOME)
+                                                    .on_click(cx.listener(
+                                                        move |this: &mut Self, _, cx| {
+                                                            this.set_album_gain(
+                                                                album_gain + GAIN_STEP,
+                                                                cx,
+                                                            )
+                                                        },
+                                                    ))
+                                                    .child("+"),
+                                            ),
                                     ),
                             ),
                     ),
@@ -296,32 +758,145 @@ impl Render for ReleaseView {
                     .when_some(self.release_info.clone(), |this, release_info| {
                         this.child(div().child(release_info))
                     })
+                    .child(div().child(self.length.clone()))
                     .when_some(self.album.release_date, |this, date| {
-                        this.child(div().child(format!("Released {}", date.format("%B %-e, %Y"))))
+                        this.child(div().child(format!(
+                            "Released {}",
+                            format_date(&date, date_format.as_deref())
+                        )))
                     })
                     .when_some(self.album.isrc.as_ref(), |this, isrc| {
                         this.child(div().child(isrc.clone()))
+                    })
+                    .when(!self.credits.is_empty(), |this| {
+                        this.child(
+                            div()
+                                .id("release-credits-toggle")
+                                .mt(px(6.0))
+                                .flex()
+                                .gap(px(6.0))
+                                .cursor_pointer()
+                                .hover(|this| this.text_color(theme.text))
+                                .on_click(cx.listener(|this: &mut Self, _, cx| {
+                                    this.show_credits = !this.show_credits;
+                                    cx.notify();
+                                }))
+                                .child(
+                                    div().font_family(FONT_AWESOME).child(if self.show_credits {
+                                        ""
+                                    } else {
+                                        ""
+                                    }),
+                                )
+                                .child("Credits"),
+                        )
+                    })
+                    .when(self.show_credits, |this| {
+                        this.children(self.credits.iter().map(|(role, names)| {
+                            div()
+                                .flex()
+                                .gap(px(6.0))
+                                .child(div().flex_none().child(role.label()))
+                                .child(
+                                    div()
+                                        .font_weight(FontWeight::NORMAL)
+                                        .text_color(theme.text)
+                                        .child(names.clone()),
+                                )
+                        }))
                     }),
             )
+            .key_context("ReleaseView")
+            .on_action(cx.listener(Self::focus_next))
+            .on_action(cx.listener(Self::focus_previous))
+            .on_action(cx.listener(Self::activate))
+            .track_focus(&self.focus_handle)
     }
 }
 
 #[derive(IntoElement)]
 struct TrackItem {
     pub track: Track,
+    pub focused: bool,
     pub is_start: bool,
     pub tracks: Arc<Vec<Track>>,
+    /// The credits of every track in the album, as (track ID, role, name).
+    pub album_credits: Arc<Vec<(i64, CreditRole, String)>>,
+    pub artist: Option<SharedString>,
+    pub album_mbid: Option<DBString>,
+    pub album_gain: f64,
+    pub track_gains: Model<AHashMap<i64, f64>>,
+    pub compact: bool,
+    pub idx: usize,
+    pub selected: bool,
+    pub selection: Model<TrackSelection>,
+    pub double_click_to_play: bool,
+    pub skipped: bool,
 }
 
 impl RenderOnce for TrackItem {
     fn render(self, cx: &mut WindowContext) -> impl IntoElement {
         let theme = cx.global::<Theme>();
 
+        let details = TrackDetails {
+            track: self.track.clone(),
+            album_mbid: self.album_mbid.clone(),
+            credits: self
+                .album_credits
+                .iter()
+                .filter(|(track_id, _, _)| *track_id == self.track.id)
+                .map(|(_, role, name)| (*role, name.clone()))
+                .collect(),
+        };
         let tracks = self.tracks.clone();
         let tracks_2 = self.tracks.clone();
         let track_location = self.track.location.clone();
-        let track_location_2 = self.track.location;
+        let track_location_2 = self.track.location.clone();
+        let track_location_3 = self.track.location.clone();
+        let track_location_4 = self.track.location.clone();
+        let track_title: SharedString = self.track.title.clone().into();
         let track_id = self.track.id;
+        let trim_target = TrimTarget {
+            track_id,
+            location: self.track.location.clone(),
+            title: track_title.to_string(),
+            duration: self.track.duration.max(0) as u64,
+        };
+        let track_location_5 = self.track.location;
+        let track_gain = self
+            .track_gains
+            .read(cx)
+            .get(&track_id)
+            .copied()
+            .unwrap_or_default();
+        let effective_gain = clamp_gain(track_gain + self.album_gain);
+        let track_gains = self.track_gains.clone();
+        let track_gains_2 = self.track_gains.clone();
+        let track_gains_3 = self.track_gains;
+        let idx = self.idx;
+        let selection = self.selection.clone();
+        let double_click_to_play = self.double_click_to_play;
+        let skipped = self.skipped;
+        let album_paths: Vec<String> = self
+            .tracks
+            .iter()
+            .map(|track| track.location.clone())
+            .collect();
+
+        // the context menu acts on the whole selection if it includes this track
+        let selected_paths: Option<Arc<Vec<String>>> = {
+            let ids = &self.selection.read(cx).ids;
+
+            (ids.len() > 1 && ids.contains(&track_id)).then(|| {
+                Arc::new(
+                    self.tracks
+                        .iter()
+                        .filter(|track| ids.contains(&track.id))
+                        .map(|track| track.location.clone())
+                        .collect(),
+                )
+            })
+        };
         context(("context", self.track.id as usize))
             .with(
                 div()
@@ -329,7 +904,33 @@ impl RenderOnce for TrackItem {
                     .flex_col()
                     .w_full()
                     .id(self.track.id as usize)
-                    .on_click(move |_, cx| play_from_track(cx, &tracks, track_id))
+                    .on_click(move |ev, cx| {
+                        let modifiers = ev.down.modifiers;
+
+                        if modifiers.shift {
+                            select_range(cx, &selection, &tracks, idx);
+                        } else if modifiers.control || modifiers.platform {
+                            selection.update(cx, |m, cx| {
+                                if !m.ids.remove(&track_id) {
+                                    m.ids.insert(track_id);
+                                }
+                                m.anchor = Some(idx);
+                                cx.notify();
+                            });
+                        } else if double_click_to_play && ev.down.click_count < 2 {
+                            selection.update(cx, |m, cx| {
+                                m.ids = AHashSet::from_iter([track_id]);
+                                m.anchor = Some(idx);
+                                cx.notify();
+                            });
+                        } else {
+                            selection.update(cx, |m, cx| {
+                                *m = TrackSelection::default();
+                                cx.notify();
+                            });
+                            play_from_track(cx, &tracks, track_id);
+                        }
+                    })
                     .when(self.is_start, |this| {
                         this.child(
                             div()
@@ -342,10 +943,17 @@ impl RenderOnce for TrackItem {
                                 .border_color(theme.border_color)
                                 .mt(px(24.0))
                                 .pb(px(6.0))
-                                .child(format!(
-                                    "DISC {}",
-                                    self.track.disc_number.unwrap_or_default()
-                                )),
+                                .child(match self.track.disc_subtitle.as_ref() {
+                                    Some(subtitle) => format!(
+                                        "DISC {}: {}",
+                                        self.track.disc_number.unwrap_or_default(),
+                                        subtitle
+                                    ),
+                                    None => format!(
+                                        "DISC {}",
+                                        self.track.disc_number.unwrap_or_default()
+                                    ),
+                                }),
                         )
                     })
                     .child(
@@ -358,9 +966,14 @@ impl RenderOnce for TrackItem {
                             .border_color(theme.border_color)
                             .cursor_pointer()
                             .px(px(24.0))
-                            .py(px(6.0))
+                            .py(px(if self.compact { 3.0 } else { 6.0 }))
+                            .when(self.compact, |this| this.text_sm())
+                            .when(self.skipped, |this| this.text_color(theme.text_secondary))
                             .hover(|this| this.bg(theme.nav_button_hover))
                             .active(|this| this.bg(theme.nav_button_active))
+                            .when(self.selected, |this| this.bg(theme.track_selected))
+                            .when(self.focused, |this| this.bg(theme.nav_button_hover))
+                            .relative()
                             .max_w_full()
                             .child(
                                 div()
@@ -373,64 +986,225 @@ impl RenderOnce for TrackItem {
                                     )),
                             )
                             .child(
-                                div()
-                                    .font_weight(FontWeight::BOLD)
-                                    .overflow_x_hidden()
-                                    .text_ellipsis()
-                                    .child(self.track.title),
+                                truncated_text(
+                                    ("track-title", self.track.id as u64),
+                                    self.track.title,
+                                    self.artist.clone(),
+                                )
+                                .font_weight(FontWeight::BOLD),
                             )
                             .child(
                                 div()
                                     .font_family("Roboto Mono")
                                     .ml_auto()
                                     .flex_shrink_0()
-                                    .child(format!(
-                                        "{}:{:02}",
-                                        self.track.duration / 60,
-                                        self.track.duration % 60
-                                    )),
-                            ),
+                                    .child(format_duration(self.track.duration)),
+                            )
+                            .when(self.focused, |this| {
+                                this.child(focus_ring(theme.focus_ring, px(0.0)))
+                            }),
                     ),
             )
             .child(
                 div().bg(theme.elevated_background).child(
                     menu()
+                        // TODO: add the selection to a playlist once playlists are supported
+                        .when_some(selected_paths.clone(), |this, paths| {
+                            let paths_2 = paths.clone();
+                            let count = paths.len();
+
+                            this.item(menu_item(
+                                "track_play_selected",
+                                Some(""),
+                                format!("Play {} selected", count),
+                                move |_, cx| replace_queue(paths.to_vec(), cx),
+                            ))
+                            .item(menu_item(
+                                "track_add_selected_to_queue",
+                                Some("+"),
+                                format!("Add {} selected to queue", count),
+                                move |_, cx| {
+                                    cx.global::<GPUIPlaybackInterface>()
+                                        .queue_list(paths_2.to_vec())
+                                },
+                            ))
+                        })
+                        .when(selected_paths.is_none(), move |this| {
+                            this.item(menu_item(
+                                "track_play",
+                                Some(""),
+                                "Play",
+                                move |_, cx| {
+                                    let playback_interface = cx.global::<GPUIPlaybackInterface>();
+                                    let queue_length = cx.global::<Models>().queue.read(cx).0.len();
+                                    playback_interface.queue(&track_location);
+                                    playback_interface.jump(queue_length);
+                                },
+                            ))
+                            .item(menu_item(
+                                "track_play_from_here",
+                                Some(""),
+                                "Play from here",
+                                move |_, cx| play_from_track(cx, &tracks_2, track_id),
+                            ))
+                            .item(menu_item(
+                                "track_add_to_queue",
+                                Some("+"),
+                                "Add to queue",
+                                move |_, cx| {
+                                    let playback_interface = cx.global::<GPUIPlaybackInterface>();
+                                    playback_interface.queue(&track_location_2);
+                                },
+                            ))
+                        })
+                        .when_some(
+                            self.artist.filter(|_| radio_available()),
+                            move |this, artist| {
+                                this.item(menu_item(
+                                    "track_start_radio",
+                                    Some(""),
+                                    "Start radio",
+                                    move |_, cx| {
+                                        start_radio(
+                                            cx,
+                                            track_location_3.clone(),
+                                            artist.to_string(),
+                                            track_title.to_string(),
+                                        )
+                                    },
+                                ))
+                            },
+                        )
+                        .item(menu_item(
+                            "track_skip_always",
+                            Some(""),
+                            if skipped {
+                                "Stop always skipping"
+                            } else {
+                                "Always skip"
+                            },
+                            move |_, cx| {
+                                set_skip_always(cx, track_id, track_location_4.clone(), !skipped)
+                            },
+                        ))
                         .item(menu_item(
-                            "track_play",
-                            Some(""),
-                            "Play",
+                            "track_trim",
+                            Some(""),
+                            "Trim start and end",
                             move |_, cx| {
-                                let playback_interface = cx.global::<GPUIPlaybackInterface>();
-                                let queue_length = cx.global::<Models>().queue.read(cx).0.len();
-                                playback_interface.queue(&track_location);
-                                playback_interface.jump(queue_length);
+                                let trim_track = cx.global::<Models>().trim_track.clone();
+                                trim_track.update(cx, |m, cx| {
+                                    *m = Some(trim_target.clone());
+                                    cx.notify();
+                                })
                             },
                         ))
                         .item(menu_item(
-                            "track_play_from_here",
-                            Some(""),
-                            "Play from here",
-                            move |_, cx| play_from_track(cx, &tracks_2, track_id),
+                            "track_reload_metadata",
+                            Some(""),
+                            "Reload metadata",
+                            move |_, cx| reload_metadata(cx, vec![track_location_5.clone()]),
+                        ))
+                        .item(menu_item(
+                            "track_reload_album_metadata",
+                            Some(""),
+                            "Reload album metadata",
+                            move |_, cx| reload_metadata(cx, album_paths.clone()),
+                        ))
+                        .item(menu_item(
+                            "track_details",
+                            Some(""),
+                            "Show details",
+                            move |_, cx| {
+                                let track_details = cx.global::<Models>().track_details.clone();
+                                track_details.update(cx, |m, cx| {
+                                    *m = Some(details.clone());
+                                    cx.notify();
+                                })
+                            },
                         ))
+                        .item(CMenuItem::Seperator)
                         .item(menu_item(
-                            "track_add_to_queue",
+                            "track_gain_up",
                             Some("+"),
-                            "Add to queue",
+                            "Increase gain",
                             move |_, cx| {
-                                let playback_interface = cx.global::<GPUIPlaybackInterface>();
-                                playback_interface.queue(&track_location_2);
+                                set_track_gain(cx, &track_gains, track_id, track_gain + GAIN_STEP)
                             },
+                        ))
+                        .item(menu_item(
+                            "track_gain_down",
+                            Some(""),
+                            "Decrease gain",
+                            move |_, cx| {
+                                set_track_gain(cx, &track_gains_2, track_id, track_gain - GAIN_STEP)
+                            },
+                        ))
+                        .item(menu_item(
+                            "track_gain_reset",
+                            None::<&str>,
+                            format!("Reset gain ({} total)", format_gain(effective_gain)),
+                            move |_, cx| set_track_gain(cx, &track_gains_3, track_id, 0.0),
                         )),
                 ),
             )
     }
 }
 
+fn set_track_gain(
+    cx: &mut WindowContext,
+    track_gains: &Model<AHashMap<i64, f64>>,
+    track_id: i64,
+    gain: f64,
+) {
+    let gain = clamp_gain(gain);
+
+    if let Err(e) = cx.set_track_gain(track_id, gain) {
+        error!("Could not save track gain offset: {:?}", e);
+        return;
+    }
+
+    track_gains.update(cx, |m, cx| {
+        m.insert(track_id, gain);
+        cx.notify();
+    });
+
+    refresh_gain(cx);
+}
+
+/// Selects every track between the anchor and the clicked track, or just the clicked track if
+/// nothing has been clicked yet.
+fn select_range(
+    cx: &mut WindowContext,
+    selection: &Model<TrackSelection>,
+    tracks: &Arc<Vec<Track>>,
+    idx: usize,
+) {
+    selection.update(cx, |m, cx| {
+        let anchor = m.anchor.unwrap_or(idx);
+        let range = anchor.min(idx)..=anchor.max(idx);
+
+        m.ids = tracks[range].iter().map(|track| track.id).collect();
+        m.anchor = Some(anchor);
+        cx.notify();
+    });
+}
+
 fn play_from_track(cx: &mut WindowContext, tracks: &Arc<Vec<Track>>, id: i64) {
     let paths = tracks.iter().map(|track| track.location.clone()).collect();
 
     replace_queue(paths, cx);
 
+    // the track can be missing if the library changed since the list was loaded, in which case
+    // the release is played from the start
+    let Some(idx) = tracks.iter().position(|t| t.id == id) else {
+        warn!(
+            "Track {} is no longer in the release, playing from the start",
+            id
+        );
+        return;
+    };
+
     let playback_interface = cx.global::<GPUIPlaybackInterface>();
-    playback_interface.jump(tracks.iter().position(|t| t.id == id).unwrap())
+    playback_interface.jump(idx)
 }
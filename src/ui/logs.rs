@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use gpui::*;
+use tracing::Level;
+
+use crate::logging::{generation, recent_lines, LogLine};
+
+use super::{
+    components::button::{button, ButtonIntent},
+    models::Models,
+    theme::Theme,
+};
+
+/// How often the log viewer checks for new events while it's open.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Shows the most recent log events, so that they can be copied into bug reports.
+pub struct LogViewer {
+    show_logs: Model<bool>,
+    lines: Vec<LogLine>,
+    generation: u64,
+}
+
+impl LogViewer {
+    pub fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
+        cx.new_view(|cx| {
+            let show_logs = cx.global::<Models>().show_logs.clone();
+
+            cx.observe(&show_logs, |this: &mut Self, show_logs, cx| {
+                if *show_logs.read(cx) {
+                    this.refresh();
+                }
+                cx.notify();
+            })
+            .detach();
+
+            cx.spawn(|this, mut cx| async move {
+                loop {
+                    cx.background_executor().timer(REFRESH_INTERVAL).await;
+
+                    let result = this.update(&mut cx, |this: &mut Self, cx| {
+                        if *this.show_logs.read(cx) && this.generation != generation() {
+                            this.refresh();
+                            cx.notify();
+                        }
+                    });
+
+                    if result.is_err() {
+                        break;
+                    }
+                }
+            })
+            .detach();
+
+            Self {
+                show_logs,
+                lines: Vec::new(),
+                generation: 0,
+            }
+        })
+    }
+
+    fn refresh(&mut self) {
+        self.generation = generation();
+        self.lines = recent_lines();
+    }
+}
+
+fn format_line(line: &LogLine) -> String {
+    format!(
+        "{} {:>5} {}: {}",
+        line.time.format("%H:%M:%S%.3f"),
+        line.level,
+        line.target,
+        line.message
+    )
+}
+
+impl Render for LogViewer {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        if !*self.show_logs.read(cx) {
+            return div().id("log-viewer");
+        }
+
+        let theme = cx.global::<Theme>();
+        let show_logs = self.show_logs.clone();
+        let text = self
+            .lines
+            .iter()
+            .map(format_line)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        div()
+            .id("log-viewer")
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full()
+            .flex()
+            .items_center()
+            .justify_center()
+            .occlude()
+            .child(
+                div()
+                    .id("log-viewer-content")
+                    .w(px(720.0))
+                    .h(px(480.0))
+                    .p(px(16.0))
+                    .flex()
+                    .flex_col()
+                    .gap(px(6.0))
+                    .border_1()
+                    .shadow_sm()
+                    .rounded(px(4.0))
+                    .border_color(theme.elevated_border_color)
+                    .bg(theme.elevated_background)
+                    .on_mouse_down_out(move |_, cx| {
+                        show_logs.update(cx, |m, cx| {
+                            *m = false;
+                            cx.notify();
+                        })
+                    })
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .child(
+                                div()
+                                    .font_weight(FontWeight::BOLD)
+                                    .text_size(px(18.0))
+                                    .child("Logs"),
+                            )
+                            .child(
+                                div().ml_auto().flex().child(
+                                    button()
+                                        .intent(ButtonIntent::Secondary)
+                                        .child("Copy")
+                                        .id("log-viewer-copy")
+                                        .on_click(move |_, cx| {
+                                            cx.write_to_clipboard(ClipboardItem::new_string(
+                                                text.clone(),
+                                            ));
+                                        }),
+                                ),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .id("log-viewer-lines")
+                            .flex_1()
+                            .min_h(px(0.0))
+                            .flex()
+                            .flex_col()
+                            .overflow_y_scroll()
+                            .text_xs()
+                            .font_family("monospace")
+                            .when(self.lines.is_empty(), |this| {
+                                this.child(
+                                    div()
+                                        .text_color(theme.text_secondary)
+                                        .child("Nothing has been logged yet."),
+                                )
+                            })
+                            // newest first, so that the latest events are visible without scrolling
+                            .children(self.lines.iter().rev().map(|line| {
+                                let color = match line.level {
+                                    Level::ERROR => theme.button_danger,
+                                    Level::WARN => theme.button_warning,
+                                    Level::INFO => theme.text,
+                                    _ => theme.text_secondary,
+                                };
+
+                                div().text_color(color).child(format_line(line))
+                            })),
+                    ),
+            )
+    }
+}
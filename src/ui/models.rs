@@ -1,27 +1,42 @@
 use std::{
     fs::{File, OpenOptions},
-    sync::Arc,
+    sync::{
+        mpsc::{channel, TryRecvError},
+        Arc,
+    },
+    time::Duration,
 };
 
 use ahash::AHashMap;
 use async_std::sync::Mutex;
-use gpui::{AppContext, Context, EventEmitter, Global, Model, RenderImage};
+use gpui::{AppContext, Context, EventEmitter, Global, Model, RenderImage, Rgba};
 use tracing::{debug, error, warn};
 
 use crate::{
     data::{
-        events::{ImageLayout, ImageType},
+        events::{ImageType, ReadFailure},
         interface::GPUIDataInterface,
         types::UIQueueItem,
     },
-    library::scan::ScanEvent,
-    media::metadata::Metadata,
-    playback::thread::PlaybackState,
+    devices::format::OutputInfo,
+    library::{
+        scan::{load_last_scan, LastScan, ScanEvent},
+        types::{DBString, Track},
+    },
+    media::metadata::{CreditRole, Metadata},
+    playback::{events::PlaybackError, thread::PlaybackState},
     services::mmb::{
-        lastfm::{client::LastFMClient, types::Session, LastFM, LASTFM_API_KEY, LASTFM_API_SECRET},
+        lastfm::{
+            client::LastFMClient,
+            pending::{PendingScrobbles, SharedPendingScrobbles},
+            types::Session,
+            LastFM, ScrobbleStatus, SharedLastFMSettings, LASTFM_API_KEY, LASTFM_API_SECRET,
+        },
         MediaMetadataBroadcastService,
     },
+    settings::{playback::EndOfQueue, SettingsGlobal},
     ui::app::get_dirs,
+    util::image_layout,
 };
 
 // yes this looks a little silly
@@ -44,11 +59,50 @@ impl EventEmitter<Session> for LastFMState {}
 pub struct Models {
     pub metadata: Model<Metadata>,
     pub albumart: Model<Option<Arc<RenderImage>>>,
+    /// The dominant color of the current album art, if dynamic accent colors are enabled.
+    pub albumart_color: Model<Option<Rgba>>,
     pub queue: Model<Queue>,
+    /// The metadata that has been read for queued files, so that it isn't read again when the
+    /// queue changes. Entries are removed once their file leaves the queue.
+    pub queue_metadata: Model<AHashMap<String, UIQueueItem>>,
     pub image_transfer_model: Model<TransferDummy>,
     pub scan_state: Model<ScanEvent>,
+    pub last_scan: Model<Option<LastScan>>,
+    /// Incremented whenever the metadata of some files has been reloaded, so that views showing
+    /// them can be refreshed.
+    pub metadata_reloaded: Model<u64>,
     pub mmbs: Model<MMBSList>,
     pub lastfm: Model<LastFMState>,
+    pub scrobble_status: Model<Option<ScrobbleStatus>>,
+    /// How many scrobbles are waiting to be submitted.
+    pub pending_scrobbles: Model<usize>,
+    pub show_queue: Model<bool>,
+    pub show_about: Model<bool>,
+    pub show_logs: Model<bool>,
+    pub show_accent_picker: Model<bool>,
+    pub show_palette: Model<bool>,
+    /// Set to open a release in the library, which resets it once the release is shown.
+    pub open_release: Model<Option<i64>>,
+    pub replaced_queue: Model<Option<ReplacedQueue>>,
+    /// The track shown in the track details dialog, if it's open.
+    pub track_details: Model<Option<TrackDetails>>,
+    /// The track shown in the trim dialog, if it's open.
+    pub trim_track: Model<Option<TrimTarget>>,
+    /// The waveform peaks of the current track, once they have been loaded or computed.
+    pub peaks: Model<Option<Arc<Vec<u8>>>>,
+    /// The last file that couldn't be played, until the error is dismissed.
+    pub playback_failure: Model<Option<PlaybackFailure>>,
+    /// The name of the last output device that couldn't be opened, until the error is dismissed.
+    pub output_failure: Model<Option<String>>,
+    /// Incremented whenever albums are pinned, unpinned, or moved, so that the library can be
+    /// refreshed.
+    pub pinned_albums: Model<u64>,
+    /// The files whose metadata couldn't be read when they were queued, and why.
+    pub unreadable_files: Model<Vec<(String, ReadFailure)>>,
+    pub show_unreadable_files: Model<bool>,
+    /// Whether an album to continue playback with is being looked for, once the last track in the
+    /// queue has started.
+    pub finding_continuation: Model<bool>,
 }
 
 impl Global for Models {}
@@ -61,18 +115,110 @@ pub struct PlaybackInfo {
     pub current_track: Model<Option<String>>,
     pub shuffling: Model<bool>,
     pub volume: Model<f64>,
+    /// The playback speed, where 1.0 is normal speed.
+    pub speed: Model<f64>,
+    /// The format audio is being sent to the device in, once the output stream has been opened.
+    pub output: Model<Option<OutputInfo>>,
+    /// The output devices that can be switched to, as their UIDs and names.
+    pub devices: Model<Vec<(String, String)>>,
+    /// The smoothed peak level of the audio, from 0.0 to 1.0, while the level meter is enabled.
+    pub level: Model<f32>,
 }
 
 impl Global for PlaybackInfo {}
 
+/// A copy of the current playback state at one point in time, for integrations that need all of
+/// it at once rather than observing each model.
+#[derive(Debug, Clone)]
+pub struct PlaybackSnapshot {
+    pub state: PlaybackState,
+    /// The path of the current track, or None if nothing is playing.
+    pub track: Option<String>,
+    pub metadata: Metadata,
+    /// The position within the track, in seconds.
+    pub position: u64,
+    /// The duration of the track, in seconds.
+    pub duration: u64,
+    pub volume: f64,
+    pub shuffling: bool,
+    /// Whether the queue starts over once it runs out.
+    pub repeat: bool,
+    pub queue_length: usize,
+}
+
+impl PlaybackSnapshot {
+    pub fn read(cx: &AppContext) -> Self {
+        let info = cx.global::<PlaybackInfo>();
+        let models = cx.global::<Models>();
+
+        Self {
+            state: *info.playback_state.read(cx),
+            track: info.current_track.read(cx).clone(),
+            metadata: models.metadata.read(cx).clone(),
+            position: *info.position.read(cx),
+            duration: *info.duration.read(cx),
+            volume: *info.volume.read(cx),
+            shuffling: *info.shuffling.read(cx),
+            repeat: cx
+                .global::<SettingsGlobal>()
+                .model
+                .read(cx)
+                .playback
+                .end_of_queue
+                == EndOfQueue::RepeatAll,
+            queue_length: models.queue.read(cx).0.len(),
+        }
+    }
+}
+
 pub struct ImageTransfer(pub ImageType, pub Arc<RenderImage>);
+/// Emitted instead of an [`ImageTransfer`] when the image couldn't be decoded, so that whatever
+/// was waiting for it can stop showing the previous image.
+pub struct ImageTransferFailed(pub ImageType);
 pub struct TransferDummy;
 
 impl EventEmitter<ImageTransfer> for TransferDummy {}
+impl EventEmitter<ImageTransferFailed> for TransferDummy {}
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Queue(pub Vec<String>);
 
+/// A file that couldn't be played, and why.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlaybackFailure {
+    pub path: String,
+    pub error: PlaybackError,
+}
+
+/// A queue that was replaced, kept so that the replacement can be undone.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReplacedQueue {
+    pub paths: Vec<String>,
+    /// The index of the track that was playing, if any.
+    pub current: Option<usize>,
+    /// How far into the track that was playing playback was, in seconds.
+    pub position: u64,
+}
+
+/// A track and the details of its album shown alongside it.
+#[derive(Clone)]
+pub struct TrackDetails {
+    pub track: Track,
+    pub album_mbid: Option<DBString>,
+    /// The track's composers, performers, and other credits, in tag order.
+    pub credits: Vec<(CreditRole, String)>,
+}
+
+/// The track being trimmed in the trim dialog.
+#[derive(Clone)]
+pub struct TrimTarget {
+    pub track_id: i64,
+    pub location: String,
+    pub title: String,
+    /// The length of the file, in seconds.
+    pub duration: u64,
+}
+
 impl EventEmitter<UIQueueItem> for Queue {}
 
 #[derive(Clone)]
@@ -93,10 +239,61 @@ pub fn build_models(cx: &mut AppContext) {
     debug!("Building models");
     let metadata: Model<Metadata> = cx.new_model(|_| Metadata::default());
     let albumart: Model<Option<Arc<RenderImage>>> = cx.new_model(|_| None);
+    let albumart_color: Model<Option<Rgba>> = cx.new_model(|_| None);
     let queue: Model<Queue> = cx.new_model(|_| Queue(Vec::new()));
+    let queue_metadata: Model<AHashMap<String, UIQueueItem>> = cx.new_model(|_| AHashMap::new());
     let image_transfer_model: Model<TransferDummy> = cx.new_model(|_| TransferDummy);
     let scan_state: Model<ScanEvent> = cx.new_model(|_| ScanEvent::ScanCompleteIdle);
+    let last_scan: Model<Option<LastScan>> = cx.new_model(|_| load_last_scan());
+    let metadata_reloaded: Model<u64> = cx.new_model(|_| 0);
     let mmbs: Model<MMBSList> = cx.new_model(|_| MMBSList(AHashMap::new()));
+    let show_queue: Model<bool> = cx.new_model(|_| true);
+    let show_about: Model<bool> = cx.new_model(|_| false);
+    let show_logs: Model<bool> = cx.new_model(|_| false);
+    let show_accent_picker: Model<bool> = cx.new_model(|_| false);
+    let show_palette: Model<bool> = cx.new_model(|_| false);
+    let open_release: Model<Option<i64>> = cx.new_model(|_| None);
+    let replaced_queue: Model<Option<ReplacedQueue>> = cx.new_model(|_| None);
+    let track_details: Model<Option<TrackDetails>> = cx.new_model(|_| None);
+    let trim_track: Model<Option<TrimTarget>> = cx.new_model(|_| None);
+    let peaks: Model<Option<Arc<Vec<u8>>>> = cx.new_model(|_| None);
+    let playback_failure: Model<Option<PlaybackFailure>> = cx.new_model(|_| None);
+    let output_failure: Model<Option<String>> = cx.new_model(|_| None);
+    let pinned_albums: Model<u64> = cx.new_model(|_| 0);
+    let unreadable_files: Model<Vec<(String, ReadFailure)>> = cx.new_model(|_| Vec::new());
+    let show_unreadable_files: Model<bool> = cx.new_model(|_| false);
+    let finding_continuation: Model<bool> = cx.new_model(|_| false);
+    let scrobble_status: Model<Option<ScrobbleStatus>> = cx.new_model(|_| None);
+    let pending_scrobbles: Model<usize> = cx.new_model(|_| 0);
+
+    let (count_tx, count_rx) = channel();
+    cx.set_global(SharedPendingScrobbles(Arc::new(std::sync::Mutex::new(
+        PendingScrobbles::load(count_tx),
+    ))));
+
+    let pending_scrobbles_clone = pending_scrobbles.clone();
+    cx.spawn(|mut cx| async move {
+        loop {
+            match count_rx.try_recv() {
+                Ok(count) => pending_scrobbles_clone
+                    .update(&mut cx, |m, cx| {
+                        if *m != count {
+                            *m = count;
+                            cx.notify();
+                        }
+                    })
+                    .expect("failed to update pending scrobble count"),
+                Err(TryRecvError::Empty) => {
+                    cx.background_executor()
+                        .timer(Duration::from_millis(100))
+                        .await
+                }
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+    })
+    .detach();
+
     let lastfm: Model<LastFMState> = cx.new_model(|cx| {
         let dirs = get_dirs();
         let directory = dirs.data_dir().to_path_buf();
@@ -106,7 +303,7 @@ pub fn build_models(cx: &mut AppContext) {
             let reader = std::io::BufReader::new(file);
 
             if let Ok(session) = serde_json::from_reader::<std::io::BufReader<File>, Session>(reader) {
-                create_last_fm_mmbs(cx, &mmbs, session.key.clone());
+                create_last_fm_mmbs(cx, &mmbs, &scrobble_status, session.key.clone());
                 LastFMState::Connected(session)
             } else {
                 error!("The last.fm session information is stored on disk but the file could not be opened.");
@@ -119,21 +316,37 @@ pub fn build_models(cx: &mut AppContext) {
     });
 
     cx.subscribe(&albumart, |_, ev, cx| {
+        if cx
+            .global::<SettingsGlobal>()
+            .model
+            .read(cx)
+            .interface
+            .disable_album_art
+        {
+            return;
+        }
+
         let img = ev.0.clone();
         cx.global::<GPUIDataInterface>().decode_image(
             img,
             ImageType::CurrentAlbumArt,
-            ImageLayout::BGR,
+            image_layout(),
             true,
         );
     })
     .detach();
 
     let mmbs_clone = mmbs.clone();
+    let scrobble_status_clone = scrobble_status.clone();
 
     cx.subscribe(&lastfm, move |m, ev, cx| {
         let session_clone = ev.clone();
-        create_last_fm_mmbs(cx, &mmbs_clone, session_clone.key.clone());
+        create_last_fm_mmbs(
+            cx,
+            &mmbs_clone,
+            &scrobble_status_clone,
+            session_clone.key.clone(),
+        );
         m.update(cx, |m, cx| {
             *m = LastFMState::Connected(session_clone);
             cx.notify();
@@ -187,11 +400,33 @@ pub fn build_models(cx: &mut AppContext) {
     cx.set_global(Models {
         metadata,
         albumart,
+        albumart_color,
         queue,
+        queue_metadata,
         image_transfer_model,
         scan_state,
+        last_scan,
+        metadata_reloaded,
         mmbs,
         lastfm,
+        scrobble_status,
+        pending_scrobbles,
+        show_queue,
+        show_about,
+        show_logs,
+        show_accent_picker,
+        show_palette,
+        open_release,
+        replaced_queue,
+        track_details,
+        trim_track,
+        peaks,
+        playback_failure,
+        output_failure,
+        pinned_albums,
+        unreadable_files,
+        show_unreadable_files,
+        finding_continuation,
     });
 
     let position: Model<u64> = cx.new_model(|_| 0);
@@ -200,6 +435,10 @@ pub fn build_models(cx: &mut AppContext) {
     let current_track: Model<Option<String>> = cx.new_model(|_| None);
     let shuffling: Model<bool> = cx.new_model(|_| false);
     let volume: Model<f64> = cx.new_model(|_| 1.0);
+    let speed: Model<f64> = cx.new_model(|_| 1.0);
+    let output: Model<Option<OutputInfo>> = cx.new_model(|_| None);
+    let devices: Model<Vec<(String, String)>> = cx.new_model(|_| Vec::new());
+    let level: Model<f32> = cx.new_model(|_| 0.0);
 
     cx.set_global(PlaybackInfo {
         position,
@@ -208,16 +447,99 @@ pub fn build_models(cx: &mut AppContext) {
         current_track,
         shuffling,
         volume,
+        speed,
+        output,
+        devices,
+        level,
     });
 }
 
-pub fn create_last_fm_mmbs(cx: &mut AppContext, mmbs_list: &Model<MMBSList>, session: String) {
+/// How often the last.fm service checks whether queued scrobbles are due to be submitted, in
+/// seconds.
+const SYNC_CHECK_INTERVAL: u64 = 60;
+
+pub fn create_last_fm_mmbs(
+    cx: &mut AppContext,
+    mmbs_list: &Model<MMBSList>,
+    scrobble_status: &Model<Option<ScrobbleStatus>>,
+    session: String,
+) {
     if let (Some(key), Some(secret)) = (LASTFM_API_KEY, LASTFM_API_SECRET) {
         let mut client = LastFMClient::new(key.to_string(), secret);
         client.set_session(session);
-        let mmbs = LastFM::new(client);
+
+        let (status_tx, status_rx) = channel();
+        let scrobble_status = scrobble_status.clone();
+
+        // the loop ends once the service (and with it the sender) is dropped
+        cx.spawn(|mut cx| async move {
+            loop {
+                match status_rx.try_recv() {
+                    Ok(status) => scrobble_status
+                        .update(&mut cx, |m, cx| {
+                            *m = Some(status);
+                            cx.notify();
+                        })
+                        .expect("failed to update scrobble status"),
+                    Err(TryRecvError::Empty) => {
+                        cx.background_executor()
+                            .timer(Duration::from_millis(100))
+                            .await
+                    }
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            }
+        })
+        .detach();
+
+        // signing in again keeps what's pending from the previous session
+        let pending = cx.global::<SharedPendingScrobbles>().clone();
+
+        let settings = match cx.try_global::<SharedLastFMSettings>() {
+            Some(settings) => settings.clone(),
+            None => {
+                let settings_model = cx.global::<SettingsGlobal>().model.clone();
+                let settings = SharedLastFMSettings(Arc::new(std::sync::Mutex::new(
+                    settings_model.read(cx).lastfm.clone(),
+                )));
+
+                let shared = settings.clone();
+                cx.observe(&settings_model, move |settings_model, cx| {
+                    *shared.0.lock().expect("could not lock last.fm settings") =
+                        settings_model.read(cx).lastfm.clone();
+                })
+                .detach();
+
+                cx.set_global(settings.clone());
+                settings
+            }
+        };
+
+        let mmbs = Arc::new(Mutex::new(LastFM::new(
+            client, status_tx, pending, settings,
+        )));
         mmbs_list.update(cx, |m, _| {
-            m.0.insert("lastfm".to_string(), Arc::new(Mutex::new(mmbs)));
+            m.0.insert("lastfm".to_string(), mmbs.clone());
+        });
+
+        // the timer ends once the service is replaced by signing in again
+        let weak = Arc::downgrade(&mmbs);
+        cx.spawn(|cx| async move {
+            mmbs.lock().await.retry_pending().await;
+            drop(mmbs);
+
+            loop {
+                let Some(mmbs) = weak.upgrade() else {
+                    break;
+                };
+                mmbs.lock().await.sync_if_due().await;
+                drop(mmbs);
+
+                cx.background_executor()
+                    .timer(Duration::from_secs(SYNC_CHECK_INTERVAL))
+                    .await;
+            }
         })
+        .detach();
     }
 }
@@ -0,0 +1,398 @@
+use std::time::Duration;
+
+use gpui::*;
+use prelude::FluentBuilder;
+use tracing::error;
+
+use crate::{
+    library::{db::LibraryAccess, types::SearchResult},
+    playback::interface::{replace_queue, GPUIPlaybackInterface},
+};
+
+use super::{
+    constants::FONT_AWESOME, global_actions::palette_actions, models::Models, theme::Theme,
+};
+
+actions!(
+    command_palette,
+    [InsertSpace, SelectNext, SelectPrevious, Confirm, Dismiss]
+);
+
+/// How long typing has to pause for before the library is searched.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+/// The most entries that are shown at once.
+const MAX_ENTRIES: usize = 12;
+
+enum Entry {
+    Result(SearchResult),
+    /// An index into the palette's actions.
+    Action(usize),
+}
+
+/// An overlay that searches the library and the app's actions as you type, and plays, opens, or
+/// runs the selected entry.
+pub struct CommandPalette {
+    show_palette: Model<bool>,
+    focus_handle: FocusHandle,
+    query: String,
+    actions: Vec<(&'static str, Box<dyn Action>)>,
+    entries: Vec<Entry>,
+    selected: usize,
+    /// Incremented whenever the query changes, so that searches for outdated queries are dropped.
+    generation: usize,
+}
+
+/// Lower is better: titles that are the query come first, then ones that start with it, then ones
+/// that contain it, then ones that only match together with their artist. Shorter titles break
+/// ties.
+fn rank(text: &str, query: &str) -> (u8, usize) {
+    let text_lower = text.to_lowercase();
+    let query = query.to_lowercase();
+
+    let tier = if text_lower == query {
+        0
+    } else if text_lower.starts_with(&query) {
+        1
+    } else if text_lower.contains(&query) {
+        2
+    } else {
+        UNMATCHED
+    };
+
+    (tier, text.len())
+}
+
+/// The tier of text that doesn't contain the query. Search results can still match this way,
+/// through their artist.
+const UNMATCHED: u8 = 3;
+
+/// Ranks the actions whose names contain the query, as pairs of their rank and index. Actions
+/// don't have artists to match with, so the others are left out.
+fn rank_actions<'a>(
+    names: impl Iterator<Item = &'a str>,
+    query: &str,
+) -> Vec<((u8, usize), usize)> {
+    names
+        .enumerate()
+        .map(|(idx, name)| (rank(name, query), idx))
+        .filter(|(rank, _)| rank.0 < UNMATCHED)
+        .collect()
+}
+
+impl CommandPalette {
+    pub fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
+        cx.new_view(|cx| {
+            let show_palette = cx.global::<Models>().show_palette.clone();
+
+            cx.observe(&show_palette, |this: &mut Self, show_palette, cx| {
+                if *show_palette.read(cx) {
+                    this.set_query(String::new(), cx);
+                    cx.focus(&this.focus_handle);
+                }
+
+                cx.notify();
+            })
+            .detach();
+
+            Self {
+                show_palette,
+                focus_handle: cx.focus_handle(),
+                query: String::new(),
+                actions: palette_actions(),
+                entries: Vec::new(),
+                selected: 0,
+                generation: 0,
+            }
+        })
+    }
+
+    fn set_query(&mut self, query: String, cx: &mut ViewContext<Self>) {
+        self.query = query;
+        self.generation += 1;
+        self.update_entries(Vec::new());
+
+        if self.query.trim().is_empty() {
+            return;
+        }
+
+        let generation = self.generation;
+
+        cx.spawn(|this, mut cx| async move {
+            cx.background_executor().timer(DEBOUNCE).await;
+
+            this.update(&mut cx, |this: &mut Self, cx| {
+                if this.generation != generation {
+                    return;
+                }
+
+                match cx.search(this.query.trim()) {
+                    Ok(results) => this.update_entries(results),
+                    Err(e) => error!("Could not search library: {:?}", e),
+                }
+
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Ranks the search results together with the actions that match the query.
+    fn update_entries(&mut self, results: Vec<SearchResult>) {
+        let query = self.query.trim();
+
+        let mut entries: Vec<((u8, usize), Entry)> = results
+            .into_iter()
+            .map(|result| (rank(result.title(), query), Entry::Result(result)))
+            .chain(
+                rank_actions(self.actions.iter().map(|(name, _)| *name), query)
+                    .into_iter()
+                    .map(|(rank, idx)| (rank, Entry::Action(idx))),
+            )
+            .collect();
+
+        entries.sort_by_key(|(rank, _)| *rank);
+
+        self.entries = entries
+            .into_iter()
+            .take(MAX_ENTRIES)
+            .map(|(_, entry)| entry)
+            .collect();
+        self.selected = 0;
+    }
+
+    fn dismiss(&mut self, _: &Dismiss, cx: &mut ViewContext<Self>) {
+        self.set_query(String::new(), cx);
+        self.show_palette.update(cx, |m, cx| {
+            *m = false;
+            cx.notify();
+        });
+    }
+
+    fn insert_space(&mut self, _: &InsertSpace, cx: &mut ViewContext<Self>) {
+        let query = format!("{} ", self.query);
+        self.set_query(query, cx);
+        cx.notify();
+    }
+
+    fn select_next(&mut self, _: &SelectNext, cx: &mut ViewContext<Self>) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+            cx.notify();
+        }
+    }
+
+    fn select_previous(&mut self, _: &SelectPrevious, cx: &mut ViewContext<Self>) {
+        if self.selected > 0 {
+            self.selected -= 1;
+            cx.notify();
+        }
+    }
+
+    fn confirm(&mut self, _: &Confirm, cx: &mut ViewContext<Self>) {
+        let Some(entry) = self.entries.get(self.selected) else {
+            return;
+        };
+
+        match entry {
+            Entry::Result(SearchResult::Track { location, .. }) => {
+                let queue_length = cx.global::<Models>().queue.read(cx).0.len();
+                let playback_interface = cx.global::<GPUIPlaybackInterface>();
+                playback_interface.queue(location);
+                playback_interface.jump(queue_length);
+            }
+            Entry::Result(SearchResult::Album { id, .. }) => {
+                let id = *id;
+                let open_release = cx.global::<Models>().open_release.clone();
+                open_release.update(cx, |m, cx| {
+                    *m = Some(id);
+                    cx.notify();
+                });
+            }
+            Entry::Result(SearchResult::Artist { id, .. }) => {
+                match cx.list_track_locations_by_artist(*id) {
+                    Ok(paths) if !paths.is_empty() => replace_queue(paths, cx),
+                    Ok(_) => (),
+                    Err(e) => error!("Could not retrieve tracks by artist: {:?}", e),
+                }
+            }
+            Entry::Action(idx) => {
+                let action = self.actions[*idx].1.boxed_clone();
+                cx.dispatch_action(action);
+            }
+        }
+
+        self.dismiss(&Dismiss, cx);
+    }
+
+    fn key_down(&mut self, event: &KeyDownEvent, cx: &mut ViewContext<Self>) {
+        let keystroke = &event.keystroke;
+
+        if keystroke.modifiers.control || keystroke.modifiers.platform {
+            return;
+        }
+
+        if keystroke.key == "backspace" {
+            let mut query = self.query.clone();
+            query.pop();
+            self.set_query(query, cx);
+            cx.notify();
+        } else if let Some(text) = keystroke
+            .ime_key
+            .as_ref()
+            // space is bound to play/pause, so it's handled by an action instead
+            .filter(|v| keystroke.key != "space" && !v.chars().any(char::is_control))
+        {
+            let query = format!("{}{}", self.query, text);
+            self.set_query(query, cx);
+            cx.notify();
+        }
+    }
+}
+
+impl Render for CommandPalette {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        if !*self.show_palette.read(cx) {
+            return div().id("command-palette");
+        }
+
+        let theme = cx.global::<Theme>();
+        let show_palette = self.show_palette.clone();
+
+        div()
+            .id("command-palette")
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full()
+            .flex()
+            .justify_center()
+            .pt(px(80.0))
+            .occlude()
+            .child(
+                div()
+                    .id("command-palette-content")
+                    .key_context("CommandPalette")
+                    .track_focus(&self.focus_handle)
+                    .on_action(cx.listener(Self::dismiss))
+                    .on_action(cx.listener(Self::insert_space))
+                    .on_action(cx.listener(Self::select_next))
+                    .on_action(cx.listener(Self::select_previous))
+                    .on_action(cx.listener(Self::confirm))
+                    .on_key_down(cx.listener(Self::key_down))
+                    .w(px(480.0))
+                    .h_min()
+                    .flex()
+                    .flex_col()
+                    .border_1()
+                    .shadow_sm()
+                    .rounded(px(4.0))
+                    .border_color(theme.elevated_border_color)
+                    .bg(theme.elevated_background)
+                    .on_mouse_down_out(move |_, cx| {
+                        show_palette.update(cx, |m, cx| {
+                            *m = false;
+                            cx.notify();
+                        })
+                    })
+                    .child(
+                        div()
+                            .flex()
+                            .px(px(12.0))
+                            .py(px(10.0))
+                            .border_b_1()
+                            .border_color(theme.elevated_border_color)
+                            .child(
+                                div()
+                                    .font_family(FONT_AWESOME)
+                                    .mr(px(10.0))
+                                    .text_sm()
+                                    .text_color(theme.text_secondary)
+                                    .child(""),
+                            )
+                            .child(if self.query.is_empty() {
+                                div()
+                                    .text_color(theme.text_secondary)
+                                    .child("Search or run a command")
+                            } else {
+                                div().child(self.query.clone())
+                            }),
+                    )
+                    .children(self.entries.iter().enumerate().map(|(idx, entry)| {
+                        let (icon, title, secondary) = match entry {
+                            Entry::Result(SearchResult::Track { title, artist, .. }) => {
+                                ("", title.clone(), artist.clone())
+                            }
+                            Entry::Result(SearchResult::Album { title, artist, .. }) => {
+                                ("", title.clone(), artist.clone())
+                            }
+                            Entry::Result(SearchResult::Artist { name, .. }) => {
+                                ("", name.clone(), Some("Play all tracks".to_string()))
+                            }
+                            Entry::Action(idx) => ("", self.actions[*idx].0.to_string(), None),
+                        };
+
+                        div()
+                            .flex()
+                            .items_center()
+                            .px(px(12.0))
+                            .py(px(6.0))
+                            .text_sm()
+                            .when(idx == self.selected, |this| this.bg(theme.menu_item_hover))
+                            .child(
+                                div()
+                                    .font_family(FONT_AWESOME)
+                                    .w(px(16.0))
+                                    .mr(px(10.0))
+                                    .text_color(theme.text_secondary)
+                                    .child(icon),
+                            )
+                            .child(div().overflow_x_hidden().text_ellipsis().child(title))
+                            .when_some(secondary, |this, secondary| {
+                                this.child(
+                                    div()
+                                        .ml_auto()
+                                        .pl(px(10.0))
+                                        .flex_shrink_0()
+                                        .text_color(theme.text_secondary)
+                                        .child(secondary),
+                                )
+                            })
+                    })),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_matches_rank_above_substring_matches() {
+        assert!(rank("Shuffle", "shuffle") < rank("Shuffle Queue", "shuffle"));
+        assert!(rank("Shuffle Queue", "shuffle") < rank("Toggle Shuffle", "shuffle"));
+        assert!(rank("Toggle Shuffle", "shuffle") < rank("Something Else", "shuffle"));
+    }
+
+    #[test]
+    fn shorter_titles_break_ties() {
+        assert!(rank("Play", "pl") < rank("Playlist", "pl"));
+    }
+
+    #[test]
+    fn matching_ignores_case() {
+        assert_eq!(rank("Next Track", "NEXT").0, 1);
+        assert_eq!(rank("NEXT TRACK", "next track").0, 0);
+        assert_eq!(rank("Ärger", "är").0, 1);
+    }
+
+    #[test]
+    fn non_matching_actions_are_left_out() {
+        let names = ["Play", "Pause", "Next Track", "Previous Track"];
+        let ranked = rank_actions(names.into_iter(), "track");
+        let indices: Vec<usize> = ranked.iter().map(|(_, idx)| *idx).collect();
+
+        assert_eq!(indices, [2, 3]);
+        assert_eq!(rank("Play", "track").0, UNMATCHED);
+    }
+}
@@ -1,17 +1,29 @@
 use crate::{
     data::{interface::GPUIDataInterface, types::UIQueueItem},
-    playback::interface::GPUIPlaybackInterface,
+    library::db::find_duration_by_location,
+    playback::{
+        history::{clear_history, play_from_history, PlaybackHistory},
+        interface::GPUIPlaybackInterface,
+        shuffle::toggle_shuffle,
+    },
+    settings::{interface::ListDensity, playback::ShuffleMode, save_settings, SettingsGlobal},
 };
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use gpui::*;
 use prelude::FluentBuilder;
+use tracing::error;
 
 use super::{
-    components::button::{button, ButtonSize, ButtonStyle},
+    app::{DropOnNavigateQueue, Pool},
+    components::{
+        button::{button, ButtonSize, ButtonStyle},
+        context::context,
+        menu::{menu, menu_item},
+    },
     constants::FONT_AWESOME,
     models::{Models, PlaybackInfo},
     theme::Theme,
-    util::{create_or_retrieve_view, prune_views},
+    util::{create_or_retrieve_view, format_remaining, prune_views},
 };
 
 pub struct QueueItem {
@@ -22,30 +34,69 @@ pub struct QueueItem {
 }
 
 impl QueueItem {
-    pub fn new(cx: &mut WindowContext, path: String, idx: usize, clear_cache: bool) -> View<Self> {
+    pub fn new(cx: &mut WindowContext, path: String, idx: usize) -> View<Self> {
         cx.new_view(move |cx| {
             let current_track = cx.global::<PlaybackInfo>().current_track.clone();
 
             let interface = cx.global::<GPUIDataInterface>();
 
-            if clear_cache {
-                interface.evict_cache();
-            }
+            let item = cx
+                .global::<Models>()
+                .queue_metadata
+                .read(cx)
+                .get(&path)
+                .cloned();
 
-            interface.get_metadata(path.clone());
+            match item.as_ref() {
+                Some(item) => {
+                    if let Some(album_art) = item.album_art.clone() {
+                        cx.global::<DropOnNavigateQueue>().track_queue_image(
+                            idx,
+                            path.clone(),
+                            album_art,
+                        );
+                    }
+                }
+                None => interface.get_metadata(path.clone()),
+            }
 
             let queue_model = cx.global::<Models>().queue.clone();
 
             cx.subscribe(&queue_model, move |this: &mut QueueItem, _, ev, cx| {
                 if ev.file_path == this.path {
+                    if let Some(album_art) = ev.album_art.clone() {
+                        cx.global::<DropOnNavigateQueue>().track_queue_image(
+                            this.idx,
+                            this.path.clone(),
+                            album_art,
+                        );
+                    }
+
                     this.item = Some(ev.clone());
                     cx.notify();
                 }
             })
             .detach();
 
+            // the metadata of items that aren't visible is kept, so that they don't have to be
+            // read again when scrolled back to, but their album art would keep the image cache
+            // (and every image in it) alive for the whole queue
+            cx.on_release(|this: &mut QueueItem, cx| {
+                cx.global::<DropOnNavigateQueue>()
+                    .untrack_queue_image(this.idx, &this.path);
+
+                let queue_metadata = cx.global::<Models>().queue_metadata.clone();
+
+                queue_metadata.update(cx, |m, _| {
+                    if m.get(&this.path).is_some_and(|v| v.album_art.is_some()) {
+                        m.remove(&this.path);
+                    }
+                });
+            })
+            .detach();
+
             Self {
-                item: None,
+                item,
                 path,
                 current_track,
                 idx,
@@ -57,6 +108,10 @@ impl QueueItem {
 impl Render for QueueItem {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
+        let settings = &cx.global::<SettingsGlobal>().model.read(cx).interface;
+        let show_quality = settings.show_audio_quality;
+        let show_art = !settings.disable_album_art;
+        let compact = settings.list_density == ListDensity::Compact;
 
         if let Some(item) = self.item.as_ref() {
             let is_current = self
@@ -69,18 +124,21 @@ impl Render for QueueItem {
             let album_art = item
                 .album_art
                 .as_ref()
+                .filter(|_| show_art)
                 .map(|v| ImageSource::Render(v.clone()));
 
             let idx = self.idx;
+            let album_id = item.album_id;
 
-            div()
+            let row = div()
                 .w_full()
                 .id(ElementId::View(cx.entity_id()))
                 .flex()
                 .overflow_x_hidden()
                 .gap(px(11.0))
-                .h(px(59.0))
-                .p(px(11.0))
+                .h(px(if compact { 45.0 } else { 59.0 }))
+                .px(px(11.0))
+                .py(px(if compact { 4.0 } else { 11.0 }))
                 .border_b(px(1.0))
                 .cursor_pointer()
                 .border_color(theme.border_color)
@@ -113,7 +171,7 @@ impl Render for QueueItem {
                         .flex()
                         .flex_col()
                         .line_height(rems(1.0))
-                        .text_size(px(15.0))
+                        .text_size(px(if compact { 13.0 } else { 15.0 }))
                         .gap_1()
                         .overflow_x_hidden()
                         .child(
@@ -124,26 +182,250 @@ impl Render for QueueItem {
                                                                  //     item.file_path.split(MAIN_SEPARATOR).last().unwrap().into(),
                                                                  // )),
                         )
-                        .child(div().text_ellipsis().child(item.artist_name.clone())),
+                        .child(
+                            div()
+                                .flex()
+                                .gap(px(6.0))
+                                .overflow_x_hidden()
+                                .child(div().text_ellipsis().child(item.artist_name.clone()))
+                                .when_some(
+                                    item.quality.clone().filter(|_| show_quality),
+                                    |this, quality| {
+                                        this.child(
+                                            div()
+                                                .ml_auto()
+                                                .flex_shrink_0()
+                                                .text_size(px(11.0))
+                                                .text_color(theme.text_secondary)
+                                                .child(quality),
+                                        )
+                                    },
+                                ),
+                        ),
+                );
+
+            context(("queue-item-context", idx))
+                .with(row)
+                .child(
+                    div().bg(theme.elevated_background).child(
+                        menu()
+                            .item(menu_item(
+                                "queue_item_duplicate",
+                                Some(""),
+                                "Duplicate",
+                                move |_, cx| {
+                                    cx.global::<GPUIPlaybackInterface>()
+                                        .duplicate_queue_item(idx)
+                                },
+                            ))
+                            .item(menu_item(
+                                "queue_item_move_to_top",
+                                Some(""),
+                                "Move to top",
+                                move |_, cx| {
+                                    cx.global::<GPUIPlaybackInterface>().move_queue_item(idx, 0)
+                                },
+                            ))
+                            .item(menu_item(
+                                "queue_item_move_to_bottom",
+                                Some(""),
+                                "Move to bottom",
+                                move |_, cx| {
+                                    let last = cx
+                                        .global::<Models>()
+                                        .queue
+                                        .read(cx)
+                                        .0
+                                        .len()
+                                        .saturating_sub(1);
+                                    cx.global::<GPUIPlaybackInterface>()
+                                        .move_queue_item(idx, last)
+                                },
+                            ))
+                            .when_some(album_id, |this, album_id| {
+                                this.item(menu_item(
+                                    "queue_item_open_album",
+                                    Some(""),
+                                    "Open album",
+                                    move |_, cx| {
+                                        let open_release =
+                                            cx.global::<Models>().open_release.clone();
+                                        open_release.update(cx, |m, cx| {
+                                            *m = Some(album_id);
+                                            cx.notify();
+                                        });
+                                    },
+                                ))
+                            }),
+                    ),
                 )
+                .into_any_element()
         } else {
             // TODO: Skeleton for this
             div()
-                .h(px(59.0))
+                .h(px(if compact { 45.0 } else { 59.0 }))
                 .border_t(px(1.0))
                 .border_color(theme.border_color)
                 .w_full()
                 .id(ElementId::View(cx.entity_id()))
+                .into_any_element()
         }
     }
 }
 
+/// The time left until the end of the queue, from the current position in the current track.
+struct RemainingTime {
+    /// The duration of each queued file in seconds, or None if it isn't known. Durations come from
+    /// the library, or from the file itself once its queue item has been loaded.
+    durations: Model<AHashMap<String, Option<u64>>>,
+}
+
+impl RemainingTime {
+    fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
+        cx.new_view(|cx| {
+            let durations: Model<AHashMap<String, Option<u64>>> = cx.new_model(|_| AHashMap::new());
+            let queue = cx.global::<Models>().queue.clone();
+            let playback_info = cx.global::<PlaybackInfo>().clone();
+
+            cx.observe(&durations, |_, _, cx| cx.notify()).detach();
+            cx.observe(&playback_info.position, |_, _, cx| cx.notify())
+                .detach();
+            cx.observe(&playback_info.current_track, |_, _, cx| cx.notify())
+                .detach();
+
+            let durations_clone = durations.clone();
+
+            cx.observe(&queue, move |_, queue, cx| {
+                cx.notify();
+
+                let unresolved: Vec<String> = {
+                    let known = durations_clone.read(cx);
+                    let mut seen = AHashSet::new();
+
+                    queue
+                        .read(cx)
+                        .0
+                        .iter()
+                        .filter(|path| !known.contains_key(*path) && seen.insert(*path))
+                        .cloned()
+                        .collect()
+                };
+
+                if unresolved.is_empty() {
+                    return;
+                }
+
+                let Some(pool) = cx.try_global::<Pool>().map(|v| v.0.clone()) else {
+                    return;
+                };
+                let durations = durations_clone.clone();
+
+                cx.spawn(|_, mut cx| async move {
+                    let mut found = Vec::new();
+
+                    for path in unresolved {
+                        match find_duration_by_location(&pool, &path).await {
+                            // a duration of 0 means it isn't known
+                            Ok(duration) => {
+                                found.push((path, duration.filter(|v| *v > 0).map(|v| v as u64)))
+                            }
+                            Err(e) => error!("Could not find duration of {}: {:?}", path, e),
+                        }
+                    }
+
+                    durations
+                        .update(&mut cx, |m, cx| {
+                            for (path, duration) in found {
+                                // files outside the library may have had their duration read
+                                // from the file already
+                                let entry = m.entry(path).or_default();
+
+                                if duration.is_some() {
+                                    *entry = duration;
+                                }
+                            }
+
+                            cx.notify();
+                        })
+                        .ok();
+                })
+                .detach();
+            })
+            .detach();
+
+            let durations_clone = durations.clone();
+
+            cx.subscribe(&queue, move |_, _, item: &UIQueueItem, cx| {
+                let Some(duration) = item.duration else {
+                    return;
+                };
+
+                durations_clone.update(cx, |m, cx| {
+                    if m.get(&item.file_path) != Some(&Some(duration)) {
+                        m.insert(item.file_path.clone(), Some(duration));
+                        cx.notify();
+                    }
+                });
+            })
+            .detach();
+
+            Self { durations }
+        })
+    }
+}
+
+impl Render for RemainingTime {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let queue = &cx.global::<Models>().queue.read(cx).0;
+        let playback_info = cx.global::<PlaybackInfo>();
+        let current = playback_info
+            .current_track
+            .read(cx)
+            .as_ref()
+            .and_then(|track| queue.iter().position(|v| v == track));
+        let durations = self.durations.read(cx);
+
+        let mut total = 0;
+        let mut approximate = false;
+
+        for path in &queue[current.unwrap_or_default()..] {
+            match durations.get(path).copied().flatten() {
+                Some(duration) => total += duration,
+                None => approximate = true,
+            }
+        }
+
+        if current.is_some() {
+            total = total.saturating_sub(*playback_info.position.read(cx));
+        }
+
+        div()
+            .ml(px(10.0))
+            .mt_auto()
+            .text_sm()
+            .text_color(theme.text_secondary)
+            .when(!queue.is_empty(), |this| {
+                this.child(format!(
+                    "{}{} left",
+                    if approximate { "~" } else { "" },
+                    format_remaining(total)
+                ))
+            })
+    }
+}
+
 pub struct Queue {
     views_model: Model<AHashMap<usize, View<QueueItem>>>,
     render_counter: Model<usize>,
     state: ListState,
     shuffling: Model<bool>,
     show_queue: Model<bool>,
+    density: ListDensity,
+    /// Shows the history of played tracks in place of the queue.
+    show_history: bool,
+    remaining: View<RemainingTime>,
+    finding_continuation: Model<bool>,
 }
 
 impl Queue {
@@ -154,10 +436,14 @@ impl Queue {
             let items = cx.global::<Models>().queue.clone();
 
             cx.observe(&items, move |this: &mut Queue, m, cx| {
-                this.views_model = cx.new_model(|_| AHashMap::new());
-                this.render_counter = cx.new_model(|_| 0);
-
                 let items = m.read(cx).clone();
+
+                // views for indices that still hold the same file are kept, so that changing a
+                // long queue doesn't recreate every visible item
+                this.views_model.update(cx, |views, cx| {
+                    views.retain(|idx, view| items.0.get(*idx) == Some(&view.read(cx).path));
+                });
+
                 let views_model = this.views_model.clone();
                 let render_counter = this.render_counter.clone();
 
@@ -167,14 +453,13 @@ impl Queue {
                     px(200.0),
                     move |idx, cx| {
                         let item = items.0.get(idx).unwrap().clone();
-                        let was_removed =
-                            prune_views(views_model.clone(), render_counter.clone(), idx, cx);
+                        prune_views(views_model.clone(), render_counter.clone(), idx, cx);
 
                         div()
                             .child(create_or_retrieve_view(
                                 views_model.clone(),
                                 idx,
-                                move |cx| QueueItem::new(cx, item, idx, was_removed),
+                                move |cx| QueueItem::new(cx, item, idx),
                                 cx,
                             ))
                             .into_any_element()
@@ -187,11 +472,42 @@ impl Queue {
 
             let shuffling = cx.global::<PlaybackInfo>().shuffling.clone();
 
+            let settings = cx.global::<SettingsGlobal>().model.clone();
+            let density = settings.read(cx).interface.list_density;
+
+            cx.observe(&settings, |this: &mut Queue, settings, cx| {
+                let density = settings.read(cx).interface.list_density;
+
+                // queue items change height with the density, so they have to be measured again
+                if density != this.density {
+                    this.density = density;
+                    this.state.reset(this.state.item_count());
+                    cx.notify();
+                }
+            })
+            .detach();
+
             cx.observe(&shuffling, |_, _, cx| {
                 cx.notify();
             })
             .detach();
 
+            let finding_continuation = cx.global::<Models>().finding_continuation.clone();
+
+            cx.observe(&finding_continuation, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            let history = cx.global::<PlaybackHistory>().0.clone();
+
+            cx.observe(&history, |this: &mut Queue, _, cx| {
+                if this.show_history {
+                    cx.notify();
+                }
+            })
+            .detach();
+
             Self {
                 views_model,
                 render_counter,
@@ -200,6 +516,10 @@ impl Queue {
                 }),
                 shuffling,
                 show_queue,
+                density,
+                show_history: false,
+                remaining: RemainingTime::new(cx),
+                finding_continuation,
             }
         })
     }
@@ -209,6 +529,14 @@ impl Render for Queue {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
         let shuffling = self.shuffling.read(cx);
+        let shuffle_mode = cx
+            .global::<SettingsGlobal>()
+            .model
+            .read(cx)
+            .playback
+            .shuffle_mode;
+        let show_history = self.show_history;
+        let finding_continuation = *self.finding_continuation.read(cx);
 
         div()
             // .absolute()
@@ -275,43 +603,168 @@ impl Render for Queue {
                             .line_height(px(26.0))
                             .font_weight(FontWeight::BOLD)
                             .text_size(px(26.0))
-                            .child("Queue"),
-                    ),
-            )
-            .child(
-                div()
-                    .w_full()
-                    .flex()
-                    .border_t_1()
-                    .border_b_1()
-                    .border_color(theme.border_color)
-                    .child(
-                        button()
-                            .style(ButtonStyle::MinimalNoRounding)
-                            .size(ButtonSize::Large)
-                            .child(div().font_family(FONT_AWESOME).child(""))
-                            .child("Clear")
-                            .w_full()
-                            .id("clear-queue")
-                            .on_click(|_, cx| {
-                                cx.global::<GPUIPlaybackInterface>().clear_queue();
-                                cx.global::<GPUIPlaybackInterface>().stop();
-                            }),
+                            .child(if show_history { "History" } else { "Queue" }),
                     )
+                    .when(!show_history, |this| this.child(self.remaining.clone()))
                     .child(
                         button()
-                            .style(ButtonStyle::MinimalNoRounding)
-                            .size(ButtonSize::Large)
-                            .child(div().font_family(FONT_AWESOME).child(""))
-                            .when(*shuffling, |this| this.child("Shuffling"))
-                            .when(!shuffling, |this| this.child("Shuffle"))
-                            .w_full()
-                            .id("queue-shuffle")
-                            .on_click(|_, cx| {
-                                cx.global::<GPUIPlaybackInterface>().toggle_shuffle()
-                            }),
+                            .ml_auto()
+                            .child(if show_history { "Queue" } else { "History" })
+                            .id("queue-toggle-history")
+                            .on_click(cx.listener(|this: &mut Self, _, cx| {
+                                this.show_history = !this.show_history;
+                                cx.notify();
+                            })),
                     ),
             )
-            .child(list(self.state.clone()).w_full().h_full().flex().flex_col())
+            .when(show_history, |this| {
+                this.child(
+                    div()
+                        .w_full()
+                        .flex()
+                        .border_t_1()
+                        .border_b_1()
+                        .border_color(theme.border_color)
+                        .child(
+                            button()
+                                .style(ButtonStyle::MinimalNoRounding)
+                                .size(ButtonSize::Large)
+                                .child(div().font_family(FONT_AWESOME).child(""))
+                                .child("Clear history")
+                                .w_full()
+                                .id("clear-history")
+                                .on_click(|_, cx| clear_history(cx)),
+                        ),
+                )
+                .child(history_list(cx))
+            })
+            .when(!show_history, |this| {
+                this.child(
+                    div()
+                        .w_full()
+                        .flex()
+                        .border_t_1()
+                        .border_b_1()
+                        .border_color(theme.border_color)
+                        .child(
+                            button()
+                                .style(ButtonStyle::MinimalNoRounding)
+                                .size(ButtonSize::Large)
+                                .child(div().font_family(FONT_AWESOME).child(""))
+                                .child("Clear")
+                                .w_full()
+                                .id("clear-queue")
+                                .on_click(|_, cx| {
+                                    cx.global::<GPUIPlaybackInterface>().clear_queue();
+                                    cx.global::<GPUIPlaybackInterface>().stop();
+                                }),
+                        )
+                        .child(
+                            button()
+                                .style(ButtonStyle::MinimalNoRounding)
+                                .size(ButtonSize::Large)
+                                .child(div().font_family(FONT_AWESOME).child(""))
+                                .when(*shuffling, |this| this.child("Shuffling"))
+                                .when(!shuffling, |this| this.child("Shuffle"))
+                                .w_full()
+                                .id("queue-shuffle")
+                                .on_click(|_, cx| toggle_shuffle(cx)),
+                        )
+                        .child(
+                            button()
+                                .style(ButtonStyle::MinimalNoRounding)
+                                .size(ButtonSize::Large)
+                                .when(shuffle_mode == ShuffleMode::Tracks, |this| {
+                                    this.child("By track")
+                                })
+                                .when(shuffle_mode == ShuffleMode::Albums, |this| {
+                                    this.child("By album")
+                                })
+                                .w_full()
+                                .id("queue-shuffle-mode")
+                                .on_click(move |_, cx| {
+                                    save_settings(cx, |settings| {
+                                        settings.playback.shuffle_mode = match shuffle_mode {
+                                            ShuffleMode::Tracks => ShuffleMode::Albums,
+                                            ShuffleMode::Albums => ShuffleMode::Tracks,
+                                        };
+                                    })
+                                }),
+                        ),
+                )
+                .child(list(self.state.clone()).w_full().h_full().flex().flex_col())
+                .when(finding_continuation, |this| {
+                    this.child(
+                        div()
+                            .w_full()
+                            .px(px(12.0))
+                            .py(px(8.0))
+                            .border_t_1()
+                            .border_color(theme.border_color)
+                            .text_sm()
+                            .text_color(theme.text_secondary)
+                            .child("Finding an album to play next..."),
+                    )
+                })
+            })
     }
 }
+
+/// The played tracks, most recent first.
+fn history_list(cx: &WindowContext) -> impl IntoElement {
+    let theme = cx.global::<Theme>();
+    let current_track = cx.global::<PlaybackInfo>().current_track.read(cx).clone();
+    let entries = cx.global::<PlaybackHistory>().0.read(cx).entries.clone();
+
+    div()
+        .id("history")
+        .w_full()
+        .h_full()
+        .flex()
+        .flex_col()
+        .overflow_y_scroll()
+        .children(entries.into_iter().enumerate().rev().map(|(idx, entry)| {
+            let location = entry.location.clone();
+            let title = entry.title.clone().unwrap_or_else(|| {
+                std::path::Path::new(&entry.location)
+                    .file_name()
+                    .map(|v| v.to_string_lossy().to_string())
+                    .unwrap_or(entry.location.clone())
+            });
+
+            div()
+                .id(("history-item", idx))
+                .w_full()
+                .px(px(12.0))
+                .py(px(8.0))
+                .border_b_1()
+                .border_color(theme.border_color)
+                .cursor_pointer()
+                .hover(|this| this.bg(theme.nav_button_hover))
+                .active(|this| this.bg(theme.nav_button_active))
+                .when(current_track.as_ref() == Some(&entry.location), |this| {
+                    this.bg(theme.queue_item_current)
+                })
+                .on_click(move |_, cx| play_from_history(cx, &location))
+                .child(
+                    div()
+                        .text_sm()
+                        .font_weight(FontWeight::BOLD)
+                        .overflow_x_hidden()
+                        .whitespace_nowrap()
+                        .text_ellipsis()
+                        .child(title),
+                )
+                .when_some(entry.artist, |this, artist| {
+                    this.child(
+                        div()
+                            .text_sm()
+                            .text_color(theme.text_secondary)
+                            .overflow_x_hidden()
+                            .whitespace_nowrap()
+                            .text_ellipsis()
+                            .child(artist),
+                    )
+                })
+        }))
+}
@@ -1,10 +1,17 @@
-use std::{fs::File, io::BufReader, path::PathBuf, sync::mpsc::channel, time::Duration};
+use std::{
+    cell::RefCell, fs::File, io::BufReader, path::PathBuf, rc::Rc, sync::mpsc::channel,
+    time::Duration,
+};
 
 use gpui::{rgb, rgba, AppContext, AsyncAppContext, Context, EventEmitter, Global, Rgba};
 use notify::{Event, RecursiveMode, Watcher};
 use serde::Deserialize;
 use tracing::{error, info, warn};
 
+use crate::settings::SettingsGlobal;
+
+use super::models::Models;
+
 #[derive(Deserialize, Clone)]
 #[serde(default)]
 pub struct Theme {
@@ -15,6 +22,7 @@ pub struct Theme {
     pub border_color: Rgba,
 
     pub album_art_background: Rgba,
+    pub album_art_backdrop_overlay: Rgba,
     pub text: Rgba,
     pub text_secondary: Rgba,
 
@@ -68,6 +76,9 @@ pub struct Theme {
     pub menu_item: Rgba,
     pub menu_item_hover: Rgba,
     pub menu_item_active: Rgba,
+
+    pub focus_ring: Rgba,
+    pub track_selected: Rgba,
 }
 
 impl Default for Theme {
@@ -81,6 +92,7 @@ impl Default for Theme {
             border_color: rgb(0x272D37),
 
             album_art_background: rgb(0x4C5974),
+            album_art_backdrop_overlay: rgba(0x0C1116BB),
             text: rgb(0xF4F5F6),
             text_secondary: rgb(0xBEC4CA),
 
@@ -134,12 +146,131 @@ impl Default for Theme {
             menu_item: rgba(0x282F3D00),
             menu_item_hover: rgb(0x282F3D),
             menu_item_active: rgb(0x0D1014),
+
+            focus_ring: rgb(0x087AD1),
+            track_selected: rgb(0x0F2438),
         }
     }
 }
 
 impl Global for Theme {}
 
+/// The theme as loaded from theme.json, before the accent color is applied.
+pub struct BaseTheme(pub Theme);
+
+impl Global for BaseTheme {}
+
+/// Parses an accent color in the "#RRGGBB" format.
+pub fn parse_accent(value: &str) -> Option<Rgba> {
+    Rgba::try_from(value)
+        .map_err(|e| warn!("Invalid accent color {}: {:?}", value, e))
+        .ok()
+}
+
+fn mix(from: Rgba, to: Rgba, amount: f32) -> Rgba {
+    Rgba {
+        r: from.r + (to.r - from.r) * amount,
+        g: from.g + (to.g - from.g) * amount,
+        b: from.b + (to.b - from.b) * amount,
+        a: from.a,
+    }
+}
+
+fn luminance(color: Rgba) -> f32 {
+    0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b
+}
+
+/// Album art is often very dark or very light, neither of which works well for buttons, so the
+/// luminance of colors taken from it is pulled into a usable range.
+fn tame(color: Rgba) -> Rgba {
+    let luminance = luminance(color);
+
+    if luminance < 0.2 {
+        mix(color, rgb(0xFFFFFF), (0.2 - luminance) / (1.0 - luminance))
+    } else if luminance > 0.7 {
+        mix(color, rgb(0x000000), (luminance - 0.7) / luminance)
+    } else {
+        color
+    }
+}
+
+/// Replaces the primary colors of the theme with shades of the accent color.
+pub fn apply_accent(theme: &mut Theme, accent: Rgba) {
+    let white = rgb(0xFFFFFF);
+    let black = rgb(0x000000);
+
+    theme.button_primary = accent;
+    theme.button_primary_hover = mix(accent, white, 0.12);
+    theme.button_primary_active = mix(accent, black, 0.12);
+    theme.button_primary_text = if luminance(accent) > 0.5 {
+        mix(accent, black, 0.85)
+    } else {
+        mix(accent, white, 0.9)
+    };
+    theme.slider_foreground = accent;
+    theme.focus_ring = theme.button_primary_hover;
+    theme.track_selected = mix(theme.background_primary, accent, 0.2);
+}
+
+/// The accent color that should be applied to the theme: the color of the current album art if
+/// dynamic accent colors are enabled and it's known, otherwise the accent color from the
+/// settings.
+fn current_accent(cx: &AppContext) -> Option<Rgba> {
+    let interface = &cx.try_global::<SettingsGlobal>()?.model.read(cx).interface;
+
+    let dynamic = cx
+        .try_global::<Models>()
+        .filter(|_| interface.dynamic_accent && !interface.disable_album_art)
+        .and_then(|models| *models.albumart_color.read(cx))
+        .map(tame);
+
+    dynamic.or_else(|| interface.accent_color.as_deref().and_then(parse_accent))
+}
+
+/// Sets the theme, with the current accent color (if there is one) applied on top.
+fn set_theme(cx: &mut AppContext, theme: Theme) {
+    let mut applied = theme.clone();
+
+    if let Some(accent) = current_accent(cx) {
+        apply_accent(&mut applied, accent);
+    }
+
+    cx.set_global(BaseTheme(theme));
+    cx.set_global(applied);
+}
+
+/// Applies the current accent color, and again whenever it changes. This must be called after the
+/// theme and settings have been set up.
+pub fn setup_accent(cx: &mut AppContext) {
+    let accent = Rc::new(RefCell::new(current_accent(cx)));
+
+    let theme = cx.global::<BaseTheme>().0.clone();
+    set_theme(cx, theme);
+
+    let update = move |cx: &mut AppContext| {
+        let current = current_accent(cx);
+
+        if *accent.borrow() == current {
+            return;
+        }
+
+        *accent.borrow_mut() = current;
+
+        let theme = cx.global::<BaseTheme>().0.clone();
+        set_theme(cx, theme);
+        cx.refresh();
+    };
+    let update = Rc::new(update);
+    let update_clone = update.clone();
+
+    let settings = cx.global::<SettingsGlobal>().model.clone();
+    cx.observe(&settings, move |_, cx| update(cx)).detach();
+
+    let albumart_color = cx.global::<Models>().albumart_color.clone();
+    cx.observe(&albumart_color, move |_, cx| update_clone(cx))
+        .detach();
+}
+
 pub fn create_theme(path: &PathBuf) -> Theme {
     if let Ok(file) = File::open(path) {
         let reader = BufReader::new(file);
@@ -166,11 +297,11 @@ pub struct ThemeWatcher(pub Box<dyn Watcher>);
 impl Global for ThemeWatcher {}
 
 pub fn setup_theme(cx: &mut AppContext, path: PathBuf) {
-    cx.set_global(create_theme(&path));
+    set_theme(cx, create_theme(&path));
     let theme_transmitter = cx.new_model(|_| ThemeEvTransmitter);
 
     cx.subscribe(&theme_transmitter, |_, theme, cx| {
-        cx.set_global(theme.clone());
+        set_theme(cx, theme.clone());
         cx.refresh();
     })
     .detach();
@@ -0,0 +1,239 @@
+use std::{path::Path, time::Duration};
+
+use gpui::*;
+
+use crate::playback::interface::undo_replace_queue;
+
+use super::{
+    components::button::{button, ButtonIntent, ButtonStyle},
+    constants::FONT_AWESOME,
+    models::{Models, PlaybackFailure, ReplacedQueue},
+    theme::Theme,
+};
+
+/// How long a toast is shown before it is dismissed.
+const TOAST_DURATION: Duration = Duration::from_secs(10);
+
+/// Dismisses a toast once it has been shown for `TOAST_DURATION`. Showing it again starts the
+/// timer over, since replacing the task cancels the earlier one.
+#[derive(Default)]
+struct DismissTimer(Option<Task<()>>);
+
+impl DismissTimer {
+    fn restart<V: 'static>(
+        &mut self,
+        cx: &mut ViewContext<V>,
+        dismiss: impl FnOnce(&mut V, &mut ViewContext<V>) + 'static,
+    ) {
+        self.0 = Some(cx.spawn(|this, mut cx| async move {
+            cx.background_executor().timer(TOAST_DURATION).await;
+            this.update(&mut cx, dismiss).ok();
+        }));
+    }
+}
+
+/// Shown after a queue with unplayed tracks is replaced, offering to bring it back.
+pub struct UndoToast {
+    replaced_queue: Model<Option<ReplacedQueue>>,
+    dismiss_timer: DismissTimer,
+}
+
+impl UndoToast {
+    pub fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
+        cx.new_view(|cx| {
+            let replaced_queue = cx.global::<Models>().replaced_queue.clone();
+
+            cx.observe(&replaced_queue, |this: &mut Self, replaced_queue, cx| {
+                if replaced_queue.read(cx).is_some() {
+                    this.dismiss_timer
+                        .restart(cx, |this: &mut Self, cx| dismiss(&this.replaced_queue, cx));
+                }
+
+                cx.notify();
+            })
+            .detach();
+
+            Self {
+                replaced_queue,
+                dismiss_timer: DismissTimer::default(),
+            }
+        })
+    }
+}
+
+fn dismiss<T: 'static>(model: &Model<Option<T>>, cx: &mut WindowContext) {
+    model.update(cx, |m, cx| {
+        *m = None;
+        cx.notify();
+    });
+}
+
+impl Render for UndoToast {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let Some(replaced) = self.replaced_queue.read(cx).as_ref() else {
+            return div().id("undo-toast");
+        };
+
+        let theme = cx.global::<Theme>();
+        let count = replaced.paths.len();
+        let replaced_queue = self.replaced_queue.clone();
+
+        div()
+            .id("undo-toast")
+            .absolute()
+            .bottom(px(72.0))
+            .right(px(12.0))
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap(px(8.0))
+                    .pl(px(12.0))
+                    .pr(px(6.0))
+                    .py(px(6.0))
+                    .border_1()
+                    .shadow_sm()
+                    .rounded(px(4.0))
+                    .border_color(theme.elevated_border_color)
+                    .bg(theme.elevated_background)
+                    .text_sm()
+                    .child(format!(
+                        "Replaced a queue of {} track{}",
+                        count,
+                        if count == 1 { "" } else { "s" }
+                    ))
+                    .child(
+                        button()
+                            .intent(ButtonIntent::Primary)
+                            .child("Undo")
+                            .id("undo-toast-undo")
+                            .on_click(|_, cx| undo_replace_queue(cx)),
+                    )
+                    .child(
+                        button()
+                            .style(ButtonStyle::Minimal)
+                            .child(div().font_family(FONT_AWESOME).child(""))
+                            .id("undo-toast-dismiss")
+                            .on_click(move |_, cx| dismiss(&replaced_queue, cx)),
+                    ),
+            )
+    }
+}
+
+/// Shown when a file couldn't be played, explaining why, or when an output device couldn't be
+/// opened.
+pub struct ErrorToast {
+    playback_failure: Model<Option<PlaybackFailure>>,
+    output_failure: Model<Option<String>>,
+    dismiss_timer: DismissTimer,
+}
+
+impl ErrorToast {
+    pub fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
+        cx.new_view(|cx| {
+            let playback_failure = cx.global::<Models>().playback_failure.clone();
+            let output_failure = cx.global::<Models>().output_failure.clone();
+
+            cx.observe(
+                &playback_failure,
+                |this: &mut Self, playback_failure, cx| {
+                    if playback_failure.read(cx).is_some() {
+                        this.start_timer(cx);
+                    }
+
+                    cx.notify();
+                },
+            )
+            .detach();
+
+            cx.observe(&output_failure, |this: &mut Self, output_failure, cx| {
+                if output_failure.read(cx).is_some() {
+                    this.start_timer(cx);
+                }
+
+                cx.notify();
+            })
+            .detach();
+
+            Self {
+                playback_failure,
+                output_failure,
+                dismiss_timer: DismissTimer::default(),
+            }
+        })
+    }
+}
+
+impl ErrorToast {
+    fn start_timer(&mut self, cx: &mut ViewContext<Self>) {
+        self.dismiss_timer.restart(cx, |this: &mut Self, cx| {
+            dismiss(&this.playback_failure, cx);
+            dismiss(&this.output_failure, cx);
+        });
+    }
+}
+
+impl Render for ErrorToast {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let (title, message) = if let Some(failure) = self.playback_failure.read(cx).as_ref() {
+            let file_name = Path::new(&failure.path)
+                .file_name()
+                .map(|v| v.to_string_lossy().to_string())
+                .unwrap_or_else(|| failure.path.clone());
+
+            (
+                format!("Couldn't play {}", file_name),
+                failure.error.message(),
+            )
+        } else if let Some(device) = self.output_failure.read(cx).as_ref() {
+            (
+                format!("Couldn't switch to {}", device),
+                "The device may be busy or disconnected",
+            )
+        } else {
+            return div().id("error-toast");
+        };
+
+        let theme = cx.global::<Theme>();
+        let playback_failure = self.playback_failure.clone();
+        let output_failure = self.output_failure.clone();
+
+        div()
+            .id("error-toast")
+            .absolute()
+            .bottom(px(72.0))
+            .left(px(12.0))
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap(px(8.0))
+                    .pl(px(12.0))
+                    .pr(px(6.0))
+                    .py(px(6.0))
+                    .border_1()
+                    .shadow_sm()
+                    .rounded(px(4.0))
+                    .border_color(theme.elevated_border_color)
+                    .bg(theme.elevated_background)
+                    .text_sm()
+                    .child(
+                        div()
+                            .flex()
+                            .flex_col()
+                            .child(title)
+                            .child(div().text_color(theme.text_secondary).child(message)),
+                    )
+                    .child(
+                        button()
+                            .style(ButtonStyle::Minimal)
+                            .child(div().font_family(FONT_AWESOME).child(""))
+                            .id("error-toast-dismiss")
+                            .on_click(move |_, cx| {
+                                dismiss(&playback_failure, cx);
+                                dismiss(&output_failure, cx);
+                            }),
+                    ),
+            )
+    }
+}
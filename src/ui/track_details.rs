@@ -0,0 +1,93 @@
+use gpui::*;
+
+use crate::library::types::DBString;
+
+use super::{
+    about::info_row,
+    models::{Models, TrackDetails},
+    theme::Theme,
+    util::format_duration,
+};
+
+/// Shows the details of a track that aren't shown in the track list, like its location and
+/// MusicBrainz IDs.
+pub struct TrackDetailsDialog {
+    track_details: Model<Option<TrackDetails>>,
+}
+
+impl TrackDetailsDialog {
+    pub fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
+        cx.new_view(|cx| {
+            let track_details = cx.global::<Models>().track_details.clone();
+
+            cx.observe(&track_details, |_, _, cx| {
+                cx.notify();
+            })
+            .detach();
+
+            Self { track_details }
+        })
+    }
+}
+
+impl Render for TrackDetailsDialog {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let Some(details) = self.track_details.read(cx).clone() else {
+            return div().id("track-details-dialog");
+        };
+
+        let theme = cx.global::<Theme>();
+        let track_details = self.track_details.clone();
+        let track = details.track;
+        let mbid = |v: Option<DBString>| v.map(SharedString::from).unwrap_or("None".into());
+
+        div()
+            .id("track-details-dialog")
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full()
+            .flex()
+            .items_center()
+            .justify_center()
+            .occlude()
+            .child(
+                div()
+                    .id("track-details-dialog-content")
+                    .w(px(480.0))
+                    .p(px(16.0))
+                    .flex()
+                    .flex_col()
+                    .gap(px(6.0))
+                    .border_1()
+                    .shadow_sm()
+                    .rounded(px(4.0))
+                    .border_color(theme.elevated_border_color)
+                    .bg(theme.elevated_background)
+                    .on_mouse_down_out(move |_, cx| {
+                        track_details.update(cx, |m, cx| {
+                            *m = None;
+                            cx.notify();
+                        })
+                    })
+                    .child(
+                        div()
+                            .mb(px(10.0))
+                            .font_weight(FontWeight::EXTRA_BOLD)
+                            .text_size(px(20.0))
+                            .line_height(px(22.0))
+                            .child(track.title.clone()),
+                    )
+                    .child(info_row("Duration", format_duration(track.duration), theme))
+                    .child(info_row("Location", track.location.clone(), theme))
+                    .child(info_row("Recording MBID", mbid(track.mbid), theme))
+                    .child(info_row("Release MBID", mbid(details.album_mbid), theme))
+                    .children(
+                        details
+                            .credits
+                            .into_iter()
+                            .map(|(role, name)| info_row(role.label(), name, theme)),
+                    ),
+            )
+    }
+}
@@ -0,0 +1,343 @@
+use gpui::*;
+use prelude::FluentBuilder;
+
+use crate::playback::trim::{set_track_trim, TrackTrim, TrackTrims};
+
+use super::{
+    components::button::{button, ButtonIntent},
+    models::{Models, TrimTarget},
+    theme::Theme,
+};
+
+actions!(trim_dialog, [SwitchField, Save, Cancel]);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Start,
+    End,
+}
+
+/// Edits where a track starts and ends, as "m:ss" times. Empty times play from the start or to
+/// the end of the file.
+pub struct TrimDialog {
+    trim_track: Model<Option<TrimTarget>>,
+    focus_handle: FocusHandle,
+    start: String,
+    end: String,
+    field: Field,
+    error: Option<&'static str>,
+}
+
+fn format_time(secs: u64) -> String {
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Parses "m:ss", "h:mm:ss", or a number of seconds. An empty time is parsed as None. Minutes and
+/// seconds after the first component have to be below 60, and times too large to store are
+/// rejected.
+fn parse_time(text: &str) -> Result<Option<u64>, ()> {
+    let text = text.trim();
+
+    if text.is_empty() {
+        return Ok(None);
+    }
+
+    let parts: Vec<&str> = text.split(':').collect();
+
+    if parts.len() > 3 {
+        return Err(());
+    }
+
+    let mut secs: u64 = 0;
+
+    for (i, part) in parts.iter().enumerate() {
+        let value: u64 = part.parse().map_err(|_| ())?;
+
+        if i > 0 && value >= 60 {
+            return Err(());
+        }
+
+        secs = secs
+            .checked_mul(60)
+            .and_then(|v| v.checked_add(value))
+            .ok_or(())?;
+    }
+
+    Ok(Some(secs))
+}
+
+impl TrimDialog {
+    pub fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
+        cx.new_view(|cx| {
+            let trim_track = cx.global::<Models>().trim_track.clone();
+
+            cx.observe(&trim_track, |this: &mut Self, trim_track, cx| {
+                if let Some(target) = trim_track.read(cx).clone() {
+                    let trim = cx
+                        .global::<TrackTrims>()
+                        .0
+                        .read(cx)
+                        .get(&target.location)
+                        .copied()
+                        .unwrap_or_default();
+
+                    this.start = trim.start.map(format_time).unwrap_or_default();
+                    this.end = trim.end.map(format_time).unwrap_or_default();
+                    this.field = Field::Start;
+                    this.error = None;
+                    cx.focus(&this.focus_handle);
+                }
+
+                cx.notify();
+            })
+            .detach();
+
+            Self {
+                trim_track,
+                focus_handle: cx.focus_handle(),
+                start: String::new(),
+                end: String::new(),
+                field: Field::Start,
+                error: None,
+            }
+        })
+    }
+
+    fn text_mut(&mut self) -> &mut String {
+        match self.field {
+            Field::Start => &mut self.start,
+            Field::End => &mut self.end,
+        }
+    }
+
+    fn switch_field(&mut self, _: &SwitchField, cx: &mut ViewContext<Self>) {
+        self.field = match self.field {
+            Field::Start => Field::End,
+            Field::End => Field::Start,
+        };
+        cx.notify();
+    }
+
+    fn cancel(&mut self, _: &Cancel, cx: &mut ViewContext<Self>) {
+        self.trim_track.update(cx, |m, cx| {
+            *m = None;
+            cx.notify();
+        });
+    }
+
+    fn save(&mut self, _: &Save, cx: &mut ViewContext<Self>) {
+        let Some(target) = self.trim_track.read(cx).clone() else {
+            return;
+        };
+
+        let (Ok(start), Ok(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            self.error = Some("Times should look like 1:23.");
+            cx.notify();
+            return;
+        };
+
+        if start.unwrap_or(0) >= end.unwrap_or(target.duration) {
+            self.error = Some("The track has to end after it starts.");
+            cx.notify();
+            return;
+        }
+
+        // an end past the end of the file doesn't trim anything
+        let end = end.filter(|v| *v < target.duration);
+
+        set_track_trim(
+            cx,
+            target.track_id,
+            target.location,
+            TrackTrim { start, end },
+        );
+        self.cancel(&Cancel, cx);
+    }
+
+    fn key_down(&mut self, event: &KeyDownEvent, cx: &mut ViewContext<Self>) {
+        let keystroke = &event.keystroke;
+
+        if keystroke.modifiers.control || keystroke.modifiers.platform {
+            return;
+        }
+
+        if keystroke.key == "backspace" {
+            self.text_mut().pop();
+            cx.notify();
+        } else if let Some(text) = keystroke
+            .ime_key
+            .as_ref()
+            .filter(|v| v.chars().all(|c| c.is_ascii_digit() || c == ':'))
+        {
+            self.text_mut().push_str(text);
+            cx.notify();
+        }
+    }
+
+    fn time_field(&self, field: Field, label: &'static str, theme: &Theme) -> Stateful<Div> {
+        let text = match field {
+            Field::Start => &self.start,
+            Field::End => &self.end,
+        };
+        let placeholder = match field {
+            Field::Start => "Beginning",
+            Field::End => "End of file",
+        };
+
+        div()
+            .id(label)
+            .flex()
+            .items_center()
+            .text_sm()
+            .child(div().text_color(theme.text_secondary).child(label))
+            .child(
+                div()
+                    .ml_auto()
+                    .w(px(120.0))
+                    .px(px(8.0))
+                    .py(px(4.0))
+                    .border_1()
+                    .rounded(px(4.0))
+                    .border_color(if self.field == field {
+                        theme.focus_ring
+                    } else {
+                        theme.elevated_border_color
+                    })
+                    .child(if text.is_empty() {
+                        div().text_color(theme.text_secondary).child(placeholder)
+                    } else {
+                        div().child(text.clone())
+                    }),
+            )
+    }
+}
+
+impl Render for TrimDialog {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let Some(target) = self.trim_track.read(cx).clone() else {
+            return div().id("trim-dialog");
+        };
+
+        let theme = cx.global::<Theme>();
+        let trim_track = self.trim_track.clone();
+
+        div()
+            .id("trim-dialog")
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full()
+            .flex()
+            .items_center()
+            .justify_center()
+            .occlude()
+            .child(
+                div()
+                    .id("trim-dialog-content")
+                    .key_context("TrimDialog")
+                    .track_focus(&self.focus_handle)
+                    .on_action(cx.listener(Self::switch_field))
+                    .on_action(cx.listener(Self::save))
+                    .on_action(cx.listener(Self::cancel))
+                    .on_key_down(cx.listener(Self::key_down))
+                    .w(px(320.0))
+                    .p(px(16.0))
+                    .flex()
+                    .flex_col()
+                    .gap(px(6.0))
+                    .border_1()
+                    .shadow_sm()
+                    .rounded(px(4.0))
+                    .border_color(theme.elevated_border_color)
+                    .bg(theme.elevated_background)
+                    .on_mouse_down_out(move |_, cx| {
+                        trim_track.update(cx, |m, cx| {
+                            *m = None;
+                            cx.notify();
+                        })
+                    })
+                    .child(
+                        div()
+                            .font_weight(FontWeight::BOLD)
+                            .text_size(px(18.0))
+                            .child("Trim track"),
+                    )
+                    .child(
+                        div()
+                            .mb(px(10.0))
+                            .text_sm()
+                            .text_color(theme.text_secondary)
+                            .child(format!(
+                                "{} ({})",
+                                target.title,
+                                format_time(target.duration)
+                            )),
+                    )
+                    .child(
+                        self.time_field(Field::Start, "Start", theme)
+                            .on_click(cx.listener(|this, _, cx| {
+                                this.field = Field::Start;
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        self.time_field(Field::End, "End", theme)
+                            .on_click(cx.listener(|this, _, cx| {
+                                this.field = Field::End;
+                                cx.notify();
+                            })),
+                    )
+                    .when_some(self.error, |this, error| {
+                        this.child(div().text_sm().text_color(theme.button_danger).child(error))
+                    })
+                    .child(
+                        div()
+                            .mt(px(10.0))
+                            .flex()
+                            .gap(px(6.0))
+                            .child(
+                                button()
+                                    .intent(ButtonIntent::Primary)
+                                    .child("Save")
+                                    .id("trim-save")
+                                    .on_click(cx.listener(|this, _, cx| this.save(&Save, cx))),
+                            )
+                            .child(
+                                button()
+                                    .intent(ButtonIntent::Secondary)
+                                    .child("Cancel")
+                                    .id("trim-cancel")
+                                    .on_click(cx.listener(|this, _, cx| this.cancel(&Cancel, cx))),
+                            ),
+                    ),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(parse_time(""), Ok(None));
+        assert_eq!(parse_time(" 90 "), Ok(Some(90)));
+        assert_eq!(parse_time("1:15"), Ok(Some(75)));
+        assert_eq!(parse_time("1:02:03"), Ok(Some(3723)));
+    }
+
+    #[test]
+    fn rejects_out_of_range_components() {
+        assert_eq!(parse_time("1:75"), Err(()));
+        assert_eq!(parse_time("1:60:00"), Err(()));
+        assert_eq!(parse_time("1:2:3:4"), Err(()));
+        assert_eq!(parse_time("1:"), Err(()));
+        assert_eq!(parse_time("-1"), Err(()));
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        assert_eq!(parse_time("99999999999999999999"), Err(()));
+        assert_eq!(parse_time(&format!("{}:00", u64::MAX)), Err(()));
+    }
+}
@@ -0,0 +1,145 @@
+use gpui::*;
+use prelude::FluentBuilder;
+
+use crate::data::events::ReadFailure;
+
+use super::{
+    components::button::{button, ButtonIntent},
+    models::Models,
+    theme::Theme,
+};
+
+/// Lists the queued files whose metadata couldn't be read, so that broken files can be found and
+/// fixed.
+pub struct UnreadableFilesDialog {
+    show_unreadable_files: Model<bool>,
+    unreadable_files: Model<Vec<(String, ReadFailure)>>,
+}
+
+impl UnreadableFilesDialog {
+    pub fn new<V: 'static>(cx: &mut ViewContext<V>) -> View<Self> {
+        cx.new_view(|cx| {
+            let show_unreadable_files = cx.global::<Models>().show_unreadable_files.clone();
+            let unreadable_files = cx.global::<Models>().unreadable_files.clone();
+
+            cx.observe(&show_unreadable_files, |_, _, cx| cx.notify())
+                .detach();
+            cx.observe(&unreadable_files, |_, _, cx| cx.notify())
+                .detach();
+
+            Self {
+                show_unreadable_files,
+                unreadable_files,
+            }
+        })
+    }
+}
+
+impl Render for UnreadableFilesDialog {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        if !*self.show_unreadable_files.read(cx) {
+            return div().id("unreadable-files-dialog");
+        }
+
+        let theme = cx.global::<Theme>();
+        let show_unreadable_files = self.show_unreadable_files.clone();
+        let unreadable_files = self.unreadable_files.clone();
+        let files = self.unreadable_files.read(cx);
+
+        div()
+            .id("unreadable-files-dialog")
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full()
+            .flex()
+            .items_center()
+            .justify_center()
+            .occlude()
+            .child(
+                div()
+                    .id("unreadable-files-dialog-content")
+                    .w(px(560.0))
+                    .max_h(px(480.0))
+                    .p(px(16.0))
+                    .flex()
+                    .flex_col()
+                    .gap(px(6.0))
+                    .border_1()
+                    .shadow_sm()
+                    .rounded(px(4.0))
+                    .border_color(theme.elevated_border_color)
+                    .bg(theme.elevated_background)
+                    .on_mouse_down_out(move |_, cx| {
+                        show_unreadable_files.update(cx, |m, cx| {
+                            *m = false;
+                            cx.notify();
+                        })
+                    })
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .child(
+                                div()
+                                    .font_weight(FontWeight::BOLD)
+                                    .text_size(px(18.0))
+                                    .child("Files that couldn't be read"),
+                            )
+                            .child(
+                                div().ml_auto().flex().child(
+                                    button()
+                                        .intent(ButtonIntent::Secondary)
+                                        .child("Clear")
+                                        .id("unreadable-files-clear")
+                                        .on_click(move |_, cx| {
+                                            unreadable_files.update(cx, |m, cx| {
+                                                m.clear();
+                                                cx.notify();
+                                            })
+                                        }),
+                                ),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .mb(px(10.0))
+                            .text_sm()
+                            .text_color(theme.text_secondary)
+                            .child(
+                                "These files were queued, but their tags couldn't be read, so \
+                                 their titles were guessed from their paths.",
+                            ),
+                    )
+                    .child(
+                        div()
+                            .id("unreadable-files-list")
+                            .flex()
+                            .flex_col()
+                            .gap(px(6.0))
+                            .overflow_y_scroll()
+                            .when(files.is_empty(), |this| {
+                                this.child(
+                                    div()
+                                        .text_sm()
+                                        .text_color(theme.text_secondary)
+                                        .child("Every queued file has been read."),
+                                )
+                            })
+                            .children(files.iter().map(|(path, failure)| {
+                                div()
+                                    .flex()
+                                    .flex_col()
+                                    .text_sm()
+                                    .child(div().child(path.clone()))
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(theme.text_secondary)
+                                            .child(failure.describe()),
+                                    )
+                            })),
+                    ),
+            )
+    }
+}
@@ -1,7 +1,18 @@
+use std::{sync::OnceLock, time::SystemTime};
+
 use ahash::AHashMap;
-use gpui::{AppContext, Model, Render, View, WindowContext};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Datelike, Locale, Utc,
+};
+use gpui::{px, AppContext, Model, Pixels, Render, RenderImage, Size, View, WindowContext};
 use tracing::debug;
 
+use crate::{
+    devices::format::{ChannelSpec, OutputInfo},
+    media::metadata::Metadata,
+};
+
 pub fn prune_views<T>(
     views_model: Model<AHashMap<usize, View<T>>>,
     render_counter: Model<usize>,
@@ -63,3 +74,274 @@ where
         }
     }
 }
+
+/// Returns the size an image has to be rendered at in order to fully cover a square of `target`
+/// pixels while keeping its aspect ratio. The image should be centered in a clipping container of
+/// the target size.
+///
+/// This exists because `ObjectFit::Cover` causes the image element to grow past its container.
+pub fn cover_size(image: &RenderImage, target: Pixels) -> Size<Pixels> {
+    let size = image.size(0);
+    let width = size.width.0 as f32;
+    let height = size.height.0 as f32;
+
+    if width <= 0.0 || height <= 0.0 {
+        return gpui::size(target, target);
+    }
+
+    let scale = (target.0 / width).max(target.0 / height);
+
+    gpui::size(px(width * scale), px(height * scale))
+}
+
+/// Formats how long ago a Unix timestamp was, e.g. "2 hours ago".
+pub fn format_time_ago(timestamp: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or_default();
+    let elapsed = now.saturating_sub(timestamp);
+
+    let (amount, unit) = match elapsed {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (elapsed / 60, "minute"),
+        3600..=86399 => (elapsed / 3600, "hour"),
+        _ => (elapsed / 86400, "day"),
+    };
+
+    if amount == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", amount, unit)
+    }
+}
+
+/// The system's locale, or `en_US` if it can't be determined.
+fn system_locale() -> Locale {
+    static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+    *LOCALE.get_or_init(|| {
+        sys_locale::get_locale()
+            // locales are reported as e.g. "en-GB", or "en_GB.UTF-8" on some systems
+            .and_then(|v| {
+                let name = v.split('.').next().unwrap_or_default().replace('-', "_");
+                Locale::try_from(name.as_str()).ok()
+            })
+            .unwrap_or(Locale::en_US)
+    })
+}
+
+/// Formats a date with the user's `date_format`, or the system locale's date format if it isn't
+/// set or isn't a valid pattern.
+pub fn format_date(date: &DateTime<Utc>, format: Option<&str>) -> String {
+    // formatting with an invalid pattern panics, so those are checked for first
+    let format = format.filter(|v| StrftimeItems::new(v).all(|item| item != Item::Error));
+
+    match format {
+        Some(format) => date.format(format).to_string(),
+        None => date.format_localized("%x", system_locale()).to_string(),
+    }
+}
+
+/// The value of a field in a metadata template, or None if the name isn't one of the known fields.
+fn metadata_field(name: &str, metadata: &Metadata) -> Option<Option<String>> {
+    Some(match name {
+        "title" => metadata.name.clone(),
+        "artist" => metadata.artist(),
+        "album" => metadata.album.clone(),
+        "album_artist" => metadata.album_artist.clone(),
+        "composer" => metadata.composer.clone(),
+        "genre" => metadata.genre(),
+        "year" => metadata.date.map(|v| v.year().to_string()),
+        "track" => metadata.track_current.map(|v| v.to_string()),
+        "disc" => metadata.disc_current.map(|v| v.to_string()),
+        _ => return None,
+    })
+}
+
+/// Fills in a template like `{title}[ ({album})]` with the track's metadata. Sections in square
+/// brackets are left out if any of the fields in them are missing, and braces that aren't one of
+/// the known fields are kept as they are.
+///
+/// Malformed templates are filled in as far as possible rather than rejected: a `{` without a
+/// closing `}` is kept as it is, a section that isn't closed runs to the end of the template,
+/// sections don't nest (a `[` inside one is kept as it is, and the first `]` ends it), and a `]`
+/// outside a section is kept as it is.
+pub fn format_metadata(template: &str, metadata: &Metadata) -> String {
+    let mut out = String::new();
+    // the bracketed section being filled in, and whether all of its fields have been found
+    let mut section: Option<(String, bool)> = None;
+    let mut rest = template;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '[' && section.is_none() {
+            section = Some((String::new(), true));
+            rest = &rest[1..];
+            continue;
+        }
+
+        if c == ']' {
+            if let Some((text, complete)) = section.take() {
+                if complete {
+                    out.push_str(&text);
+                }
+
+                rest = &rest[1..];
+                continue;
+            }
+        }
+
+        let field = if c == '{' {
+            rest.find('}').and_then(|end| {
+                metadata_field(&rest[1..end], metadata).map(|value| (value, end + 1))
+            })
+        } else {
+            None
+        };
+
+        let (value, len) = match field {
+            Some((Some(value), len)) => (value, len),
+            Some((None, len)) => {
+                if let Some((_, complete)) = &mut section {
+                    *complete = false;
+                }
+
+                rest = &rest[len..];
+                continue;
+            }
+            None => (c.to_string(), c.len_utf8()),
+        };
+
+        match &mut section {
+            Some((text, _)) => text.push_str(&value),
+            None => out.push_str(&value),
+        }
+
+        rest = &rest[len..];
+    }
+
+    // a section that isn't closed runs to the end of the template
+    if let Some((text, true)) = section {
+        out.push_str(&text);
+    }
+
+    out.trim().to_string()
+}
+
+/// Describes the format audio is being output in, e.g. "48 kHz • Float32 • 2 channels".
+pub fn format_output(output: &OutputInfo) -> String {
+    let format = &output.format;
+    let channels = match &format.channels {
+        ChannelSpec::Count(v) => *v as u32,
+        ChannelSpec::Bitmask(v) => v.bits().count_ones(),
+    };
+
+    format!(
+        "{} kHz • {:?} • {} channel{}",
+        format.sample_rate as f64 / 1000.0,
+        format.sample_type,
+        channels,
+        if channels == 1 { "" } else { "s" }
+    )
+}
+
+/// Formats the duration of a track, e.g. "3:05". Tracks whose duration isn't known are stored
+/// with a duration of 0, and are shown as "--:--".
+pub fn format_duration(secs: i64) -> String {
+    if secs <= 0 {
+        return "--:--".to_string();
+    }
+
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Formats a length of time for display, e.g. "1h 5m" or "12m".
+pub fn format_remaining(secs: u64) -> String {
+    let hours = secs / 3600;
+    let minutes = (secs % 3600) / 60;
+
+    match (hours, minutes) {
+        (0, 0) => format!("{}s", secs),
+        (0, _) => format!("{}m", minutes),
+        _ => format!("{}h {}m", hours, minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> Metadata {
+        Metadata {
+            name: Some("Title".to_string()),
+            artists: vec!["Artist".to_string()],
+            album: Some("Album".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fills_in_fields() {
+        assert_eq!(
+            format_metadata("{artist} - {title}", &metadata()),
+            "Artist - Title"
+        );
+        assert_eq!(
+            format_metadata("{title}[ ({album})]", &metadata()),
+            "Title (Album)"
+        );
+    }
+
+    #[test]
+    fn leaves_out_sections_with_missing_fields() {
+        assert_eq!(
+            format_metadata("{title}[ ({composer})]", &metadata()),
+            "Title"
+        );
+        assert_eq!(
+            format_metadata("{title}[ ({album}, {year})] end", &metadata()),
+            "Title end"
+        );
+        // a missing field outside a section is just empty
+        assert_eq!(format_metadata("{composer} {title}", &metadata()), "Title");
+    }
+
+    #[test]
+    fn keeps_unknown_fields() {
+        assert_eq!(
+            format_metadata("{title} {unknown}", &metadata()),
+            "Title {unknown}"
+        );
+        // unknown fields don't count as missing
+        assert_eq!(format_metadata("[{x} {album}]", &metadata()), "{x} Album");
+    }
+
+    #[test]
+    fn handles_malformed_templates() {
+        assert_eq!(format_metadata("{title", &metadata()), "{title");
+        assert_eq!(
+            format_metadata("{title} {album", &metadata()),
+            "Title {album"
+        );
+        assert_eq!(
+            format_metadata("{title}[ - {album}", &metadata()),
+            "Title - Album"
+        );
+        assert_eq!(format_metadata("{title}[ - {year}", &metadata()), "Title");
+        assert_eq!(format_metadata("{title}]", &metadata()), "Title]");
+        assert_eq!(
+            format_metadata("[a [{album}] b]", &metadata()),
+            "a [Album b]"
+        );
+    }
+
+    #[test]
+    fn handles_multibyte_text() {
+        assert_eq!(
+            format_metadata("é{title}ü[ – {album}]→", &metadata()),
+            "éTitleü – Album→"
+        );
+        assert_eq!(format_metadata("ü{", &metadata()), "ü{");
+        assert_eq!(format_metadata("[ü]", &metadata()), "ü");
+    }
+}
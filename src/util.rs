@@ -1,4 +1,33 @@
-use image::{Pixel, RgbaImage};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use image::{
+    imageops::{crop_imm, thumbnail},
+    Pixel, RgbaImage,
+};
+
+use crate::data::events::ImageLayout;
+
+/// Whether images are decoded as BGR, which is what gpui expects on every platform it currently
+/// supports. Shared between threads, since images are also decoded when they're read from the
+/// library.
+static DECODE_AS_BGR: AtomicBool = AtomicBool::new(true);
+
+/// The layout images should be decoded into to be displayed correctly.
+pub fn image_layout() -> ImageLayout {
+    if DECODE_AS_BGR.load(Ordering::Relaxed) {
+        ImageLayout::BGR
+    } else {
+        ImageLayout::RGB
+    }
+}
+
+/// Overrides the layout images are decoded into, or goes back to the default if None.
+pub fn set_image_layout(layout: Option<ImageLayout>) {
+    DECODE_AS_BGR.store(
+        layout.unwrap_or(ImageLayout::BGR) == ImageLayout::BGR,
+        Ordering::Relaxed,
+    );
+}
 
 pub fn rgb_to_bgr(image: &mut RgbaImage) {
     image.pixels_mut().for_each(|v| {
@@ -6,3 +35,155 @@ pub fn rgb_to_bgr(image: &mut RgbaImage) {
         *v = *image::Rgba::from_slice(&[slice[2], slice[1], slice[0], slice[3]]);
     });
 }
+
+/// Crops the image to the largest square that fits inside of it, centered on the middle of the
+/// image.
+pub fn crop_square(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let side = width.min(height);
+
+    crop_imm(image, (width - side) / 2, (height - side) / 2, side, side).to_image()
+}
+
+/// Creates a thumbnail that fits within a `size` by `size` square. If `crop` is true the image is
+/// center-cropped to a square first, otherwise the original aspect ratio is kept.
+pub fn make_thumbnail(image: &RgbaImage, size: u32, crop: bool) -> RgbaImage {
+    if crop {
+        return thumbnail(&crop_square(image), size, size);
+    }
+
+    let (width, height) = image.dimensions();
+    let scale = size as f32 / width.max(height).max(1) as f32;
+
+    thumbnail(
+        image,
+        ((width as f32 * scale).round() as u32).max(1),
+        ((height as f32 * scale).round() as u32).max(1),
+    )
+}
+
+/// Finds a representative color for the image, as (r, g, b). This is the average of its pixels,
+/// weighted towards more saturated ones so that white or black borders and backgrounds don't wash
+/// it out. Returns None if the image is fully transparent. This looks at every pixel, so it
+/// should only be used on thumbnails.
+pub fn dominant_color(image: &RgbaImage) -> Option<[u8; 3]> {
+    let mut total = [0.0f32; 3];
+    let mut total_weight = 0.0;
+
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        let max = r.max(g).max(b) as f32;
+        let min = r.min(g).min(b) as f32;
+        let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+        let weight = (a as f32 / 255.0) * (0.05 + saturation * saturation);
+
+        total[0] += r as f32 * weight;
+        total[1] += g as f32 * weight;
+        total[2] += b as f32 * weight;
+        total_weight += weight;
+    }
+
+    (total_weight > 0.0).then(|| total.map(|v| (v / total_weight).round() as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    /// An image split into three equal bands (red, green, then blue) along its longest side.
+    fn banded(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let (pos, len) = if width >= height {
+                (x, width)
+            } else {
+                (y, height)
+            };
+
+            match pos * 3 / len {
+                0 => RED,
+                1 => GREEN,
+                _ => BLUE,
+            }
+        })
+    }
+
+    #[test]
+    fn crop_square_wide() {
+        let cropped = crop_square(&banded(300, 100));
+
+        assert_eq!(cropped.dimensions(), (100, 100));
+        assert!(cropped.pixels().all(|v| *v == GREEN));
+    }
+
+    #[test]
+    fn crop_square_tall() {
+        let cropped = crop_square(&banded(100, 300));
+
+        assert_eq!(cropped.dimensions(), (100, 100));
+        assert!(cropped.pixels().all(|v| *v == GREEN));
+    }
+
+    #[test]
+    fn crop_square_keeps_squares() {
+        let image = banded(64, 64);
+
+        assert_eq!(crop_square(&image), image);
+    }
+
+    #[test]
+    fn thumbnail_cropped() {
+        let wide = make_thumbnail(&banded(600, 200), 50, true);
+        let tall = make_thumbnail(&banded(200, 600), 50, true);
+
+        assert_eq!(wide.dimensions(), (50, 50));
+        assert_eq!(tall.dimensions(), (50, 50));
+        assert_eq!(*wide.get_pixel(0, 0), GREEN);
+        assert_eq!(*tall.get_pixel(49, 49), GREEN);
+    }
+
+    #[test]
+    fn thumbnail_keeps_aspect_ratio() {
+        let wide = make_thumbnail(&banded(400, 200), 100, false);
+        let tall = make_thumbnail(&banded(200, 400), 100, false);
+
+        assert_eq!(wide.dimensions(), (100, 50));
+        assert_eq!(tall.dimensions(), (50, 100));
+        assert_eq!(*wide.get_pixel(0, 25), RED);
+        assert_eq!(*tall.get_pixel(25, 99), BLUE);
+    }
+
+    #[test]
+    fn thumbnail_of_thin_image() {
+        // the short side is rounded down to nothing, but should still be a pixel
+        let thin = make_thumbnail(&banded(1000, 1), 100, false);
+
+        assert_eq!(thin.dimensions(), (100, 1));
+    }
+
+    #[test]
+    fn rgb_to_bgr_swaps_red_and_blue() {
+        let mut image = RgbaImage::from_pixel(2, 2, Rgba([10, 20, 30, 40]));
+        rgb_to_bgr(&mut image);
+
+        assert!(image.pixels().all(|v| *v == Rgba([30, 20, 10, 40])));
+    }
+
+    #[test]
+    fn rgb_to_bgr_round_trip() {
+        let original = banded(30, 10);
+        let mut image = original.clone();
+
+        rgb_to_bgr(&mut image);
+        assert_eq!(*image.get_pixel(0, 0), BLUE);
+        assert_eq!(*image.get_pixel(29, 0), RED);
+
+        rgb_to_bgr(&mut image);
+        assert_eq!(image, original);
+    }
+}